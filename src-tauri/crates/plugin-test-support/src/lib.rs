@@ -0,0 +1,763 @@
+//! 插件单元测试用的模拟主程序
+//!
+//! `MockPlugin` 像聊天客户端一样通过导出的 `create_plugin` / `destroy_plugin`
+//! 创建插件，并按 FFI 调用生命周期函数；主程序回调由内存中的记录器实现，
+//! 插件发往前端的消息、流式消息和 UI 更新都会被记录下来，供测试断言。
+//!
+//! 与聊天客户端一样，每个实例拿到一组专属的回调函数，插件在任何线程中的回调都归属到该实例。
+//! `config.toml` 的 `[permissions]` 视为已批准：自定义事件和 `get_app_config` 按其中的
+//! `events`、`config` 检查，被拒绝时插件得到和聊天客户端中相同的结果。
+//!
+//! ```ignore
+//! #[cfg(test)]
+//! mod tests {
+//!     use plugin_test_support::MockPlugin;
+//!
+//!     #[test]
+//!     fn echoes_message() {
+//!         let mut plugin = MockPlugin::new(super::create_plugin, super::destroy_plugin)
+//!             .with_config(concat!(env!("CARGO_MANIFEST_DIR"), "/config.toml"))
+//!             .unwrap();
+//!         plugin.mount().unwrap();
+//!         plugin.connect().unwrap();
+//!         plugin.send_message("hello").unwrap();
+//!         plugin.assert_message_contains("hello");
+//!     }
+//! }
+//! ```
+
+use libloading::{Library, Symbol};
+use plugin_interfaces::{
+    metadata::HistoryMessage,
+    pluginui::{Context, Ui},
+    CreatePluginFn, DestroyPluginFn, HostCallbacks, PluginInterface, PluginMetadata,
+    CREATE_PLUGIN_SYMBOL, DESTROY_PLUGIN_SYMBOL,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::c_char;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+/// 宿主定义的插件事件，无需申请即可发送
+const HOST_EVENTS: &[&str] = &[
+    "plugin-message",
+    "plugin-stream",
+    "plugin-ui-updated",
+    "plugin-ui-refreshed",
+    "plugin-disconnect-request",
+];
+/// 同时存在的模拟实例数上限
+const SLOT_COUNT: usize = 32;
+
+type SendToFrontendFn = extern "C" fn(*const c_char, *const c_char) -> bool;
+type GetAppConfigFn = extern "C" fn(*const c_char) -> *const c_char;
+type CallOtherPluginFn = extern "C" fn(*const c_char, *const c_char) -> *const c_char;
+
+/// 插件申请的权限，模拟主程序视为已批准
+///
+/// 只检查模拟主程序实现的回调用到的部分，其余权限由聊天客户端的沙箱限制。
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MockPermissions {
+    /// 可以发送给前端的自定义事件名
+    #[serde(default)]
+    pub events: Vec<String>,
+    /// 可以通过 `get_app_config` 读取的设置项
+    #[serde(default)]
+    pub config: Vec<String>,
+}
+
+impl MockPermissions {
+    fn allows_event(&self, event: &str) -> bool {
+        HOST_EVENTS.contains(&event) || matches_any(&self.events, event)
+    }
+
+    fn allows_config(&self, key: &str) -> bool {
+        matches_any(&self.config, key)
+    }
+}
+
+/// 插件发往前端的一条事件
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostEvent {
+    pub event: String,
+    pub payload: Value,
+}
+
+/// 流式消息的状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamState {
+    Active,
+    Completed,
+    Failed(Option<String>),
+    Cancelled,
+}
+
+/// 一条流式消息的记录
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedStream {
+    pub stream_id: String,
+    pub chunks: Vec<String>,
+    pub state: StreamState,
+}
+
+impl RecordedStream {
+    /// 所有数据块拼接后的内容
+    pub fn content(&self) -> String {
+        self.chunks.concat()
+    }
+}
+
+/// 单个插件实例的事件记录器
+#[derive(Debug, Default)]
+struct Recorder {
+    events: Mutex<Vec<HostEvent>>,
+    streams: Mutex<Vec<RecordedStream>>,
+    config: Mutex<HashMap<String, Value>>,
+    permissions: Mutex<MockPermissions>,
+}
+
+impl Recorder {
+    /// 记录事件；未申请的自定义事件和已取消的流式消息返回 false，插件会得到发送失败
+    fn record(&self, event: &str, payload: Value) -> bool {
+        if !self.permissions.lock().unwrap().allows_event(event) {
+            return false;
+        }
+        if event == "plugin-stream" && !self.record_stream(&payload) {
+            return false;
+        }
+        self.events.lock().unwrap().push(HostEvent {
+            event: event.to_string(),
+            payload,
+        });
+        true
+    }
+
+    fn record_stream(&self, payload: &Value) -> bool {
+        let data = &payload["data"];
+        let stream_id = data["stream_id"].as_str().unwrap_or_default();
+        let mut streams = self.streams.lock().unwrap();
+
+        if payload["type"] == "stream_start" {
+            streams.push(RecordedStream {
+                stream_id: stream_id.to_string(),
+                chunks: Vec::new(),
+                state: StreamState::Active,
+            });
+            return true;
+        }
+
+        let Some(stream) = streams
+            .iter_mut()
+            .find(|stream| stream.stream_id == stream_id)
+        else {
+            return true;
+        };
+        if stream.state == StreamState::Cancelled {
+            return false;
+        }
+        match payload["type"].as_str().unwrap_or_default() {
+            "stream_data" => {
+                stream
+                    .chunks
+                    .push(data["chunk"].as_str().unwrap_or_default().to_string());
+            }
+            "stream_end" => {
+                stream.state = if data["success"] == Value::Bool(false) {
+                    StreamState::Failed(data["error"].as_str().map(str::to_string))
+                } else {
+                    StreamState::Completed
+                };
+            }
+            "stream_cancel" => stream.state = StreamState::Cancelled,
+            _ => {}
+        }
+        true
+    }
+}
+
+/// 各槽位所属实例的记录器和空闲队列
+struct Slots {
+    owners: Vec<Option<Arc<Recorder>>>,
+    free: VecDeque<usize>,
+}
+
+static SLOTS: OnceLock<RwLock<Slots>> = OnceLock::new();
+static NEXT_INSTANCE: AtomicU64 = AtomicU64::new(1);
+
+fn slots() -> &'static RwLock<Slots> {
+    SLOTS.get_or_init(|| {
+        RwLock::new(Slots {
+            owners: vec![None; SLOT_COUNT],
+            free: (0..SLOT_COUNT).collect(),
+        })
+    })
+}
+
+/// 为实例分配回调槽位
+fn allocate(recorder: Arc<Recorder>) -> usize {
+    let mut slots = slots().write().unwrap();
+    let slot = slots
+        .free
+        .pop_front()
+        .unwrap_or_else(|| panic!("more than {} mock plugins alive at once", SLOT_COUNT));
+    slots.owners[slot] = Some(recorder);
+    slot
+}
+
+fn release(slot: usize) {
+    let mut slots = slots().write().unwrap();
+    if slots.owners[slot].take().is_some() {
+        slots.free.push_back(slot);
+    }
+}
+
+fn owner(slot: usize) -> Option<Arc<Recorder>> {
+    slots().read().unwrap().owners[slot].clone()
+}
+
+/// 槽位专属的回调函数
+fn host_callbacks(slot: usize) -> HostCallbacks {
+    HostCallbacks {
+        send_to_frontend: SEND_TO_FRONTEND[slot],
+        get_app_config: GET_APP_CONFIG[slot],
+        call_other_plugin: CALL_OTHER_PLUGIN[slot],
+    }
+}
+
+fn callback_str<'a>(ptr: *const c_char) -> Option<&'a str> {
+    if ptr.is_null() {
+        return None;
+    }
+    unsafe { CStr::from_ptr(ptr) }.to_str().ok()
+}
+
+extern "C" fn mock_send_to_frontend<const SLOT: usize>(
+    event: *const c_char,
+    payload: *const c_char,
+) -> bool {
+    let (Some(event), Some(payload), Some(recorder)) =
+        (callback_str(event), callback_str(payload), owner(SLOT))
+    else {
+        return false;
+    };
+    let payload: Value = serde_json::from_str(payload).unwrap_or(Value::String(payload.into()));
+    recorder.record(event, payload)
+}
+
+/// 与聊天客户端一样，只能读取申请了的设置项，值以 JSON 返回
+extern "C" fn mock_get_app_config<const SLOT: usize>(key: *const c_char) -> *const c_char {
+    let (Some(key), Some(recorder)) = (callback_str(key), owner(SLOT)) else {
+        return std::ptr::null();
+    };
+    if !recorder.permissions.lock().unwrap().allows_config(key) {
+        return std::ptr::null();
+    }
+    let value = recorder
+        .config
+        .lock()
+        .unwrap()
+        .get(key)
+        .map(Value::to_string);
+    match value.and_then(|value| CString::new(value).ok()) {
+        Some(value) => value.into_raw(),
+        None => std::ptr::null(),
+    }
+}
+
+/// 模拟主程序中没有其他插件，调用总是失败
+extern "C" fn mock_call_other_plugin<const SLOT: usize>(
+    _plugin_id: *const c_char,
+    _message: *const c_char,
+) -> *const c_char {
+    std::ptr::null()
+}
+
+/// 为每个槽位实例化一组回调函数
+macro_rules! slot_callbacks {
+    ($($slot:literal)*) => {
+        static SEND_TO_FRONTEND: [SendToFrontendFn; SLOT_COUNT] =
+            [$(mock_send_to_frontend::<$slot>),*];
+        static GET_APP_CONFIG: [GetAppConfigFn; SLOT_COUNT] =
+            [$(mock_get_app_config::<$slot>),*];
+        static CALL_OTHER_PLUGIN: [CallOtherPluginFn; SLOT_COUNT] =
+            [$(mock_call_other_plugin::<$slot>),*];
+    };
+}
+
+slot_callbacks!(
+    0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15
+    16 17 18 19 20 21 22 23 24 25 26 27 28 29 30 31
+);
+
+#[derive(Deserialize)]
+struct ConfigFile {
+    plugin: ConfigPlugin,
+    #[serde(default)]
+    permissions: MockPermissions,
+}
+
+#[derive(Deserialize)]
+struct ConfigPlugin {
+    id: String,
+    name: String,
+    #[serde(default)]
+    version: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    author: Option<String>,
+    #[serde(default)]
+    require_history: bool,
+}
+
+/// 在模拟主程序中运行的插件实例
+pub struct MockPlugin {
+    interface: *mut PluginInterface,
+    destroy: DestroyPluginFn,
+    metadata: PluginMetadata,
+    instance_id: String,
+    recorder: Arc<Recorder>,
+    /// 回调槽位，插件销毁后释放
+    slot: usize,
+    ui: Arc<Mutex<Ui>>,
+    initialized: bool,
+    mounted: bool,
+    connected: bool,
+    // 从动态库加载时持有库句柄，必须在插件销毁后释放
+    _library: Option<Library>,
+}
+
+impl MockPlugin {
+    /// 使用插件 crate 导出的 `create_plugin` 和 `destroy_plugin` 创建插件
+    pub fn new(create: CreatePluginFn, destroy: DestroyPluginFn) -> Self {
+        Self::create(create, destroy, None)
+    }
+
+    /// 从编译好的插件动态库创建插件
+    pub fn load(library_path: impl AsRef<Path>) -> Result<Self, String> {
+        let library_path = library_path.as_ref();
+        let library = unsafe {
+            Library::new(library_path).map_err(|e| format!("加载动态库失败: {}", e))?
+        };
+        let (create, destroy) = unsafe {
+            let create: Symbol<CreatePluginFn> = library
+                .get(CREATE_PLUGIN_SYMBOL)
+                .map_err(|e| format!("找不到插件创建函数: {}", e))?;
+            let destroy: Symbol<DestroyPluginFn> = library
+                .get(DESTROY_PLUGIN_SYMBOL)
+                .map_err(|e| format!("找不到插件销毁函数: {}", e))?;
+            (*create, *destroy)
+        };
+        let mut plugin = Self::create(create, destroy, Some(library));
+        plugin.metadata.library_path = Some(library_path.to_string_lossy().to_string());
+        Ok(plugin)
+    }
+
+    fn create(create: CreatePluginFn, destroy: DestroyPluginFn, library: Option<Library>) -> Self {
+        let instance_id = format!("mock-{}", NEXT_INSTANCE.fetch_add(1, Ordering::Relaxed));
+        let recorder = Arc::new(Recorder::default());
+        let slot = allocate(Arc::clone(&recorder));
+
+        let interface = unsafe { create() };
+        assert!(!interface.is_null(), "create_plugin returned null");
+
+        Self {
+            interface,
+            destroy,
+            metadata: PluginMetadata {
+                id: "mock_plugin".to_string(),
+                disabled: false,
+                name: "Mock Plugin".to_string(),
+                description: String::new(),
+                version: "0.0.0".to_string(),
+                author: None,
+                library_path: None,
+                config_path: String::new(),
+                instance_id: Some(instance_id.clone()),
+                require_history: false,
+            },
+            ui: Ui::new(instance_id.clone()),
+            instance_id,
+            recorder,
+            slot,
+            initialized: false,
+            mounted: false,
+            connected: false,
+            _library: library,
+        }
+    }
+
+    /// 从插件的 config.toml 读取插件ID、名称等元数据和申请的权限
+    pub fn with_config(mut self, config_path: impl AsRef<Path>) -> Result<Self, String> {
+        let config_path = config_path.as_ref();
+        let content = std::fs::read_to_string(config_path)
+            .map_err(|e| format!("读取插件配置失败 {:?}: {}", config_path, e))?;
+        let config: ConfigFile =
+            toml::from_str(&content).map_err(|e| format!("解析插件配置失败: {}", e))?;
+        self.metadata.id = config.plugin.id;
+        self.metadata.name = config.plugin.name;
+        self.metadata.version = config.plugin.version;
+        self.metadata.description = config.plugin.description;
+        self.metadata.author = config.plugin.author;
+        self.metadata.require_history = config.plugin.require_history;
+        self.metadata.config_path = config_path.to_string_lossy().to_string();
+        *self.recorder.permissions.lock().unwrap() = config.permissions;
+        Ok(self)
+    }
+
+    /// 自定义插件的权限，替换 config.toml 中申请的权限
+    pub fn with_permissions(self, permissions: MockPermissions) -> Self {
+        *self.recorder.permissions.lock().unwrap() = permissions;
+        self
+    }
+
+    /// 自定义插件元数据（实例ID由模拟主程序分配，不会被覆盖）
+    pub fn with_metadata(mut self, metadata: PluginMetadata) -> Self {
+        self.metadata = PluginMetadata {
+            instance_id: Some(self.instance_id.clone()),
+            ..metadata
+        };
+        self
+    }
+
+    /// 设置插件通过 `get_app_config` 读取到的配置，插件得到值的 JSON
+    ///
+    /// 与聊天客户端一样，插件还需要在 `config` 权限中申请该设置项才能读取。
+    pub fn with_app_config(self, key: &str, value: impl Into<Value>) -> Self {
+        self.recorder
+            .config
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), value.into());
+        self
+    }
+
+    pub fn instance_id(&self) -> &str {
+        &self.instance_id
+    }
+
+    pub fn metadata(&self) -> &PluginMetadata {
+        &self.metadata
+    }
+
+    /// 初始化并挂载插件，随后渲染一次 UI
+    pub fn mount(&mut self) -> Result<(), String> {
+        if !self.initialized {
+            let callbacks = host_callbacks(self.slot);
+            let metadata_ffi = self.metadata.to_ffi();
+            let result = unsafe {
+                ((*self.interface).initialize)(
+                    (*self.interface).plugin_ptr,
+                    callbacks,
+                    metadata_ffi,
+                )
+            };
+            unsafe { plugin_interfaces::metadata::free_plugin_metadata_ffi(metadata_ffi) };
+            check(result, "initialize")?;
+            self.initialized = true;
+        }
+
+        check(
+            unsafe { ((*self.interface).on_mount)((*self.interface).plugin_ptr) },
+            "on_mount",
+        )?;
+        self.mounted = true;
+        self.render_ui(None)
+    }
+
+    pub fn connect(&mut self) -> Result<(), String> {
+        check(
+            unsafe { ((*self.interface).on_connect)((*self.interface).plugin_ptr) },
+            "on_connect",
+        )?;
+        self.connected = true;
+        Ok(())
+    }
+
+    pub fn disconnect(&mut self) -> Result<(), String> {
+        self.connected = false;
+        check(
+            unsafe { ((*self.interface).on_disconnect)((*self.interface).plugin_ptr) },
+            "on_disconnect",
+        )
+    }
+
+    /// 断开连接并卸载插件
+    pub fn dispose(&mut self) -> Result<(), String> {
+        if self.connected {
+            self.disconnect()?;
+        }
+        self.mounted = false;
+        check(
+            unsafe { ((*self.interface).on_dispose)((*self.interface).plugin_ptr) },
+            "on_dispose",
+        )
+    }
+
+    /// 设置下一条消息可以读取到的历史记录，None 表示清除
+    pub fn set_history(&mut self, history: Option<&[HistoryMessage]>) -> Result<(), String> {
+        let history = history
+            .map(|history| {
+                let json = serde_json::to_string(history)
+                    .map_err(|e| format!("序列化历史记录失败: {}", e))?;
+                CString::new(json).map_err(|_| "历史记录转换失败".to_string())
+            })
+            .transpose()?;
+        let history_ptr = history
+            .as_ref()
+            .map_or(std::ptr::null(), |history| history.as_ptr());
+        check(
+            unsafe { ((*self.interface).set_history)((*self.interface).plugin_ptr, history_ptr) },
+            "set_history",
+        )
+    }
+
+    /// 发送消息并返回插件 `handle_message` 的返回值
+    pub fn send_message(&mut self, message: &str) -> Result<String, String> {
+        let message = CString::new(message).map_err(|_| "消息转换失败".to_string())?;
+        let mut response_ptr: *mut c_char = std::ptr::null_mut();
+        check(
+            unsafe {
+                ((*self.interface).handle_message)(
+                    (*self.interface).plugin_ptr,
+                    message.as_ptr(),
+                    &mut response_ptr,
+                )
+            },
+            "handle_message",
+        )?;
+        if response_ptr.is_null() {
+            return Err("插件返回空响应".to_string());
+        }
+        unsafe { CStr::from_ptr(response_ptr) }
+            .to_str()
+            .map(str::to_string)
+            .map_err(|_| "响应转换失败".to_string())
+    }
+
+    /// 当前 UI 组件（与前端收到的 JSON 结构相同）
+    pub fn ui_components(&self) -> Vec<Value> {
+        let ui = self.ui.lock().unwrap();
+        serde_json::to_value(ui.get_components())
+            .ok()
+            .and_then(|components| components.as_array().cloned())
+            .unwrap_or_default()
+    }
+
+    /// 按显示文本查找组件ID（包括水平布局中的子组件）
+    pub fn find_component(&self, text: &str) -> Option<String> {
+        find_component(&self.ui_components(), text)
+    }
+
+    /// 按显示文本点击按钮
+    pub fn click(&mut self, label: &str) -> Result<(), String> {
+        let component_id = self
+            .find_component(label)
+            .ok_or_else(|| format!("找不到组件: {}", label))?;
+        self.ui_event(&component_id, "")
+    }
+
+    /// 向组件发送 UI 事件：文本框为文本，下拉框为选项序号，开关为 true/false
+    pub fn ui_event(&mut self, component_id: &str, value: &str) -> Result<(), String> {
+        if !self.ui.lock().unwrap().handle_ui_event(component_id, value) {
+            return Err(format!("组件 {} 没有处理该事件", component_id));
+        }
+        self.render_ui(Some((component_id, value)))
+    }
+
+    /// 重新渲染 UI（对应前端收到 plugin-ui-refreshed 后的刷新）
+    pub fn refresh_ui(&mut self) -> Result<(), String> {
+        self.render_ui(Some(("", "")))
+    }
+
+    /// 调用插件的 update_ui，与主程序一样先让 UI 实例记录事件再渲染
+    fn render_ui(&mut self, event: Option<(&str, &str)>) -> Result<(), String> {
+        let context = match event {
+            Some((component_id, value)) => Context::with_ui_event_data(
+                self.instance_id.clone(),
+                HashMap::from([(component_id.to_string(), value.to_string())]),
+            ),
+            None => Context::new(self.instance_id.clone()),
+        };
+        let mut ui = self.ui.lock().unwrap();
+        if let Some((component_id, value)) = event {
+            ui.handle_ui_event(component_id, value);
+        }
+        ui.clear_components_only();
+        let result = unsafe {
+            ((*self.interface).update_ui)(
+                (*self.interface).plugin_ptr,
+                &context as *const Context as *const c_void,
+                &mut *ui as *mut Ui as *mut c_void,
+            )
+        };
+        ui.clear_events();
+        check(result, "update_ui")
+    }
+
+    /// 所有记录的事件
+    pub fn events(&self) -> Vec<HostEvent> {
+        self.recorder.events.lock().unwrap().clone()
+    }
+
+    /// 通过 `send_message_to_frontend` 发送的消息内容
+    pub fn messages(&self) -> Vec<String> {
+        self.events()
+            .into_iter()
+            .filter(|event| event.event == "plugin-message")
+            .map(|event| {
+                event.payload["content"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    /// 所有流式消息
+    pub fn streams(&self) -> Vec<RecordedStream> {
+        self.recorder.streams.lock().unwrap().clone()
+    }
+
+    /// 清空已记录的事件和流式消息
+    pub fn clear_events(&self) {
+        self.recorder.events.lock().unwrap().clear();
+        self.recorder.streams.lock().unwrap().clear();
+    }
+
+    /// 模拟用户取消流式消息，之后插件发送的数据块会失败
+    pub fn cancel_stream(&self, stream_id: &str) -> Result<(), String> {
+        let mut streams = self.recorder.streams.lock().unwrap();
+        let stream = streams
+            .iter_mut()
+            .find(|stream| stream.stream_id == stream_id)
+            .ok_or_else(|| format!("流式消息 {} 未找到", stream_id))?;
+        stream.state = StreamState::Cancelled;
+        Ok(())
+    }
+
+    /// 等待满足条件，用于插件在后台线程中发送的消息
+    pub fn wait_until<F>(&self, timeout: Duration, condition: F) -> bool
+    where
+        F: Fn(&Self) -> bool,
+    {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if condition(self) {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                return false;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// 等待第一条流式消息结束并返回
+    pub fn wait_for_stream(&self, timeout: Duration) -> Option<RecordedStream> {
+        self.wait_until(timeout, |plugin| {
+            plugin
+                .streams()
+                .first()
+                .is_some_and(|stream| stream.state != StreamState::Active)
+        });
+        self.streams().into_iter().next()
+    }
+
+    /// 断言收到过包含指定文本的消息
+    #[track_caller]
+    pub fn assert_message_contains(&self, text: &str) {
+        let messages = self.messages();
+        assert!(
+            messages.iter().any(|message| message.contains(text)),
+            "no message contains {:?}, messages: {:#?}",
+            text,
+            messages
+        );
+    }
+
+    /// 断言 UI 中有指定显示文本的组件
+    #[track_caller]
+    pub fn assert_ui_contains(&self, text: &str) {
+        assert!(
+            self.find_component(text).is_some(),
+            "no UI component with text {:?}, components: {:#?}",
+            text,
+            self.ui_components()
+        );
+    }
+
+    /// 断言存在成功结束的流式消息，且拼接后的内容包含指定文本
+    #[track_caller]
+    pub fn assert_stream_completed_with(&self, text: &str) {
+        let streams = self.streams();
+        assert!(
+            streams.iter().any(|stream| {
+                stream.state == StreamState::Completed && stream.content().contains(text)
+            }),
+            "no completed stream contains {:?}, streams: {:#?}",
+            text,
+            streams
+        );
+    }
+}
+
+impl Drop for MockPlugin {
+    fn drop(&mut self) {
+        if self.mounted {
+            let _ = self.dispose();
+        }
+        unsafe { (self.destroy)(self.interface) };
+        release(self.slot);
+    }
+}
+
+fn check(result: i32, function: &str) -> Result<(), String> {
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(format!("插件 {} 返回错误码 {}", function, result))
+    }
+}
+
+fn matches_any(patterns: &[String], value: &str) -> bool {
+    patterns
+        .iter()
+        .any(|pattern| wildcard_match(pattern, value))
+}
+
+/// 与聊天客户端相同的通配符匹配，`*` 匹配任意长度的任意字符
+fn wildcard_match(pattern: &str, value: &str) -> bool {
+    let Some((first, rest)) = pattern.split_once('*') else {
+        return pattern == value;
+    };
+    let Some(mut remaining) = value.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts: Vec<&str> = rest.split('*').collect();
+    let last = parts.pop().unwrap_or_default();
+    for part in parts {
+        match remaining.find(part) {
+            Some(index) => remaining = &remaining[index + part.len()..],
+            None => return false,
+        }
+    }
+    remaining.ends_with(last)
+}
+
+fn find_component(components: &[Value], text: &str) -> Option<String> {
+    components.iter().find_map(|component| {
+        let body = &component["component"];
+        if body["text"].as_str() == Some(text) {
+            return component["id"].as_str().map(str::to_string);
+        }
+        body["children"]
+            .as_array()
+            .and_then(|children| find_component(children, text))
+    })
+}
@@ -1,20 +1,44 @@
 use crate::plugins::{
-    AvailablePluginInfo, DownloadResponse, PluginDownloadResult, PluginManager, PluginMetadata,
-    PluginRepository,
+    directories, download, linked, replay, resources, scaffold, watcher, AvailablePluginInfo,
+    BuildLogLine, DownloadResponse, LinkedPlugin, PermissionRequest, PluginDownloadResult,
+    PluginManager, PluginMetadata, PluginRepository, ReplayOptions, ReplayReport, ResourceUsage,
+    ScaffoldOptions, TauriEventSink, BUILD_LOG_EVENT,
 };
-use plugin_interfaces::metadata::HistoryMessage;
+use plugin_interfaces::{log_error, metadata::HistoryMessage};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, OnceLock};
-use tauri::AppHandle;
+use tauri::{AppHandle, Emitter};
 
 // 全局插件管理器实例
 static PLUGIN_MANAGER: OnceLock<Arc<PluginManager>> = OnceLock::new();
 
 /// 初始化插件管理器（应用启动时调用）
 pub fn initialize_plugin_manager(app_handle: AppHandle) {
-    let manager = Arc::new(PluginManager::new(app_handle));
+    let sink = Arc::new(TauriEventSink::new(app_handle.clone()));
+    let manager = Arc::new(PluginManager::new(sink));
     PLUGIN_MANAGER
-        .set(manager)
+        .set(Arc::clone(&manager))
         .expect("Failed to initialize plugin manager");
+
+    // 启动资源统计和限制
+    resources::start_resource_monitor(&manager);
+
+    // 启动关联开发目录的自动构建
+    watcher::start_linked_plugin_watchers(manager, build_log_emitter(app_handle));
+}
+
+/// 将构建日志作为 plugin-build-log 事件发送到前端
+fn build_log_emitter(app_handle: AppHandle) -> watcher::BuildLogCallback {
+    Arc::new(
+        move |line: &BuildLogLine| match serde_json::to_string(line) {
+            Ok(payload) => {
+                if let Err(e) = app_handle.emit(BUILD_LOG_EVENT, payload) {
+                    log_error!("发送构建日志事件失败: {}", e);
+                }
+            }
+            Err(e) => log_error!("序列化构建日志失败: {}", e),
+        },
+    )
 }
 
 /// 获取插件管理器实例
@@ -107,10 +131,14 @@ pub fn handle_plugin_ui_event(
     manager.handle_plugin_ui_event(&instance_id, &component_id, &value)
 }
 
+/// 下载插件仓库
 #[tauri::command]
-pub async fn download_github_repo() -> Result<DownloadResponse, String> {
-    let repository = PluginRepository::new();
-    repository.download_github_repo().await
+pub async fn download_github_repo(
+    app_handle: AppHandle,
+    download_id: Option<String>,
+) -> Result<DownloadResponse, String> {
+    let repository = PluginRepository::with_app_handle(app_handle);
+    repository.download_github_repo(download_id).await
 }
 
 /// 扫描可用插件列表（从插件仓库）
@@ -122,9 +150,59 @@ pub fn scan_available_plugins() -> Result<Vec<AvailablePluginInfo>, String> {
 
 /// 下载并安装插件
 #[tauri::command]
-pub async fn download_plugin(plugin_id: String) -> Result<PluginDownloadResult, String> {
-    let repository = PluginRepository::new();
-    Ok(repository.download_plugin(&plugin_id).await)
+pub async fn download_plugin(
+    app_handle: AppHandle,
+    plugin_id: String,
+    download_id: Option<String>,
+) -> Result<PluginDownloadResult, String> {
+    let repository = PluginRepository::with_app_handle(app_handle);
+    Ok(repository.download_plugin(&plugin_id, download_id).await)
+}
+
+/// 取消正在进行的下载
+#[tauri::command]
+pub fn cancel_download(download_id: String) -> Result<String, String> {
+    download::cancel_download(&download_id)
+}
+
+/// 从本地插件压缩包或动态库文件安装插件
+#[tauri::command]
+pub async fn install_plugin_from_file(
+    path: String,
+    config_path: Option<String>,
+) -> Result<PluginDownloadResult, String> {
+    tokio::task::spawn_blocking(move || {
+        let repository = PluginRepository::new();
+        repository.install_plugin_from_file(&path, config_path.as_deref())
+    })
+    .await
+    .map_err(|e| format!("安装任务失败: {}", e))
+}
+
+/// 从 Git 仓库地址或本地 Git 仓库路径安装插件
+#[tauri::command]
+pub async fn install_plugin_from_git(
+    app_handle: AppHandle,
+    url: String,
+    rev: Option<String>,
+    path: Option<String>,
+    download_id: Option<String>,
+) -> Result<PluginDownloadResult, String> {
+    let repository = PluginRepository::with_app_handle(app_handle);
+    Ok(repository
+        .install_plugin_from_git(&url, rev.as_deref(), path.as_deref(), download_id)
+        .await)
+}
+
+/// 按安装来源更新已安装的插件
+#[tauri::command]
+pub async fn update_plugin(
+    app_handle: AppHandle,
+    plugin_id: String,
+    download_id: Option<String>,
+) -> Result<PluginDownloadResult, String> {
+    let repository = PluginRepository::with_app_handle(app_handle);
+    Ok(repository.update_plugin(&plugin_id, download_id).await)
 }
 
 /// 卸载已安装的插件
@@ -134,6 +212,71 @@ pub fn uninstall_plugin(plugin_id: String) -> Result<PluginDownloadResult, Strin
     Ok(repository.uninstall_plugin(&plugin_id))
 }
 
+/// 获取插件申请的权限和批准状态
+#[tauri::command]
+pub fn get_plugin_permissions(plugin_id: String) -> Result<PermissionRequest, String> {
+    let manager = get_plugin_manager()?;
+    manager.get_plugin_permissions(&plugin_id)
+}
+
+/// 批准插件当前版本申请的全部权限
+#[tauri::command]
+pub fn approve_plugin_permissions(plugin_id: String) -> Result<PermissionRequest, String> {
+    let manager = get_plugin_manager()?;
+    manager.approve_plugin_permissions(&plugin_id)
+}
+
+/// 热重载插件的所有已挂载实例
+#[tauri::command]
+pub fn reload_plugin(plugin_id: String) -> Result<String, String> {
+    let manager = get_plugin_manager()?;
+    manager.reload_plugin(&plugin_id)
+}
+
+/// 列出关联的开发目录
+#[tauri::command]
+pub fn list_linked_plugins() -> Result<Vec<LinkedPlugin>, String> {
+    Ok(linked::load_linked_plugins())
+}
+
+/// 关联插件开发目录，`auto_rebuild` 为 true 时监听文件变化并自动构建、热重载
+#[tauri::command]
+pub fn link_plugin_directory(
+    app_handle: AppHandle,
+    path: String,
+    auto_rebuild: bool,
+) -> Result<LinkedPlugin, String> {
+    let linked = linked::link_plugin_directory(Path::new(&path), auto_rebuild)?;
+    watcher::unwatch_linked_plugin(&linked.path);
+    if linked.auto_rebuild {
+        let manager = get_plugin_manager()?;
+        watcher::watch_linked_plugin(&linked, Arc::clone(manager), build_log_emitter(app_handle))?;
+    }
+    Ok(linked)
+}
+
+/// 取消关联插件开发目录
+#[tauri::command]
+pub fn unlink_plugin_directory(path: String) -> Result<(), String> {
+    let path = Path::new(&path);
+    watcher::unwatch_linked_plugin(&path.canonicalize().unwrap_or_else(|_| path.to_path_buf()));
+    linked::unlink_plugin_directory(path)
+}
+
+/// 立即构建关联目录中的插件并热重载
+#[tauri::command]
+pub async fn rebuild_linked_plugin(app_handle: AppHandle, path: String) -> Result<String, String> {
+    let manager = get_plugin_manager()?;
+    let on_log = build_log_emitter(app_handle);
+    watcher::rebuild_linked_plugin(Path::new(&path), manager, &*on_log).await
+}
+
+/// 根据模板生成新的插件 crate，返回插件目录
+#[tauri::command]
+pub fn scaffold_plugin(options: ScaffoldOptions) -> Result<String, String> {
+    scaffold::scaffold_plugin(&options).map(|path| path.to_string_lossy().to_string())
+}
+
 /// 取消流式消息
 #[tauri::command]
 pub fn cancel_stream_message(instance_id: String, stream_id: String) -> Result<String, String> {
@@ -141,6 +284,64 @@ pub fn cancel_stream_message(instance_id: String, stream_id: String) -> Result<S
     manager.cancel_stream_message(&instance_id, &stream_id)
 }
 
+/// 开始记录插件跟踪，省略路径时写入数据目录下的 traces 目录，返回跟踪文件路径
+#[tauri::command]
+pub fn start_plugin_trace(path: Option<String>) -> Result<String, String> {
+    let manager = get_plugin_manager()?;
+    let path = match path {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let timestamp = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_millis();
+            directories::get_traces_directory().join(format!("trace-{}.jsonl", timestamp))
+        }
+    };
+    manager.start_trace(&path)?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// 停止记录插件跟踪，返回跟踪文件路径（未在跟踪时为空）
+#[tauri::command]
+pub fn stop_plugin_trace() -> Result<Option<String>, String> {
+    let manager = get_plugin_manager()?;
+    Ok(manager
+        .stop_trace()
+        .map(|path| path.to_string_lossy().to_string()))
+}
+
+/// 获取当前跟踪文件路径
+#[tauri::command]
+pub fn get_plugin_trace_path() -> Result<Option<String>, String> {
+    let manager = get_plugin_manager()?;
+    Ok(manager
+        .trace_path()
+        .map(|path| path.to_string_lossy().to_string()))
+}
+
+/// 回放跟踪文件并与记录的输出比较，`realtime` 为 false 时不等待原始调用间隔
+#[tauri::command]
+pub async fn replay_plugin_trace(
+    path: String,
+    realtime: Option<bool>,
+) -> Result<ReplayReport, String> {
+    let options = ReplayOptions {
+        realtime: realtime.unwrap_or(true),
+        ..ReplayOptions::default()
+    };
+    tokio::task::spawn_blocking(move || replay::replay_trace(Path::new(&path), &options))
+        .await
+        .map_err(|e| format!("回放任务失败: {}", e))?
+}
+
+/// 获取所有插件实例的资源占用
+#[tauri::command]
+pub fn get_plugin_resource_usage() -> Result<Vec<ResourceUsage>, String> {
+    let manager = get_plugin_manager()?;
+    Ok(manager.get_resource_usage())
+}
+
 /// 清理所有插件（应用关闭时调用）
 pub fn cleanup_all_plugins() {
     if let Ok(manager) = get_plugin_manager() {
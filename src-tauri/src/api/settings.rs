@@ -0,0 +1,74 @@
+use serde::Serialize;
+
+use crate::plugins::{
+    directories::{get_plugin_repository_root, get_plugins_directories},
+    http, sanitize,
+    settings::{load_path_settings, load_settings, save_path_settings, save_settings},
+    NetworkSettings, PathSettings, SanitizeSettings,
+};
+
+/// 当前生效的目录
+#[derive(Debug, Clone, Serialize)]
+pub struct EffectiveDirectories {
+    pub data_root: String,
+    pub plugin_directories: Vec<String>,
+}
+
+/// 获取网络设置（设置文件中保存的值，不含环境变量覆盖）
+#[tauri::command]
+pub fn get_network_settings() -> NetworkSettings {
+    load_settings().network
+}
+
+/// 保存网络设置并重建共享的HTTP客户端
+///
+/// 先按新设置（含环境变量覆盖）创建客户端，创建失败时不保存，避免无效的代理或证书写入设置文件。
+#[tauri::command]
+pub fn set_network_settings(network: NetworkSettings) -> Result<(), String> {
+    let client = http::build_client(&network.clone().with_env_overrides()?)?;
+    let mut settings = load_settings();
+    settings.network = network;
+    save_settings(&settings)?;
+    http::replace_shared_client(client);
+    Ok(())
+}
+
+/// 获取插件消息的清理设置
+#[tauri::command]
+pub fn get_sanitize_settings() -> SanitizeSettings {
+    load_settings().sanitize
+}
+
+/// 保存插件消息的清理设置，之后发送的消息立即使用新设置
+#[tauri::command]
+pub fn set_sanitize_settings(sanitize: SanitizeSettings) -> Result<(), String> {
+    let mut settings = load_settings();
+    settings.sanitize = sanitize;
+    save_settings(&settings)?;
+    sanitize::reload_settings();
+    Ok(())
+}
+
+/// 获取目录设置（paths.toml 中保存的值）
+#[tauri::command]
+pub fn get_path_settings() -> PathSettings {
+    load_path_settings()
+}
+
+/// 保存目录设置；插件目录立即生效，数据目录在重启后生效
+#[tauri::command]
+pub fn set_path_settings(paths: PathSettings) -> Result<(), String> {
+    save_path_settings(&paths)
+}
+
+/// 获取当前生效的数据根目录和插件目录（含命令行参数和环境变量覆盖）
+#[tauri::command]
+pub fn get_effective_directories() -> EffectiveDirectories {
+    EffectiveDirectories {
+        data_root: get_plugin_repository_root().to_string_lossy().to_string(),
+        plugin_directories: get_plugins_directories()
+            .iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect(),
+    }
+}
@@ -0,0 +1,568 @@
+//! 无界面的命令行前端
+//!
+//! 不启动 webview，直接通过 `PluginManager` 列出、挂载和连接插件并收发消息，
+//! 便于在 shell 管道、CI 和 SSH 会话中使用和调试插件。
+
+use chat_client_lib::plugins::{
+    directories, replay_trace, EventSink, PluginManager, RecordingEventSink, ReplayOptions,
+};
+use clap::{Parser, Subcommand};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// 最后一个事件之后没有新事件的等待时间，用于判断插件是否已经输出完毕
+const SETTLE_DURATION: Duration = Duration::from_millis(300);
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "chat-client-cli",
+    version,
+    about = "Chat Client 无界面命令行前端"
+)]
+struct Cli {
+    /// 数据根目录（同 GUI 的 --data-dir）
+    #[arg(long, global = true)]
+    data_dir: Option<PathBuf>,
+    /// 额外的插件目录，可重复
+    #[arg(long, global = true)]
+    plugin_dir: Vec<PathBuf>,
+    /// 以 JSON 行输出插件事件，便于脚本处理
+    #[arg(long, global = true)]
+    json: bool,
+    /// 把本次会话的宿主调用和插件事件记录到跟踪文件，可用 replay 子命令回放
+    #[arg(long, global = true)]
+    trace: Option<PathBuf>,
+    /// 批准插件清单中申请的权限（与 GUI 中的确认相同）
+    #[arg(long, global = true)]
+    approve_permissions: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// 列出可用插件
+    List,
+    /// 发送消息并打印插件的回复，省略消息时逐行读取标准输入
+    Send {
+        /// 插件ID
+        plugin: String,
+        /// 消息内容
+        message: Vec<String>,
+        /// 每条消息等待插件输出结束的最长时间（秒）
+        #[arg(long, default_value_t = 300)]
+        timeout: u64,
+    },
+    /// 打印插件 UI
+    Ui {
+        /// 插件ID
+        plugin: String,
+    },
+    /// 交互式会话：输入消息发送给插件，以 `:` 开头的行为命令（输入 :help 查看）
+    Run {
+        /// 插件ID
+        plugin: String,
+    },
+    /// 回放跟踪文件，把插件的返回值和事件与记录比较，不一致时以非零状态退出
+    Replay {
+        /// 跟踪文件
+        trace_file: PathBuf,
+        /// 不等待记录中的调用间隔，依次立即调用
+        #[arg(long)]
+        fast: bool,
+        /// 最后一次调用后没有新事件多少毫秒视为插件输出结束
+        #[arg(long, default_value_t = 500)]
+        settle: u64,
+    },
+}
+
+/// 需要在主线程中处理的插件请求（事件回调中不能再调用插件管理器）
+#[derive(Debug)]
+enum HostRequest {
+    /// 插件UI已更新，需要重新打印
+    UiUpdated,
+    /// 插件请求刷新UI
+    UiRefresh,
+    /// 插件请求断开连接
+    Disconnect,
+}
+
+/// 插件事件的输出状态
+#[derive(Debug)]
+struct OutputState {
+    json: bool,
+    active_streams: HashSet<String>,
+    last_event: Instant,
+}
+
+/// 把插件发往前端的事件打印到终端
+struct EventPrinter {
+    state: Mutex<OutputState>,
+    requests: Mutex<Sender<HostRequest>>,
+}
+
+impl EventSink for EventPrinter {
+    fn emit(&self, event: &str, payload: &str) -> Result<(), String> {
+        self.handle_event(event, payload);
+        Ok(())
+    }
+}
+
+impl EventPrinter {
+    fn handle_event(&self, event: &str, payload: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.last_event = Instant::now();
+
+        let data: Value = serde_json::from_str(payload).unwrap_or(Value::Null);
+        if state.json {
+            println!("{}", serde_json::json!({ "event": event, "payload": data }));
+        }
+
+        match event {
+            "plugin-message" if !state.json => {
+                println!("{}", data["content"].as_str().unwrap_or(payload));
+            }
+            "plugin-stream" => {
+                let stream_id = data["data"]["stream_id"].as_str().unwrap_or_default();
+                match data["type"].as_str().unwrap_or_default() {
+                    "stream_start" => {
+                        state.active_streams.insert(stream_id.to_string());
+                    }
+                    "stream_data" if !state.json => {
+                        print!("{}", data["data"]["chunk"].as_str().unwrap_or_default());
+                        let _ = io::stdout().flush();
+                    }
+                    "stream_end" => {
+                        state.active_streams.remove(stream_id);
+                        if !state.json {
+                            println!();
+                            if data["data"]["success"] == Value::Bool(false) {
+                                eprintln!(
+                                    "[流式消息失败] {}",
+                                    data["data"]["error"].as_str().unwrap_or_default()
+                                );
+                            }
+                        }
+                    }
+                    "stream_cancel" => {
+                        state.active_streams.remove(stream_id);
+                        if !state.json {
+                            println!();
+                            eprintln!("[流式消息已取消]");
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            "plugin-ui-updated" => self.request(HostRequest::UiUpdated),
+            "plugin-ui-refreshed" => self.request(HostRequest::UiRefresh),
+            "plugin-disconnect-request" => self.request(HostRequest::Disconnect),
+            _ => {}
+        }
+    }
+
+    fn request(&self, request: HostRequest) {
+        let _ = self.requests.lock().unwrap().send(request);
+    }
+
+    /// 等待所有流式消息结束且一段时间内没有新事件，超时返回 false
+    fn wait_until_settled(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        loop {
+            {
+                let state = self.state.lock().unwrap();
+                if state.active_streams.is_empty() && state.last_event.elapsed() >= SETTLE_DURATION
+                {
+                    return true;
+                }
+            }
+            if Instant::now() >= deadline {
+                return false;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    fn active_streams(&self) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        state.active_streams.iter().cloned().collect()
+    }
+}
+
+/// 已挂载并连接的插件会话
+struct Session {
+    manager: PluginManager,
+    printer: Arc<EventPrinter>,
+    requests: Receiver<HostRequest>,
+    plugin_id: String,
+    instance_id: String,
+    json: bool,
+    /// UI 更新后是否重新打印（只在交互式会话中打印，避免干扰管道输出）
+    print_ui_updates: bool,
+}
+
+impl Session {
+    fn open(cli: &Cli, plugin_id: &str, print_ui_updates: bool) -> Result<Self, String> {
+        let (sender, requests) = mpsc::channel();
+        let printer = Arc::new(EventPrinter {
+            state: Mutex::new(OutputState {
+                json: cli.json,
+                active_streams: HashSet::new(),
+                last_event: Instant::now(),
+            }),
+            requests: Mutex::new(sender),
+        });
+        let manager = PluginManager::new(Arc::clone(&printer) as Arc<dyn EventSink>);
+        if let Some(trace) = &cli.trace {
+            manager.start_trace(trace)?;
+        }
+
+        if cli.approve_permissions {
+            manager.approve_plugin_permissions(plugin_id)?;
+        } else if !manager.get_plugin_permissions(plugin_id)?.is_approved() {
+            return Err(format!(
+                "插件 {} 申请的权限尚未获得批准，可使用 --approve-permissions 批准",
+                plugin_id
+            ));
+        }
+
+        let instance_id = uuid::Uuid::new_v4().to_string();
+        manager.mount_plugin(plugin_id, Some(instance_id.clone()))?;
+        let session = Self {
+            manager,
+            printer,
+            requests,
+            plugin_id: plugin_id.to_string(),
+            instance_id,
+            json: cli.json,
+            print_ui_updates,
+        };
+        session.manager.connect_plugin(&session.instance_id)?;
+        Ok(session)
+    }
+
+    /// 发送消息并等待插件输出结束
+    fn send(&self, message: &str, timeout: Duration) -> Result<(), String> {
+        let response = self.manager.send_message_to_plugin_instance(
+            &self.plugin_id,
+            &self.instance_id,
+            message,
+            None,
+        )?;
+        if self.json {
+            println!(
+                "{}",
+                serde_json::json!({ "event": "response", "payload": response })
+            );
+        }
+
+        let settled = self.printer.wait_until_settled(timeout);
+        self.process_requests()?;
+        if settled {
+            Ok(())
+        } else {
+            Err(format!("等待插件输出超时（{} 秒）", timeout.as_secs()))
+        }
+    }
+
+    /// 处理事件回调中收到的插件请求
+    fn process_requests(&self) -> Result<(), String> {
+        let mut print_ui = false;
+        while let Ok(request) = self.requests.try_recv() {
+            match request {
+                HostRequest::UiUpdated => print_ui = true,
+                // 刷新会同步触发一次 UiUpdated，在本循环中继续处理
+                HostRequest::UiRefresh => {
+                    self.manager
+                        .handle_plugin_ui_update(&self.instance_id, "", "")?;
+                }
+                HostRequest::Disconnect => {
+                    self.manager.disconnect_plugin(&self.instance_id)?;
+                    eprintln!("[插件请求断开连接]");
+                }
+            }
+        }
+        if print_ui && self.print_ui_updates {
+            self.print_ui()?;
+        }
+        Ok(())
+    }
+
+    fn print_ui(&self) -> Result<(), String> {
+        let ui = self.manager.get_plugin_ui(&self.instance_id)?;
+        if self.json {
+            let components: Value = serde_json::from_str(&ui).unwrap_or(Value::Null);
+            println!(
+                "{}",
+                serde_json::json!({ "event": "ui", "payload": components })
+            );
+            return Ok(());
+        }
+
+        let components: Vec<Value> =
+            serde_json::from_str(&ui).map_err(|e| format!("解析插件UI失败: {}", e))?;
+        println!("---- {} UI ----", self.plugin_id);
+        for component in &components {
+            print_component(component, 0);
+        }
+        println!("----");
+        Ok(())
+    }
+
+    /// 触发 UI 组件事件，按钮的值为空字符串
+    fn ui_event(&self, component_id: &str, value: &str) -> Result<(), String> {
+        if !self
+            .manager
+            .handle_plugin_ui_event(&self.instance_id, component_id, value)?
+        {
+            eprintln!("组件 {} 没有处理该事件", component_id);
+        }
+        self.printer.wait_until_settled(Duration::from_secs(5));
+        self.process_requests()
+    }
+
+    fn cancel_streams(&self) -> Result<(), String> {
+        for stream_id in self.printer.active_streams() {
+            self.manager
+                .cancel_stream_message(&self.instance_id, &stream_id)?;
+        }
+        Ok(())
+    }
+
+    fn close(self) {
+        self.manager.cleanup_all_plugins();
+    }
+}
+
+/// 以文本形式打印一个 UI 组件
+fn print_component(component: &Value, depth: usize) {
+    let indent = "  ".repeat(depth);
+    let id = component["id"].as_str().unwrap_or_default();
+    let body = &component["component"];
+    let text = |key: &str| body[key].as_str().unwrap_or_default().to_string();
+
+    match body["type"].as_str().unwrap_or_default() {
+        "Label" => println!("{}{}", indent, text("text")),
+        "Button" => {
+            let disabled = if body["enabled"] == Value::Bool(false) {
+                "（不可用）"
+            } else {
+                ""
+            };
+            println!("{}[{}] <{}>{}", indent, id, text("text"), disabled);
+        }
+        "TextEdit" => println!(
+            "{}[{}] 文本: {:?}（{}）",
+            indent,
+            id,
+            text("value"),
+            text("hint")
+        ),
+        "ComboBox" => {
+            let selected = body["selected"].as_u64().map(|index| index as usize);
+            let options = body["options"]
+                .as_array()
+                .map(|options| {
+                    options
+                        .iter()
+                        .enumerate()
+                        .map(|(index, option)| {
+                            let marker = if selected == Some(index) { "*" } else { "" };
+                            format!(
+                                "{}{}={}",
+                                marker,
+                                index,
+                                option.as_str().unwrap_or_default()
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                })
+                .unwrap_or_default();
+            println!("{}[{}] 选择: {}", indent, id, options);
+        }
+        "Toggle" => {
+            let state = if body["value"] == Value::Bool(true) {
+                "开"
+            } else {
+                "关"
+            };
+            println!("{}[{}] 开关: {}", indent, id, state);
+        }
+        "Horizontal" => {
+            for child in body["children"].as_array().into_iter().flatten() {
+                print_component(child, depth + 1);
+            }
+        }
+        other => println!("{}[{}] {}: {}", indent, id, other, body),
+    }
+}
+
+const RUN_HELP: &str = "\
+命令：
+  :ui                 打印插件 UI
+  :click <组件ID>      点击按钮
+  :set <组件ID> <值>   设置文本框、下拉框（选项序号）或开关（true/false）的值
+  :cancel             取消正在进行的流式消息
+  :quit               退出
+其他输入作为消息发送给插件，以 :: 开头可以发送以 : 开头的消息。";
+
+fn run_interactive(session: &Session) -> Result<(), String> {
+    if !session.json {
+        session.print_ui()?;
+        eprintln!("输入 :help 查看命令");
+    }
+
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        let line = line.map_err(|e| format!("读取输入失败: {}", e))?;
+        session.process_requests()?;
+
+        let result = if let Some(message) = line.strip_prefix("::") {
+            session.send(&format!(":{}", message), Duration::from_secs(300))
+        } else if let Some(command) = line.strip_prefix(':') {
+            let mut parts = command.trim().splitn(3, ' ');
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some("quit" | "q"), _, _) => break,
+                (Some("help" | "h"), _, _) => {
+                    eprintln!("{}", RUN_HELP);
+                    Ok(())
+                }
+                (Some("ui"), _, _) => session.print_ui(),
+                (Some("click"), Some(id), _) => session.ui_event(id, ""),
+                (Some("set"), Some(id), value) => session.ui_event(id, value.unwrap_or_default()),
+                (Some("cancel"), _, _) => session.cancel_streams(),
+                _ => Err(format!("未知命令: {}（输入 :help 查看命令）", line)),
+            }
+        } else if line.trim().is_empty() {
+            Ok(())
+        } else {
+            session.send(&line, Duration::from_secs(300))
+        };
+
+        if let Err(e) = result {
+            eprintln!("错误: {}", e);
+        }
+    }
+    Ok(())
+}
+
+fn run(cli: &Cli) -> Result<(), String> {
+    match &cli.command {
+        Command::List => {
+            let manager = PluginManager::new(Arc::new(RecordingEventSink::new()));
+            let plugins = manager.scan_plugins();
+            if cli.json {
+                let plugins = serde_json::to_string(&plugins)
+                    .map_err(|e| format!("序列化插件列表失败: {}", e))?;
+                println!("{}", plugins);
+            } else {
+                for plugin in plugins {
+                    let state = if plugin.disabled {
+                        "（已禁用）"
+                    } else {
+                        ""
+                    };
+                    println!(
+                        "{}\t{}\t{}{}",
+                        plugin.id, plugin.version, plugin.name, state
+                    );
+                }
+            }
+            Ok(())
+        }
+        Command::Send {
+            plugin,
+            message,
+            timeout,
+        } => {
+            let session = Session::open(cli, plugin, false)?;
+            let timeout = Duration::from_secs(*timeout);
+            let result = if message.is_empty() {
+                io::stdin()
+                    .lock()
+                    .lines()
+                    .map(|line| line.map_err(|e| format!("读取输入失败: {}", e)))
+                    .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+                    .try_for_each(|line| session.send(&line?, timeout))
+            } else {
+                session.send(&message.join(" "), timeout)
+            };
+            session.close();
+            result
+        }
+        Command::Ui { plugin } => {
+            let session = Session::open(cli, plugin, false)?;
+            let result = session.print_ui();
+            session.close();
+            result
+        }
+        Command::Run { plugin } => {
+            let session = Session::open(cli, plugin, true)?;
+            let result = run_interactive(&session);
+            session.close();
+            result
+        }
+        Command::Replay {
+            trace_file,
+            fast,
+            settle,
+        } => {
+            let options = ReplayOptions {
+                realtime: !fast,
+                settle: Duration::from_millis(*settle),
+                ..ReplayOptions::default()
+            };
+            let report = replay_trace(trace_file, &options)?;
+            if cli.json {
+                let report = serde_json::to_string(&report)
+                    .map_err(|e| format!("序列化回放报告失败: {}", e))?;
+                println!("{}", report);
+            } else {
+                println!(
+                    "调用 {} 次，记录事件 {} 个，回放事件 {} 个",
+                    report.calls, report.expected_events, report.actual_events
+                );
+                for difference in &report.differences {
+                    println!("差异: {}", difference.location);
+                    println!(
+                        "  记录: {}",
+                        difference.expected.as_deref().unwrap_or("（无）")
+                    );
+                    println!(
+                        "  回放: {}",
+                        difference.actual.as_deref().unwrap_or("（无）")
+                    );
+                }
+            }
+            if report.is_identical() {
+                Ok(())
+            } else {
+                Err(format!(
+                    "回放结果与跟踪记录不一致，共 {} 处差异",
+                    report.differences.len()
+                ))
+            }
+        }
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Some(data_dir) = &cli.data_dir {
+        directories::set_data_root_override(Some(data_dir.clone()));
+    }
+    if !cli.plugin_dir.is_empty() {
+        directories::set_plugin_directory_overrides(cli.plugin_dir.clone());
+    }
+
+    if let Err(e) = run(&cli) {
+        eprintln!("错误: {}", e);
+        std::process::exit(1);
+    }
+}
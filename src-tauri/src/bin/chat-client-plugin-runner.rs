@@ -0,0 +1,109 @@
+//! 远程插件运行器
+//!
+//! 在实验室机器等环境中加载本机插件，通过 TCP 提供给 `kind = "remote"` 的远程插件连接。
+
+use chat_client_lib::plugins::runner::RUNNER_TOKEN_ENV;
+use chat_client_lib::plugins::{directories, sandbox, PluginConfig, PluginRunner};
+use clap::Parser;
+use std::net::{TcpListener, ToSocketAddrs};
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "chat-client-plugin-runner",
+    version,
+    about = "通过网络提供本机插件，供远程插件连接"
+)]
+struct Args {
+    /// 插件ID，或包含 config.toml 的插件目录
+    plugin: String,
+    /// 监听地址
+    #[arg(long, default_value = "127.0.0.1:7890")]
+    listen: String,
+    /// 认证令牌，省略时读取环境变量 CHAT_CLIENT_RUNNER_TOKEN
+    #[arg(long)]
+    token: Option<String>,
+    /// 数据根目录（同 GUI 的 --data-dir）
+    #[arg(long)]
+    data_dir: Option<PathBuf>,
+    /// 额外的插件目录，可重复
+    #[arg(long)]
+    plugin_dir: Vec<PathBuf>,
+    /// 允许监听非回环地址。连接不加密，令牌和消息都以明文传输
+    #[arg(long)]
+    insecure: bool,
+    /// 作为插件沙箱的辅助进程运行，通过标准输入输出服务一条连接（由宿主启动）
+    #[arg(long)]
+    stdio: bool,
+}
+
+fn run(args: Args) -> Result<(), String> {
+    if let Some(data_dir) = &args.data_dir {
+        directories::set_data_root_override(Some(data_dir.clone()));
+    }
+    let mut plugin_dirs = args.plugin_dir.clone();
+
+    // 指定的是插件目录时，扫描其上级目录并使用配置中的插件ID
+    let plugin_path = PathBuf::from(&args.plugin);
+    let plugin_id = if plugin_path.join("config.toml").is_file() {
+        let config = PluginConfig::from_file(plugin_path.join("config.toml"))
+            .map_err(|e| format!("读取插件配置失败: {}", e))?;
+        let plugin_path = plugin_path
+            .canonicalize()
+            .map_err(|e| format!("无法访问插件目录: {}", e))?;
+        if let Some(parent) = plugin_path.parent() {
+            plugin_dirs.push(parent.to_path_buf());
+        }
+        config.plugin.id
+    } else {
+        args.plugin.clone()
+    };
+    if !plugin_dirs.is_empty() {
+        directories::set_plugin_directory_overrides(plugin_dirs);
+    }
+
+    let token = args
+        .token
+        .or_else(|| std::env::var(RUNNER_TOKEN_ENV).ok())
+        .ok_or("需要通过 --token 或环境变量 CHAT_CLIENT_RUNNER_TOKEN 指定认证令牌")?;
+    if args.stdio {
+        sandbox::mark_helper_process();
+    }
+    let runner = PluginRunner::new(&plugin_id, &token)?;
+    if args.stdio {
+        return serve_stdio(&runner);
+    }
+    let loopback_only = args
+        .listen
+        .to_socket_addrs()
+        .map_err(|e| format!("无法解析监听地址 {}: {}", args.listen, e))?
+        .all(|address| address.ip().is_loopback());
+    if !loopback_only && !args.insecure {
+        return Err(format!(
+            "监听地址 {} 不是本机回环地址。连接不加密，请监听 127.0.0.1 并通过 SSH 隧道访问，\
+             或加上 --insecure",
+            args.listen
+        ));
+    }
+    let listener =
+        TcpListener::bind(&args.listen).map_err(|e| format!("监听 {} 失败: {}", args.listen, e))?;
+    eprintln!("插件 {} 正在 {} 上等待连接", plugin_id, args.listen);
+    runner.serve(listener)
+}
+
+#[cfg(target_os = "linux")]
+fn serve_stdio(runner: &PluginRunner) -> Result<(), String> {
+    runner.serve_stdio()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn serve_stdio(_runner: &PluginRunner) -> Result<(), String> {
+    Err("--stdio 仅用于 Linux 上的插件沙箱".to_string())
+}
+
+fn main() {
+    if let Err(e) = run(Args::parse()) {
+        eprintln!("错误: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,84 @@
+//! 命令行子命令
+//!
+//! 第一个参数是已知子命令时在命令行中执行并退出，否则启动图形界面。
+
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+use crate::plugins::scaffold::{scaffold_plugin, PluginTemplate, ScaffoldOptions};
+
+/// 所有子命令的名称，用于判断是否需要进入命令行模式
+const SUBCOMMANDS: &[&str] = &["new-plugin"];
+
+#[derive(Debug, Parser)]
+#[command(name = "chat-client", version, about = "Chat Client 命令行工具")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// 根据模板生成新的插件 crate
+    NewPlugin {
+        /// 插件ID（小写字母、数字、_ 和 -）
+        #[arg(long)]
+        id: String,
+        /// 插件显示名称
+        #[arg(long)]
+        name: String,
+        #[arg(long, default_value = "")]
+        author: String,
+        /// 动态库名称，默认由插件ID转换而来
+        #[arg(long)]
+        library: Option<String>,
+        /// 插件模板：echo、streaming 或 ui
+        #[arg(long, default_value = "echo", value_parser = PluginTemplate::parse)]
+        template: PluginTemplate,
+        /// 新插件的父目录
+        #[arg(long, default_value = ".")]
+        output: PathBuf,
+        /// 将新 crate 加入该目录下 Cargo.toml 的 workspace
+        #[arg(long)]
+        workspace: Option<PathBuf>,
+    },
+}
+
+/// 如果命令行参数是子命令则执行并返回退出码，否则返回 None
+pub fn try_run_subcommand() -> Option<i32> {
+    let is_subcommand = std::env::args()
+        .nth(1)
+        .is_some_and(|arg| SUBCOMMANDS.contains(&arg.as_str()));
+    if !is_subcommand {
+        return None;
+    }
+
+    let result = match Cli::parse().command {
+        Command::NewPlugin {
+            id,
+            name,
+            author,
+            library,
+            template,
+            output,
+            workspace,
+        } => scaffold_plugin(&ScaffoldOptions {
+            id,
+            name,
+            author,
+            library_name: library,
+            template,
+            output_dir: output,
+            workspace,
+        })
+        .map(|plugin_dir| println!("插件已生成: {}", plugin_dir.display())),
+    };
+
+    Some(match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            1
+        }
+    })
+}
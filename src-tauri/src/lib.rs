@@ -1,13 +1,20 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 mod api;
+pub mod cli;
 pub mod plugins;
 
 // 导入所有 API 命令
 use api::{
-    cancel_stream_message, connect_plugin, disconnect_plugin, dispose_plugin, download_github_repo,
-    download_plugin, get_plugin_status, get_plugin_ui, greet, handle_plugin_ui_event,
-    handle_plugin_ui_update, mount_plugin, scan_available_plugins, scan_plugins,
-    send_message_to_plugin, uninstall_plugin,
+    approve_plugin_permissions, cancel_download, cancel_stream_message, connect_plugin,
+    disconnect_plugin, dispose_plugin, download_github_repo, download_plugin,
+    get_effective_directories, get_network_settings, get_path_settings, get_plugin_permissions,
+    get_plugin_resource_usage, get_plugin_status, get_plugin_trace_path, get_plugin_ui,
+    get_sanitize_settings, greet, handle_plugin_ui_event, handle_plugin_ui_update,
+    install_plugin_from_file, install_plugin_from_git, link_plugin_directory, list_linked_plugins,
+    mount_plugin, rebuild_linked_plugin, reload_plugin, replay_plugin_trace, scaffold_plugin,
+    scan_available_plugins, scan_plugins, send_message_to_plugin, set_network_settings,
+    set_path_settings, set_sanitize_settings, start_plugin_trace, stop_plugin_trace,
+    uninstall_plugin, unlink_plugin_directory, update_plugin,
 };
 
 use plugin_interfaces::log_info;
@@ -15,6 +22,9 @@ use tauri::{RunEvent, WindowEvent};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    // 命令行参数 --data-dir / --plugin-dir
+    plugins::directories::apply_command_line_overrides(std::env::args().skip(1));
+
     let app = tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .invoke_handler(tauri::generate_handler![
@@ -33,7 +43,31 @@ pub fn run() {
             scan_available_plugins,
             download_plugin,
             uninstall_plugin,
-            cancel_stream_message
+            get_plugin_permissions,
+            approve_plugin_permissions,
+            cancel_stream_message,
+            cancel_download,
+            get_network_settings,
+            set_network_settings,
+            get_sanitize_settings,
+            set_sanitize_settings,
+            install_plugin_from_file,
+            install_plugin_from_git,
+            update_plugin,
+            reload_plugin,
+            list_linked_plugins,
+            link_plugin_directory,
+            unlink_plugin_directory,
+            rebuild_linked_plugin,
+            get_path_settings,
+            set_path_settings,
+            get_effective_directories,
+            scaffold_plugin,
+            start_plugin_trace,
+            stop_plugin_trace,
+            get_plugin_trace_path,
+            replay_plugin_trace,
+            get_plugin_resource_usage
         ])
         .build(tauri::generate_context!())
         .expect("error while building tauri application");
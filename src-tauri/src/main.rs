@@ -2,5 +2,9 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+    // new-plugin 等子命令在命令行中执行，不启动图形界面
+    if let Some(code) = chat_client_lib::cli::try_run_subcommand() {
+        std::process::exit(code);
+    }
     chat_client_lib::run()
 }
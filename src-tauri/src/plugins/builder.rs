@@ -0,0 +1,254 @@
+use plugin_interfaces::log_info;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+
+use crate::plugins::{
+    config::SourceConfig,
+    directories::get_build_directory,
+    download::DownloadTask,
+    git::{checkout, source_checkout_dir},
+};
+
+/// 构建日志事件名称
+pub const BUILD_LOG_EVENT: &str = "plugin-build-log";
+
+/// 构建期间检查取消标记的间隔
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// 构建日志的来源
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BuildLogStream {
+    /// 宿主自身的状态信息（克隆、开始构建等）
+    Status,
+    Stdout,
+    Stderr,
+}
+
+/// 一行构建日志（作为 plugin-build-log 事件载荷发送到前端）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildLogLine {
+    /// 与触发构建的下载任务ID相同
+    pub build_id: String,
+    pub plugin_id: String,
+    pub stream: BuildLogStream,
+    pub line: String,
+}
+
+/// 源码构建的结果
+#[derive(Debug, Clone)]
+pub struct BuildOutput {
+    /// 构建出的动态库路径
+    pub library_path: PathBuf,
+    /// 构建时检出的提交（仅 Git 源码）
+    pub commit: Option<String>,
+}
+
+/// 从源码构建插件动态库
+///
+/// Git 源码会被检出到 `sources` 下的对应目录，否则 `source.path` 相对于 `repository_root` 解析。
+/// 构建使用 `cargo build --release`，目标目录固定为 `build/<id>/target` 以便复用增量编译结果。
+/// 构建输出逐行通过 `on_log` 回调，`task` 被取消时终止构建进程。
+pub async fn build_plugin_from_source<F>(
+    plugin_id: &str,
+    source: &SourceConfig,
+    repository_root: &Path,
+    task: &DownloadTask,
+    on_log: F,
+) -> Result<BuildOutput, String>
+where
+    F: Fn(&BuildLogLine),
+{
+    let log = |stream: BuildLogStream, line: String| {
+        on_log(&BuildLogLine {
+            build_id: task.download_id.clone(),
+            plugin_id: plugin_id.to_string(),
+            stream,
+            line,
+        })
+    };
+
+    let build_dir = get_build_directory().join(plugin_id);
+    tokio::fs::create_dir_all(&build_dir)
+        .await
+        .map_err(|e| format!("创建构建目录失败: {}", e))?;
+
+    let (source_root, commit) = match &source.git {
+        Some(url) => {
+            let checkout_dir = source_checkout_dir(url);
+            log(BuildLogStream::Status, format!("正在获取源码: {}", url));
+            let commit = checkout(url, source.rev.as_deref(), &checkout_dir).await?;
+            log(BuildLogStream::Status, format!("已检出提交 {}", commit));
+            (checkout_dir, Some(commit))
+        }
+        None => (repository_root.to_path_buf(), None),
+    };
+
+    let crate_dir = match &source.path {
+        Some(path) => source_root.join(path),
+        None => source_root.clone(),
+    };
+    let manifest_path = crate_dir.join("Cargo.toml");
+    if !manifest_path.is_file() {
+        return Err(format!("源码目录中缺少 Cargo.toml: {:?}", crate_dir));
+    }
+    // 防止 path 通过 .. 指向源码目录之外
+    let canonical_root = source_root
+        .canonicalize()
+        .map_err(|e| format!("无法解析源码目录: {}", e))?;
+    let canonical_manifest = manifest_path
+        .canonicalize()
+        .map_err(|e| format!("无法解析 Cargo.toml 路径: {}", e))?;
+    if !canonical_manifest.starts_with(&canonical_root) {
+        return Err(format!("源码路径超出仓库范围: {:?}", source.path));
+    }
+
+    log_info!("开始从源码构建插件 {}: {:?}", plugin_id, crate_dir);
+    let library_path = cargo_build(
+        &CargoBuild {
+            manifest_path: &canonical_manifest,
+            target_dir: &build_dir.join("target"),
+            release: true,
+            package: source.package.as_deref(),
+        },
+        || task.is_cancelled(),
+        log,
+    )
+    .await?;
+
+    Ok(BuildOutput {
+        library_path,
+        commit,
+    })
+}
+
+/// 一次 cargo 构建的参数
+pub struct CargoBuild<'a> {
+    pub manifest_path: &'a Path,
+    /// 作为 CARGO_TARGET_DIR 传给 cargo
+    pub target_dir: &'a Path,
+    pub release: bool,
+    pub package: Option<&'a str>,
+}
+
+/// 运行 `cargo build --lib` 并返回构建出的 cdylib 路径
+///
+/// 构建输出逐行通过 `log` 回调，`is_cancelled` 返回 true 时终止构建进程。
+pub async fn cargo_build<C, L>(
+    build: &CargoBuild<'_>,
+    is_cancelled: C,
+    log: L,
+) -> Result<PathBuf, String>
+where
+    C: Fn() -> bool,
+    L: Fn(BuildLogStream, String),
+{
+    let crate_dir = build.manifest_path.parent().unwrap_or(Path::new("."));
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let mut command = Command::new(cargo);
+    command.arg("build");
+    if build.release {
+        command.arg("--release");
+    }
+    command
+        .arg("--lib")
+        .arg("--message-format=json-render-diagnostics")
+        .arg("--manifest-path")
+        .arg(build.manifest_path)
+        .env("CARGO_TARGET_DIR", build.target_dir)
+        .current_dir(crate_dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    if let Some(package) = build.package {
+        command.arg("--package").arg(package);
+    }
+
+    log(
+        BuildLogStream::Status,
+        format!(
+            "开始构建: cargo build{} ({:?})",
+            if build.release { " --release" } else { "" },
+            crate_dir
+        ),
+    );
+
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("无法启动 cargo，请确认已安装 Rust 工具链: {}", e))?;
+    let mut stdout = BufReader::new(child.stdout.take().unwrap()).lines();
+    let mut stderr = BufReader::new(child.stderr.take().unwrap()).lines();
+
+    let mut library_path = None;
+    let (mut stdout_open, mut stderr_open) = (true, true);
+    // 输出持续不断时 select 总是先选中读取分支，因此每轮都检查取消状态，
+    // 定时器只负责在没有输出时唤醒
+    let mut cancel_poll = tokio::time::interval(CANCEL_POLL_INTERVAL);
+    cancel_poll.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    while stdout_open || stderr_open {
+        if is_cancelled() {
+            let _ = child.kill().await;
+            return Err("构建已取消".to_string());
+        }
+        tokio::select! {
+            line = stdout.next_line(), if stdout_open => match line {
+                Ok(Some(line)) => {
+                    // 标准输出是 cargo 的 JSON 消息，只从中提取构建产物
+                    if let Some(path) = cdylib_artifact(&line) {
+                        library_path = Some(path);
+                    } else if !line.starts_with('{') {
+                        log(BuildLogStream::Stdout, line);
+                    }
+                }
+                _ => stdout_open = false,
+            },
+            line = stderr.next_line(), if stderr_open => match line {
+                Ok(Some(line)) => log(BuildLogStream::Stderr, line),
+                _ => stderr_open = false,
+            },
+            _ = cancel_poll.tick() => {}
+        }
+    }
+
+    let status = child
+        .wait()
+        .await
+        .map_err(|e| format!("等待构建进程失败: {}", e))?;
+    if !status.success() {
+        return Err(format!("构建失败: cargo 退出状态 {}", status));
+    }
+
+    let library_path =
+        library_path.ok_or_else(|| "构建完成，但没有产生 cdylib 动态库".to_string())?;
+    log(
+        BuildLogStream::Status,
+        format!("构建完成: {:?}", library_path),
+    );
+    Ok(library_path)
+}
+
+/// 从 cargo 的 compiler-artifact 消息中提取当前平台的 cdylib 路径
+fn cdylib_artifact(line: &str) -> Option<PathBuf> {
+    let message: serde_json::Value = serde_json::from_str(line).ok()?;
+    if message["reason"] != "compiler-artifact" {
+        return None;
+    }
+    let is_cdylib = message["target"]["crate_types"]
+        .as_array()?
+        .iter()
+        .any(|crate_type| crate_type == "cdylib");
+    if !is_cdylib {
+        return None;
+    }
+    message["filenames"]
+        .as_array()?
+        .iter()
+        .filter_map(|filename| filename.as_str())
+        .find(|filename| filename.ends_with(std::env::consts::DLL_SUFFIX))
+        .map(PathBuf::from)
+}
@@ -0,0 +1,105 @@
+use super::{builtin_plugins, BuiltinPlugin};
+use crate::plugins::directories::{get_plugin_repository_root, get_plugins_directories};
+use plugin_interfaces::{
+    pluginui::{Context, Ui},
+    PluginHandler, PluginInstanceContext,
+};
+
+pub fn plugin() -> BuiltinPlugin {
+    BuiltinPlugin {
+        id: "builtin-diagnostics",
+        name: "Diagnostics",
+        version: env!("CARGO_PKG_VERSION"),
+        description: "报告客户端版本、平台、插件目录和当前会话信息",
+        author: "chat-client",
+        require_history: true,
+        create: || Box::new(DiagnosticsPlugin),
+    }
+}
+
+/// 诊断插件，收到任意消息时回复宿主环境信息
+struct DiagnosticsPlugin;
+
+impl DiagnosticsPlugin {
+    fn report(&self, plugin_ctx: &PluginInstanceContext) -> String {
+        let metadata = plugin_ctx.get_metadata();
+        let mut lines = vec![
+            "## 诊断信息".to_string(),
+            format!("- 客户端版本: {}", env!("CARGO_PKG_VERSION")),
+            format!(
+                "- 平台: {} / {}",
+                std::env::consts::OS,
+                std::env::consts::ARCH
+            ),
+            format!(
+                "- 插件实例: {}",
+                metadata.instance_id.as_deref().unwrap_or("-")
+            ),
+            format!(
+                "- 历史消息数: {}",
+                plugin_ctx.get_history().map_or(0, |history| history.len())
+            ),
+            format!("- 数据目录: {}", get_plugin_repository_root().display()),
+            "- 插件目录:".to_string(),
+        ];
+        for dir in get_plugins_directories() {
+            let state = if dir.exists() { "" } else { "（不存在）" };
+            lines.push(format!("  - {}{}", dir.display(), state));
+        }
+        let builtin: Vec<&str> = builtin_plugins().iter().map(|p| p.id).collect();
+        lines.push(format!("- 内置插件: {}", builtin.join(", ")));
+        lines.join("\n")
+    }
+}
+
+impl PluginHandler for DiagnosticsPlugin {
+    fn update_ui(&mut self, _ctx: &Context, ui: &mut Ui, plugin_ctx: &PluginInstanceContext) {
+        ui.label(&format!(
+            "chat-client {} ({} / {})",
+            env!("CARGO_PKG_VERSION"),
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        ));
+        if ui.button("发送诊断信息").clicked() {
+            plugin_ctx.send_message_to_frontend(&self.report(plugin_ctx));
+        }
+    }
+
+    fn on_mount(
+        &mut self,
+        _plugin_ctx: &PluginInstanceContext,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    fn on_dispose(
+        &mut self,
+        _plugin_ctx: &PluginInstanceContext,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    fn on_connect(
+        &mut self,
+        _plugin_ctx: &PluginInstanceContext,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    fn on_disconnect(
+        &mut self,
+        _plugin_ctx: &PluginInstanceContext,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    fn handle_message(
+        &mut self,
+        _message: &str,
+        plugin_ctx: &PluginInstanceContext,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let report = self.report(plugin_ctx);
+        plugin_ctx.send_message_to_frontend(&report);
+        Ok(report)
+    }
+}
@@ -0,0 +1,76 @@
+use super::BuiltinPlugin;
+use plugin_interfaces::{
+    pluginui::{Context, Ui},
+    PluginHandler, PluginInstanceContext,
+};
+
+pub fn plugin() -> BuiltinPlugin {
+    BuiltinPlugin {
+        id: "builtin-echo",
+        name: "Echo",
+        version: env!("CARGO_PKG_VERSION"),
+        description: "原样返回收到的消息，可添加前缀",
+        author: "chat-client",
+        require_history: false,
+        create: || Box::new(EchoPlugin::default()),
+    }
+}
+
+/// 回显插件，用于检查消息收发是否正常
+#[derive(Default)]
+struct EchoPlugin {
+    prefix: String,
+    uppercase: bool,
+}
+
+impl PluginHandler for EchoPlugin {
+    fn update_ui(&mut self, _ctx: &Context, ui: &mut Ui, _plugin_ctx: &PluginInstanceContext) {
+        ui.label("前缀");
+        ui.text_edit_singleline(&mut self.prefix);
+        ui.horizontal(|ui| {
+            ui.label("转为大写");
+            ui.toggle(&mut self.uppercase);
+        });
+    }
+
+    fn on_mount(
+        &mut self,
+        _plugin_ctx: &PluginInstanceContext,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    fn on_dispose(
+        &mut self,
+        _plugin_ctx: &PluginInstanceContext,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    fn on_connect(
+        &mut self,
+        _plugin_ctx: &PluginInstanceContext,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    fn on_disconnect(
+        &mut self,
+        _plugin_ctx: &PluginInstanceContext,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    fn handle_message(
+        &mut self,
+        message: &str,
+        plugin_ctx: &PluginInstanceContext,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let mut reply = format!("{}{}", self.prefix, message);
+        if self.uppercase {
+            reply = reply.to_uppercase();
+        }
+        plugin_ctx.send_message_to_frontend(&reply);
+        Ok(reply)
+    }
+}
@@ -0,0 +1,104 @@
+//! 编译进宿主的内置插件
+//!
+//! 内置插件直接在宿主中实现 `PluginHandler`，不需要插件目录、config.toml 和动态库。
+//! 它们由 cargo 特性控制是否编译，首次使用注册表时登记，也可以通过 `register_builtin_plugin`
+//! 在启动时登记其他实现。`scan_plugins` 会在所有插件目录之后列出内置插件，
+//! 目录中相同 ID 的插件优先，便于把内置插件当作动态库开发调试。
+
+#[cfg(feature = "builtin-diagnostics")]
+mod diagnostics;
+#[cfg(feature = "builtin-echo")]
+mod echo;
+
+use plugin_interfaces::{log_warn, PluginHandler, PluginMetadata};
+use std::sync::{OnceLock, RwLock};
+
+/// 内置插件元数据中 `config_path` 和 `library_path` 的前缀
+pub const BUILTIN_PATH_PREFIX: &str = "builtin:";
+
+/// 一个内置插件
+#[derive(Clone)]
+pub struct BuiltinPlugin {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub version: &'static str,
+    pub description: &'static str,
+    pub author: &'static str,
+    /// 是否需要接收历史记录
+    pub require_history: bool,
+    /// 为每个插件实例创建新的处理器
+    pub create: fn() -> Box<dyn PluginHandler>,
+}
+
+impl BuiltinPlugin {
+    /// 生成与目录插件相同格式的元数据，路径以 `builtin:` 开头
+    pub fn metadata(&self) -> PluginMetadata {
+        let path = format!("{}{}", BUILTIN_PATH_PREFIX, self.id);
+        PluginMetadata {
+            id: self.id.to_string(),
+            disabled: false,
+            name: self.name.to_string(),
+            description: self.description.to_string(),
+            version: self.version.to_string(),
+            author: Some(self.author.to_string()),
+            library_path: Some(path.clone()),
+            config_path: path,
+            instance_id: None,
+            require_history: self.require_history,
+        }
+    }
+}
+
+impl std::fmt::Debug for BuiltinPlugin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BuiltinPlugin")
+            .field("id", &self.id)
+            .field("version", &self.version)
+            .finish()
+    }
+}
+
+fn registry() -> &'static RwLock<Vec<BuiltinPlugin>> {
+    static REGISTRY: OnceLock<RwLock<Vec<BuiltinPlugin>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let plugins: Vec<BuiltinPlugin> = vec![
+            #[cfg(feature = "builtin-echo")]
+            echo::plugin(),
+            #[cfg(feature = "builtin-diagnostics")]
+            diagnostics::plugin(),
+        ];
+        RwLock::new(plugins)
+    })
+}
+
+/// 登记内置插件，已存在相同 ID 时替换
+pub fn register_builtin_plugin(plugin: BuiltinPlugin) {
+    let mut plugins = registry().write().unwrap();
+    if let Some(existing) = plugins.iter_mut().find(|p| p.id == plugin.id) {
+        log_warn!("内置插件 {} 已登记，替换为新的实现", plugin.id);
+        *existing = plugin;
+    } else {
+        plugins.push(plugin);
+    }
+}
+
+/// 所有已登记的内置插件
+pub fn builtin_plugins() -> Vec<BuiltinPlugin> {
+    registry().read().unwrap().clone()
+}
+
+/// 元数据是否属于内置插件
+pub fn is_builtin(metadata: &PluginMetadata) -> bool {
+    metadata.config_path.starts_with(BUILTIN_PATH_PREFIX)
+}
+
+/// 为内置插件创建新的处理器
+pub fn create_builtin_handler(plugin_id: &str) -> Result<Box<dyn PluginHandler>, String> {
+    registry()
+        .read()
+        .unwrap()
+        .iter()
+        .find(|p| p.id == plugin_id)
+        .map(|p| (p.create)())
+        .ok_or_else(|| format!("内置插件 {} 未登记", plugin_id))
+}
@@ -0,0 +1,135 @@
+//! 每个插件实例专属的主程序回调
+//!
+//! `HostCallbacks` 中的回调函数没有上下文参数。宿主为每个挂载的实例分配一个槽位，
+//! 交给插件的是该槽位专属的一组函数指针，回调被调用时由函数指针本身确定调用方实例，
+//! 与调用线程和载荷内容无关：插件在自己创建的线程（流式输出、后台任务）中调用回调
+//! 也能归属到正确的实例，载荷中的实例ID只用于和调用方比对。
+//!
+//! 槽位数量固定为 [`SLOT_COUNT`]，即同时挂载的实例数上限。释放的槽位按先进先出的顺序复用，
+//! 尽量推迟同一组函数指针被交给另一个实例。
+
+use crate::plugins::manager::PluginManager;
+use plugin_interfaces::{log_warn, HostCallbacks};
+use std::collections::VecDeque;
+use std::os::raw::c_char;
+use std::sync::{OnceLock, RwLock};
+
+/// 槽位数量
+pub const SLOT_COUNT: usize = 128;
+
+type SendToFrontendFn = extern "C" fn(*const c_char, *const c_char) -> bool;
+type GetAppConfigFn = extern "C" fn(*const c_char) -> *const c_char;
+type CallOtherPluginFn = extern "C" fn(*const c_char, *const c_char) -> *const c_char;
+
+/// 各槽位的占用实例和空闲队列
+struct Slots {
+    owners: Vec<Option<String>>,
+    free: VecDeque<usize>,
+}
+
+static SLOTS: OnceLock<RwLock<Slots>> = OnceLock::new();
+
+fn slots() -> &'static RwLock<Slots> {
+    SLOTS.get_or_init(|| {
+        RwLock::new(Slots {
+            owners: vec![None; SLOT_COUNT],
+            free: (0..SLOT_COUNT).collect(),
+        })
+    })
+}
+
+/// 为实例分配槽位，返回槽位号和交给插件的回调函数
+pub fn allocate(instance_id: &str) -> Result<(usize, HostCallbacks), String> {
+    let mut slots = slots().write().unwrap();
+    let slot = slots.free.pop_front().ok_or_else(|| {
+        format!(
+            "同时挂载的插件实例超过上限 {}，无法挂载实例 {}",
+            SLOT_COUNT, instance_id
+        )
+    })?;
+    slots.owners[slot] = Some(instance_id.to_string());
+    Ok((
+        slot,
+        HostCallbacks {
+            send_to_frontend: SEND_TO_FRONTEND[slot],
+            get_app_config: GET_APP_CONFIG[slot],
+            call_other_plugin: CALL_OTHER_PLUGIN[slot],
+        },
+    ))
+}
+
+/// 释放槽位，之后通过该槽位的回调都会被拒绝，直到槽位被重新分配
+pub fn release(slot: usize) {
+    let mut slots = slots().write().unwrap();
+    if slots.owners[slot].take().is_some() {
+        slots.free.push_back(slot);
+    }
+}
+
+/// 槽位当前所属的实例
+fn owner(slot: usize) -> Option<String> {
+    slots().read().unwrap().owners[slot].clone()
+}
+
+extern "C" fn send_to_frontend<const SLOT: usize>(
+    event: *const c_char,
+    payload: *const c_char,
+) -> bool {
+    match owner(SLOT) {
+        Some(instance_id) => PluginManager::host_send_to_frontend(&instance_id, event, payload),
+        None => {
+            log_warn!(
+                "[PLUGIN->FRONTEND] 回调槽位 {} 没有对应的实例，事件已丢弃",
+                SLOT
+            );
+            false
+        }
+    }
+}
+
+extern "C" fn get_app_config<const SLOT: usize>(key: *const c_char) -> *const c_char {
+    match owner(SLOT) {
+        Some(instance_id) => PluginManager::host_get_app_config(&instance_id, key),
+        None => {
+            log_warn!("回调槽位 {} 没有对应的实例，已拒绝读取配置", SLOT);
+            std::ptr::null()
+        }
+    }
+}
+
+extern "C" fn call_other_plugin<const SLOT: usize>(
+    plugin_id: *const c_char,
+    message: *const c_char,
+) -> *const c_char {
+    match owner(SLOT) {
+        Some(instance_id) => {
+            PluginManager::host_call_other_plugin(&instance_id, plugin_id, message)
+        }
+        None => {
+            log_warn!("回调槽位 {} 没有对应的实例，已拒绝调用其他插件", SLOT);
+            std::ptr::null()
+        }
+    }
+}
+
+/// 为每个槽位实例化一组回调函数
+macro_rules! slot_callbacks {
+    ($($slot:literal)*) => {
+        static SEND_TO_FRONTEND: [SendToFrontendFn; SLOT_COUNT] =
+            [$(send_to_frontend::<$slot>),*];
+        static GET_APP_CONFIG: [GetAppConfigFn; SLOT_COUNT] = [$(get_app_config::<$slot>),*];
+        static CALL_OTHER_PLUGIN: [CallOtherPluginFn; SLOT_COUNT] =
+            [$(call_other_plugin::<$slot>),*];
+    };
+}
+
+slot_callbacks!(
+    0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15
+    16 17 18 19 20 21 22 23 24 25 26 27 28 29 30 31
+    32 33 34 35 36 37 38 39 40 41 42 43 44 45 46 47
+    48 49 50 51 52 53 54 55 56 57 58 59 60 61 62 63
+    64 65 66 67 68 69 70 71 72 73 74 75 76 77 78 79
+    80 81 82 83 84 85 86 87 88 89 90 91 92 93 94 95
+    96 97 98 99 100 101 102 103 104 105 106 107 108 109 110 111
+    112 113 114 115 116 117 118 119 120 121 122 123 124 125 126 127
+);
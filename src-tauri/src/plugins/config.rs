@@ -1,3 +1,4 @@
+use crate::plugins::runtime::PluginKind;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -8,6 +9,18 @@ pub struct PluginConfig {
     #[serde(default)]
     pub download: Option<DownloadConfig>,
     #[serde(default)]
+    pub source: Option<SourceConfig>,
+    #[serde(default)]
+    pub subprocess: Option<SubprocessConfig>,
+    #[serde(default)]
+    pub remote: Option<RemoteConfig>,
+    #[serde(default)]
+    pub sandbox: Option<SandboxConfig>,
+    #[serde(default)]
+    pub limits: Option<ResourceLimits>,
+    #[serde(default)]
+    pub permissions: PluginPermissions,
+    #[serde(default)]
     pub metadata: HashMap<String, toml::Value>,
 }
 
@@ -41,6 +54,9 @@ pub struct PluginInfo {
     pub dependencies: Vec<String>,
     #[serde(default)]
     pub library: Option<String>, // 动态库文件名
+    /// 插件类型（native / wasm / subprocess / script / remote），默认为 native
+    #[serde(default)]
+    pub kind: PluginKind,
     /// 是否需要接收历史记录，默认为 false
     #[serde(default)]
     pub require_history: bool,
@@ -64,6 +80,162 @@ pub struct PlatformDownload {
     pub download_url: String,
 }
 
+/// 源码位置，当前平台没有预编译的动态库时用于从源码构建
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceConfig {
+    /// Git 仓库地址；为空时表示源码位于插件仓库中
+    #[serde(default)]
+    pub git: Option<String>,
+    /// 要检出的分支、标签或提交，默认为远程仓库的默认分支
+    #[serde(default)]
+    pub rev: Option<String>,
+    /// crate 所在目录，相对于 Git 仓库根目录（或插件仓库根目录）
+    #[serde(default)]
+    pub path: Option<String>,
+    /// 工作区中需要构建的包名
+    #[serde(default)]
+    pub package: Option<String>,
+}
+
+/// 子进程插件的启动方式，`library` 为可执行文件或脚本
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubprocessConfig {
+    /// 运行插件文件的解释器，如 `python3`、`node`；为空时直接执行插件文件
+    #[serde(default)]
+    pub command: Option<String>,
+    /// 追加在插件文件之后的参数
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// 额外的环境变量
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// 等待插件响应一次请求的最长秒数
+    #[serde(default = "default_subprocess_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_subprocess_timeout() -> u64 {
+    60
+}
+
+impl Default for SubprocessConfig {
+    fn default() -> Self {
+        Self {
+            command: None,
+            args: Vec::new(),
+            env: HashMap::new(),
+            timeout_secs: default_subprocess_timeout(),
+        }
+    }
+}
+
+/// 远程插件的连接方式，插件运行在另一台机器的 `chat-client-plugin-runner` 中
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteConfig {
+    /// 运行器的主机和端口，如 `127.0.0.1:7890`（非回环地址需要设置 `insecure`）
+    pub address: String,
+    /// 认证令牌
+    #[serde(default)]
+    pub token: Option<String>,
+    /// 保存认证令牌的环境变量名，设置后优先于 `token`
+    #[serde(default)]
+    pub token_env: Option<String>,
+    /// 建立连接的最长秒数
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout_secs: u64,
+    /// 等待插件响应一次请求的最长秒数
+    #[serde(default = "default_subprocess_timeout")]
+    pub timeout_secs: u64,
+    /// 允许连接非回环地址。连接不加密，令牌和消息都以明文传输
+    #[serde(default)]
+    pub insecure: bool,
+}
+
+fn default_connect_timeout() -> u64 {
+    10
+}
+
+/// 进程外插件的沙箱策略，仅在 Linux 上生效
+///
+/// 路径可以是绝对路径、以 `~/` 开头的主目录路径，或相对于插件目录的路径。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SandboxConfig {
+    /// 是否在沙箱中运行插件；原生、WebAssembly 和脚本插件会改为在辅助进程中运行
+    #[serde(default)]
+    pub enabled: bool,
+    /// 额外允许读取的路径，需要在 `[permissions]` 的 `filesystem` 或 `serial` 范围内
+    #[serde(default)]
+    pub read: Vec<String>,
+    /// 额外允许读写的路径，如串口设备，需要在 `[permissions]` 的 `filesystem` 或 `serial` 范围内
+    #[serde(default)]
+    pub write: Vec<String>,
+    /// 是否允许访问网络，只有申请了 `network` 权限时才生效
+    #[serde(default)]
+    pub network: bool,
+}
+
+/// 插件资源限制，未设置的项不限制
+///
+/// CPU 占用按 `window_secs` 内的平均值计算，100 表示占满一个核；内存只对进程外插件生效。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResourceLimits {
+    /// 超过时记录警告并通知前端
+    #[serde(default)]
+    pub cpu_soft_percent: Option<f64>,
+    /// 超过时停止插件实例；进程内插件在当前调用返回后卸载
+    #[serde(default)]
+    pub cpu_hard_percent: Option<f64>,
+    /// 常驻内存软限制（MB）
+    #[serde(default)]
+    pub memory_soft_mb: Option<u64>,
+    /// 常驻内存硬限制（MB）
+    #[serde(default)]
+    pub memory_hard_mb: Option<u64>,
+    /// 计算 CPU 占用的时间窗口（秒）
+    #[serde(default = "default_limits_window")]
+    pub window_secs: u64,
+}
+
+fn default_limits_window() -> u64 {
+    10
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self {
+            cpu_soft_percent: None,
+            cpu_hard_percent: None,
+            memory_soft_mb: None,
+            memory_hard_mb: None,
+            window_secs: default_limits_window(),
+        }
+    }
+}
+
+/// 插件申请的权限，需要用户批准后插件才能挂载
+///
+/// 条目中的 `*` 匹配任意字符。`plugins`、`events` 和 `config` 由宿主回调检查；
+/// `network`、`filesystem`、`serial` 和 `subprocess` 是插件自己发起的访问，启用沙箱时由沙箱限制，
+/// 未启用沙箱时宿主只向用户说明，无法拦截。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PluginPermissions {
+    /// 访问的网络主机，如 `api.deepseek.com`、`*.example.com`
+    pub network: Vec<String>,
+    /// 读写的文件或目录
+    pub filesystem: Vec<String>,
+    /// 打开的串口设备，如 `/dev/ttyUSB*`、`COM*`
+    pub serial: Vec<String>,
+    /// 启动的程序
+    pub subprocess: Vec<String>,
+    /// 可以通过 `call_other_plugin` 调用的插件ID
+    pub plugins: Vec<String>,
+    /// 可以发送给前端的事件名（宿主定义的插件事件无需申请）
+    pub events: Vec<String>,
+    /// 可以通过 `get_app_config` 读取的设置项，如 `network.proxy`
+    pub config: Vec<String>,
+}
+
 impl PluginConfig {
     /// 从 TOML 文件加载插件配置
     pub fn from_file<P: AsRef<std::path::Path>>(
@@ -1,27 +1,181 @@
-use std::path::PathBuf;
+use plugin_interfaces::log_info;
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
 
+use crate::plugins::settings::load_path_settings;
+
+/// 应用名称，用于平台默认目录
+const APP_DIR_NAME: &str = "chat-client";
+
+/// 运行时指定的目录（命令行参数或测试代码），优先级最高
+#[derive(Debug, Default)]
+struct DirectoryOverrides {
+    data_root: Option<PathBuf>,
+    plugin_directories: Vec<PathBuf>,
+}
+
+static DIRECTORY_OVERRIDES: OnceLock<RwLock<DirectoryOverrides>> = OnceLock::new();
+
+fn get_directory_overrides() -> &'static RwLock<DirectoryOverrides> {
+    DIRECTORY_OVERRIDES.get_or_init(|| RwLock::new(DirectoryOverrides::default()))
+}
+
+/// 指定数据根目录，None 表示恢复按环境变量和设置解析
+///
+/// 供命令行参数和测试使用，测试可以借此使用临时目录。
+pub fn set_data_root_override(data_root: Option<PathBuf>) {
+    get_directory_overrides().write().unwrap().data_root = data_root;
+}
+
+/// 指定额外的插件目录，替换之前通过此函数指定的目录
+pub fn set_plugin_directory_overrides(directories: Vec<PathBuf>) {
+    get_directory_overrides()
+        .write()
+        .unwrap()
+        .plugin_directories = directories;
+}
+
+/// 解析命令行中的 `--data-dir <路径>` 和 `--plugin-dir <路径>`（可重复）参数
+///
+/// 同时支持 `--data-dir=<路径>` 形式，其他参数被忽略。
+pub fn apply_command_line_overrides<I>(args: I)
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut data_root = None;
+    let mut plugin_directories = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+            None => (arg, None),
+        };
+        match flag.as_str() {
+            "--data-dir" => data_root = inline_value.or_else(|| args.next()).map(PathBuf::from),
+            "--plugin-dir" => {
+                if let Some(value) = inline_value.or_else(|| args.next()) {
+                    plugin_directories.push(PathBuf::from(value));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(data_root) = data_root {
+        log_info!("使用命令行指定的数据目录: {:?}", data_root);
+        set_data_root_override(Some(data_root));
+    }
+    if !plugin_directories.is_empty() {
+        log_info!("使用命令行指定的插件目录: {:?}", plugin_directories);
+        set_plugin_directory_overrides(plugin_directories);
+    }
+}
+
+/// 平台配置目录下的路径设置文件
+///
+/// 路径设置决定数据根目录的位置，因此不能保存在数据根目录中。
+/// Linux 上为 `$XDG_CONFIG_HOME/chat-client/paths.toml`。
+pub fn get_path_settings_file() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| dirs::home_dir().unwrap_or_default().join(".config"))
+        .join(APP_DIR_NAME)
+        .join("paths.toml")
+}
+
+/// 旧版本使用的数据目录 `~/.chat_client`
+fn get_legacy_data_root() -> Option<PathBuf> {
+    dirs::home_dir().map(|home_dir| home_dir.join(".chat_client"))
+}
+
+/// 平台默认的数据目录，Linux 上为 `$XDG_DATA_HOME/chat-client`
+fn get_default_data_root() -> PathBuf {
+    match dirs::data_dir() {
+        Some(data_dir) => data_dir.join(APP_DIR_NAME),
+        None => dirs::home_dir().unwrap_or_default().join(".chat_client"),
+    }
+}
+
+/// 未指定运行时目录时解析出的数据根目录，首次使用时确定
+static RESOLVED_DATA_ROOT: OnceLock<PathBuf> = OnceLock::new();
+
+/// 应用数据根目录
+///
+/// 依次使用：运行时指定的目录（`--data-dir`）、环境变量 `CHAT_CLIENT_DATA_DIR`、
+/// 路径设置中的 `data_dir`、已存在的旧版目录 `~/.chat_client`、平台默认目录。
+/// 除运行时指定的目录外，结果在首次调用时确定，修改路径设置后需要重启应用才生效。
 pub fn get_plugin_repository_root() -> PathBuf {
-    let home_dir = std::env::home_dir().unwrap_or_default();
-    home_dir.join(".chat_client")
+    if let Some(data_root) = &get_directory_overrides().read().unwrap().data_root {
+        return data_root.clone();
+    }
+    RESOLVED_DATA_ROOT.get_or_init(resolve_data_root).clone()
+}
+
+fn resolve_data_root() -> PathBuf {
+    if let Some(data_root) = std::env::var_os("CHAT_CLIENT_DATA_DIR").filter(|v| !v.is_empty()) {
+        return PathBuf::from(data_root);
+    }
+    if let Some(data_root) = load_path_settings().data_dir {
+        return data_root;
+    }
+    match get_legacy_data_root() {
+        Some(legacy) if legacy.is_dir() => legacy,
+        _ => get_default_data_root(),
+    }
 }
 
 pub fn get_root_plugin_installed_directory() -> PathBuf {
     get_plugin_repository_root().join("installed_plugins")
 }
 
+/// 开发构建时 src-tauri 所在的目录，发布构建中为 None
+pub fn get_development_workspace_directory() -> Option<PathBuf> {
+    if cfg!(debug_assertions) {
+        Some(Path::new(env!("CARGO_MANIFEST_DIR")).to_path_buf())
+    } else {
+        None
+    }
+}
+
+/// 需要扫描的插件目录（按优先级排列）
+///
+/// 包括运行时指定的目录（`--plugin-dir`）、环境变量 `CHAT_CLIENT_PLUGIN_DIRS`
+/// （按系统路径分隔符分隔）、路径设置中的 `plugin_dirs`、开发构建中的 src-tauri/src/plugins，
+/// 以及数据根目录下的 installed_plugins。
 pub fn get_plugins_directories() -> Vec<PathBuf> {
-    let current_dir = std::env::current_dir().unwrap_or_default();
-    let mut directories = Vec::new();
+    let mut directories = get_directory_overrides()
+        .read()
+        .unwrap()
+        .plugin_directories
+        .clone();
+
+    if let Some(paths) = std::env::var_os("CHAT_CLIENT_PLUGIN_DIRS") {
+        directories.extend(std::env::split_paths(&paths).filter(|p| !p.as_os_str().is_empty()));
+    }
+    directories.extend(load_path_settings().plugin_dirs);
 
-    // 添加 (pwd)/src-tauri/src/plugins 目录
-    let src_plugins_dir = current_dir.join("src").join("plugins");
-    directories.push(src_plugins_dir);
+    // 开发构建中的内置示例插件，与启动时的工作目录无关
+    if let Some(workspace_dir) = get_development_workspace_directory() {
+        directories.push(workspace_dir.join("src").join("plugins"));
+    }
 
-    // 添加 ~/.chat_client/installed_plugins 目录
-    let installed_plugins_dir = get_root_plugin_installed_directory();
-    directories.push(installed_plugins_dir);
+    directories.push(get_root_plugin_installed_directory());
 
-    directories
+    let mut unique = Vec::with_capacity(directories.len());
+    for directory in directories {
+        if !unique.contains(&directory) {
+            unique.push(directory);
+        }
+    }
+    unique
+}
+
+pub fn get_settings_file() -> PathBuf {
+    get_plugin_repository_root().join("settings.toml")
+}
+
+pub fn get_download_temp_directory() -> PathBuf {
+    get_plugin_repository_root().join("downloads")
 }
 
 pub fn get_repository_directory() -> PathBuf {
@@ -31,3 +185,31 @@ pub fn get_repository_directory() -> PathBuf {
 pub fn get_plugin_repository_directory() -> PathBuf {
     get_repository_directory().join("plugins")
 }
+
+pub fn get_build_directory() -> PathBuf {
+    get_plugin_repository_root().join("build")
+}
+
+pub fn get_sources_directory() -> PathBuf {
+    get_plugin_repository_root().join("sources")
+}
+
+pub fn get_traces_directory() -> PathBuf {
+    get_plugin_repository_root().join("traces")
+}
+
+/// 插件自己的数据目录，沙箱中的插件只能写入这里和声明过的路径
+pub fn get_plugin_data_directory(plugin_id: &str) -> PathBuf {
+    get_plugin_repository_root()
+        .join("plugin_data")
+        .join(plugin_id)
+}
+
+/// 用户批准的插件权限
+pub fn get_plugin_permissions_file() -> PathBuf {
+    get_plugin_repository_root().join("plugin_permissions.toml")
+}
+
+pub fn get_linked_plugins_file() -> PathBuf {
+    get_plugin_repository_root().join("linked_plugins.toml")
+}
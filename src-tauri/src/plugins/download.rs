@@ -0,0 +1,452 @@
+use plugin_interfaces::{log_info, log_warn};
+use reqwest::{header, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+
+use crate::plugins::settings::NetworkSettings;
+
+/// 下载进度事件名称
+pub const DOWNLOAD_PROGRESS_EVENT: &str = "plugin-download-progress";
+
+/// 两次进度事件之间的最小间隔
+const PROGRESS_EMIT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 退避等待期间检查取消标记的间隔
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// 下载进度信息（作为 plugin-download-progress 事件载荷发送到前端）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadProgress {
+    pub download_id: String,
+    /// 下载目标（插件ID或仓库名称）
+    pub target: String,
+    /// 已下载字节数
+    pub downloaded: u64,
+    /// 总字节数，服务器未提供 Content-Length 时为 None
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+}
+
+/// 下载重试配置
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    /// 遇到可恢复错误时的最大重试次数
+    pub max_retries: u32,
+    /// 第一次重试前的等待时间，之后每次翻倍
+    pub initial_backoff: Duration,
+    /// 单次等待时间上限
+    pub max_backoff: Duration,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl DownloadOptions {
+    /// 按网络设置中的重试次数创建配置
+    pub fn from_settings(settings: &NetworkSettings) -> Self {
+        Self {
+            max_retries: settings.download_retries,
+            ..Self::default()
+        }
+    }
+
+    /// 第 `attempt` 次重试前的等待时间
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// 单次下载尝试的失败原因
+enum AttemptError {
+    /// 用户取消
+    Cancelled,
+    /// 可以重试的错误（网络中断、超时、服务端 5xx 等）
+    Transient(String),
+    /// 不可重试的错误
+    Fatal(String),
+}
+
+/// 正在进行的下载任务，键为 download_id，值为取消标记
+static ACTIVE_DOWNLOADS: OnceLock<Mutex<HashMap<String, Arc<AtomicBool>>>> = OnceLock::new();
+
+fn get_active_downloads() -> &'static Mutex<HashMap<String, Arc<AtomicBool>>> {
+    ACTIVE_DOWNLOADS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// 取消指定的下载任务
+pub fn cancel_download(download_id: &str) -> Result<String, String> {
+    let downloads = get_active_downloads().lock().unwrap();
+    match downloads.get(download_id) {
+        Some(cancelled) => {
+            cancelled.store(true, Ordering::SeqCst);
+            log_info!("下载任务 {} 已请求取消", download_id);
+            Ok(format!("下载任务 {} 已取消", download_id))
+        }
+        None => Err(format!("下载任务 {} 未找到", download_id)),
+    }
+}
+
+/// 单个下载任务的句柄
+///
+/// 创建时注册到全局下载表中，释放时自动移除，
+/// 因此 `cancel_download` 只能取消仍在进行的任务。
+pub struct DownloadTask {
+    pub download_id: String,
+    pub target: String,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl DownloadTask {
+    pub fn new(download_id: String, target: &str) -> Self {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        get_active_downloads()
+            .lock()
+            .unwrap()
+            .insert(download_id.clone(), Arc::clone(&cancelled));
+
+        Self {
+            download_id,
+            target: target.to_string(),
+            cancelled,
+        }
+    }
+
+    /// 是否已被取消
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    fn progress(&self, downloaded: u64, total: Option<u64>) -> DownloadProgress {
+        DownloadProgress {
+            download_id: self.download_id.clone(),
+            target: self.target.clone(),
+            downloaded,
+            total,
+        }
+    }
+
+    /// 以流式方式将响应内容写入临时文件，完成后重命名为 `dest`
+    ///
+    /// 临时文件为 `dest` 加上 `.part` 后缀。如果临时文件已存在（上次下载中断），
+    /// 会通过 HTTP Range 请求从断点继续；可恢复的错误按 `options` 指数退避重试。
+    /// 重试耗尽后保留临时文件以便下次续传，取消或遇到不可恢复的错误时删除临时文件。
+    ///
+    /// `on_progress` 按固定间隔被调用，下载结束时保证再调用一次。
+    pub async fn download_to_file<F>(
+        &self,
+        client: &reqwest::Client,
+        url: &str,
+        dest: &Path,
+        options: &DownloadOptions,
+        on_progress: F,
+    ) -> Result<u64, String>
+    where
+        F: Fn(&DownloadProgress),
+    {
+        let temp_path = temp_path_for(dest);
+        if let Some(parent) = temp_path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| format!("创建临时目录失败: {}", e))?;
+        }
+
+        let mut attempt: u32 = 0;
+        loop {
+            let error = match self
+                .download_attempt(client, url, &temp_path, &on_progress)
+                .await
+            {
+                Ok(downloaded) => {
+                    tokio::fs::rename(&temp_path, dest)
+                        .await
+                        .map_err(|e| format!("保存下载文件失败: {}", e))?;
+                    return Ok(downloaded);
+                }
+                Err(AttemptError::Cancelled) => {
+                    let _ = tokio::fs::remove_file(&temp_path).await;
+                    return Err("下载已取消".to_string());
+                }
+                Err(AttemptError::Fatal(error)) => {
+                    let _ = tokio::fs::remove_file(&temp_path).await;
+                    return Err(error);
+                }
+                Err(AttemptError::Transient(error)) => error,
+            };
+
+            attempt += 1;
+            if attempt > options.max_retries {
+                return Err(format!("{}（已重试 {} 次）", error, options.max_retries));
+            }
+
+            let backoff = options.backoff(attempt);
+            log_warn!(
+                "下载 {} 失败: {}，{:?} 后进行第 {} 次重试",
+                url,
+                error,
+                backoff,
+                attempt
+            );
+            if !self.wait_unless_cancelled(backoff).await {
+                let _ = tokio::fs::remove_file(&temp_path).await;
+                return Err("下载已取消".to_string());
+            }
+        }
+    }
+
+    /// 等待指定时间，期间被取消则返回 false
+    async fn wait_unless_cancelled(&self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        while Instant::now() < deadline {
+            if self.is_cancelled() {
+                return false;
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            tokio::time::sleep(remaining.min(CANCEL_POLL_INTERVAL)).await;
+        }
+        !self.is_cancelled()
+    }
+
+    /// 执行一次下载尝试，从临时文件的当前长度处续传
+    async fn download_attempt<F>(
+        &self,
+        client: &reqwest::Client,
+        url: &str,
+        temp_path: &Path,
+        on_progress: &F,
+    ) -> Result<u64, AttemptError>
+    where
+        F: Fn(&DownloadProgress),
+    {
+        if self.is_cancelled() {
+            return Err(AttemptError::Cancelled);
+        }
+
+        let existing = tokio::fs::metadata(temp_path)
+            .await
+            .map(|metadata| metadata.len())
+            .unwrap_or(0);
+
+        let mut request = client.get(url);
+        if existing > 0 {
+            log_info!("从断点 {} 字节处继续下载: {}", existing, url);
+            request = request.header(header::RANGE, format!("bytes={}-", existing));
+        }
+
+        let mut response = request.send().await.map_err(classify_request_error)?;
+        let status = response.status();
+
+        let resumed = match status {
+            StatusCode::PARTIAL_CONTENT if existing > 0 => {
+                if content_range_start(&response) != Some(existing) {
+                    // 返回的片段接不上临时文件，追加会损坏文件，丢弃后从头下载
+                    let _ = tokio::fs::remove_file(temp_path).await;
+                    return Err(AttemptError::Transient(format!(
+                        "服务器返回的续传位置与已下载的 {} 字节不一致，将重新下载",
+                        existing
+                    )));
+                }
+                true
+            }
+            StatusCode::RANGE_NOT_SATISFIABLE => {
+                // 服务端文件已变化或临时文件已损坏，丢弃后从头下载
+                let _ = tokio::fs::remove_file(temp_path).await;
+                return Err(AttemptError::Transient(
+                    "服务器拒绝续传请求，将重新下载".to_string(),
+                ));
+            }
+            status if status.is_success() => false,
+            status if is_transient_status(status) => {
+                return Err(AttemptError::Transient(format!(
+                    "下载失败，HTTP状态码: {}",
+                    status
+                )));
+            }
+            status => {
+                return Err(AttemptError::Fatal(format!(
+                    "下载失败，HTTP状态码: {}",
+                    status
+                )));
+            }
+        };
+
+        let mut downloaded = if resumed { existing } else { 0 };
+        let total = if resumed {
+            content_range_total(&response)
+                .or_else(|| response.content_length().map(|len| len + existing))
+        } else {
+            response.content_length()
+        };
+
+        let mut file = if resumed {
+            tokio::fs::OpenOptions::new()
+                .append(true)
+                .open(temp_path)
+                .await
+        } else {
+            tokio::fs::File::create(temp_path).await
+        }
+        .map_err(|e| AttemptError::Fatal(format!("创建临时文件失败: {}", e)))?;
+
+        let mut last_emit = Instant::now();
+        on_progress(&self.progress(downloaded, total));
+
+        loop {
+            if self.is_cancelled() {
+                return Err(AttemptError::Cancelled);
+            }
+
+            let chunk = match response.chunk().await {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => break,
+                Err(e) => {
+                    let _ = file.flush().await;
+                    return Err(AttemptError::Transient(format!("读取下载数据失败: {}", e)));
+                }
+            };
+
+            file.write_all(&chunk)
+                .await
+                .map_err(|e| AttemptError::Fatal(format!("写入临时文件失败: {}", e)))?;
+            downloaded += chunk.len() as u64;
+
+            if last_emit.elapsed() >= PROGRESS_EMIT_INTERVAL {
+                on_progress(&self.progress(downloaded, total));
+                last_emit = Instant::now();
+            }
+        }
+
+        file.flush()
+            .await
+            .map_err(|e| AttemptError::Fatal(format!("写入临时文件失败: {}", e)))?;
+        on_progress(&self.progress(downloaded, total));
+
+        if let Some(total) = total {
+            if downloaded < total {
+                return Err(AttemptError::Transient(format!(
+                    "连接提前关闭，已下载 {}/{} 字节",
+                    downloaded, total
+                )));
+            }
+        }
+
+        Ok(downloaded)
+    }
+}
+
+impl Drop for DownloadTask {
+    fn drop(&mut self) {
+        get_active_downloads()
+            .lock()
+            .unwrap()
+            .remove(&self.download_id);
+    }
+}
+
+/// 区分请求错误是否可以重试
+fn classify_request_error(error: reqwest::Error) -> AttemptError {
+    if error.is_builder() {
+        AttemptError::Fatal(format!("下载失败: {}", error))
+    } else {
+        AttemptError::Transient(format!("下载失败: {}", error))
+    }
+}
+
+/// 服务端临时性错误（超时、限流、5xx）
+fn is_transient_status(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::REQUEST_TIMEOUT
+        || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// 从 `Content-Range: bytes start-end/total` 中解析片段的起始位置
+fn content_range_start(response: &reqwest::Response) -> Option<u64> {
+    let value = response
+        .headers()
+        .get(header::CONTENT_RANGE)?
+        .to_str()
+        .ok()?;
+    let (start, _) = value.trim().strip_prefix("bytes ")?.split_once('-')?;
+    start.trim().parse().ok()
+}
+
+/// 从 `Content-Range: bytes start-end/total` 中解析文件总大小
+fn content_range_total(response: &reqwest::Response) -> Option<u64> {
+    let value = response
+        .headers()
+        .get(header::CONTENT_RANGE)?
+        .to_str()
+        .ok()?;
+    value.rsplit('/').next()?.trim().parse().ok()
+}
+
+/// 下载过程中使用的临时文件路径
+fn temp_path_for(dest: &Path) -> PathBuf {
+    let mut file_name = dest.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".part");
+    dest.with_file_name(file_name)
+}
+
+/// 校验文件的 SHA-256 摘要
+///
+/// `expected` 可以是十六进制摘要本身，也可以带 `sha256:` 前缀；为空时跳过校验。
+pub fn verify_checksum(path: &Path, expected: &str) -> Result<(), String> {
+    let expected = expected.trim();
+    if expected.is_empty() {
+        log_warn!("未提供校验和，跳过校验: {:?}", path);
+        return Ok(());
+    }
+
+    let expected_hex = match expected.split_once(':') {
+        Some((algorithm, digest)) if algorithm.eq_ignore_ascii_case("sha256") => digest,
+        Some((algorithm, _)) => return Err(format!("不支持的校验算法: {}", algorithm)),
+        None => expected,
+    };
+
+    let actual_hex = sha256_file(path)?;
+    if actual_hex.eq_ignore_ascii_case(expected_hex.trim()) {
+        Ok(())
+    } else {
+        Err(format!(
+            "校验和不匹配: 期望 {}, 实际 {}",
+            expected_hex, actual_hex
+        ))
+    }
+}
+
+/// 计算文件的 SHA-256 摘要（小写十六进制）
+pub fn sha256_file(path: &Path) -> Result<String, String> {
+    let mut file = std::fs::File::open(path).map_err(|e| format!("打开文件失败: {}", e))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .map_err(|e| format!("读取文件失败: {}", e))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
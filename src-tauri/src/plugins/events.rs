@@ -0,0 +1,122 @@
+use plugin_interfaces::log_warn;
+use serde::Serialize;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter};
+
+/// 插件事件的接收方
+///
+/// `PluginManager` 把插件发往前端的事件（`plugin-message`、`plugin-stream`、
+/// `plugin-ui-updated` 等）交给事件接收方，载荷为 JSON 字符串。
+/// 返回错误时，插件一侧的发送调用会得到失败结果。
+pub trait EventSink: Send + Sync {
+    fn emit(&self, event: &str, payload: &str) -> Result<(), String>;
+}
+
+/// 发送到 Tauri 前端的事件接收方
+pub struct TauriEventSink {
+    app_handle: AppHandle,
+}
+
+impl TauriEventSink {
+    pub fn new(app_handle: AppHandle) -> Self {
+        Self { app_handle }
+    }
+}
+
+impl EventSink for TauriEventSink {
+    fn emit(&self, event: &str, payload: &str) -> Result<(), String> {
+        self.app_handle
+            .emit(event, payload)
+            .map_err(|e| format!("发送事件 {} 失败: {}", event, e))
+    }
+}
+
+/// 一条插件事件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PluginEvent {
+    pub event: String,
+    pub payload: String,
+}
+
+impl PluginEvent {
+    /// 将载荷解析为 JSON
+    pub fn json(&self) -> Result<serde_json::Value, String> {
+        serde_json::from_str(&self.payload)
+            .map_err(|e| format!("解析事件 {} 的载荷失败: {}", self.event, e))
+    }
+}
+
+/// 在内存中记录所有事件的接收方，用于测试
+#[derive(Debug, Default)]
+pub struct RecordingEventSink {
+    events: Mutex<Vec<PluginEvent>>,
+}
+
+impl RecordingEventSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 已记录的所有事件
+    pub fn events(&self) -> Vec<PluginEvent> {
+        self.events.lock().unwrap().clone()
+    }
+
+    /// 已记录的指定名称的事件
+    pub fn events_named(&self, event: &str) -> Vec<PluginEvent> {
+        self.events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|recorded| recorded.event == event)
+            .cloned()
+            .collect()
+    }
+
+    /// 取出并清空已记录的事件
+    pub fn take(&self) -> Vec<PluginEvent> {
+        std::mem::take(&mut *self.events.lock().unwrap())
+    }
+}
+
+impl EventSink for RecordingEventSink {
+    fn emit(&self, event: &str, payload: &str) -> Result<(), String> {
+        self.events.lock().unwrap().push(PluginEvent {
+            event: event.to_string(),
+            payload: payload.to_string(),
+        });
+        Ok(())
+    }
+}
+
+/// 把事件发送到通道的接收方，供其他前端在自己的线程中处理事件
+pub struct ChannelEventSink {
+    sender: Mutex<Sender<PluginEvent>>,
+}
+
+impl ChannelEventSink {
+    /// 创建接收方和对应的事件通道
+    pub fn new() -> (Self, Receiver<PluginEvent>) {
+        let (sender, receiver) = mpsc::channel();
+        (
+            Self {
+                sender: Mutex::new(sender),
+            },
+            receiver,
+        )
+    }
+}
+
+impl EventSink for ChannelEventSink {
+    fn emit(&self, event: &str, payload: &str) -> Result<(), String> {
+        let event = PluginEvent {
+            event: event.to_string(),
+            payload: payload.to_string(),
+        };
+        self.sender.lock().unwrap().send(event).map_err(|e| {
+            log_warn!("事件通道已关闭: {}", e);
+            "事件通道已关闭".to_string()
+        })
+    }
+}
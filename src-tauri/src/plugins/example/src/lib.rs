@@ -428,3 +428,58 @@ pub unsafe extern "C" fn destroy_plugin(interface: *mut PluginInterface) {
         let _ = Box::from_raw(interface);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{create_plugin, destroy_plugin};
+    use plugin_test_support::{MockPlugin, StreamState};
+    use std::time::Duration;
+
+    fn connected_plugin() -> MockPlugin {
+        let mut plugin = MockPlugin::new(create_plugin, destroy_plugin)
+            .with_config(concat!(env!("CARGO_MANIFEST_DIR"), "/config.toml"))
+            .unwrap();
+        plugin.mount().unwrap();
+        plugin.connect().unwrap();
+        plugin
+    }
+
+    #[test]
+    fn echoes_messages() {
+        let mut plugin = connected_plugin();
+
+        let response = plugin.send_message("hello").unwrap();
+        assert!(response.contains("Echo from Example Plugin: hello"));
+        plugin.assert_message_contains("收到消息：hello");
+    }
+
+    #[test]
+    fn short_button_sends_message() {
+        let mut plugin = connected_plugin();
+        plugin.assert_ui_contains("Test Plugin");
+
+        plugin.click("Short").unwrap();
+        plugin.assert_message_contains("Test Message");
+    }
+
+    #[test]
+    fn background_stream_stops_after_cancel() {
+        let mut plugin = connected_plugin();
+        plugin.click("Background Stream Demo").unwrap();
+
+        assert!(plugin.wait_until(Duration::from_secs(5), |plugin| {
+            plugin
+                .streams()
+                .first()
+                .is_some_and(|stream| !stream.chunks.is_empty())
+        }));
+        let stream_id = plugin.streams()[0].stream_id.clone();
+        plugin.cancel_stream(&stream_id).unwrap();
+
+        // 每 2 秒发送一块数据，取消后的数据块不会再被接收
+        std::thread::sleep(Duration::from_millis(2500));
+        let stream = &plugin.streams()[0];
+        assert_eq!(stream.state, StreamState::Cancelled);
+        assert_eq!(stream.chunks, vec!["后台第一部分数据...\n"]);
+    }
+}
@@ -0,0 +1,104 @@
+use plugin_interfaces::log_warn;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::process::Command;
+
+use crate::plugins::directories::get_sources_directory;
+
+/// 仓库地址对应的检出目录
+///
+/// 目录名由仓库名加上地址摘要组成，同一个地址总是检出到同一个目录，以便更新时只拉取增量。
+pub fn source_checkout_dir(url: &str) -> PathBuf {
+    let name = url
+        .trim_end_matches('/')
+        .trim_end_matches(".git")
+        .rsplit(['/', '\\', ':'])
+        .next()
+        .filter(|name| !name.is_empty())
+        .unwrap_or("repo");
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let digest = hex::encode(Sha256::digest(url.as_bytes()));
+    get_sources_directory().join(format!("{}-{}", name, &digest[..12]))
+}
+
+/// 规范化仓库地址：本地路径转换为绝对路径，远程地址保持不变
+pub fn normalize_url(url: &str) -> String {
+    let path = Path::new(url);
+    if path.exists() {
+        if let Ok(path) = path.canonicalize() {
+            return path.to_string_lossy().to_string();
+        }
+    }
+    url.trim().to_string()
+}
+
+/// 检出 Git 仓库的指定版本，返回检出的提交哈希
+///
+/// 已存在的检出目录会被复用，只拉取需要的提交。
+/// 以 `-` 开头的地址或版本会被 git 当作选项解析，因此直接拒绝。
+pub async fn checkout(url: &str, rev: Option<&str>, checkout_dir: &Path) -> Result<String, String> {
+    if url.starts_with('-') {
+        return Err(format!("无效的仓库地址: {}", url));
+    }
+    let rev = rev.unwrap_or("HEAD");
+    if rev.is_empty() || rev.starts_with('-') {
+        return Err(format!("无效的 Git 版本: {}", rev));
+    }
+
+    if !checkout_dir.join(".git").exists() {
+        if checkout_dir.exists() {
+            log_warn!("检出目录不是有效的 Git 仓库，重新创建: {:?}", checkout_dir);
+            tokio::fs::remove_dir_all(checkout_dir)
+                .await
+                .map_err(|e| format!("清理检出目录失败: {}", e))?;
+        }
+        tokio::fs::create_dir_all(checkout_dir)
+            .await
+            .map_err(|e| format!("创建检出目录失败: {}", e))?;
+        run_git(checkout_dir, &["init", "--quiet"]).await?;
+        run_git(checkout_dir, &["remote", "add", "--", "origin", url]).await?;
+    } else {
+        run_git(checkout_dir, &["remote", "set-url", "--", "origin", url]).await?;
+    }
+
+    run_git(
+        checkout_dir,
+        &["fetch", "--depth", "1", "--", "origin", rev],
+    )
+    .await?;
+    run_git(
+        checkout_dir,
+        &["checkout", "--force", "--quiet", "FETCH_HEAD", "--"],
+    )
+    .await?;
+    run_git(checkout_dir, &["rev-parse", "HEAD"]).await
+}
+
+/// 运行 git 命令，返回去除首尾空白的标准输出
+async fn run_git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .map_err(|e| format!("无法运行 git，请确认已安装 Git: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "git {} 失败: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
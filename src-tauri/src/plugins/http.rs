@@ -0,0 +1,75 @@
+use plugin_interfaces::log_info;
+use std::sync::{OnceLock, RwLock};
+use std::time::Duration;
+
+use crate::plugins::settings::{effective_network_settings, NetworkSettings};
+
+/// 默认 User-Agent
+const DEFAULT_USER_AGENT: &str = concat!("chat-client/", env!("CARGO_PKG_VERSION"));
+
+/// 共享的HTTP客户端，首次使用时按当前设置创建
+static SHARED_CLIENT: OnceLock<RwLock<Option<reqwest::Client>>> = OnceLock::new();
+
+fn get_shared_client_slot() -> &'static RwLock<Option<reqwest::Client>> {
+    SHARED_CLIENT.get_or_init(|| RwLock::new(None))
+}
+
+/// 获取共享的HTTP客户端
+///
+/// `reqwest::Client` 内部使用引用计数，克隆开销很小，多个请求可以复用同一个连接池。
+pub fn shared_client() -> Result<reqwest::Client, String> {
+    if let Some(client) = get_shared_client_slot().read().unwrap().as_ref() {
+        return Ok(client.clone());
+    }
+
+    let mut slot = get_shared_client_slot().write().unwrap();
+    if let Some(client) = slot.as_ref() {
+        return Ok(client.clone());
+    }
+    let client = build_client(&effective_network_settings()?)?;
+    *slot = Some(client.clone());
+    Ok(client)
+}
+
+/// 替换共享的HTTP客户端（新设置已经验证并保存后调用）
+pub fn replace_shared_client(client: reqwest::Client) {
+    *get_shared_client_slot().write().unwrap() = Some(client);
+    log_info!("HTTP客户端已按最新网络设置重建");
+}
+
+/// 按网络设置创建HTTP客户端
+pub fn build_client(settings: &NetworkSettings) -> Result<reqwest::Client, String> {
+    let mut builder = reqwest::Client::builder()
+        .user_agent(
+            settings
+                .user_agent
+                .clone()
+                .unwrap_or_else(|| DEFAULT_USER_AGENT.to_string()),
+        )
+        .connect_timeout(Duration::from_secs(settings.connect_timeout_secs))
+        .read_timeout(Duration::from_secs(settings.read_timeout_secs));
+
+    // 显式代理会禁用 reqwest 对 HTTP_PROXY 等系统环境变量的自动处理
+    if let Some(proxy_url) = &settings.proxy {
+        let mut proxy =
+            reqwest::Proxy::all(proxy_url).map_err(|e| format!("代理地址无效: {}", e))?;
+        if !settings.no_proxy.is_empty() {
+            proxy = proxy.no_proxy(reqwest::NoProxy::from_string(&settings.no_proxy.join(",")));
+        }
+        builder = builder.proxy(proxy);
+    }
+
+    for certificate_path in &settings.ca_certificates {
+        let pem = std::fs::read(certificate_path)
+            .map_err(|e| format!("读取CA证书失败 {:?}: {}", certificate_path, e))?;
+        let certificates = reqwest::Certificate::from_pem_bundle(&pem)
+            .map_err(|e| format!("解析CA证书失败 {:?}: {}", certificate_path, e))?;
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    builder
+        .build()
+        .map_err(|e| format!("创建HTTP客户端失败: {}", e))
+}
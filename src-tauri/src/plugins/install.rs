@@ -0,0 +1,303 @@
+use plugin_interfaces::{log_error, log_info, log_warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use walkdir::WalkDir;
+use zip::ZipArchive;
+
+use crate::plugins::{
+    config::PluginConfig,
+    directories::get_root_plugin_installed_directory,
+    package::{install_package, is_plugin_package},
+    runtime::validate_plugin_file,
+    settings::load_settings,
+};
+
+/// 安装记录文件名，保存在插件安装目录中
+pub const INSTALL_RECORD_FILE: &str = "install.toml";
+
+/// 插件的安装来源
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum InstallSource {
+    /// 从插件仓库下载
+    Repository { download_url: String },
+    /// 从本地文件旁加载
+    Local { path: String },
+    /// 从 Git 仓库安装
+    Git {
+        url: String,
+        #[serde(default)]
+        rev: Option<String>,
+        commit: String,
+        #[serde(default)]
+        path: Option<String>,
+    },
+    /// 从源码构建
+    Source {
+        #[serde(default)]
+        git: Option<String>,
+        #[serde(default)]
+        commit: Option<String>,
+        #[serde(default)]
+        path: Option<String>,
+    },
+}
+
+/// 插件安装记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstallRecord {
+    pub source: InstallSource,
+    /// 安装时间（Unix 秒）
+    pub installed_at: u64,
+}
+
+impl InstallRecord {
+    /// 读取插件目录中的安装记录
+    pub fn load(plugin_dir: &Path) -> Option<Self> {
+        let content = fs::read_to_string(plugin_dir.join(INSTALL_RECORD_FILE)).ok()?;
+        match toml::from_str(&content) {
+            Ok(record) => Some(record),
+            Err(e) => {
+                log_warn!("解析安装记录失败 {:?}: {}", plugin_dir, e);
+                None
+            }
+        }
+    }
+}
+
+/// 插件的动态库名称（不含平台前缀和扩展名）
+///
+/// 优先使用配置中的 `library`，未设置时为 `id-version`，与 `PluginLoader` 的查找规则一致。
+pub fn plugin_library_name(config: &PluginConfig) -> String {
+    config
+        .plugin
+        .library
+        .clone()
+        .unwrap_or_else(|| format!("{}-{}", config.plugin.id, config.plugin.version))
+}
+
+/// 插件文件名：原生插件为当前平台的动态库文件名，WebAssembly 插件为 `<名称>.wasm`
+pub fn plugin_file_name(config: &PluginConfig) -> String {
+    config.plugin.kind.file_name(&plugin_library_name(config))
+}
+
+/// 校验插件配置
+pub fn validate_plugin_config(config: &PluginConfig) -> Result<(), String> {
+    let id = &config.plugin.id;
+    if id.is_empty() {
+        return Err("插件ID不能为空".to_string());
+    }
+    if !id
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+        || id.starts_with('.')
+    {
+        return Err(format!("插件ID包含非法字符: {}", id));
+    }
+    if config.plugin.version.is_empty() {
+        return Err(format!("插件 {} 缺少版本号", id));
+    }
+    Ok(())
+}
+
+/// 将插件安装到已安装插件目录
+///
+/// 复制配置文件和动态库到 `installed_plugins/<id>`（覆盖已有安装），
+/// 动态库按 `PluginLoader` 的查找规则重命名，并写入安装记录。
+/// `extra_files` 为需要一并复制的其他文件（源路径, 插件目录内的相对路径），如资源、许可证等。
+/// 新版本先在同级的临时目录中准备好再替换现有目录，任何一步失败都保留现有安装。
+pub fn install_plugin(
+    config_path: &Path,
+    library_path: &Path,
+    extra_files: &[(PathBuf, String)],
+    source: InstallSource,
+) -> Result<PathBuf, String> {
+    let config =
+        PluginConfig::from_file(config_path).map_err(|e| format!("读取插件配置失败: {}", e))?;
+    validate_plugin_config(&config)?;
+    validate_plugin_file(config.plugin.kind, library_path)?;
+
+    // 插件ID不能以 `.` 开头，临时目录不会与插件目录重名，扫描插件时也会跳过
+    let install_dir = get_root_plugin_installed_directory();
+    let plugin_dir = install_dir.join(&config.plugin.id);
+    let staging_dir = install_dir.join(format!(
+        ".{}.installing-{}",
+        config.plugin.id,
+        uuid::Uuid::new_v4()
+    ));
+    let result = write_plugin_directory(
+        &staging_dir,
+        &config,
+        config_path,
+        library_path,
+        extra_files,
+        source,
+    )
+    .and_then(|_| replace_directory(&staging_dir, &plugin_dir));
+    if let Err(e) = result {
+        if staging_dir.exists() {
+            if let Err(remove_error) = fs::remove_dir_all(&staging_dir) {
+                log_warn!("删除临时安装目录失败 {:?}: {}", staging_dir, remove_error);
+            }
+        }
+        return Err(e);
+    }
+
+    log_info!("插件 {} 安装完成: {:?}", config.plugin.name, plugin_dir);
+    Ok(plugin_dir)
+}
+
+/// 在 `plugin_dir` 中写入插件文件和安装记录
+fn write_plugin_directory(
+    plugin_dir: &Path,
+    config: &PluginConfig,
+    config_path: &Path,
+    library_path: &Path,
+    extra_files: &[(PathBuf, String)],
+    source: InstallSource,
+) -> Result<(), String> {
+    fs::create_dir_all(plugin_dir).map_err(|e| format!("创建插件目录失败: {}", e))?;
+
+    let target_library_path = plugin_dir.join(plugin_file_name(config));
+    fs::copy(library_path, &target_library_path)
+        .map_err(|e| format!("保存动态链接库失败: {}", e))?;
+
+    fs::copy(config_path, plugin_dir.join("config.toml"))
+        .map_err(|e| format!("复制配置文件失败: {}", e))?;
+
+    for (file_path, relative_path) in extra_files {
+        let target_path = plugin_dir.join(relative_path);
+        if let Some(parent) = target_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("创建目录失败: {}", e))?;
+        }
+        fs::copy(file_path, &target_path)
+            .map_err(|e| format!("复制文件失败 {}: {}", relative_path, e))?;
+    }
+
+    let record = InstallRecord {
+        source,
+        installed_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs(),
+    };
+    let record_content =
+        toml::to_string_pretty(&record).map_err(|e| format!("序列化安装记录失败: {}", e))?;
+    fs::write(plugin_dir.join(INSTALL_RECORD_FILE), record_content)
+        .map_err(|e| format!("写入安装记录失败: {}", e))
+}
+
+/// 用准备好的目录替换插件目录
+///
+/// 现有目录先改名为同级的备份目录，新目录改名到位后才删除备份；改名失败时恢复现有目录。
+fn replace_directory(staging_dir: &Path, plugin_dir: &Path) -> Result<(), String> {
+    if !plugin_dir.exists() {
+        return fs::rename(staging_dir, plugin_dir).map_err(|e| format!("移动插件目录失败: {}", e));
+    }
+
+    let file_name = plugin_dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let backup_dir =
+        plugin_dir.with_file_name(format!(".{}.replaced-{}", file_name, uuid::Uuid::new_v4()));
+    fs::rename(plugin_dir, &backup_dir).map_err(|e| format!("替换现有插件目录失败: {}", e))?;
+    if let Err(e) = fs::rename(staging_dir, plugin_dir) {
+        if let Err(restore_error) = fs::rename(&backup_dir, plugin_dir) {
+            log_error!(
+                "恢复插件目录失败，原有文件保留在 {:?}: {}",
+                backup_dir,
+                restore_error
+            );
+        }
+        return Err(format!("移动插件目录失败: {}", e));
+    }
+
+    if let Err(e) = fs::remove_dir_all(&backup_dir) {
+        log_warn!("删除旧的插件目录失败 {:?}: {}", backup_dir, e);
+    }
+    Ok(())
+}
+
+/// 从本地文件安装插件
+///
+/// `path` 可以是插件包（.ccpkg），插件压缩包（.zip，包含 config.toml 和动态库），
+/// 也可以是单独的动态库文件；后者需要同目录下的 config.toml 或显式指定 `config_path`。
+pub fn install_plugin_from_file(
+    path: &Path,
+    config_path: Option<&Path>,
+    work_dir: &Path,
+) -> Result<PathBuf, String> {
+    if !path.is_file() {
+        return Err(format!("文件不存在: {:?}", path));
+    }
+
+    let source = InstallSource::Local {
+        path: path.to_string_lossy().to_string(),
+    };
+
+    if is_plugin_package(path) {
+        return install_package(path, work_dir, source, &load_settings().packages);
+    }
+
+    let is_archive = path
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case("zip"))
+        .unwrap_or(false);
+    if !is_archive {
+        let config_path = match config_path {
+            Some(config_path) => config_path.to_path_buf(),
+            None => path.with_file_name("config.toml"),
+        };
+        if !config_path.is_file() {
+            return Err(format!("找不到插件配置文件: {:?}", config_path));
+        }
+        return install_plugin(&config_path, path, &[], source);
+    }
+
+    // 解压到临时目录后按普通目录安装
+    let extract_dir = work_dir.join(format!("sideload-{}", uuid::Uuid::new_v4()));
+    let result = extract_archive(path, &extract_dir).and_then(|_| {
+        let (config_path, library_path) = locate_plugin_files(&extract_dir)?;
+        install_plugin(&config_path, &library_path, &[], source)
+    });
+    let _ = fs::remove_dir_all(&extract_dir);
+    result
+}
+
+/// 解压ZIP文件到指定目录
+fn extract_archive(archive_path: &Path, target_dir: &Path) -> Result<(), String> {
+    let file = fs::File::open(archive_path).map_err(|e| format!("无法打开压缩包: {}", e))?;
+    let mut archive = ZipArchive::new(file).map_err(|e| format!("无法打开压缩包: {}", e))?;
+    fs::create_dir_all(target_dir).map_err(|e| format!("创建临时目录失败: {}", e))?;
+    archive
+        .extract(target_dir)
+        .map_err(|e| format!("解压插件压缩包失败: {}", e))
+}
+
+/// 在解压目录中查找 config.toml 及其对应的动态库
+///
+/// 允许压缩包内有一层顶级目录。
+fn locate_plugin_files(dir: &Path) -> Result<(PathBuf, PathBuf), String> {
+    let config_path = WalkDir::new(dir)
+        .min_depth(1)
+        .max_depth(2)
+        .sort_by_key(|entry| entry.depth())
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .find(|entry| entry.file_type().is_file() && entry.file_name() == "config.toml")
+        .map(|entry| entry.into_path())
+        .ok_or_else(|| "压缩包中缺少 config.toml".to_string())?;
+
+    let config =
+        PluginConfig::from_file(&config_path).map_err(|e| format!("读取插件配置失败: {}", e))?;
+    let library_file = plugin_file_name(&config);
+    let library_path = config_path.with_file_name(&library_file);
+    if !library_path.is_file() {
+        return Err(format!("压缩包中缺少当前平台的动态库: {}", library_file));
+    }
+
+    Ok((config_path, library_path))
+}
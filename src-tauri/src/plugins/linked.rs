@@ -0,0 +1,115 @@
+use plugin_interfaces::{log_info, log_warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::plugins::{
+    config::PluginConfig, directories::get_linked_plugins_file, install::validate_plugin_config,
+};
+
+/// 关联的开发目录
+///
+/// 关联目录中的插件会被 `PluginLoader` 直接扫描，无需安装；
+/// 开启 `auto_rebuild` 后，目录中的文件变化会触发重新构建并热替换已挂载的实例。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkedPlugin {
+    /// 插件目录（包含 config.toml 和 Cargo.toml）
+    pub path: PathBuf,
+    #[serde(default)]
+    pub auto_rebuild: bool,
+    /// 关联时读取到的插件ID，前端据此确认插件申请的权限
+    #[serde(default)]
+    pub plugin_id: Option<String>,
+}
+
+impl LinkedPlugin {
+    /// 读取关联目录中的插件配置
+    pub fn config(&self) -> Result<PluginConfig, String> {
+        PluginConfig::from_file(self.path.join("config.toml"))
+            .map_err(|e| format!("读取插件配置失败 {:?}: {}", self.path, e))
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LinkedPluginsFile {
+    #[serde(default)]
+    plugins: Vec<LinkedPlugin>,
+}
+
+/// 读取所有关联的开发目录
+pub fn load_linked_plugins() -> Vec<LinkedPlugin> {
+    let linked_file = get_linked_plugins_file();
+    if !linked_file.exists() {
+        return Vec::new();
+    }
+
+    match fs::read_to_string(&linked_file)
+        .map_err(|e| e.to_string())
+        .and_then(|content| {
+            toml::from_str::<LinkedPluginsFile>(&content).map_err(|e| e.to_string())
+        }) {
+        Ok(file) => file.plugins,
+        Err(e) => {
+            log_warn!("读取关联插件列表失败 {:?}: {}", linked_file, e);
+            Vec::new()
+        }
+    }
+}
+
+fn save_linked_plugins(plugins: Vec<LinkedPlugin>) -> Result<(), String> {
+    let linked_file = get_linked_plugins_file();
+    if let Some(parent) = linked_file.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("创建目录失败: {}", e))?;
+    }
+    let content = toml::to_string_pretty(&LinkedPluginsFile { plugins })
+        .map_err(|e| format!("序列化关联插件列表失败: {}", e))?;
+    fs::write(&linked_file, content).map_err(|e| format!("保存关联插件列表失败: {}", e))
+}
+
+/// 关联开发目录，已关联的目录会更新 `auto_rebuild` 设置
+pub fn link_plugin_directory(path: &Path, auto_rebuild: bool) -> Result<LinkedPlugin, String> {
+    let path = path
+        .canonicalize()
+        .map_err(|e| format!("目录不存在 {:?}: {}", path, e))?;
+    let mut linked = LinkedPlugin {
+        path,
+        auto_rebuild,
+        plugin_id: None,
+    };
+    let config = linked.config()?;
+    validate_plugin_config(&config)?;
+    linked.plugin_id = Some(config.plugin.id.clone());
+    if auto_rebuild && !linked.path.join("Cargo.toml").is_file() {
+        return Err(format!(
+            "目录中缺少 Cargo.toml，无法自动构建: {:?}",
+            linked.path
+        ));
+    }
+
+    let mut plugins = load_linked_plugins();
+    plugins.retain(|plugin| plugin.path != linked.path);
+    plugins.push(linked.clone());
+    save_linked_plugins(plugins)?;
+
+    log_info!(
+        "已关联插件 {} 的开发目录: {:?}",
+        config.plugin.id,
+        linked.path
+    );
+    Ok(linked)
+}
+
+/// 取消关联开发目录
+pub fn unlink_plugin_directory(path: &Path) -> Result<(), String> {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let mut plugins = load_linked_plugins();
+    let count = plugins.len();
+    plugins.retain(|plugin| plugin.path != path);
+    if plugins.len() == count {
+        return Err(format!("目录未关联: {:?}", path));
+    }
+    save_linked_plugins(plugins)?;
+
+    log_info!("已取消关联开发目录: {:?}", path);
+    Ok(())
+}
@@ -1,7 +1,13 @@
 use plugin_interfaces::{log_warn, PluginMetadata};
 use walkdir::WalkDir;
 
-use crate::plugins::{config::PluginConfig, directories::get_plugins_directories};
+use crate::plugins::{
+    builtin::builtin_plugins,
+    config::PluginConfig,
+    directories::{get_development_workspace_directory, get_plugins_directories},
+    linked::load_linked_plugins,
+    runtime::PluginKind,
+};
 
 #[derive(Debug)]
 pub struct PluginLoader;
@@ -12,10 +18,22 @@ impl PluginLoader {
     }
 
     /// 扫描并返回插件列表
-    /// 同时扫描 (pwd)/plugins 和 (pwd)/src-tauri/src/plugins 目录下的所有包含 config.toml 的子目录
+    /// 先扫描关联的开发目录，再扫描 `get_plugins_directories` 中各目录下所有包含 config.toml 的子目录
+    /// （跳过以 `.` 开头的目录）。
+    /// 出现相同ID时以先扫描到的为准，因此关联的开发版本会覆盖已安装的版本。
+    /// 编译进宿主的内置插件排在最后。
     pub fn scan_plugins(&self) -> Vec<PluginMetadata> {
         let mut plugins = Vec::new();
 
+        // 关联的开发目录本身就是插件目录
+        for linked in load_linked_plugins() {
+            if !linked.path.exists() {
+                log_warn!("Linked plugin directory does not exist: {:?}", linked.path);
+                continue;
+            }
+            self.add_plugin_from_directory(&mut plugins, &linked.path);
+        }
+
         // 获取要扫描的插件目录列表
         let plugin_directories = get_plugins_directories();
 
@@ -33,32 +51,55 @@ impl PluginLoader {
                 .into_iter()
                 .filter_map(|e| e.ok())
             {
-                if entry.file_type().is_dir() {
-                    if let Some(plugin_metadata) = self.load_plugin_from_directory(entry.path()) {
-                        if plugin_metadata.disabled {
-                            continue;
-                        }
-
-                        // 检查是否已经存在相同ID的插件，避免重复加载
-                        if !plugins
-                            .iter()
-                            .any(|p: &PluginMetadata| p.id == plugin_metadata.id)
-                        {
-                            plugins.push(plugin_metadata);
-                        } else {
-                            log_warn!(
-                                "Plugin with ID '{}' already loaded, skipping duplicate",
-                                plugin_metadata.id
-                            );
-                        }
-                    }
+                // 以 `.` 开头的是安装过程中的临时目录
+                if entry.file_type().is_dir()
+                    && !entry.file_name().to_string_lossy().starts_with('.')
+                {
+                    self.add_plugin_from_directory(&mut plugins, entry.path());
                 }
             }
         }
 
+        for builtin in builtin_plugins() {
+            if plugins.iter().any(|p| p.id == builtin.id) {
+                log_warn!(
+                    "Plugin directory overrides built-in plugin '{}'",
+                    builtin.id
+                );
+                continue;
+            }
+            plugins.push(builtin.metadata());
+        }
+
         plugins
     }
 
+    /// 加载目录中的插件并加入列表，跳过已禁用和重复的插件
+    fn add_plugin_from_directory(
+        &self,
+        plugins: &mut Vec<PluginMetadata>,
+        plugin_dir: &std::path::Path,
+    ) {
+        if let Some(plugin_metadata) = self.load_plugin_from_directory(plugin_dir) {
+            if plugin_metadata.disabled {
+                return;
+            }
+
+            // 检查是否已经存在相同ID的插件，避免重复加载
+            if !plugins
+                .iter()
+                .any(|p: &PluginMetadata| p.id == plugin_metadata.id)
+            {
+                plugins.push(plugin_metadata);
+            } else {
+                log_warn!(
+                    "Plugin with ID '{}' already loaded, skipping duplicate",
+                    plugin_metadata.id
+                );
+            }
+        }
+    }
+
     /// 从目录加载插件元数据
     fn load_plugin_from_directory(&self, plugin_dir: &std::path::Path) -> Option<PluginMetadata> {
         let config_path = plugin_dir.join("config.toml");
@@ -68,13 +109,16 @@ impl PluginLoader {
         }
         match PluginConfig::from_file(&config_path) {
             Ok(config) => {
-                let library_path = if let Some(library_name) = config.plugin.library {
-                    self.find_library_file(plugin_dir, library_name.as_str())
-                } else {
-                    self.find_library_file(
-                        plugin_dir,
-                        &format!("{}-{}", config.plugin.id, config.plugin.version),
-                    )
+                let library_name = config
+                    .plugin
+                    .library
+                    .unwrap_or_else(|| format!("{}-{}", config.plugin.id, config.plugin.version));
+                // 远程插件没有本地文件，以运行器地址代替
+                let library_path = match (config.plugin.kind, &config.remote) {
+                    (PluginKind::Remote, remote) => {
+                        remote.as_ref().map(|remote| remote.address.clone())
+                    }
+                    (kind, _) => self.find_library_file(plugin_dir, &kind.file_name(&library_name)),
                 };
                 if library_path.is_none() {
                     log_warn!("Failed to find library file for plugin: {:?}", config_path);
@@ -99,69 +143,66 @@ impl PluginLoader {
         }
     }
 
-    /// 查找动态库文件
-    fn find_library_file(
-        &self,
-        plugin_dir: &std::path::Path,
-        library_name: &str,
-    ) -> Option<String> {
-        // 判断是哪个平台 windows / macos / linux
-        let library_name_dylib = if cfg!(target_os = "windows") {
-            format!("{}.dll", library_name)
-        } else if cfg!(target_os = "macos") {
-            format!("lib{}.dylib", library_name)
-        } else {
-            format!("lib{}.so", library_name)
-        };
-
+    /// 查找插件文件（动态库或 WebAssembly 组件），`file_name` 为包含平台前缀和扩展名的文件名
+    fn find_library_file(&self, plugin_dir: &std::path::Path, file_name: &str) -> Option<String> {
         // 直接在插件目录中查找
-        let direct_path = plugin_dir.join(&library_name_dylib);
+        let direct_path = plugin_dir.join(file_name);
         if direct_path.exists() {
             return Some(direct_path.to_string_lossy().to_string());
         }
 
         // 在插件目录的 target/debug 目录中查找（开发环境）
-        let debug_path = plugin_dir
-            .join("target")
-            .join("debug")
-            .join(&library_name_dylib);
+        let debug_path = plugin_dir.join("target").join("debug").join(file_name);
         if debug_path.exists() {
             return Some(debug_path.to_string_lossy().to_string());
         }
 
         // 在插件目录的 target/release 目录中查找（开发环境）
-        let target_path = plugin_dir
-            .join("target")
-            .join("release")
-            .join(&library_name_dylib);
+        let target_path = plugin_dir.join("target").join("release").join(file_name);
         if target_path.exists() {
             return Some(target_path.to_string_lossy().to_string());
         }
 
-        // 在工作空间的 target/release 目录中查找
-        let workspace_release_path = std::env::current_dir()
-            .unwrap_or_default()
-            .join("target")
-            .join("release")
-            .join(&library_name_dylib);
-        if workspace_release_path.exists() {
-            return Some(workspace_release_path.to_string_lossy().to_string());
+        // WebAssembly 组件在插件目录的 target/<wasm 目标>/release 目录中查找（开发环境）
+        if file_name.ends_with(".wasm") {
+            for target in ["wasm32-wasip2", "wasm32-wasip1"] {
+                let wasm_path = plugin_dir
+                    .join("target")
+                    .join(target)
+                    .join("release")
+                    .join(file_name);
+                if wasm_path.exists() {
+                    return Some(wasm_path.to_string_lossy().to_string());
+                }
+            }
         }
 
-        // 在工作空间的 target/debug 目录中查找
-        let workspace_debug_path = std::env::current_dir()
-            .unwrap_or_default()
-            .join("target")
-            .join("debug")
-            .join(&library_name_dylib);
-        if workspace_debug_path.exists() {
-            return Some(workspace_debug_path.to_string_lossy().to_string());
+        // 开发构建中，在工作空间的 target/release 和 target/debug 目录中查找
+        if let Some(workspace_dir) = get_development_workspace_directory() {
+            for profile in ["release", "debug"] {
+                let workspace_path = workspace_dir.join("target").join(profile).join(file_name);
+                if workspace_path.exists() {
+                    return Some(workspace_path.to_string_lossy().to_string());
+                }
+            }
         }
 
         None
     }
 }
 
+/// 根据库名生成当前平台的动态库文件名
+pub fn library_file_name(library_name: &str) -> String {
+    // 判断是哪个平台 windows / macos / linux
+    if cfg!(target_os = "windows") {
+        format!("{}.dll", library_name)
+    } else if cfg!(target_os = "macos") {
+        format!("lib{}.dylib", library_name)
+    } else {
+        format!("lib{}.so", library_name)
+    }
+}
+
 impl Default for PluginLoader {
     fn default() -> Self {
         Self::new()
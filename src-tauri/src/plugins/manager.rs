@@ -1,23 +1,36 @@
-use crate::plugins::PluginLoader;
-use libloading::{Library, Symbol};
+use crate::plugins::{
+    builtin, callbacks,
+    config::{PluginConfig, PluginPermissions},
+    directories::get_build_directory,
+    events::EventSink,
+    outbound::{self, EventSender, OutboundEvent},
+    permissions::{self, PermissionRequest},
+    resources::{
+        ResourceAlert, ResourceMonitor, ResourceUsage, RESOURCE_FAULT_EVENT, RESOURCE_WARNING_EVENT,
+    },
+    runtime::{PluginKind, PluginRuntime},
+    sanitize::{self, StreamSanitizers},
+    settings::load_settings,
+    trace::{TraceCall, TraceRecorder},
+    PluginLoader,
+};
 use plugin_interfaces::metadata::HistoryMessage;
 use plugin_interfaces::{
-    log_error, log_info,
+    log_error, log_info, log_warn,
     pluginui::{Context, Ui},
-    CreatePluginFn, DestroyPluginFn, HostCallbacks, PluginInterface, PluginMetadata, StreamStatus,
-    CREATE_PLUGIN_SYMBOL, DESTROY_PLUGIN_SYMBOL,
+    HostCallbacks, PluginInterface, PluginMetadata, StreamStatus,
 };
+use serde::Serialize;
 use serde_json;
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
-use std::sync::{Arc, Mutex, OnceLock};
-use tauri::{AppHandle, Emitter};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, RwLock, TryLockError, Weak};
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
-// 全局AppHandle存储，用于在回调函数中访问
-static GLOBAL_APP_HANDLE: OnceLock<AppHandle> = OnceLock::new();
-
 /// 后端流状态信息
 #[derive(Debug, Clone)]
 pub struct BackendStreamInfo {
@@ -28,9 +41,380 @@ pub struct BackendStreamInfo {
     pub created_at: u64,
 }
 
-/// 后端流状态管理器（与插件中的STREAM_MANAGER分离）
-static BACKEND_STREAM_MANAGER: OnceLock<Arc<Mutex<HashMap<String, BackendStreamInfo>>>> =
-    OnceLock::new();
+/// 插件管理器的事件出口：事件接收方、后端流状态表（与插件中的STREAM_MANAGER分离）、
+/// 流式消息的清理状态和可选的跟踪记录
+struct HostContext {
+    sink: Arc<dyn EventSink>,
+    streams: Mutex<HashMap<String, BackendStreamInfo>>,
+    sanitizers: StreamSanitizers,
+    trace: RwLock<Option<Arc<TraceRecorder>>>,
+}
+
+impl std::fmt::Debug for HostContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HostContext")
+            .field("streams", &self.streams)
+            .field("trace", &self.trace)
+            .finish_non_exhaustive()
+    }
+}
+
+/// 插件管理器的实例表，键为 instance_id
+type InstanceTable = Mutex<HashMap<String, PluginInstance>>;
+
+/// 插件回调中等待实例表锁的最长时间
+///
+/// 插件自己的线程调用其他插件时需要等宿主当前的调用结束；宿主线程在等待这个线程时会超时失败，而不是死锁。
+const PLUGIN_CALL_LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 插件接口指针，只在持有所属实例表的锁时使用
+#[derive(Clone, Copy)]
+struct HandlerPtr(*mut PluginInterface);
+
+unsafe impl Send for HandlerPtr {}
+unsafe impl Sync for HandlerPtr {}
+
+/// 已登记实例的事件出口、资源统计、批准的权限和回调槽位
+#[derive(Clone)]
+struct InstanceHost {
+    host: Arc<HostContext>,
+    resources: Arc<ResourceMonitor>,
+    plugin_id: String,
+    permissions: Arc<PluginPermissions>,
+    slot: usize,
+    /// 所属管理器的实例表，插件间调用时用于和宿主的调用互斥
+    table: Weak<InstanceTable>,
+    handler: HandlerPtr,
+    /// 挂载完成、尚未卸载，可以被其他插件调用
+    callable: bool,
+}
+
+thread_local! {
+    /// 当前线程持有的实例表锁（按实例表地址）
+    static HELD_TABLES: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+    /// 当前线程上正在执行的插件实例（插件间调用链）
+    static CALL_CHAIN: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+fn table_key(table: &InstanceTable) -> usize {
+    table as *const InstanceTable as usize
+}
+
+/// 持有实例表锁的守卫，记录当前线程持有该锁，插件回调据此判断能否直接调用其他实例
+struct TableGuard<'a> {
+    guard: MutexGuard<'a, HashMap<String, PluginInstance>>,
+    key: usize,
+}
+
+impl<'a> TableGuard<'a> {
+    fn new(guard: MutexGuard<'a, HashMap<String, PluginInstance>>, table: &InstanceTable) -> Self {
+        let key = table_key(table);
+        HELD_TABLES.with(|held| held.borrow_mut().push(key));
+        Self { guard, key }
+    }
+}
+
+impl std::ops::Deref for TableGuard<'_> {
+    type Target = HashMap<String, PluginInstance>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl std::ops::DerefMut for TableGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+impl Drop for TableGuard<'_> {
+    fn drop(&mut self) {
+        HELD_TABLES.with(|held| {
+            let mut held = held.borrow_mut();
+            if let Some(index) = held.iter().rposition(|key| *key == self.key) {
+                held.remove(index);
+            }
+        });
+    }
+}
+
+/// 当前线程是否持有该实例表的锁
+fn holds_table(table: &InstanceTable) -> bool {
+    let key = table_key(table);
+    HELD_TABLES.with(|held| held.borrow().contains(&key))
+}
+
+/// 在超时前获取实例表锁
+fn lock_table_within(table: &InstanceTable, timeout: Duration) -> Option<TableGuard<'_>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        match table.try_lock() {
+            Ok(guard) => return Some(TableGuard::new(guard, table)),
+            Err(TryLockError::WouldBlock) if Instant::now() < deadline => {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            Err(_) => return None,
+        }
+    }
+}
+
+/// 实例ID到所属插件管理器事件出口的映射
+///
+/// 回调槽位（见 [`callbacks`]）确定调用方实例后，按实例ID找到实例所属的管理器，
+/// 因此多个管理器可以同时存在，各自的事件互不干扰。
+static INSTANCE_HOSTS: OnceLock<RwLock<HashMap<String, InstanceHost>>> = OnceLock::new();
+
+fn get_instance_hosts() -> &'static RwLock<HashMap<String, InstanceHost>> {
+    INSTANCE_HOSTS.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+/// 登记实例所属的事件出口、实例表和批准的权限，返回交给该实例的回调函数
+fn register_instance_host(
+    instance_id: &str,
+    plugin_id: &str,
+    manager: &PluginManager,
+    handler: *mut PluginInterface,
+    permissions: Arc<PluginPermissions>,
+) -> Result<HostCallbacks, String> {
+    let (slot, host_callbacks) = callbacks::allocate(instance_id)?;
+    let previous = get_instance_hosts().write().unwrap().insert(
+        instance_id.to_string(),
+        InstanceHost {
+            host: Arc::clone(&manager.host),
+            resources: Arc::clone(&manager.resources),
+            plugin_id: plugin_id.to_string(),
+            permissions,
+            slot,
+            table: Arc::downgrade(&manager.instances),
+            handler: HandlerPtr(handler),
+            callable: false,
+        },
+    );
+    if let Some(previous) = previous {
+        callbacks::release(previous.slot);
+    }
+    Ok(host_callbacks)
+}
+
+/// 移除实例的事件出口并释放回调槽位（只移除属于 `host` 的登记）
+fn unregister_instance_host(instance_id: &str, host: &Arc<HostContext>) {
+    let mut hosts = get_instance_hosts().write().unwrap();
+    if hosts
+        .get(instance_id)
+        .is_some_and(|registered| Arc::ptr_eq(&registered.host, host))
+    {
+        if let Some(removed) = hosts.remove(instance_id) {
+            callbacks::release(removed.slot);
+        }
+    }
+}
+
+/// 回调参数中的字符串，空指针或不是 UTF-8 时返回 None
+fn callback_str<'a>(value: *const c_char) -> Option<&'a str> {
+    if value.is_null() {
+        return None;
+    }
+    unsafe { CStr::from_ptr(value) }.to_str().ok()
+}
+
+/// 设置实例能否被其他插件调用
+fn set_instance_callable(instance_id: &str, callable: bool) {
+    if let Some(entry) = get_instance_hosts().write().unwrap().get_mut(instance_id) {
+        entry.callable = callable;
+    }
+}
+
+/// 已登记实例的事件出口，同时把调用回调的线程计入实例的资源统计
+fn instance_host(instance_id: &str) -> Option<InstanceHost> {
+    let entry = get_instance_hosts()
+        .read()
+        .unwrap()
+        .get(instance_id)
+        .cloned()?;
+    entry.resources.attribute_current_thread(instance_id);
+    Some(entry)
+}
+
+impl HostContext {
+    /// 发送通过检查的插件事件；消息内容和流式数据先按清理设置处理
+    ///
+    /// `plugin_id` 取自回调槽位登记的实例，而不是插件提供的载荷，插件无法冒充受信任的插件。
+    fn send_outbound(&self, plugin_id: &str, event: OutboundEvent) -> bool {
+        let settings = sanitize::current_settings();
+        if !settings.applies_to(plugin_id) {
+            return self.send(&event.name, &event.payload);
+        }
+        match event.name.as_str() {
+            "plugin-message" => self.send(
+                &event.name,
+                &sanitize::sanitize_message_payload(&settings, &event.payload),
+            ),
+            "plugin-stream" => {
+                let mut sent = true;
+                for payload in self.sanitizers.sanitize(&settings, &event.payload) {
+                    sent &= self.send(&event.name, &payload);
+                }
+                sent
+            }
+            _ => self.send(&event.name, &event.payload),
+        }
+    }
+
+    /// 发送事件；流式消息会先检查取消状态并更新后端流状态
+    fn send(&self, event: &str, payload: &str) -> bool {
+        // 如果是流式消息事件，检查和更新后端流状态
+        if event == "plugin-stream" {
+            // 检查流是否被取消，如果被取消则拒绝发送
+            if let Some(stream_id) = Self::extract_stream_id(payload) {
+                if self.is_stream_cancelled(&stream_id) {
+                    log_info!("流 {} 已被取消，拒绝发送消息", stream_id);
+                    return false; // 返回false表示发送失败
+                }
+            }
+
+            // 更新后端流状态
+            self.handle_stream_event(payload);
+        }
+
+        match self.emit(event, payload) {
+            Ok(()) => true,
+            Err(e) => {
+                log_error!("[PLUGIN->FRONTEND] Failed to send event {}: {}", event, e);
+                false
+            }
+        }
+    }
+
+    /// 直接交给事件接收方，跟踪开启时同时写入跟踪文件
+    fn emit(&self, event: &str, payload: &str) -> Result<(), String> {
+        if let Some(trace) = self.trace() {
+            trace.record_event(event, payload);
+        }
+        self.sink.emit(event, payload)
+    }
+
+    /// 当前的跟踪记录器
+    fn trace(&self) -> Option<Arc<TraceRecorder>> {
+        self.trace.read().unwrap().clone()
+    }
+
+    /// 从流式消息载荷中提取流ID
+    fn extract_stream_id(payload: &str) -> Option<String> {
+        use plugin_interfaces::StreamMessageWrapper;
+
+        if let Ok(wrapper) = serde_json::from_str::<StreamMessageWrapper>(payload) {
+            let stream_id = match &wrapper.data {
+                plugin_interfaces::StreamMessageData::Start(data) => &data.stream_id,
+                plugin_interfaces::StreamMessageData::Data(data) => &data.stream_id,
+                plugin_interfaces::StreamMessageData::End(data) => &data.stream_id,
+                plugin_interfaces::StreamMessageData::Control(data) => &data.stream_id,
+            };
+            Some(stream_id.clone())
+        } else {
+            None
+        }
+    }
+
+    /// 检查流是否已被取消
+    fn is_stream_cancelled(&self, stream_id: &str) -> bool {
+        if let Ok(manager) = self.streams.lock() {
+            if let Some(stream_info) = manager.get(stream_id) {
+                matches!(stream_info.status, StreamStatus::Cancelled)
+            } else {
+                false
+            }
+        } else {
+            false
+        }
+    }
+
+    /// 处理流式消息事件，更新后端流状态
+    fn handle_stream_event(&self, payload: &str) {
+        use plugin_interfaces::StreamMessageWrapper;
+
+        if let Ok(wrapper) = serde_json::from_str::<StreamMessageWrapper>(payload) {
+            let stream_id = match &wrapper.data {
+                plugin_interfaces::StreamMessageData::Start(data) => &data.stream_id,
+                plugin_interfaces::StreamMessageData::Data(data) => &data.stream_id,
+                plugin_interfaces::StreamMessageData::End(data) => &data.stream_id,
+                plugin_interfaces::StreamMessageData::Control(data) => &data.stream_id,
+            };
+
+            let status = match wrapper.r#type.as_str() {
+                "stream_start" => StreamStatus::Active,
+                "stream_data" => {
+                    if let plugin_interfaces::StreamMessageData::Data(data) = &wrapper.data {
+                        if data.is_final {
+                            StreamStatus::Finalizing
+                        } else {
+                            StreamStatus::Active
+                        }
+                    } else {
+                        StreamStatus::Active
+                    }
+                }
+                "stream_end" => {
+                    if let plugin_interfaces::StreamMessageData::End(data) = &wrapper.data {
+                        if data.success {
+                            StreamStatus::Completed
+                        } else {
+                            StreamStatus::Error
+                        }
+                    } else {
+                        StreamStatus::Completed
+                    }
+                }
+                "stream_pause" => StreamStatus::Paused,
+                "stream_resume" => StreamStatus::Active,
+                "stream_cancel" => StreamStatus::Cancelled,
+                _ => return, // 未知类型，忽略
+            };
+
+            self.update_stream_status(stream_id, &wrapper.plugin_id, &wrapper.instance_id, status);
+        }
+    }
+
+    /// 更新后端流状态
+    fn update_stream_status(
+        &self,
+        stream_id: &str,
+        plugin_id: &str,
+        instance_id: &str,
+        status: StreamStatus,
+    ) {
+        if let Ok(mut manager) = self.streams.lock() {
+            match status {
+                StreamStatus::Active => {
+                    // 新建或更新流状态
+                    let stream_info = BackendStreamInfo {
+                        stream_id: stream_id.to_string(),
+                        plugin_id: plugin_id.to_string(),
+                        instance_id: instance_id.to_string(),
+                        status: status.clone(),
+                        created_at: std::time::SystemTime::now()
+                            .duration_since(std::time::UNIX_EPOCH)
+                            .unwrap()
+                            .as_secs(),
+                    };
+                    manager.insert(stream_id.to_string(), stream_info);
+                }
+                StreamStatus::Completed | StreamStatus::Error | StreamStatus::Cancelled => {
+                    // 移除已结束的流
+                    manager.remove(stream_id);
+                    log_info!("后端流管理器：移除流 {}", stream_id);
+                }
+                _ => {
+                    // 更新现有流状态
+                    if let Some(stream_info) = manager.get_mut(stream_id) {
+                        stream_info.status = status.clone();
+                        log_info!("后端流管理器：更新流 {} 状态为 {:?}", stream_id, status);
+                    }
+                }
+            }
+        }
+    }
+}
 
 /// 插件实例信息
 pub struct PluginInstance {
@@ -38,7 +422,7 @@ pub struct PluginInstance {
     pub instance_id: String,                 // 插件实例ID，用于多实例支持
     pub plugin_id: String,                   // 插件ID，用于标识插件类型
     pub handler: *mut PluginInterface,       // 插件处理函数集合
-    pub library: Library,                    // 插件库句柄，用于卸载
+    pub runtime: PluginRuntime,              // 插件运行时（动态库句柄等），用于卸载
     pub is_mounted: bool,                    // 是否已经挂载
     pub is_connected: bool,                  // 是否已经连接
     pub ui_data: Option<String>,             // 保存序列化的UI数据
@@ -66,119 +450,297 @@ unsafe impl Sync for PluginInstance {}
 #[derive(Debug)]
 pub struct PluginManager {
     loader: PluginLoader,
-    instances: Arc<Mutex<HashMap<String, PluginInstance>>>, // 键为 instance_id
+    instances: Arc<InstanceTable>, // 键为 instance_id
     plugin_instances: Arc<Mutex<HashMap<String, Vec<String>>>>, // 键为 plugin_id，值为 instance_id 列表
-    app_handle: AppHandle,
-}
-
-/// 获取后端流管理器实例
-fn get_backend_stream_manager() -> &'static Arc<Mutex<HashMap<String, BackendStreamInfo>>> {
-    BACKEND_STREAM_MANAGER.get_or_init(|| Arc::new(Mutex::new(HashMap::new())))
+    host: Arc<HostContext>,                                     // 事件接收方和流状态
+    resources: Arc<ResourceMonitor>,                            // 各实例的资源统计
+    trust_manifests: bool,                                      // 是否不经批准直接授予清单中的权限
 }
 
 impl PluginManager {
-    pub fn new(app_handle: AppHandle) -> Self {
+    /// 创建插件管理器，插件发往前端的事件交给 `sink` 处理
+    pub fn new(sink: Arc<dyn EventSink>) -> Self {
         Self {
             loader: PluginLoader::new(),
             instances: Arc::new(Mutex::new(HashMap::new())),
             plugin_instances: Arc::new(Mutex::new(HashMap::new())),
-            app_handle,
+            host: Arc::new(HostContext {
+                sink,
+                streams: Mutex::new(HashMap::new()),
+                sanitizers: StreamSanitizers::default(),
+                trace: RwLock::new(None),
+            }),
+            resources: Arc::new(ResourceMonitor::default()),
+            trust_manifests: false,
         }
     }
 
-    /// 创建主程序回调函数集合
-    fn create_host_callbacks(&self) -> HostCallbacks {
-        // 将AppHandle克隆并存储在静态变量中，供回调函数使用
-        GLOBAL_APP_HANDLE.set(self.app_handle.clone()).ok();
+    /// 不经用户批准，直接授予插件清单中申请的权限
+    ///
+    /// 用于沙箱辅助进程（宿主已检查过批准状态）、远程运行器和跟踪回放。
+    pub fn trust_manifests(mut self) -> Self {
+        self.trust_manifests = true;
+        self
+    }
 
-        HostCallbacks {
-            send_to_frontend: Self::host_send_to_frontend,
-            get_app_config: Self::host_get_app_config,
-            call_other_plugin: Self::host_call_other_plugin,
+    /// 开始跟踪：此后的宿主调用、返回值和插件事件都会带时间戳写入 `path`，
+    /// 已有的跟踪会被替换。跟踪文件可用 [`crate::plugins::replay::replay_trace`] 回放。
+    pub fn start_trace(&self, path: &Path) -> Result<(), String> {
+        let recorder = Arc::new(TraceRecorder::create(path)?);
+        if let Some(previous) = self.host.trace.write().unwrap().replace(recorder) {
+            let _ = previous.flush();
         }
+        log_info!("开始记录插件跟踪: {:?}", path);
+        Ok(())
     }
 
-    /// 向前端发送消息
-    extern "C" fn host_send_to_frontend(event: *const c_char, payload: *const c_char) -> bool {
-        if !event.is_null() && !payload.is_null() {
-            unsafe {
-                if let (Ok(event_str), Ok(payload_str)) = (
-                    CStr::from_ptr(event).to_str(),
-                    CStr::from_ptr(payload).to_str(),
-                ) {
-                    // 如果是流式消息事件，检查和更新后端流状态
-                    if event_str == "plugin-stream" {
-                        // 检查流是否被取消，如果被取消则拒绝发送
-                        if let Some(stream_id) = Self::extract_stream_id(payload_str) {
-                            if Self::is_stream_cancelled(&stream_id) {
-                                log_info!("流 {} 已被取消，拒绝发送消息", stream_id);
-                                return false; // 返回false表示发送失败
-                            }
-                        }
+    /// 停止跟踪，返回跟踪文件路径
+    pub fn stop_trace(&self) -> Option<PathBuf> {
+        let recorder = self.host.trace.write().unwrap().take()?;
+        if let Err(e) = recorder.flush() {
+            log_error!("{}", e);
+        }
+        log_info!("停止记录插件跟踪: {:?}", recorder.path());
+        Some(recorder.path().to_path_buf())
+    }
 
-                        // 更新后端流状态
-                        Self::handle_stream_event(payload_str);
-                    }
+    /// 当前跟踪文件路径，未开启跟踪时为 None
+    pub fn trace_path(&self) -> Option<PathBuf> {
+        self.host
+            .trace()
+            .map(|recorder| recorder.path().to_path_buf())
+    }
 
-                    // 实现实际的Tauri事件发送
-                    if let Some(app_handle) = GLOBAL_APP_HANDLE.get() {
-                        match app_handle.emit(event_str, payload_str) {
-                            Ok(_) => {
-                                return true;
-                            }
-                            Err(e) => {
-                                log_error!(
-                                    "[PLUGIN->FRONTEND] Failed to send event {}: {}",
-                                    event_str,
-                                    e
-                                );
-                            }
-                        }
-                    } else {
-                        log_error!("[PLUGIN->FRONTEND] AppHandle not available");
-                    }
-                }
+    /// 获取实例表锁
+    fn lock_instances(&self) -> TableGuard<'_> {
+        TableGuard::new(self.instances.lock().unwrap(), &self.instances)
+    }
+
+    /// 执行一次宿主调用；开启跟踪时记录调用和返回值
+    fn traced<T: Serialize>(
+        &self,
+        call: impl FnOnce() -> TraceCall,
+        f: impl FnOnce() -> Result<T, String>,
+    ) -> Result<T, String> {
+        match self.host.trace() {
+            Some(trace) => {
+                let seq = trace.record_call(call());
+                let result = f();
+                trace.record_return(seq, &result);
+                result
             }
+            None => f(),
         }
-        false
     }
 
-    /// 获取应用配置
-    extern "C" fn host_get_app_config(key: *const c_char) -> *const c_char {
-        if !key.is_null() {
-            unsafe {
-                if let Ok(key_str) = CStr::from_ptr(key).to_str() {
-                    // TODO: 实现实际的配置获取逻辑
-                    let config_value = format!("config_value_for_{}", key_str);
-                    if let Ok(c_string) = CString::new(config_value) {
-                        return c_string.into_raw();
-                    }
-                }
+    /// 执行一次对实例的宿主调用，调用期间的 CPU 时间计入该实例
+    fn host_call<T: Serialize>(
+        &self,
+        instance_id: &str,
+        call: impl FnOnce() -> TraceCall,
+        f: impl FnOnce() -> Result<T, String>,
+    ) -> Result<T, String> {
+        self.resources.measure(instance_id, || self.traced(call, f))
+    }
+
+    /// 向前端发送消息
+    ///
+    /// `instance_id` 由回调槽位确定。按 [`outbound::check_event`] 检查事件，通过检查的事件清理
+    /// Markdown 后交给实例所属管理器的事件出口；实例已卸载或不符合要求的事件被丢弃。
+    pub(crate) fn host_send_to_frontend(
+        instance_id: &str,
+        event: *const c_char,
+        payload: *const c_char,
+    ) -> bool {
+        if event.is_null() || payload.is_null() {
+            return false;
+        }
+        let (event_str, payload_str) = unsafe {
+            (
+                CStr::from_ptr(event).to_str(),
+                CStr::from_ptr(payload).to_str(),
+            )
+        };
+        let (Ok(event_str), Ok(payload_str)) = (event_str, payload_str) else {
+            return false;
+        };
+
+        let Some(entry) = instance_host(instance_id) else {
+            log_warn!(
+                "[PLUGIN->FRONTEND] 实例 {} 未登记，事件 {} 已丢弃",
+                instance_id,
+                event_str
+            );
+            return false;
+        };
+        let sender = EventSender {
+            plugin_id: &entry.plugin_id,
+            instance_id,
+            permissions: &entry.permissions,
+        };
+        match outbound::check_event(&sender, event_str, payload_str) {
+            Ok(event) => entry.host.send_outbound(&entry.plugin_id, event),
+            Err(e) => {
+                log_warn!(
+                    "[PLUGIN->FRONTEND] 已丢弃插件 {} ({}) 的事件: {}",
+                    entry.plugin_id,
+                    instance_id,
+                    e
+                );
+                false
             }
         }
-        std::ptr::null()
+    }
+
+    /// 获取应用配置
+    ///
+    /// `key` 是 `settings.toml` 中用点分隔的设置项，如 `network.proxy`。只有批准的 `config`
+    /// 权限中列出的设置项可以读取，值以 JSON 返回；被拒绝或设置项不存在时返回空指针。
+    pub(crate) fn host_get_app_config(instance_id: &str, key: *const c_char) -> *const c_char {
+        let Some(key) = callback_str(key) else {
+            return std::ptr::null();
+        };
+        let Some(entry) = instance_host(instance_id) else {
+            log_warn!("实例 {} 未登记，读取配置 {} 被拒绝", instance_id, key);
+            return std::ptr::null();
+        };
+        if !entry.permissions.allows_config(key) {
+            log_warn!(
+                "插件 {} ({}) 读取配置 {} 被拒绝: 未获得 config 权限",
+                entry.plugin_id,
+                instance_id,
+                key
+            );
+            return std::ptr::null();
+        }
+
+        let settings = serde_json::to_value(load_settings()).unwrap_or_default();
+        let value = key
+            .split('.')
+            .try_fold(&settings, |value, part| value.get(part));
+        match value.and_then(|value| CString::new(value.to_string()).ok()) {
+            Some(value) => value.into_raw(),
+            None => std::ptr::null(),
+        }
     }
 
     /// 调用其他插件
-    extern "C" fn host_call_other_plugin(
+    ///
+    /// 只能调用批准的 `plugins` 权限中列出的插件。消息交给目标插件在同一管理器中已挂载的实例
+    /// （有多个时取实例ID最小的），返回其回复；被拒绝或调用失败时返回空指针。
+    /// 宿主调用插件期间一直持有实例表锁：回调在宿主调用中发生时直接调用目标实例，
+    /// 在插件自己的线程中发生时先等待实例表锁，因此同一实例不会被并发调用。
+    /// 会重新进入调用链上已有实例的调用被拒绝。
+    pub(crate) fn host_call_other_plugin(
+        instance_id: &str,
         plugin_id: *const c_char,
         message: *const c_char,
     ) -> *const c_char {
-        if !plugin_id.is_null() && !message.is_null() {
-            unsafe {
-                if let (Ok(id_str), Ok(_msg_str)) = (
-                    CStr::from_ptr(plugin_id).to_str(),
-                    CStr::from_ptr(message).to_str(),
-                ) {
-                    // TODO: 实现实际的插件间通信逻辑
-                    let response = format!("response_from_{}", id_str);
-                    if let Ok(c_string) = CString::new(response) {
-                        return c_string.into_raw();
-                    }
-                }
+        let (Some(target_plugin), Some(message)) = (callback_str(plugin_id), callback_str(message))
+        else {
+            return std::ptr::null();
+        };
+        let Some(entry) = instance_host(instance_id) else {
+            log_warn!(
+                "实例 {} 未登记，调用插件 {} 被拒绝",
+                instance_id,
+                target_plugin
+            );
+            return std::ptr::null();
+        };
+        if !entry.permissions.allows_plugin(target_plugin) {
+            log_warn!(
+                "插件 {} ({}) 调用插件 {} 被拒绝: 未获得 plugins 权限",
+                entry.plugin_id,
+                instance_id,
+                target_plugin
+            );
+            return std::ptr::null();
+        }
+
+        match Self::call_plugin_instance(instance_id, &entry, target_plugin, message) {
+            Ok(response) => CString::new(response)
+                .map(|response| response.into_raw() as *const c_char)
+                .unwrap_or(std::ptr::null()),
+            Err(e) => {
+                log_warn!(
+                    "插件 {} ({}) 调用插件 {} 失败: {}",
+                    entry.plugin_id,
+                    instance_id,
+                    target_plugin,
+                    e
+                );
+                std::ptr::null()
             }
         }
-        std::ptr::null()
+    }
+
+    fn call_plugin_instance(
+        caller_id: &str,
+        caller: &InstanceHost,
+        target_plugin: &str,
+        message: &str,
+    ) -> Result<String, String> {
+        let table = caller.table.upgrade().ok_or("插件管理器已释放")?;
+        let _guard = if holds_table(&table) {
+            None
+        } else {
+            Some(
+                lock_table_within(&table, PLUGIN_CALL_LOCK_TIMEOUT)
+                    .ok_or("等待宿主当前的插件调用结束超时")?,
+            )
+        };
+
+        let chain = CALL_CHAIN.with(|chain| chain.borrow().clone());
+        let (target_id, target) = get_instance_hosts()
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(id, target)| {
+                target.callable
+                    && target.plugin_id == target_plugin
+                    && Arc::ptr_eq(&target.host, &caller.host)
+                    && id.as_str() != caller_id
+                    && !chain.contains(id)
+            })
+            .min_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(id, target)| (id.clone(), target.handler))
+            .ok_or("没有可以调用的已挂载实例（或调用会重新进入调用链上的实例）")?;
+
+        let message = CString::new(message).map_err(|_| "消息转换失败".to_string())?;
+        let pushed: Vec<String> = [caller_id, target_id.as_str()]
+            .into_iter()
+            .filter(|id| !chain.iter().any(|existing| existing == id))
+            .map(str::to_string)
+            .collect();
+        CALL_CHAIN.with(|chain| chain.borrow_mut().extend(pushed.iter().cloned()));
+
+        // 目标实例在调用方的线程中运行，CPU 时间计入调用方
+        let mut response_ptr: *mut c_char = std::ptr::null_mut();
+        let result = unsafe {
+            ((*target.0).handle_message)(
+                (*target.0).plugin_ptr,
+                message.as_ptr(),
+                &mut response_ptr,
+            )
+        };
+
+        CALL_CHAIN.with(|chain| {
+            let mut chain = chain.borrow_mut();
+            let len = chain.len() - pushed.len();
+            chain.truncate(len);
+        });
+
+        if result != 0 {
+            return Err(format!("插件实例 {} 处理消息失败", target_id));
+        }
+        if response_ptr.is_null() {
+            return Err(format!("插件实例 {} 返回空响应", target_id));
+        }
+        unsafe { CStr::from_ptr(response_ptr) }
+            .to_str()
+            .map(str::to_string)
+            .map_err(|_| "响应转换失败".to_string())
     }
 
     /// 扫描插件列表
@@ -191,11 +753,38 @@ impl PluginManager {
         &self,
         plugin_id: &str,
         instance_id: Option<String>,
+    ) -> Result<String, String> {
+        let plugin_metadata = self.find_plugin_metadata(plugin_id)?;
+        self.mount_plugin_with_metadata(plugin_id, instance_id, plugin_metadata)
+    }
+
+    /// 使用给定的元数据挂载插件实例（热重载时用于指定动态库副本的路径）
+    fn mount_plugin_with_metadata(
+        &self,
+        plugin_id: &str,
+        instance_id: Option<String>,
+        plugin_metadata: PluginMetadata,
     ) -> Result<String, String> {
         // 生成或使用提供的实例ID
         let instance_id = instance_id.unwrap_or_else(|| Uuid::new_v4().to_string());
 
-        let mut instances = self.instances.lock().unwrap();
+        self.host_call(
+            &instance_id,
+            || TraceCall::Mount {
+                plugin_id: plugin_id.to_string(),
+                instance_id: instance_id.clone(),
+            },
+            || self.mount_instance(plugin_id, instance_id.clone(), plugin_metadata),
+        )
+    }
+
+    fn mount_instance(
+        &self,
+        plugin_id: &str,
+        instance_id: String,
+        mut plugin_metadata: PluginMetadata,
+    ) -> Result<String, String> {
+        let mut instances = self.lock_instances();
 
         // 如果实例已经存在且已挂载，直接返回成功
         if let Some(instance) = instances.get(&instance_id) {
@@ -205,33 +794,39 @@ impl PluginManager {
         }
 
         // 加载插件
-        let mut plugin_metadata = self.find_plugin_metadata(plugin_id)?;
         plugin_metadata.instance_id = Some(instance_id.clone());
         let library_path = plugin_metadata
             .library_path
             .as_ref()
             .ok_or_else(|| format!("插件 {} 没有找到动态库文件", plugin_id))?;
 
-        // 动态加载库
-        let library = unsafe {
-            Library::new(library_path).map_err(|e| format!("加载动态库失败: {}", e))?
-        };
-
-        // 获取创建函数
-        let create_plugin: Symbol<CreatePluginFn> = unsafe {
-            library
-                .get(CREATE_PLUGIN_SYMBOL)
-                .map_err(|e| format!("找不到插件创建函数: {}", e))?
-        };
+        // 申请了权限的插件需要用户批准后才能挂载
+        let permissions = Arc::new(self.granted_permissions(&plugin_metadata)?);
+
+        // 按插件类型加载并创建插件实例
+        let kind = PluginKind::of(&plugin_metadata);
+        let (runtime, handler) =
+            PluginRuntime::load(kind, Path::new(library_path), &plugin_metadata)?;
+
+        // 登记事件出口并分配回调槽位，插件在初始化和挂载时就可能发送事件
+        let callbacks =
+            match register_instance_host(&instance_id, plugin_id, self, handler, permissions) {
+                Ok(callbacks) => callbacks,
+                Err(e) => {
+                    unsafe { runtime.destroy(handler) };
+                    return Err(e);
+                }
+            };
 
-        // 创建插件实例
-        let handler = unsafe { create_plugin() };
-        if handler.is_null() {
-            return Err("插件创建失败".to_string());
-        }
+        // 开始资源统计，插件在初始化和挂载时就可能创建线程
+        let limits = PluginConfig::from_file(&plugin_metadata.config_path)
+            .ok()
+            .and_then(|config| config.limits)
+            .unwrap_or_default();
+        self.resources
+            .register(&instance_id, plugin_id, runtime.process_id(), limits);
 
         // 初始化插件（设置回调函数和元数据）
-        let callbacks = self.create_host_callbacks();
         let metadata_ffi = plugin_metadata.to_ffi();
         let init_result =
             unsafe { ((*handler).initialize)((*handler).plugin_ptr, callbacks, metadata_ffi) };
@@ -243,13 +838,9 @@ impl PluginManager {
 
         if init_result != 0 {
             // 清理失败的插件实例
-            unsafe {
-                let destroy_plugin: Result<Symbol<DestroyPluginFn>, _> =
-                    library.get(DESTROY_PLUGIN_SYMBOL);
-                if let Ok(destroy_fn) = destroy_plugin {
-                    destroy_fn(handler);
-                }
-            }
+            unsafe { runtime.destroy(handler) };
+            unregister_instance_host(&instance_id, &self.host);
+            self.resources.unregister(&instance_id);
             return Err("插件初始化失败".to_string());
         }
 
@@ -294,7 +885,7 @@ impl PluginManager {
                     instance_id: instance_id.clone(),
                     plugin_id: plugin_id.to_string(),
                     handler,
-                    library,
+                    runtime,
                     is_mounted: true,
                     is_connected: false,
                     ui_data: Some(ui_data),
@@ -302,6 +893,7 @@ impl PluginManager {
                 };
 
                 instances.insert(instance_id.clone(), instance);
+                set_instance_callable(&instance_id, true);
 
                 // 更新插件实例映射
                 let mut plugin_instances = self.plugin_instances.lock().unwrap();
@@ -315,27 +907,92 @@ impl PluginManager {
             }
             Err(e) => {
                 // 清理失败的插件实例
-                unsafe {
-                    let destroy_plugin: Result<Symbol<DestroyPluginFn>, _> =
-                        library.get(DESTROY_PLUGIN_SYMBOL);
-                    if let Ok(destroy_fn) = destroy_plugin {
-                        destroy_fn(handler);
-                    }
-                }
+                unsafe { runtime.destroy(handler) };
+                unregister_instance_host(&instance_id, &self.host);
+                self.resources.unregister(&instance_id);
                 Err(format!("插件挂载失败: {}", e))
             }
         }
     }
 
+    /// 热重载插件
+    ///
+    /// 重新扫描插件元数据，卸载该插件的所有已挂载实例，再用新的动态库以相同的实例ID重新挂载，
+    /// 原先已连接的实例会重新连接，并通知前端刷新UI。
+    /// 动态库会先复制到独立的路径再加载，避免系统因路径相同而复用已加载的旧版本。
+    pub fn reload_plugin(&self, plugin_id: &str) -> Result<String, String> {
+        let mut plugin_metadata = self.find_plugin_metadata(plugin_id)?;
+        let library_path = plugin_metadata
+            .library_path
+            .as_ref()
+            .ok_or_else(|| format!("插件 {} 没有找到动态库文件", plugin_id))?;
+        // 只有动态库会被系统按路径缓存；子进程插件需要在原目录中运行
+        if PluginKind::of(&plugin_metadata) == PluginKind::Native {
+            plugin_metadata.library_path = Some(
+                shadow_copy_library(plugin_id, std::path::Path::new(library_path))?
+                    .to_string_lossy()
+                    .to_string(),
+            );
+        }
+
+        let instance_ids = self
+            .plugin_instances
+            .lock()
+            .unwrap()
+            .get(plugin_id)
+            .cloned()
+            .unwrap_or_default();
+
+        let mut reloaded = 0;
+        for instance_id in instance_ids {
+            let was_connected = self
+                .get_plugin_status(&instance_id)
+                .map(|(_, connected)| connected)
+                .unwrap_or(false);
+
+            self.dispose_plugin(&instance_id)?;
+            // 移除旧实例以释放旧的动态库
+            self.lock_instances().remove(&instance_id);
+
+            self.mount_plugin_with_metadata(
+                plugin_id,
+                Some(instance_id.clone()),
+                plugin_metadata.clone(),
+            )?;
+            if was_connected {
+                self.connect_plugin(&instance_id)?;
+            }
+            if let Err(e) = self.notify_plugin_ui_update(plugin_id, &instance_id) {
+                log_error!("通知插件UI更新失败: {}", e);
+            }
+            reloaded += 1;
+        }
+
+        log_info!("插件 {} 已热重载，共 {} 个实例", plugin_id, reloaded);
+        Ok(format!("插件 {} 已重新加载 {} 个实例", plugin_id, reloaded))
+    }
+
     /// 卸载插件实例
     pub fn dispose_plugin(&self, instance_id: &str) -> Result<String, String> {
-        let mut instances = self.instances.lock().unwrap();
+        self.host_call(
+            instance_id,
+            || TraceCall::Dispose {
+                instance_id: instance_id.to_string(),
+            },
+            || self.dispose_instance(instance_id),
+        )
+    }
+
+    fn dispose_instance(&self, instance_id: &str) -> Result<String, String> {
+        let mut instances = self.lock_instances();
 
         if let Some(instance) = instances.get_mut(instance_id) {
             if !instance.is_mounted {
                 return Ok(format!("插件 {} 已经卸载", instance.metadata.name));
             }
 
+            set_instance_callable(instance_id, false);
+
             // 先断开连接
             if instance.is_connected {
                 let _ =
@@ -353,18 +1010,14 @@ impl PluginManager {
             };
 
             // 销毁插件实例
-            unsafe {
-                let destroy_plugin: Result<Symbol<DestroyPluginFn>, _> =
-                    instance.library.get(DESTROY_PLUGIN_SYMBOL);
-                if let Ok(destroy_fn) = destroy_plugin {
-                    destroy_fn(instance.handler);
-                }
-            }
+            unsafe { instance.runtime.destroy(instance.handler) };
 
             let plugin_id = instance.plugin_id.clone();
             let instance_name = instance.metadata.name.clone();
 
             instance.is_mounted = false;
+            unregister_instance_host(instance_id, &self.host);
+            self.resources.unregister(instance_id);
 
             // TODO: 清理插件元数据 - 需要重新实现以支持实例级别管理
 
@@ -395,7 +1048,17 @@ impl PluginManager {
 
     /// 连接插件实例
     pub fn connect_plugin(&self, instance_id: &str) -> Result<String, String> {
-        let mut instances = self.instances.lock().unwrap();
+        self.host_call(
+            instance_id,
+            || TraceCall::Connect {
+                instance_id: instance_id.to_string(),
+            },
+            || self.connect_instance(instance_id),
+        )
+    }
+
+    fn connect_instance(&self, instance_id: &str) -> Result<String, String> {
+        let mut instances = self.lock_instances();
 
         if let Some(instance) = instances.get_mut(instance_id) {
             if !instance.is_mounted {
@@ -431,7 +1094,17 @@ impl PluginManager {
 
     /// 断开插件实例连接
     pub fn disconnect_plugin(&self, instance_id: &str) -> Result<String, String> {
-        let mut instances = self.instances.lock().unwrap();
+        self.host_call(
+            instance_id,
+            || TraceCall::Disconnect {
+                instance_id: instance_id.to_string(),
+            },
+            || self.disconnect_instance(instance_id),
+        )
+    }
+
+    fn disconnect_instance(&self, instance_id: &str) -> Result<String, String> {
+        let mut instances = self.lock_instances();
 
         if let Some(instance) = instances.get_mut(instance_id) {
             if !instance.is_mounted {
@@ -469,7 +1142,7 @@ impl PluginManager {
 
     /// 获取插件实例状态
     pub fn get_plugin_status(&self, instance_id: &str) -> Option<(bool, bool)> {
-        let instances = self.instances.lock().unwrap();
+        let instances = self.lock_instances();
         instances
             .get(instance_id)
             .map(|instance| (instance.is_mounted, instance.is_connected))
@@ -477,7 +1150,17 @@ impl PluginManager {
 
     /// 获取插件实例UI定义
     pub fn get_plugin_ui(&self, instance_id: &str) -> Result<String, String> {
-        let mut instances = self.instances.lock().unwrap();
+        self.host_call(
+            instance_id,
+            || TraceCall::GetUi {
+                instance_id: instance_id.to_string(),
+            },
+            || self.read_plugin_ui(instance_id),
+        )
+    }
+
+    fn read_plugin_ui(&self, instance_id: &str) -> Result<String, String> {
+        let mut instances = self.lock_instances();
 
         if let Some(instance) = instances.get_mut(instance_id) {
             if instance.is_mounted {
@@ -508,7 +1191,24 @@ impl PluginManager {
         component_id: &str,
         value: &str,
     ) -> Result<bool, String> {
-        let mut instances = self.instances.lock().unwrap();
+        self.host_call(
+            instance_id,
+            || TraceCall::UiUpdate {
+                instance_id: instance_id.to_string(),
+                component_id: component_id.to_string(),
+                value: value.to_string(),
+            },
+            || self.apply_ui_update(instance_id, component_id, value),
+        )
+    }
+
+    fn apply_ui_update(
+        &self,
+        instance_id: &str,
+        component_id: &str,
+        value: &str,
+    ) -> Result<bool, String> {
+        let mut instances = self.lock_instances();
 
         if let Some(instance) = instances.get_mut(instance_id) {
             if !instance.is_mounted {
@@ -572,7 +1272,24 @@ impl PluginManager {
         component_id: &str,
         value: &str,
     ) -> Result<bool, String> {
-        let mut instances = self.instances.lock().unwrap();
+        self.host_call(
+            instance_id,
+            || TraceCall::UiEvent {
+                instance_id: instance_id.to_string(),
+                component_id: component_id.to_string(),
+                value: value.to_string(),
+            },
+            || self.apply_ui_event(instance_id, component_id, value),
+        )
+    }
+
+    fn apply_ui_event(
+        &self,
+        instance_id: &str,
+        component_id: &str,
+        value: &str,
+    ) -> Result<bool, String> {
+        let mut instances = self.lock_instances();
 
         if let Some(instance) = instances.get_mut(instance_id) {
             if instance.is_mounted {
@@ -655,7 +1372,26 @@ impl PluginManager {
         message: &str,
         history: Option<Vec<HistoryMessage>>,
     ) -> Result<String, String> {
-        let mut instances = self.instances.lock().unwrap();
+        self.host_call(
+            instance_id,
+            || TraceCall::SendMessage {
+                plugin_id: plugin_id.to_string(),
+                instance_id: instance_id.to_string(),
+                message: message.to_string(),
+                history: history.clone(),
+            },
+            || self.deliver_message(plugin_id, instance_id, message, history.as_deref()),
+        )
+    }
+
+    fn deliver_message(
+        &self,
+        plugin_id: &str,
+        instance_id: &str,
+        message: &str,
+        history: Option<&[HistoryMessage]>,
+    ) -> Result<String, String> {
+        let mut instances = self.lock_instances();
 
         if let Some(instance) = instances.get_mut(instance_id) {
             // 验证插件ID是否匹配
@@ -676,7 +1412,7 @@ impl PluginManager {
 
             // 如果插件需要历史记录，先设置历史记录
             if instance.metadata.require_history {
-                if let Some(history_data) = &history {
+                if let Some(history_data) = history {
                     // 将历史记录序列化为 JSON
                     match serde_json::to_string(history_data) {
                         Ok(history_json) => {
@@ -753,18 +1489,13 @@ impl PluginManager {
         plugin_id: &str,
         instance_id: &str,
     ) -> Result<(), String> {
-        // 向前端发送UI更新事件，使用 host_send_to_frontend 而不是直接调用 app_handle.emit
+        // 向前端发送UI更新事件，与插件发出的事件经过同一个事件出口
         let payload = serde_json::json!({
             "plugin": plugin_id,
             "instance": instance_id
         });
-        let payload_str = payload.to_string();
-
-        // 使用 CString 确保字符串以 null 结尾
-        let event_cstr = CString::new("plugin-ui-updated").map_err(|_| "事件名称转换失败")?;
-        let payload_cstr = CString::new(payload_str).map_err(|_| "载荷转换失败")?;
 
-        if Self::host_send_to_frontend(event_cstr.as_ptr(), payload_cstr.as_ptr()) {
+        if self.host.send("plugin-ui-updated", &payload.to_string()) {
             Ok(())
         } else {
             Err("发送UI更新事件失败".to_string())
@@ -773,7 +1504,7 @@ impl PluginManager {
 
     /// 清理所有已挂载的插件实例（应用关闭时调用）
     pub fn cleanup_all_plugins(&self) {
-        let mut instances = self.instances.lock().unwrap();
+        let mut instances = self.lock_instances();
 
         // 收集所有已挂载的实例ID
         let mounted_instance_ids: Vec<String> = instances
@@ -801,15 +1532,11 @@ impl PluginManager {
                         unsafe { ((*instance.handler).on_dispose)((*instance.handler).plugin_ptr) };
 
                     // 销毁插件实例
-                    unsafe {
-                        let destroy_plugin: Result<Symbol<DestroyPluginFn>, _> =
-                            instance.library.get(DESTROY_PLUGIN_SYMBOL);
-                        if let Ok(destroy_fn) = destroy_plugin {
-                            destroy_fn(instance.handler);
-                        }
-                    }
+                    unsafe { instance.runtime.destroy(instance.handler) };
 
                     instance.is_mounted = false;
+                    unregister_instance_host(&instance_id, &self.host);
+                    self.resources.unregister(&instance_id);
                     log_info!(
                         "插件实例 {} ({}) 清理完成",
                         instance.metadata.name,
@@ -832,10 +1559,21 @@ impl PluginManager {
         instance_id: &str,
         stream_id: &str,
     ) -> Result<String, String> {
+        self.host_call(
+            instance_id,
+            || TraceCall::CancelStream {
+                instance_id: instance_id.to_string(),
+                stream_id: stream_id.to_string(),
+            },
+            || self.cancel_stream(instance_id, stream_id),
+        )
+    }
+
+    fn cancel_stream(&self, instance_id: &str, stream_id: &str) -> Result<String, String> {
         use plugin_interfaces::{StreamControlData, StreamMessageData, StreamMessageWrapper};
         use std::time::{SystemTime, UNIX_EPOCH};
 
-        let instances = self.instances.lock().unwrap();
+        let instances = self.lock_instances();
 
         if let Some(instance) = instances.get(instance_id) {
             if !instance.is_mounted {
@@ -846,9 +1584,8 @@ impl PluginManager {
                 return Err(format!("插件实例 {} 未连接", instance_id));
             }
 
-            // 使用后端流管理器
-            let backend_manager = get_backend_stream_manager();
-            if let Ok(mut manager) = backend_manager.lock() {
+            // 使用本管理器的后端流状态表
+            if let Ok(mut manager) = self.host.streams.lock() {
                 log_info!("后端流管理器中的所有流: {:?}", manager);
 
                 if let Some(stream_info) = manager.get_mut(stream_id) {
@@ -873,8 +1610,10 @@ impl PluginManager {
                             };
 
                             if let Ok(payload) = serde_json::to_string(&wrapper) {
-                                // 直接使用 app_handle 发送到前端
-                                let _ = self.app_handle.emit("plugin-stream", payload);
+                                // 直接发送到前端，不经过已取消流的检查
+                                if let Err(e) = self.host.emit("plugin-stream", &payload) {
+                                    log_error!("发送流取消事件失败: {}", e);
+                                }
                             }
 
                             Ok(format!("流式消息 {} 取消成功", stream_id))
@@ -892,125 +1631,111 @@ impl PluginManager {
         }
     }
 
-    /// 从流式消息载荷中提取流ID
-    fn extract_stream_id(payload: &str) -> Option<String> {
-        use plugin_interfaces::StreamMessageWrapper;
-
-        if let Ok(wrapper) = serde_json::from_str::<StreamMessageWrapper>(payload) {
-            let stream_id = match &wrapper.data {
-                plugin_interfaces::StreamMessageData::Start(data) => &data.stream_id,
-                plugin_interfaces::StreamMessageData::Data(data) => &data.stream_id,
-                plugin_interfaces::StreamMessageData::End(data) => &data.stream_id,
-                plugin_interfaces::StreamMessageData::Control(data) => &data.stream_id,
-            };
-            Some(stream_id.clone())
-        } else {
-            None
+    /// 插件挂载时授予的权限
+    ///
+    /// 内置插件随应用编译，不受限制；其他插件授予清单中申请的权限，申请的权限超出用户批准的范围时拒绝挂载。
+    fn granted_permissions(&self, metadata: &PluginMetadata) -> Result<PluginPermissions, String> {
+        if builtin::is_builtin(metadata) {
+            return Ok(PluginPermissions::unrestricted());
+        }
+        let config = PluginConfig::from_file(&metadata.config_path)
+            .map_err(|e| format!("读取插件配置失败: {}", e))?;
+        if self.trust_manifests {
+            return Ok(config.permissions);
         }
+        let request = permissions::permission_request(&config);
+        if !request.is_approved() {
+            return Err(format!("插件 {} 申请的权限尚未获得批准", metadata.name));
+        }
+        Ok(request.requested)
     }
 
-    /// 检查流是否已被取消
-    fn is_stream_cancelled(stream_id: &str) -> bool {
-        let backend_manager = get_backend_stream_manager();
-        if let Ok(manager) = backend_manager.lock() {
-            if let Some(stream_info) = manager.get(stream_id) {
-                matches!(stream_info.status, StreamStatus::Cancelled)
-            } else {
-                false
-            }
-        } else {
-            false
-        }
+    /// 插件申请的权限和批准状态
+    pub fn get_plugin_permissions(&self, plugin_id: &str) -> Result<PermissionRequest, String> {
+        self.plugin_permission_request(plugin_id, false)
     }
 
-    /// 处理流式消息事件，更新后端流状态
-    fn handle_stream_event(payload: &str) {
-        use plugin_interfaces::StreamMessageWrapper;
+    /// 批准插件当前版本申请的全部权限
+    pub fn approve_plugin_permissions(&self, plugin_id: &str) -> Result<PermissionRequest, String> {
+        self.plugin_permission_request(plugin_id, true)
+    }
 
-        if let Ok(wrapper) = serde_json::from_str::<StreamMessageWrapper>(payload) {
-            let stream_id = match &wrapper.data {
-                plugin_interfaces::StreamMessageData::Start(data) => &data.stream_id,
-                plugin_interfaces::StreamMessageData::Data(data) => &data.stream_id,
-                plugin_interfaces::StreamMessageData::End(data) => &data.stream_id,
-                plugin_interfaces::StreamMessageData::Control(data) => &data.stream_id,
-            };
+    fn plugin_permission_request(
+        &self,
+        plugin_id: &str,
+        approve: bool,
+    ) -> Result<PermissionRequest, String> {
+        let metadata = self.find_plugin_metadata(plugin_id)?;
+        // 内置插件不需要批准
+        if builtin::is_builtin(&metadata) {
+            return Ok(PermissionRequest {
+                plugin_id: metadata.id,
+                plugin_name: metadata.name,
+                version: metadata.version,
+                requested: PluginPermissions::default(),
+                pending: PluginPermissions::default(),
+                approved_version: None,
+            });
+        }
+        let config = PluginConfig::from_file(&metadata.config_path)
+            .map_err(|e| format!("读取插件配置失败: {}", e))?;
+        if approve {
+            permissions::approve_permissions(&config)
+        } else {
+            Ok(permissions::permission_request(&config))
+        }
+    }
 
-            let status = match wrapper.r#type.as_str() {
-                "stream_start" => StreamStatus::Active,
-                "stream_data" => {
-                    if let plugin_interfaces::StreamMessageData::Data(data) = &wrapper.data {
-                        if data.is_final {
-                            StreamStatus::Finalizing
-                        } else {
-                            StreamStatus::Active
-                        }
-                    } else {
-                        StreamStatus::Active
-                    }
-                }
-                "stream_end" => {
-                    if let plugin_interfaces::StreamMessageData::End(data) = &wrapper.data {
-                        if data.success {
-                            StreamStatus::Completed
-                        } else {
-                            StreamStatus::Error
-                        }
-                    } else {
-                        StreamStatus::Completed
-                    }
-                }
-                "stream_pause" => StreamStatus::Paused,
-                "stream_resume" => StreamStatus::Active,
-                "stream_cancel" => StreamStatus::Cancelled,
-                _ => return, // 未知类型，忽略
-            };
+    /// 所有实例的资源占用
+    pub fn get_resource_usage(&self) -> Vec<ResourceUsage> {
+        self.resources.usage()
+    }
 
-            Self::update_backend_stream_status(
-                stream_id,
-                &wrapper.plugin_id,
-                &wrapper.instance_id,
-                status,
+    /// 采样各实例的资源占用，超过软限制的发送警告事件，返回超过硬限制的实例
+    ///
+    /// 超过硬限制的进程外插件已被结束，调用方需要用 [`Self::stop_faulted_instance`] 卸载实例；
+    /// 进程内插件只被标记为故障，同样由该方法卸载。
+    pub fn check_resource_limits(&self) -> Vec<ResourceAlert> {
+        let (faults, warnings): (Vec<_>, Vec<_>) = self
+            .resources
+            .sample()
+            .into_iter()
+            .partition(|alert| alert.fault);
+        for alert in warnings {
+            log_warn!(
+                "插件实例 {} 资源占用过高: {}",
+                alert.instance_id,
+                alert.message
             );
+            self.emit_resource_alert(RESOURCE_WARNING_EVENT, &alert);
         }
+        faults
     }
 
-    /// 更新后端流状态（供事件监听器调用）
-    pub fn update_backend_stream_status(
-        stream_id: &str,
-        plugin_id: &str,
-        instance_id: &str,
-        status: StreamStatus,
-    ) {
-        let backend_manager = get_backend_stream_manager();
-        if let Ok(mut manager) = backend_manager.lock() {
-            match status {
-                StreamStatus::Active => {
-                    // 新建或更新流状态
-                    let stream_info = BackendStreamInfo {
-                        stream_id: stream_id.to_string(),
-                        plugin_id: plugin_id.to_string(),
-                        instance_id: instance_id.to_string(),
-                        status: status.clone(),
-                        created_at: std::time::SystemTime::now()
-                            .duration_since(std::time::UNIX_EPOCH)
-                            .unwrap()
-                            .as_secs(),
-                    };
-                    manager.insert(stream_id.to_string(), stream_info);
-                }
-                StreamStatus::Completed | StreamStatus::Error | StreamStatus::Cancelled => {
-                    // 移除已结束的流
-                    manager.remove(stream_id);
-                    log_info!("后端流管理器：移除流 {}", stream_id);
-                }
-                _ => {
-                    // 更新现有流状态
-                    if let Some(stream_info) = manager.get_mut(stream_id) {
-                        stream_info.status = status.clone();
-                        log_info!("后端流管理器：更新流 {} 状态为 {:?}", stream_id, status);
-                    }
-                }
+    /// 卸载超过硬限制的实例并通知前端
+    pub fn stop_faulted_instance(&self, alert: &ResourceAlert) {
+        log_error!(
+            "插件实例 {} 超过资源限制，已停止: {}",
+            alert.instance_id,
+            alert.message
+        );
+        if let Err(e) = self.dispose_plugin(&alert.instance_id) {
+            log_error!("卸载插件实例 {} 失败: {}", alert.instance_id, e);
+        }
+        self.emit_resource_alert(RESOURCE_FAULT_EVENT, alert);
+    }
+
+    /// 资源事件不经过跟踪记录，避免采样时间影响回放
+    fn emit_resource_alert(&self, event: &str, alert: &ResourceAlert) {
+        let payload = match serde_json::to_string(alert) {
+            Ok(payload) => payload,
+            Err(e) => {
+                log_error!("序列化资源事件失败: {}", e);
+                return;
             }
+        };
+        if let Err(e) = self.host.sink.emit(event, &payload) {
+            log_error!("发送资源事件 {} 失败: {}", event, e);
         }
     }
 
@@ -1023,3 +1748,41 @@ impl PluginManager {
             .ok_or_else(|| format!("插件 {} 未找到", plugin_id))
     }
 }
+
+impl Drop for PluginManager {
+    fn drop(&mut self) {
+        // 移除本管理器登记的事件出口，实例本身由 cleanup_all_plugins 清理
+        if let Ok(instances) = self.instances.lock() {
+            for instance_id in instances.keys() {
+                unregister_instance_host(instance_id, &self.host);
+            }
+        }
+    }
+}
+
+/// 将动态库复制到热重载目录中的唯一路径，并清理该插件之前的副本
+fn shadow_copy_library(
+    plugin_id: &str,
+    library_path: &std::path::Path,
+) -> Result<std::path::PathBuf, String> {
+    let hot_reload_dir = get_build_directory().join("hot-reload").join(plugin_id);
+    if hot_reload_dir.exists() {
+        // 仍被加载的副本在部分平台上无法删除，忽略错误
+        for entry in std::fs::read_dir(&hot_reload_dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+        {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+    std::fs::create_dir_all(&hot_reload_dir).map_err(|e| format!("创建热重载目录失败: {}", e))?;
+
+    let file_name = library_path
+        .file_name()
+        .ok_or_else(|| format!("动态库路径无效: {:?}", library_path))?
+        .to_string_lossy();
+    let shadow_path = hot_reload_dir.join(format!("{}-{}", Uuid::new_v4().simple(), file_name));
+    std::fs::copy(library_path, &shadow_path).map_err(|e| format!("复制动态库失败: {}", e))?;
+    Ok(shadow_path)
+}
@@ -1,16 +1,56 @@
+pub mod builder;
+pub mod builtin;
+pub mod callbacks;
 pub mod config;
 pub mod directories;
+pub mod download;
+pub mod events;
+pub mod git;
+pub mod http;
+pub mod install;
+pub mod linked;
 pub mod loader;
 pub mod manager;
+pub mod outbound;
+pub mod package;
+pub mod permissions;
+pub mod replay;
 pub mod repository;
+pub mod resources;
+pub mod runner;
+pub mod runtime;
+pub mod sandbox;
+pub mod sanitize;
+pub mod scaffold;
+pub mod settings;
+pub mod trace;
+pub mod watcher;
 
-pub use config::{DownloadConfig, PlatformDownload, PluginConfig, PluginInfo};
+pub use builder::{BuildLogLine, BuildLogStream, BUILD_LOG_EVENT};
+pub use builtin::{register_builtin_plugin, BuiltinPlugin};
+pub use config::{
+    DownloadConfig, PlatformDownload, PluginConfig, PluginInfo, PluginPermissions, RemoteConfig,
+    ResourceLimits, SandboxConfig, SourceConfig, SubprocessConfig,
+};
+pub use download::{DownloadProgress, DOWNLOAD_PROGRESS_EVENT};
+pub use events::{ChannelEventSink, EventSink, PluginEvent, RecordingEventSink, TauriEventSink};
+pub use install::{InstallRecord, InstallSource};
+pub use linked::LinkedPlugin;
 pub use loader::PluginLoader;
 pub use manager::PluginManager;
+pub use package::{PackageBuilder, PackageManifest, PACKAGE_EXTENSION};
+pub use permissions::PermissionRequest;
 pub use plugin_interfaces::{
     CreatePluginFn, DestroyPluginFn, PluginHandler, PluginMetadata, CREATE_PLUGIN_SYMBOL,
     DESTROY_PLUGIN_SYMBOL,
 };
+pub use replay::{replay_trace, ReplayOptions, ReplayReport, TraceDifference};
 pub use repository::{
     AvailablePluginInfo, DownloadResponse, PluginDownloadResult, PluginRepository,
 };
+pub use resources::{ResourceState, ResourceUsage, RESOURCE_FAULT_EVENT, RESOURCE_WARNING_EVENT};
+pub use runner::PluginRunner;
+pub use runtime::{PluginKind, PluginRuntime};
+pub use scaffold::{PluginTemplate, ScaffoldOptions};
+pub use settings::{AppSettings, NetworkSettings, PackageSettings, PathSettings, SanitizeSettings};
+pub use trace::{load_trace, TraceCall, TraceEntry, TraceRecord, TraceRecorder};
@@ -0,0 +1,315 @@
+//! 插件发往前端的事件检查
+//!
+//! 插件通过 `send_to_frontend` 回调发送的事件都要经过这里。宿主定义的事件按各自的结构校验，
+//! 载荷中的插件ID和实例ID必须属于发送方；插件在清单中申请的自定义事件改名为
+//! `plugin:<实例ID>:<事件名>` 发送，前端按实例订阅，插件无法冒充其他实例或宿主的事件。
+//! 每种事件的载荷都有大小上限，不符合要求的事件被丢弃，由调用方记录日志。
+
+use crate::plugins::config::PluginPermissions;
+use crate::plugins::permissions::HOST_EVENTS;
+use plugin_interfaces::StreamMessageWrapper;
+use serde_json::{Map, Value};
+
+/// 消息和流式消息载荷的大小上限（字节）
+pub const MAX_MESSAGE_PAYLOAD: usize = 1024 * 1024;
+/// UI 更新和断开连接请求等控制事件载荷的大小上限（字节）
+pub const MAX_CONTROL_PAYLOAD: usize = 4 * 1024;
+/// 自定义事件载荷的大小上限（字节）
+pub const MAX_CUSTOM_PAYLOAD: usize = 64 * 1024;
+/// 自定义事件名的长度上限
+const MAX_EVENT_NAME_LEN: usize = 64;
+
+/// 流式消息的类型
+const STREAM_TYPES: &[&str] = &[
+    "stream_start",
+    "stream_data",
+    "stream_end",
+    "stream_pause",
+    "stream_resume",
+    "stream_cancel",
+];
+
+/// 发送事件的插件实例
+#[derive(Debug, Clone, Copy)]
+pub struct EventSender<'a> {
+    pub plugin_id: &'a str,
+    pub instance_id: &'a str,
+    pub permissions: &'a PluginPermissions,
+}
+
+/// 通过检查、可以发送给前端的事件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutboundEvent {
+    pub name: String,
+    pub payload: String,
+}
+
+/// 自定义事件在前端的事件名
+pub fn instance_event_name(instance_id: &str, event: &str) -> String {
+    format!("plugin:{}:{}", instance_id, event)
+}
+
+/// 检查插件发送的事件，返回实际发给前端的事件
+pub fn check_event(
+    sender: &EventSender,
+    event: &str,
+    payload: &str,
+) -> Result<OutboundEvent, String> {
+    let is_host_event = HOST_EVENTS.contains(&event);
+    if !is_host_event {
+        check_custom_event_name(sender, event)?;
+    }
+
+    let limit = match event {
+        "plugin-message" | "plugin-stream" => MAX_MESSAGE_PAYLOAD,
+        _ if is_host_event => MAX_CONTROL_PAYLOAD,
+        _ => MAX_CUSTOM_PAYLOAD,
+    };
+    if payload.len() > limit {
+        return Err(format!(
+            "事件 {} 的载荷有 {} 字节，超过上限 {} 字节",
+            event,
+            payload.len(),
+            limit
+        ));
+    }
+
+    let value: Value = serde_json::from_str(payload)
+        .map_err(|e| format!("事件 {} 的载荷不是有效的 JSON: {}", event, e))?;
+    let object = value
+        .as_object()
+        .ok_or_else(|| format!("事件 {} 的载荷必须是 JSON 对象", event))?;
+    check_identity(sender, event, object)?;
+
+    match event {
+        "plugin-message" => {
+            require_string(event, object, "content")?;
+        }
+        "plugin-stream" => {
+            let wrapper: StreamMessageWrapper = serde_json::from_value(value.clone())
+                .map_err(|e| format!("流式消息结构不正确: {}", e))?;
+            if !STREAM_TYPES.contains(&wrapper.r#type.as_str()) {
+                return Err(format!("未知的流式消息类型 {}", wrapper.r#type));
+            }
+        }
+        "plugin-ui-updated" | "plugin-ui-refreshed" => {
+            require_string(event, object, "instance")?;
+        }
+        "plugin-disconnect-request" => {
+            require_string(event, object, "instance_id")?;
+        }
+        _ => {
+            return Ok(OutboundEvent {
+                name: instance_event_name(sender.instance_id, event),
+                payload: payload.to_string(),
+            });
+        }
+    }
+
+    Ok(OutboundEvent {
+        name: event.to_string(),
+        payload: payload.to_string(),
+    })
+}
+
+/// 自定义事件名只能包含字母、数字、`-` 和 `_`，并且需要在清单中申请
+fn check_custom_event_name(sender: &EventSender, event: &str) -> Result<(), String> {
+    if event.is_empty()
+        || event.len() > MAX_EVENT_NAME_LEN
+        || !event
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!("事件名 {:?} 不合法", event));
+    }
+    if !sender.permissions.allows_event(event) {
+        return Err(format!("未获准发送事件 {}", event));
+    }
+    Ok(())
+}
+
+/// 载荷中的插件ID和实例ID必须属于发送方，宿主定义的事件必须带有实例ID
+fn check_identity(
+    sender: &EventSender,
+    event: &str,
+    object: &Map<String, Value>,
+) -> Result<(), String> {
+    let mut has_instance = false;
+    for key in ["instance_id", "instance"] {
+        if let Some(value) = object.get(key) {
+            if value.as_str() != Some(sender.instance_id) {
+                return Err(format!("事件 {} 的 {} 不是发送方的实例ID", event, key));
+            }
+            has_instance = true;
+        }
+    }
+    if !has_instance && HOST_EVENTS.contains(&event) {
+        return Err(format!("事件 {} 缺少实例ID", event));
+    }
+    for key in ["plugin_id", "plugin"] {
+        if let Some(value) = object.get(key) {
+            if value.as_str() != Some(sender.plugin_id) {
+                return Err(format!("事件 {} 的 {} 不是发送方的插件ID", event, key));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn require_string(event: &str, object: &Map<String, Value>, key: &str) -> Result<(), String> {
+    match object.get(key) {
+        Some(Value::String(_)) => Ok(()),
+        _ => Err(format!("事件 {} 缺少字符串字段 {}", event, key)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const PLUGIN: &str = "weather";
+    const INSTANCE: &str = "instance-1";
+
+    fn permissions() -> PluginPermissions {
+        PluginPermissions {
+            events: vec!["weather-*".to_string()],
+            ..PluginPermissions::default()
+        }
+    }
+
+    fn check(event: &str, payload: &Value) -> Result<OutboundEvent, String> {
+        let permissions = permissions();
+        let sender = EventSender {
+            plugin_id: PLUGIN,
+            instance_id: INSTANCE,
+            permissions: &permissions,
+        };
+        check_event(&sender, event, &payload.to_string())
+    }
+
+    /// `extra` 字段填充到载荷恰好为 `size` 字节
+    fn padded(mut payload: Value, size: usize) -> Value {
+        payload["extra"] = json!("");
+        let padding = size - payload.to_string().len();
+        payload["extra"] = json!("x".repeat(padding));
+        assert_eq!(payload.to_string().len(), size);
+        payload
+    }
+
+    fn stream_payload(kind: &str, instance_id: &str) -> Value {
+        json!({
+            "type": kind,
+            "plugin_id": PLUGIN,
+            "instance_id": instance_id,
+            "data": { "stream_id": "stream-1", "chunk": "hello", "is_final": false },
+            "timestamp": 0,
+        })
+    }
+
+    #[test]
+    fn host_events_pass_unchanged() {
+        let payload = json!({ "content": "hello", "instance_id": INSTANCE, "plugin_id": PLUGIN });
+        let event = check("plugin-message", &payload).unwrap();
+        assert_eq!(event.name, "plugin-message");
+        assert_eq!(event.payload, payload.to_string());
+    }
+
+    #[test]
+    fn spoofed_identity_is_rejected() {
+        let other_instance = json!({ "content": "hello", "instance_id": "instance-2" });
+        assert!(check("plugin-message", &other_instance).is_err());
+
+        let other_plugin =
+            json!({ "content": "hello", "instance_id": INSTANCE, "plugin_id": "other" });
+        assert!(check("plugin-message", &other_plugin).is_err());
+
+        assert!(check(
+            "plugin-stream",
+            &stream_payload("stream_data", "instance-2")
+        )
+        .is_err());
+        assert!(check("plugin-ui-updated", &json!({ "instance": "instance-2" })).is_err());
+        assert!(check("weather-update", &json!({ "instance_id": "instance-2" })).is_err());
+    }
+
+    #[test]
+    fn host_events_require_instance_id() {
+        assert!(check("plugin-message", &json!({ "content": "hello" })).is_err());
+        assert!(check("plugin-disconnect-request", &json!({})).is_err());
+    }
+
+    #[test]
+    fn message_payload_is_capped() {
+        let payload = json!({ "content": "hello", "instance_id": INSTANCE });
+        assert!(check(
+            "plugin-message",
+            &padded(payload.clone(), MAX_MESSAGE_PAYLOAD)
+        )
+        .is_ok());
+        assert!(check("plugin-message", &padded(payload, MAX_MESSAGE_PAYLOAD + 1)).is_err());
+
+        let stream = stream_payload("stream_data", INSTANCE);
+        assert!(check(
+            "plugin-stream",
+            &padded(stream.clone(), MAX_MESSAGE_PAYLOAD)
+        )
+        .is_ok());
+        assert!(check("plugin-stream", &padded(stream, MAX_MESSAGE_PAYLOAD + 1)).is_err());
+    }
+
+    #[test]
+    fn control_payload_is_capped() {
+        let payload = json!({ "instance": INSTANCE });
+        assert!(check(
+            "plugin-ui-updated",
+            &padded(payload.clone(), MAX_CONTROL_PAYLOAD)
+        )
+        .is_ok());
+        assert!(check(
+            "plugin-ui-updated",
+            &padded(payload, MAX_CONTROL_PAYLOAD + 1)
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn custom_payload_is_capped() {
+        let payload = json!({ "instance_id": INSTANCE });
+        assert!(check(
+            "weather-update",
+            &padded(payload.clone(), MAX_CUSTOM_PAYLOAD)
+        )
+        .is_ok());
+        assert!(check("weather-update", &padded(payload, MAX_CUSTOM_PAYLOAD + 1)).is_err());
+    }
+
+    #[test]
+    fn unknown_stream_type_is_rejected() {
+        for kind in STREAM_TYPES {
+            assert!(check("plugin-stream", &stream_payload(kind, INSTANCE)).is_ok());
+        }
+        assert!(check("plugin-stream", &stream_payload("stream_restart", INSTANCE)).is_err());
+    }
+
+    #[test]
+    fn custom_events_are_renamed_per_instance() {
+        let payload = json!({ "temperature": 21 });
+        let event = check("weather-update", &payload).unwrap();
+        assert_eq!(event.name, "plugin:instance-1:weather-update");
+        assert_eq!(event.name, instance_event_name(INSTANCE, "weather-update"));
+        assert_eq!(event.payload, payload.to_string());
+    }
+
+    #[test]
+    fn custom_events_need_permission_and_valid_name() {
+        assert!(check("traffic-update", &json!({})).is_err());
+        assert!(check("weather:update", &json!({})).is_err());
+        assert!(check("", &json!({})).is_err());
+        assert!(check(
+            &format!("weather-{}", "x".repeat(MAX_EVENT_NAME_LEN)),
+            &json!({})
+        )
+        .is_err());
+    }
+}
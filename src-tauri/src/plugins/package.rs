@@ -0,0 +1,659 @@
+//! 插件包格式
+//!
+//! 插件包是一个 ZIP 文件（扩展名 `.ccpkg`），结构如下：
+//!
+//! ```text
+//! manifest.toml          包清单：格式版本、插件ID、各平台动态库路径、所有文件的 SHA-256
+//! config.toml            插件配置
+//! lib/<target>/<file>    各平台的动态库，<target> 形如 linux-x86_64、windows-x86_64、macos-aarch64
+//! assets/<file>          头像等资源文件
+//! LICENSE                许可证（可选）
+//! README.md              说明文档（可选）
+//! signature              对 manifest.toml 的 Ed25519 签名，十六进制编码（可选）
+//! ```
+//!
+//! 清单中记录了除 manifest.toml 和 signature 之外每个文件的摘要，
+//! 因此签名清单即可保护整个包的内容。包内出现清单未记录的文件、
+//! 重名文件或清单中的插件ID与 config.toml 不一致时，整个包都会被拒绝。
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use plugin_interfaces::{log_info, log_warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use zip::{write::SimpleFileOptions, ZipArchive, ZipWriter};
+
+use crate::plugins::{
+    config::PluginConfig,
+    install::{install_plugin, plugin_library_name, validate_plugin_config, InstallSource},
+    settings::PackageSettings,
+};
+
+/// 插件包扩展名
+pub const PACKAGE_EXTENSION: &str = "ccpkg";
+
+/// 当前插件包格式版本
+pub const PACKAGE_FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_FILE: &str = "manifest.toml";
+const SIGNATURE_FILE: &str = "signature";
+const CONFIG_FILE: &str = "config.toml";
+
+/// 插件包清单
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageManifest {
+    pub format_version: u32,
+    pub plugin_id: String,
+    pub version: String,
+    /// 目标平台 -> 包内动态库路径
+    pub libraries: BTreeMap<String, String>,
+    /// 包内文件路径 -> SHA-256（小写十六进制）
+    pub files: BTreeMap<String, String>,
+    #[serde(default)]
+    pub license: Option<String>,
+    #[serde(default)]
+    pub readme: Option<String>,
+    #[serde(default)]
+    pub assets: Vec<String>,
+}
+
+/// 当前主机的目标平台标识，例如 `linux-x86_64`
+pub fn host_target() -> String {
+    format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH)
+}
+
+/// 指定目标平台上动态库的文件名，规则与 `loader::library_file_name` 一致
+fn target_library_file_name(target: &str, library_name: &str) -> String {
+    if target.starts_with("windows") {
+        format!("{}.dll", library_name)
+    } else if target.starts_with("macos") {
+        format!("lib{}.dylib", library_name)
+    } else {
+        format!("lib{}.so", library_name)
+    }
+}
+
+/// 判断文件是否为插件包（扩展名为 .ccpkg，或 ZIP 文件根目录包含 manifest.toml）
+pub fn is_plugin_package(path: &Path) -> bool {
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if extension == PACKAGE_EXTENSION {
+        return true;
+    }
+    if extension != "zip" {
+        return false;
+    }
+    fs::File::open(path)
+        .ok()
+        .and_then(|file| ZipArchive::new(file).ok())
+        .map(|archive| archive.index_for_name(MANIFEST_FILE).is_some())
+        .unwrap_or(false)
+}
+
+/// 插件包打包器
+///
+/// ```ignore
+/// PackageBuilder::new("config.toml")?
+///     .library("linux-x86_64", "target/release/libexample.so")
+///     .library("windows-x86_64", "target/release/example.dll")
+///     .license("LICENSE")
+///     .readme("README.md")
+///     .write("example-1.0.0.ccpkg")?;
+/// ```
+pub struct PackageBuilder {
+    config_path: PathBuf,
+    config: PluginConfig,
+    libraries: BTreeMap<String, PathBuf>,
+    assets: Vec<PathBuf>,
+    license: Option<PathBuf>,
+    readme: Option<PathBuf>,
+    signing_key: Option<SigningKey>,
+}
+
+impl PackageBuilder {
+    pub fn new<P: AsRef<Path>>(config_path: P) -> Result<Self, String> {
+        let config_path = config_path.as_ref().to_path_buf();
+        let config = PluginConfig::from_file(&config_path)
+            .map_err(|e| format!("读取插件配置失败: {}", e))?;
+        validate_plugin_config(&config)?;
+
+        Ok(Self {
+            config_path,
+            config,
+            libraries: BTreeMap::new(),
+            assets: Vec::new(),
+            license: None,
+            readme: None,
+            signing_key: None,
+        })
+    }
+
+    /// 添加指定平台的动态库
+    pub fn library<P: AsRef<Path>>(mut self, target: &str, path: P) -> Self {
+        self.libraries
+            .insert(target.to_string(), path.as_ref().to_path_buf());
+        self
+    }
+
+    /// 添加资源文件（如头像），打包到 assets/ 目录下
+    pub fn asset<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.assets.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn license<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.license = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn readme<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.readme = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// 使用 Ed25519 私钥（32 字节种子）签名插件包
+    pub fn sign_with(mut self, secret_key: &[u8; 32]) -> Self {
+        self.signing_key = Some(SigningKey::from_bytes(secret_key));
+        self
+    }
+
+    /// 写出插件包
+    pub fn write<P: AsRef<Path>>(self, output: P) -> Result<PackageManifest, String> {
+        if self.libraries.is_empty() {
+            return Err("插件包至少需要包含一个平台的动态库".to_string());
+        }
+
+        let library_name = plugin_library_name(&self.config);
+        let mut entries: Vec<(String, PathBuf)> =
+            vec![(CONFIG_FILE.to_string(), self.config_path.clone())];
+        let mut manifest = PackageManifest {
+            format_version: PACKAGE_FORMAT_VERSION,
+            plugin_id: self.config.plugin.id.clone(),
+            version: self.config.plugin.version.clone(),
+            libraries: BTreeMap::new(),
+            files: BTreeMap::new(),
+            license: None,
+            readme: None,
+            assets: Vec::new(),
+        };
+
+        for (target, path) in &self.libraries {
+            let file_name = target_library_file_name(target, &library_name);
+            let entry_name = format!("lib/{}/{}", target, file_name);
+            manifest
+                .libraries
+                .insert(target.clone(), entry_name.clone());
+            entries.push((entry_name, path.clone()));
+        }
+
+        for asset in &self.assets {
+            let file_name = asset
+                .file_name()
+                .ok_or_else(|| format!("资源文件路径无效: {:?}", asset))?
+                .to_string_lossy();
+            let entry_name = format!("assets/{}", file_name);
+            manifest.assets.push(entry_name.clone());
+            entries.push((entry_name, asset.clone()));
+        }
+        if let Some(license) = &self.license {
+            manifest.license = Some("LICENSE".to_string());
+            entries.push(("LICENSE".to_string(), license.clone()));
+        }
+        if let Some(readme) = &self.readme {
+            manifest.readme = Some("README.md".to_string());
+            entries.push(("README.md".to_string(), readme.clone()));
+        }
+
+        // 读取所有文件并计算摘要
+        let mut contents = Vec::with_capacity(entries.len());
+        for (entry_name, path) in entries {
+            let data = fs::read(&path).map_err(|e| format!("读取文件失败 {:?}: {}", path, e))?;
+            manifest.files.insert(entry_name.clone(), sha256_hex(&data));
+            contents.push((entry_name, data));
+        }
+
+        let manifest_content =
+            toml::to_string_pretty(&manifest).map_err(|e| format!("序列化包清单失败: {}", e))?;
+
+        let output = output.as_ref();
+        let file = fs::File::create(output).map_err(|e| format!("创建插件包失败: {}", e))?;
+        let mut writer = ZipWriter::new(file);
+        let options = SimpleFileOptions::default();
+
+        let mut write_entry = |name: &str, data: &[u8]| -> Result<(), String> {
+            writer
+                .start_file(name, options)
+                .map_err(|e| format!("写入插件包失败: {}", e))?;
+            writer
+                .write_all(data)
+                .map_err(|e| format!("写入插件包失败: {}", e))
+        };
+
+        write_entry(MANIFEST_FILE, manifest_content.as_bytes())?;
+        if let Some(signing_key) = &self.signing_key {
+            let signature = signing_key.sign(manifest_content.as_bytes());
+            write_entry(SIGNATURE_FILE, hex::encode(signature.to_bytes()).as_bytes())?;
+        }
+        for (entry_name, data) in &contents {
+            write_entry(entry_name, data)?;
+        }
+        writer
+            .finish()
+            .map_err(|e| format!("写入插件包失败: {}", e))?;
+
+        log_info!("插件包已生成: {:?}", output);
+        Ok(manifest)
+    }
+}
+
+/// 读取并校验插件包
+///
+/// 检查格式版本、签名（如果存在）、所有文件摘要，以及包内没有清单之外的文件、
+/// 清单引用的文件都记录了摘要、插件ID和版本与 config.toml 一致。
+pub fn read_package(
+    package_path: &Path,
+    settings: &PackageSettings,
+) -> Result<(PackageManifest, ZipArchive<fs::File>), String> {
+    let file = fs::File::open(package_path).map_err(|e| format!("无法打开插件包: {}", e))?;
+    let mut archive = ZipArchive::new(file).map_err(|e| format!("无法打开插件包: {}", e))?;
+
+    let manifest_bytes = read_entry(&mut archive, MANIFEST_FILE)?
+        .ok_or_else(|| "插件包缺少 manifest.toml".to_string())?;
+    let manifest_content =
+        String::from_utf8(manifest_bytes).map_err(|_| "包清单不是有效的 UTF-8".to_string())?;
+    let manifest: PackageManifest =
+        toml::from_str(&manifest_content).map_err(|e| format!("解析包清单失败: {}", e))?;
+
+    if manifest.format_version > PACKAGE_FORMAT_VERSION {
+        return Err(format!(
+            "不支持的插件包格式版本: {}（当前支持 {}）",
+            manifest.format_version, PACKAGE_FORMAT_VERSION
+        ));
+    }
+
+    match read_entry(&mut archive, SIGNATURE_FILE)? {
+        Some(signature) => verify_signature(&manifest_content, &signature, settings)?,
+        None if settings.require_signature => {
+            return Err("插件包未签名，当前设置要求插件包必须签名".to_string());
+        }
+        None => {}
+    }
+
+    let names: Vec<String> = archive.file_names().map(str::to_string).collect();
+    for name in &names {
+        if name == MANIFEST_FILE || name == SIGNATURE_FILE || name.ends_with('/') {
+            continue;
+        }
+        if !manifest.files.contains_key(name) {
+            return Err(format!("插件包包含清单未记录的文件: {}", name));
+        }
+    }
+    let referenced = std::iter::once(CONFIG_FILE)
+        .chain(manifest.libraries.values().map(String::as_str))
+        .chain(manifest.assets.iter().map(String::as_str))
+        .chain(manifest.license.as_deref())
+        .chain(manifest.readme.as_deref());
+    for entry_name in referenced {
+        if !manifest.files.contains_key(entry_name) {
+            return Err(format!("包清单未记录文件摘要: {}", entry_name));
+        }
+    }
+
+    for entry_name in manifest.files.keys() {
+        read_verified_entry(&mut archive, &manifest, entry_name)?;
+    }
+
+    let config_content = read_verified_entry(&mut archive, &manifest, CONFIG_FILE)?;
+    let config: PluginConfig = toml::from_str(&String::from_utf8_lossy(&config_content))
+        .map_err(|e| format!("解析插件配置失败: {}", e))?;
+    if config.plugin.id != manifest.plugin_id {
+        return Err(format!(
+            "包清单中的插件ID {} 与配置文件中的 {} 不一致",
+            manifest.plugin_id, config.plugin.id
+        ));
+    }
+    if config.plugin.version != manifest.version {
+        return Err(format!(
+            "包清单中的版本 {} 与配置文件中的 {} 不一致",
+            manifest.version, config.plugin.version
+        ));
+    }
+
+    Ok((manifest, archive))
+}
+
+/// 安装插件包
+///
+/// 只解压当前平台的动态库以及配置、资源、许可证和说明文件，然后按普通安装流程安装。
+pub fn install_package(
+    package_path: &Path,
+    work_dir: &Path,
+    source: InstallSource,
+    settings: &PackageSettings,
+) -> Result<PathBuf, String> {
+    let (manifest, mut archive) = read_package(package_path, settings)?;
+
+    let target = host_target();
+    let library_entry = manifest.libraries.get(&target).ok_or_else(|| {
+        format!(
+            "插件包不包含当前平台 ({}) 的动态库，可用平台: {}",
+            target,
+            manifest
+                .libraries
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    })?;
+
+    let extract_dir = work_dir.join(format!("package-{}", uuid::Uuid::new_v4()));
+    let result = (|| {
+        let config_path = extract_entry(&mut archive, &manifest, CONFIG_FILE, &extract_dir)?;
+        let library_path = extract_entry(&mut archive, &manifest, library_entry, &extract_dir)?;
+
+        let mut extra_files = Vec::new();
+        let optional_entries = manifest
+            .assets
+            .iter()
+            .chain(manifest.license.iter())
+            .chain(manifest.readme.iter());
+        for entry_name in optional_entries {
+            let path = extract_entry(&mut archive, &manifest, entry_name, &extract_dir)?;
+            extra_files.push((path, entry_name.clone()));
+        }
+
+        install_plugin(&config_path, &library_path, &extra_files, source)
+    })();
+    let _ = fs::remove_dir_all(&extract_dir);
+
+    if result.is_ok() {
+        log_info!(
+            "插件包 {} {} 已安装（平台 {}）",
+            manifest.plugin_id,
+            manifest.version,
+            target
+        );
+    }
+    result
+}
+
+/// 校验清单签名
+fn verify_signature(
+    manifest_content: &str,
+    signature: &[u8],
+    settings: &PackageSettings,
+) -> Result<(), String> {
+    if settings.trusted_keys.is_empty() {
+        log_warn!("插件包带有签名，但未配置受信任的公钥，跳过签名校验");
+        return if settings.require_signature {
+            Err("未配置受信任的公钥，无法校验插件包签名".to_string())
+        } else {
+            Ok(())
+        };
+    }
+
+    let signature_hex = String::from_utf8_lossy(signature);
+    let signature_bytes =
+        hex::decode(signature_hex.trim()).map_err(|_| "插件包签名格式无效".to_string())?;
+    let signature =
+        Signature::from_slice(&signature_bytes).map_err(|_| "插件包签名格式无效".to_string())?;
+
+    for key_hex in &settings.trusted_keys {
+        let Some(key) = parse_verifying_key(key_hex) else {
+            log_warn!("受信任的公钥格式无效: {}", key_hex);
+            continue;
+        };
+        if key.verify(manifest_content.as_bytes(), &signature).is_ok() {
+            return Ok(());
+        }
+    }
+    Err("插件包签名校验失败：没有匹配的受信任公钥".to_string())
+}
+
+fn parse_verifying_key(key_hex: &str) -> Option<VerifyingKey> {
+    let bytes: [u8; 32] = hex::decode(key_hex.trim()).ok()?.try_into().ok()?;
+    VerifyingKey::from_bytes(&bytes).ok()
+}
+
+/// 读取包内文件，不存在时返回 None
+fn read_entry(archive: &mut ZipArchive<fs::File>, name: &str) -> Result<Option<Vec<u8>>, String> {
+    let mut file = match archive.by_name(name) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(format!("读取插件包文件 {} 失败: {}", name, e)),
+    };
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)
+        .map_err(|e| format!("读取插件包文件 {} 失败: {}", name, e))?;
+    Ok(Some(data))
+}
+
+/// 读取包内文件并按清单校验摘要，清单未记录的文件视为校验失败
+fn read_verified_entry(
+    archive: &mut ZipArchive<fs::File>,
+    manifest: &PackageManifest,
+    name: &str,
+) -> Result<Vec<u8>, String> {
+    let expected = manifest
+        .files
+        .get(name)
+        .ok_or_else(|| format!("包清单未记录文件摘要: {}", name))?;
+    let data = read_entry(archive, name)?.ok_or_else(|| format!("插件包缺少文件: {}", name))?;
+    if !sha256_hex(&data).eq_ignore_ascii_case(expected) {
+        return Err(format!("插件包文件校验失败: {}", name));
+    }
+    Ok(data)
+}
+
+/// 将包内文件解压到目录中，保持相对路径
+///
+/// 写出的是重新校验过摘要的内容，而不是再次从压缩包中读取。
+fn extract_entry(
+    archive: &mut ZipArchive<fs::File>,
+    manifest: &PackageManifest,
+    name: &str,
+    target_dir: &Path,
+) -> Result<PathBuf, String> {
+    let relative_path = archive
+        .by_name(name)
+        .map_err(|e| format!("读取插件包文件 {} 失败: {}", name, e))?
+        .enclosed_name()
+        .ok_or_else(|| format!("插件包文件路径非法: {}", name))?;
+    let data = read_verified_entry(archive, manifest, name)?;
+    let output_path = target_dir.join(relative_path);
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("创建目录失败: {}", e))?;
+    }
+    fs::write(&output_path, data).map_err(|e| format!("无法写入文件 {:?}: {}", output_path, e))?;
+    Ok(output_path)
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const CONFIG: &str = r#"[plugin]
+id = "package_test"
+name = "Package Test"
+version = "1.0.0"
+description = "插件包测试"
+author = "tests"
+library = "package_test"
+"#;
+    const SECRET_KEY: [u8; 32] = [7; 32];
+
+    /// 在临时目录中打包一个带许可证的插件包
+    fn build_package(dir: &TempDir, sign: bool) -> PathBuf {
+        let config_path = dir.path().join("config.toml");
+        let library_path = dir.path().join("libpackage_test.so");
+        let license_path = dir.path().join("LICENSE");
+        fs::write(&config_path, CONFIG).unwrap();
+        fs::write(&library_path, b"library").unwrap();
+        fs::write(&license_path, b"MIT").unwrap();
+
+        let mut builder = PackageBuilder::new(&config_path)
+            .unwrap()
+            .library(&host_target(), &library_path)
+            .license(&license_path);
+        if sign {
+            builder = builder.sign_with(&SECRET_KEY);
+        }
+        let package_path = dir.path().join("package_test.ccpkg");
+        builder.write(&package_path).unwrap();
+        package_path
+    }
+
+    /// 读出插件包的全部条目，修改后按原顺序写回
+    fn rewrite_package(path: &Path, edit: impl FnOnce(&mut Vec<(String, Vec<u8>)>)) {
+        let mut archive = ZipArchive::new(fs::File::open(path).unwrap()).unwrap();
+        let mut entries = Vec::new();
+        for index in 0..archive.len() {
+            let mut file = archive.by_index(index).unwrap();
+            let mut data = Vec::new();
+            file.read_to_end(&mut data).unwrap();
+            entries.push((file.name().to_string(), data));
+        }
+        edit(&mut entries);
+
+        let mut writer = ZipWriter::new(fs::File::create(path).unwrap());
+        for (name, data) in entries {
+            writer
+                .start_file(name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(&data).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    fn trusting(secret_key: &[u8; 32]) -> PackageSettings {
+        let key = SigningKey::from_bytes(secret_key).verifying_key();
+        PackageSettings {
+            trusted_keys: vec![hex::encode(key.to_bytes())],
+            require_signature: true,
+        }
+    }
+
+    fn read_error(path: &Path, settings: &PackageSettings) -> String {
+        match read_package(path, settings) {
+            Ok(_) => panic!("插件包不应通过校验"),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn packed_package_verifies() {
+        let dir = TempDir::new().unwrap();
+        let package_path = build_package(&dir, true);
+
+        let (manifest, _) = read_package(&package_path, &trusting(&SECRET_KEY)).unwrap();
+        assert_eq!(manifest.plugin_id, "package_test");
+        assert_eq!(manifest.license.as_deref(), Some("LICENSE"));
+        assert!(manifest.files.contains_key(CONFIG_FILE));
+        assert!(manifest
+            .files
+            .contains_key(&manifest.libraries[&host_target()]));
+    }
+
+    #[test]
+    fn tampered_entry_is_rejected() {
+        let dir = TempDir::new().unwrap();
+        let package_path = build_package(&dir, false);
+        rewrite_package(&package_path, |entries| {
+            let (_, data) = entries
+                .iter_mut()
+                .find(|(name, _)| name.starts_with("lib/"))
+                .unwrap();
+            *data = b"patched".to_vec();
+        });
+
+        let error = read_error(&package_path, &PackageSettings::default());
+        assert!(error.contains("校验失败"), "{}", error);
+    }
+
+    #[test]
+    fn unlisted_entry_is_rejected() {
+        let dir = TempDir::new().unwrap();
+        let package_path = build_package(&dir, false);
+        rewrite_package(&package_path, |entries| {
+            entries.push(("assets/extra.js".to_string(), b"alert(1)".to_vec()));
+        });
+
+        let error = read_error(&package_path, &PackageSettings::default());
+        assert!(error.contains("assets/extra.js"), "{}", error);
+    }
+
+    #[test]
+    fn mismatched_plugin_id_is_rejected() {
+        let dir = TempDir::new().unwrap();
+        let package_path = build_package(&dir, false);
+        rewrite_package(&package_path, |entries| {
+            let (_, data) = entries
+                .iter_mut()
+                .find(|(name, _)| name == MANIFEST_FILE)
+                .unwrap();
+            *data = String::from_utf8_lossy(data)
+                .replace("plugin_id = \"package_test\"", "plugin_id = \"other\"")
+                .into_bytes();
+        });
+
+        let error = read_error(&package_path, &PackageSettings::default());
+        assert!(error.contains("不一致"), "{}", error);
+    }
+
+    #[test]
+    fn mismatched_version_is_rejected() {
+        let dir = TempDir::new().unwrap();
+        let package_path = build_package(&dir, false);
+        rewrite_package(&package_path, |entries| {
+            let (_, data) = entries
+                .iter_mut()
+                .find(|(name, _)| name == MANIFEST_FILE)
+                .unwrap();
+            *data = String::from_utf8_lossy(data)
+                .replace("version = \"1.0.0\"", "version = \"2.0.0\"")
+                .into_bytes();
+        });
+
+        let error = read_error(&package_path, &PackageSettings::default());
+        assert!(error.contains("版本"), "{}", error);
+    }
+
+    #[test]
+    fn bad_signature_is_rejected() {
+        let dir = TempDir::new().unwrap();
+        let package_path = build_package(&dir, true);
+
+        let error = read_error(&package_path, &trusting(&[9; 32]));
+        assert!(error.contains("签名校验失败"), "{}", error);
+
+        rewrite_package(&package_path, |entries| {
+            let (_, data) = entries
+                .iter_mut()
+                .find(|(name, _)| name == MANIFEST_FILE)
+                .unwrap();
+            data.extend_from_slice(b"\n# modified\n");
+        });
+        let error = read_error(&package_path, &trusting(&SECRET_KEY));
+        assert!(error.contains("签名校验失败"), "{}", error);
+    }
+
+    #[test]
+    fn unsigned_package_is_rejected_when_signature_required() {
+        let dir = TempDir::new().unwrap();
+        let package_path = build_package(&dir, false);
+
+        let error = read_error(&package_path, &trusting(&SECRET_KEY));
+        assert!(error.contains("未签名"), "{}", error);
+    }
+}
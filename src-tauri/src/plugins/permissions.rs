@@ -0,0 +1,342 @@
+//! 插件权限的批准和检查
+//!
+//! 插件在 `config.toml` 的 `[permissions]` 中声明需要的权限。申请了权限的插件在用户批准之前不能挂载，
+//! 批准记录保存在 `plugin_permissions.toml` 中；升级后申请的权限超出已批准的范围时需要重新批准，
+//! 缩小权限不需要。挂载后宿主回调按批准的权限检查插件间调用、配置读取和发往前端的事件，
+//! 启用沙箱的插件的网络、文件、串口和子进程访问由沙箱按批准的权限限制。
+
+use crate::plugins::config::{PluginConfig, PluginPermissions};
+use crate::plugins::directories::get_plugin_permissions_file;
+use plugin_interfaces::{log_info, log_warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 宿主定义的插件事件，插件无需申请即可发送
+pub const HOST_EVENTS: &[&str] = &[
+    "plugin-message",
+    "plugin-stream",
+    "plugin-ui-updated",
+    "plugin-ui-refreshed",
+    "plugin-disconnect-request",
+];
+
+/// 一个插件的权限申请和批准状态
+#[derive(Debug, Clone, Serialize)]
+pub struct PermissionRequest {
+    pub plugin_id: String,
+    pub plugin_name: String,
+    pub version: String,
+    /// 当前版本申请的全部权限
+    pub requested: PluginPermissions,
+    /// 超出已批准范围、需要用户批准的权限，为空表示无需批准
+    pub pending: PluginPermissions,
+    /// 之前批准过的版本，升级后扩大了权限时用于提示
+    pub approved_version: Option<String>,
+}
+
+impl PermissionRequest {
+    /// 申请的权限是否都已批准
+    pub fn is_approved(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+/// 一个插件的批准记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Approval {
+    version: String,
+    /// 批准时间（Unix 秒）
+    approved_at: u64,
+    permissions: PluginPermissions,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ApprovalsFile {
+    #[serde(default)]
+    plugins: BTreeMap<String, Approval>,
+}
+
+fn load_approvals() -> ApprovalsFile {
+    let path = get_plugin_permissions_file();
+    if !path.exists() {
+        return ApprovalsFile::default();
+    }
+    match fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|content| toml::from_str(&content).map_err(|e| e.to_string()))
+    {
+        Ok(approvals) => approvals,
+        Err(e) => {
+            // 读取失败时按未批准处理，插件需要重新批准
+            log_warn!("读取插件权限批准记录失败 {:?}: {}", path, e);
+            ApprovalsFile::default()
+        }
+    }
+}
+
+fn save_approvals(approvals: &ApprovalsFile) -> Result<(), String> {
+    let path = get_plugin_permissions_file();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("创建目录失败: {}", e))?;
+    }
+    let content = toml::to_string_pretty(approvals)
+        .map_err(|e| format!("序列化插件权限批准记录失败: {}", e))?;
+    fs::write(&path, content).map_err(|e| format!("保存插件权限批准记录失败: {}", e))
+}
+
+/// 插件当前版本的权限申请和批准状态
+pub fn permission_request(config: &PluginConfig) -> PermissionRequest {
+    let approval = load_approvals().plugins.remove(&config.plugin.id);
+    let requested = config.permissions.clone();
+    let pending = match &approval {
+        Some(approval) => requested.not_covered_by(&approval.permissions),
+        None => requested.clone(),
+    };
+    PermissionRequest {
+        plugin_id: config.plugin.id.clone(),
+        plugin_name: config.plugin.name.clone(),
+        version: config.plugin.version.clone(),
+        requested,
+        pending,
+        approved_version: approval.map(|approval| approval.version),
+    }
+}
+
+/// 批准插件当前版本申请的全部权限
+pub fn approve_permissions(config: &PluginConfig) -> Result<PermissionRequest, String> {
+    let mut approvals = load_approvals();
+    approvals.plugins.insert(
+        config.plugin.id.clone(),
+        Approval {
+            version: config.plugin.version.clone(),
+            approved_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            permissions: config.permissions.clone(),
+        },
+    );
+    save_approvals(&approvals)?;
+    log_info!(
+        "已批准插件 {} v{} 的权限",
+        config.plugin.id,
+        config.plugin.version
+    );
+    Ok(permission_request(config))
+}
+
+/// 删除插件的批准记录（卸载插件时调用）
+pub fn revoke_permissions(plugin_id: &str) -> Result<(), String> {
+    let mut approvals = load_approvals();
+    if approvals.plugins.remove(plugin_id).is_some() {
+        save_approvals(&approvals)?;
+    }
+    Ok(())
+}
+
+impl PluginPermissions {
+    /// 不受限制的权限，用于随应用编译的内置插件
+    pub fn unrestricted() -> Self {
+        let all = || vec!["*".to_string()];
+        Self {
+            network: all(),
+            filesystem: all(),
+            serial: all(),
+            subprocess: all(),
+            plugins: all(),
+            events: all(),
+            config: all(),
+        }
+    }
+
+    /// 是否没有申请任何权限
+    pub fn is_empty(&self) -> bool {
+        [
+            &self.network,
+            &self.filesystem,
+            &self.serial,
+            &self.subprocess,
+            &self.plugins,
+            &self.events,
+            &self.config,
+        ]
+        .iter()
+        .all(|entries| entries.is_empty())
+    }
+
+    /// 不在 `approved` 范围内的条目
+    ///
+    /// 条目按原文与已批准的模式匹配，因此 `*.example.com` 不在 `api.example.com` 的范围内。
+    pub fn not_covered_by(&self, approved: &PluginPermissions) -> PluginPermissions {
+        let uncovered = |requested: &[String], approved: &[String]| -> Vec<String> {
+            requested
+                .iter()
+                .filter(|entry| !matches_any(approved, entry))
+                .cloned()
+                .collect()
+        };
+        Self {
+            network: uncovered(&self.network, &approved.network),
+            filesystem: uncovered(&self.filesystem, &approved.filesystem),
+            serial: uncovered(&self.serial, &approved.serial),
+            subprocess: uncovered(&self.subprocess, &approved.subprocess),
+            plugins: uncovered(&self.plugins, &approved.plugins),
+            events: uncovered(&self.events, &approved.events),
+            config: uncovered(&self.config, &approved.config),
+        }
+    }
+
+    /// 是否可以向前端发送该事件
+    pub fn allows_event(&self, event: &str) -> bool {
+        HOST_EVENTS.contains(&event) || matches_any(&self.events, event)
+    }
+
+    /// 是否可以调用该插件
+    pub fn allows_plugin(&self, plugin_id: &str) -> bool {
+        matches_any(&self.plugins, plugin_id)
+    }
+
+    /// 是否可以读取该设置项
+    pub fn allows_config(&self, key: &str) -> bool {
+        matches_any(&self.config, key)
+    }
+
+    /// 该路径是否在申请的文件或串口设备范围内（按原文匹配）
+    pub fn allows_path(&self, path: &str) -> bool {
+        matches_any(&self.filesystem, path) || matches_any(&self.serial, path)
+    }
+}
+
+fn matches_any(patterns: &[String], value: &str) -> bool {
+    patterns
+        .iter()
+        .any(|pattern| wildcard_match(pattern, value))
+}
+
+/// 通配符匹配，`*` 匹配任意长度的任意字符
+pub(crate) fn wildcard_match(pattern: &str, value: &str) -> bool {
+    let Some((first, rest)) = pattern.split_once('*') else {
+        return pattern == value;
+    };
+    let Some(mut remaining) = value.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts: Vec<&str> = rest.split('*').collect();
+    let last = parts.pop().unwrap_or_default();
+    for part in parts {
+        match remaining.find(part) {
+            Some(index) => remaining = &remaining[index + part.len()..],
+            None => return false,
+        }
+    }
+    remaining.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn wildcard_matches_literal_and_star_positions() {
+        assert!(wildcard_match("api.example.com", "api.example.com"));
+        assert!(!wildcard_match("api.example.com", "api.example.org"));
+        assert!(wildcard_match("*", ""));
+        assert!(wildcard_match("*", "anything"));
+        assert!(wildcard_match("*.example.com", "api.example.com"));
+        assert!(!wildcard_match("*.example.com", "example.com"));
+        assert!(wildcard_match("/dev/tty*", "/dev/ttyUSB0"));
+        assert!(!wildcard_match("/dev/tty*", "/dev/cu.usb"));
+        assert!(wildcard_match("weather-*-done", "weather-update-done"));
+        assert!(!wildcard_match("weather-*-done", "weather-update"));
+    }
+
+    #[test]
+    fn wildcard_with_several_stars_does_not_reuse_characters() {
+        assert!(wildcard_match("*a*b*", "xaxbx"));
+        assert!(!wildcard_match("*a*b*", "xbxax"));
+        assert!(wildcard_match("a*a", "aa"));
+        assert!(!wildcard_match("a*a", "a"));
+        assert!(!wildcard_match("*ab*b", "ab"));
+        assert!(wildcard_match("*ab*b", "abb"));
+    }
+
+    #[test]
+    fn requested_patterns_must_be_covered_by_approved_patterns() {
+        let requested = PluginPermissions {
+            network: entries(&["api.example.com", "*.example.com"]),
+            events: entries(&["weather-update"]),
+            ..Default::default()
+        };
+        let approved = PluginPermissions {
+            network: entries(&["api.example.com"]),
+            events: entries(&["weather-*"]),
+            ..Default::default()
+        };
+
+        let pending = requested.not_covered_by(&approved);
+        assert_eq!(pending.network, entries(&["*.example.com"]));
+        assert!(pending.events.is_empty());
+        assert!(!pending.is_empty());
+    }
+
+    #[test]
+    fn nothing_is_covered_without_approval() {
+        let requested = PluginPermissions {
+            serial: entries(&["/dev/ttyUSB0"]),
+            subprocess: entries(&["python3"]),
+            ..Default::default()
+        };
+
+        let pending = requested.not_covered_by(&PluginPermissions::default());
+        assert_eq!(pending, requested);
+        assert!(requested
+            .not_covered_by(&PluginPermissions::unrestricted())
+            .is_empty());
+        assert!(PluginPermissions::default()
+            .not_covered_by(&PluginPermissions::default())
+            .is_empty());
+    }
+
+    #[test]
+    fn host_events_are_always_allowed() {
+        let permissions = PluginPermissions {
+            events: entries(&["weather-*"]),
+            ..Default::default()
+        };
+        assert!(permissions.allows_event("plugin-message"));
+        assert!(permissions.allows_event("weather-update"));
+        assert!(!permissions.allows_event("settings-changed"));
+    }
+
+    #[test]
+    fn callbacks_check_plugins_and_config() {
+        let permissions = PluginPermissions {
+            plugins: entries(&["translator"]),
+            config: entries(&["network.*"]),
+            ..Default::default()
+        };
+        assert!(permissions.allows_plugin("translator"));
+        assert!(!permissions.allows_plugin("translator-pro"));
+        assert!(permissions.allows_config("network.proxy"));
+        assert!(!permissions.allows_config("packages.trusted_keys"));
+        assert!(!PluginPermissions::default().allows_config("network.proxy"));
+    }
+
+    #[test]
+    fn paths_are_covered_by_filesystem_or_serial() {
+        let permissions = PluginPermissions {
+            filesystem: entries(&["~/Documents/*"]),
+            serial: entries(&["/dev/ttyUSB*"]),
+            ..Default::default()
+        };
+        assert!(permissions.allows_path("~/Documents/notes"));
+        assert!(permissions.allows_path("/dev/ttyUSB0"));
+        assert!(!permissions.allows_path("/etc/passwd"));
+    }
+}
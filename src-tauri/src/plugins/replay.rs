@@ -0,0 +1,402 @@
+use crate::plugins::{
+    trace::{load_trace, TraceCall, TraceEntry, TraceRecord},
+    EventSink, PluginEvent, PluginManager, RecordingEventSink,
+};
+use plugin_interfaces::log_info;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// 回放选项
+#[derive(Debug, Clone)]
+pub struct ReplayOptions {
+    /// 按跟踪中记录的间隔发起调用；关闭时依次立即调用
+    pub realtime: bool,
+    /// 最后一次调用后，多长时间没有新事件视为插件输出结束
+    pub settle: Duration,
+    /// 等待插件输出结束的最长时间
+    pub timeout: Duration,
+}
+
+impl Default for ReplayOptions {
+    fn default() -> Self {
+        Self {
+            realtime: true,
+            settle: Duration::from_millis(500),
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// 回放结果与跟踪记录之间的一处差异
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TraceDifference {
+    /// 差异位置，例如某次调用的返回值或某个实例的第几个事件
+    pub location: String,
+    /// 跟踪中记录的内容，None 表示跟踪中没有对应内容
+    pub expected: Option<String>,
+    /// 回放得到的内容，None 表示回放中没有对应内容
+    pub actual: Option<String>,
+}
+
+/// 回放报告
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReplayReport {
+    pub calls: usize,
+    pub expected_events: usize,
+    pub actual_events: usize,
+    pub differences: Vec<TraceDifference>,
+}
+
+impl ReplayReport {
+    /// 回放结果与跟踪记录是否一致
+    pub fn is_identical(&self) -> bool {
+        self.differences.is_empty()
+    }
+}
+
+/// 回放跟踪文件：用新的插件管理器把跟踪中的宿主调用依次发给插件，
+/// 再将返回值和插件事件与跟踪记录逐一比较。
+///
+/// 插件按跟踪中的插件ID从当前插件目录中查找，实例ID与跟踪中相同。
+/// 时间戳、消息ID和流ID每次运行都不同，比较前按出现顺序归一化。
+pub fn replay_trace(path: &Path, options: &ReplayOptions) -> Result<ReplayReport, String> {
+    let entries = load_trace(path)?;
+    log_info!("回放插件跟踪 {:?}，共 {} 条记录", path, entries.len());
+    replay_entries(&entries, options)
+}
+
+/// 回放已读取的跟踪记录
+pub fn replay_entries(
+    entries: &[TraceEntry],
+    options: &ReplayOptions,
+) -> Result<ReplayReport, String> {
+    let sink = Arc::new(RecordingEventSink::new());
+    let manager = PluginManager::new(Arc::clone(&sink) as Arc<dyn EventSink>).trust_manifests();
+
+    let returns: HashMap<u64, &Result<Value, String>> = entries
+        .iter()
+        .filter_map(|entry| match &entry.record {
+            TraceRecord::Return { seq, result } => Some((*seq, result)),
+            _ => None,
+        })
+        .collect();
+    let first_call_ms = entries.iter().find_map(|entry| match entry.record {
+        TraceRecord::Call { .. } => Some(entry.elapsed_ms),
+        _ => None,
+    });
+
+    let mut report = ReplayReport::default();
+    let mut expected_returns = Normalizer::default();
+    let mut actual_returns = Normalizer::default();
+    // 每个实例在跟踪中开始的流，按开始顺序与回放中的流对应
+    let mut recorded_streams: HashMap<String, Vec<String>> = HashMap::new();
+    let started = Instant::now();
+
+    for entry in entries {
+        match &entry.record {
+            TraceRecord::Event { event, payload } => {
+                if let Some((instance_id, stream_id)) = stream_start(event, payload) {
+                    recorded_streams
+                        .entry(instance_id)
+                        .or_default()
+                        .push(stream_id);
+                }
+            }
+            TraceRecord::Call { seq, call } => {
+                if options.realtime {
+                    let offset = entry
+                        .elapsed_ms
+                        .saturating_sub(first_call_ms.unwrap_or_default());
+                    let due = started + Duration::from_millis(offset);
+                    if let Some(delay) = due.checked_duration_since(Instant::now()) {
+                        std::thread::sleep(delay);
+                    }
+                }
+
+                let actual = execute(&manager, &sink, call, &recorded_streams, options);
+                report.calls += 1;
+
+                if let Some(expected) = returns.get(seq) {
+                    let expected = expected_returns.result(expected);
+                    let actual = actual_returns.result(&actual);
+                    if expected != actual {
+                        report.differences.push(TraceDifference {
+                            location: format!("调用 #{} {} 的返回值", seq, call_name(call)),
+                            expected: Some(expected),
+                            actual: Some(actual),
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    wait_until_settled(&sink, options);
+    manager.cleanup_all_plugins();
+
+    let expected_events: Vec<PluginEvent> = entries
+        .iter()
+        .filter_map(|entry| match &entry.record {
+            TraceRecord::Event { event, payload } => Some(PluginEvent {
+                event: event.clone(),
+                payload: payload.clone(),
+            }),
+            _ => None,
+        })
+        .collect();
+    let actual_events = sink.take();
+    report.expected_events = expected_events.len();
+    report.actual_events = actual_events.len();
+    report
+        .differences
+        .extend(diff_events(&expected_events, &actual_events));
+
+    log_info!(
+        "回放完成：{} 次调用，{} 处差异",
+        report.calls,
+        report.differences.len()
+    );
+    Ok(report)
+}
+
+/// 对插件管理器执行一次跟踪中的调用
+fn execute(
+    manager: &PluginManager,
+    sink: &RecordingEventSink,
+    call: &TraceCall,
+    recorded_streams: &HashMap<String, Vec<String>>,
+    options: &ReplayOptions,
+) -> Result<Value, String> {
+    match call {
+        TraceCall::Mount {
+            plugin_id,
+            instance_id,
+        } => to_value(manager.mount_plugin(plugin_id, Some(instance_id.clone()))),
+        TraceCall::Dispose { instance_id } => to_value(manager.dispose_plugin(instance_id)),
+        TraceCall::Connect { instance_id } => to_value(manager.connect_plugin(instance_id)),
+        TraceCall::Disconnect { instance_id } => to_value(manager.disconnect_plugin(instance_id)),
+        TraceCall::SendMessage {
+            plugin_id,
+            instance_id,
+            message,
+            history,
+        } => to_value(manager.send_message_to_plugin_instance(
+            plugin_id,
+            instance_id,
+            message,
+            history.clone(),
+        )),
+        TraceCall::GetUi { instance_id } => to_value(manager.get_plugin_ui(instance_id)),
+        TraceCall::UiUpdate {
+            instance_id,
+            component_id,
+            value,
+        } => to_value(manager.handle_plugin_ui_update(instance_id, component_id, value)),
+        TraceCall::UiEvent {
+            instance_id,
+            component_id,
+            value,
+        } => to_value(manager.handle_plugin_ui_event(instance_id, component_id, value)),
+        TraceCall::CancelStream {
+            instance_id,
+            stream_id,
+        } => {
+            match replayed_stream_id(sink, recorded_streams, instance_id, stream_id, options) {
+                // 返回信息中包含流ID，换回跟踪中的流ID以便比较
+                Some(replayed) => to_value(
+                    manager
+                        .cancel_stream_message(instance_id, &replayed)
+                        .map(|message| message.replace(&replayed, stream_id))
+                        .map_err(|e| e.replace(&replayed, stream_id)),
+                ),
+                None => to_value(manager.cancel_stream_message(instance_id, stream_id)),
+            }
+        }
+    }
+}
+
+fn to_value<T: Serialize>(result: Result<T, String>) -> Result<Value, String> {
+    result.and_then(|value| serde_json::to_value(value).map_err(|e| e.to_string()))
+}
+
+fn call_name(call: &TraceCall) -> String {
+    serde_json::to_value(call)
+        .ok()
+        .and_then(|value| value["method"].as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+/// 找到跟踪中的流在回放中对应的流ID（同一实例中开始顺序相同的流），
+/// 回放中的流还没开始时最多等待 `options.settle`
+fn replayed_stream_id(
+    sink: &RecordingEventSink,
+    recorded_streams: &HashMap<String, Vec<String>>,
+    instance_id: &str,
+    stream_id: &str,
+    options: &ReplayOptions,
+) -> Option<String> {
+    let index = recorded_streams
+        .get(instance_id)?
+        .iter()
+        .position(|recorded| recorded == stream_id)?;
+
+    let deadline = Instant::now() + options.settle;
+    loop {
+        let replayed: Vec<String> = sink
+            .events()
+            .iter()
+            .filter_map(|event| stream_start(&event.event, &event.payload))
+            .filter(|(instance, _)| instance == instance_id)
+            .map(|(_, stream_id)| stream_id)
+            .collect();
+        if let Some(stream_id) = replayed.into_iter().nth(index) {
+            return Some(stream_id);
+        }
+        if Instant::now() >= deadline {
+            return None;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// 流开始事件的实例ID和流ID
+fn stream_start(event: &str, payload: &str) -> Option<(String, String)> {
+    if event != "plugin-stream" {
+        return None;
+    }
+    let value: Value = serde_json::from_str(payload).ok()?;
+    if value["type"] != "stream_start" {
+        return None;
+    }
+    Some((
+        value["instance_id"].as_str()?.to_string(),
+        value["data"]["stream_id"].as_str()?.to_string(),
+    ))
+}
+
+/// 等待一段时间内没有新事件，最多等待 `options.timeout`
+fn wait_until_settled(sink: &RecordingEventSink, options: &ReplayOptions) {
+    let deadline = Instant::now() + options.timeout;
+    let mut count = sink.events().len();
+    let mut last_change = Instant::now();
+    while Instant::now() < deadline && last_change.elapsed() < options.settle {
+        std::thread::sleep(Duration::from_millis(20));
+        let current = sink.events().len();
+        if current != count {
+            count = current;
+            last_change = Instant::now();
+        }
+    }
+}
+
+/// 按实例分组逐个比较事件；不同实例的事件交错顺序不固定，不参与比较
+fn diff_events(expected: &[PluginEvent], actual: &[PluginEvent]) -> Vec<TraceDifference> {
+    let expected = group_by_instance(expected);
+    let actual = group_by_instance(actual);
+
+    let mut instances: Vec<&String> = expected.keys().chain(actual.keys()).collect();
+    instances.sort();
+    instances.dedup();
+
+    let mut differences = Vec::new();
+    for instance in instances {
+        let expected = expected.get(instance).map(Vec::as_slice).unwrap_or(&[]);
+        let actual = actual.get(instance).map(Vec::as_slice).unwrap_or(&[]);
+        for index in 0..expected.len().max(actual.len()) {
+            let (expected, actual) = (expected.get(index), actual.get(index));
+            if expected != actual {
+                let owner = if instance.is_empty() {
+                    "无实例".to_string()
+                } else {
+                    format!("实例 {}", instance)
+                };
+                differences.push(TraceDifference {
+                    location: format!("{} 的第 {} 个事件", owner, index + 1),
+                    expected: expected.cloned(),
+                    actual: actual.cloned(),
+                });
+            }
+        }
+    }
+    differences
+}
+
+/// 归一化事件并按实例ID分组，值为 `事件名 载荷`
+fn group_by_instance(events: &[PluginEvent]) -> BTreeMap<String, Vec<String>> {
+    let mut normalizer = Normalizer::default();
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for event in events {
+        let payload = serde_json::from_str::<Value>(&event.payload).ok();
+        let instance = payload
+            .as_ref()
+            .and_then(|payload| {
+                payload
+                    .get("instance_id")
+                    .or_else(|| payload.get("instance"))
+            })
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let payload = match payload {
+            Some(payload) => normalizer.value(payload).to_string(),
+            None => event.payload.clone(),
+        };
+        groups
+            .entry(instance)
+            .or_default()
+            .push(format!("{} {}", event.event, payload));
+    }
+    groups
+}
+
+/// 把每次运行都会变化的字段替换为稳定的值：
+/// 时间戳置空，消息ID和流ID按首次出现的顺序编号
+#[derive(Debug, Default)]
+struct Normalizer {
+    ids: HashMap<String, String>,
+}
+
+impl Normalizer {
+    fn result(&mut self, result: &Result<Value, String>) -> String {
+        match result {
+            Ok(value) => format!("Ok({})", self.value(value.clone())),
+            Err(e) => format!("Err({})", e),
+        }
+    }
+
+    fn value(&mut self, value: Value) -> Value {
+        match value {
+            Value::Object(map) => Value::Object(
+                map.into_iter()
+                    .map(|(key, value)| {
+                        let value = match (key.as_str(), value) {
+                            ("timestamp" | "created_at", _) => Value::Null,
+                            ("message_id" | "stream_id", Value::String(id)) => {
+                                Value::String(self.id(id))
+                            }
+                            (_, value) => self.value(value),
+                        };
+                        (key, value)
+                    })
+                    .collect(),
+            ),
+            Value::Array(items) => {
+                Value::Array(items.into_iter().map(|item| self.value(item)).collect())
+            }
+            value => value,
+        }
+    }
+
+    fn id(&mut self, id: String) -> String {
+        let next = self.ids.len() + 1;
+        self.ids
+            .entry(id)
+            .or_insert_with(|| format!("<id-{}>", next))
+            .clone()
+    }
+}
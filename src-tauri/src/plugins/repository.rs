@@ -1,15 +1,29 @@
-use plugin_interfaces::{log_info, log_warn};
+use plugin_interfaces::{log_error, log_info, log_warn};
 use serde::{Deserialize, Serialize};
-use std::{fs, io::Cursor};
+use std::fs;
+use tauri::{AppHandle, Emitter};
+use uuid::Uuid;
 use walkdir::WalkDir;
 use zip::ZipArchive;
 
 use crate::plugins::{
-    config::{DownloadConfig, PlatformDownload, PluginConfig},
+    builder::{build_plugin_from_source, BuildLogLine, BuildLogStream, BUILD_LOG_EVENT},
+    config::{DownloadConfig, PlatformDownload, PluginConfig, SourceConfig},
     directories::{
-        get_plugin_repository_directory, get_plugin_repository_root,
-        get_root_plugin_installed_directory,
+        get_download_temp_directory, get_plugin_repository_directory, get_plugin_repository_root,
+        get_repository_directory, get_root_plugin_installed_directory,
     },
+    download::{
+        verify_checksum, DownloadOptions, DownloadProgress, DownloadTask, DOWNLOAD_PROGRESS_EVENT,
+    },
+    git, http,
+    install::{
+        install_plugin, install_plugin_from_file, plugin_file_name, validate_plugin_config,
+        InstallRecord, InstallSource,
+    },
+    package::{install_package, PACKAGE_EXTENSION},
+    permissions,
+    settings::{effective_network_settings, load_settings},
 };
 
 /// 可用插件信息（来自插件仓库）
@@ -40,6 +54,8 @@ pub struct AvailablePluginInfo {
     pub dependencies: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub download: Option<DownloadConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<SourceConfig>,
 }
 
 /// 插件下载结果
@@ -91,7 +107,11 @@ fn extract_repo_info(url: &str) -> Result<RepoInfo, String> {
 }
 
 #[derive(Debug)]
-pub struct PluginRepository;
+pub struct PluginRepository {
+    app_handle: Option<AppHandle>, // 用于发送下载进度事件，为 None 时不发送
+    client: Option<reqwest::Client>, // 自定义HTTP客户端，为 None 时使用共享客户端
+    download_options: DownloadOptions, // 下载重试配置
+}
 
 impl Default for PluginRepository {
     fn default() -> Self {
@@ -101,7 +121,69 @@ impl Default for PluginRepository {
 
 impl PluginRepository {
     pub fn new() -> Self {
-        Self
+        Self {
+            app_handle: None,
+            client: None,
+            download_options: DownloadOptions::from_settings(
+                &effective_network_settings().unwrap_or_else(|e| {
+                    log_warn!("网络设置无效，使用设置文件中的值: {}", e);
+                    load_settings().network
+                }),
+            ),
+        }
+    }
+
+    /// 创建可以向前端发送下载进度事件的插件仓库
+    pub fn with_app_handle(app_handle: AppHandle) -> Self {
+        Self {
+            app_handle: Some(app_handle),
+            ..Self::new()
+        }
+    }
+
+    /// 使用指定的HTTP客户端代替共享客户端
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// 设置下载重试配置
+    pub fn with_download_options(mut self, download_options: DownloadOptions) -> Self {
+        self.download_options = download_options;
+        self
+    }
+
+    /// 获取HTTP客户端
+    fn http_client(&self) -> Result<reqwest::Client, String> {
+        match &self.client {
+            Some(client) => Ok(client.clone()),
+            None => http::shared_client(),
+        }
+    }
+
+    /// 向前端发送下载进度事件
+    fn emit_download_progress(&self, progress: &DownloadProgress) {
+        self.emit_event(DOWNLOAD_PROGRESS_EVENT, progress);
+    }
+
+    /// 向前端发送构建日志事件
+    fn emit_build_log(&self, line: &BuildLogLine) {
+        self.emit_event(BUILD_LOG_EVENT, line);
+    }
+
+    /// 将载荷序列化为 JSON 字符串后发送到前端
+    fn emit_event<T: Serialize>(&self, event: &str, payload: &T) {
+        let Some(app_handle) = &self.app_handle else {
+            return;
+        };
+        match serde_json::to_string(payload) {
+            Ok(payload) => {
+                if let Err(e) = app_handle.emit(event, payload) {
+                    log_error!("发送 {} 事件失败: {}", event, e);
+                }
+            }
+            Err(e) => log_error!("序列化 {} 事件失败: {}", event, e),
+        }
     }
 
     /// 扫描可用插件列表（从插件仓库目录）
@@ -136,7 +218,13 @@ impl PluginRepository {
     }
 
     /// 下载并安装插件
-    pub async fn download_plugin(&self, plugin_id: &str) -> PluginDownloadResult {
+    ///
+    /// `download_id` 用于标识本次下载，可通过 `cancel_download` 取消；为 None 时自动生成。
+    pub async fn download_plugin(
+        &self,
+        plugin_id: &str,
+        download_id: Option<String>,
+    ) -> PluginDownloadResult {
         log_info!("开始下载插件: {}", plugin_id);
 
         // 首先查找插件信息
@@ -153,10 +241,25 @@ impl PluginRepository {
             }
         };
 
-        // 获取当前平台的下载信息
-        let platform_download = match self.get_platform_download_info(&plugin_info.download) {
-            Some(download) => download,
-            None => {
+        // 执行下载；当前平台没有预编译的动态库时，回退到从源码构建
+        let task = DownloadTask::new(
+            download_id.unwrap_or_else(|| Uuid::new_v4().to_string()),
+            plugin_id,
+        );
+        let result = match (
+            self.get_platform_download_info(&plugin_info.download),
+            &plugin_info.source,
+        ) {
+            (Some(platform_download), _) => {
+                self.download_and_install_plugin(plugin_info, platform_download, &task)
+                    .await
+            }
+            (None, Some(source)) => {
+                log_info!("插件 {} 没有当前平台的预编译版本，从源码构建", plugin_id);
+                self.build_and_install_plugin(plugin_info, source, &task)
+                    .await
+            }
+            (None, None) => {
                 return PluginDownloadResult {
                     success: false,
                     message: format!("插件 {} 不支持当前平台", plugin_id),
@@ -166,11 +269,7 @@ impl PluginRepository {
             }
         };
 
-        // 执行下载
-        match self
-            .download_and_install_plugin(plugin_info, platform_download)
-            .await
-        {
+        match result {
             Ok(installed_path) => PluginDownloadResult {
                 success: true,
                 message: format!("插件 {} 下载安装成功", plugin_info.name),
@@ -186,6 +285,158 @@ impl PluginRepository {
         }
     }
 
+    /// 从 Git 仓库安装插件
+    ///
+    /// `url` 可以是远程仓库地址或本地 Git 仓库路径，`rev` 为分支、标签或提交，
+    /// `path` 为插件（config.toml 所在目录）在仓库中的相对路径。
+    /// 仓库中有当前平台的预编译动态库时直接使用，否则从源码构建。
+    pub async fn install_plugin_from_git(
+        &self,
+        url: &str,
+        rev: Option<&str>,
+        path: Option<&str>,
+        download_id: Option<String>,
+    ) -> PluginDownloadResult {
+        log_info!("开始从 Git 仓库安装插件: {}", url);
+
+        let url = git::normalize_url(url);
+        let task = DownloadTask::new(
+            download_id.unwrap_or_else(|| Uuid::new_v4().to_string()),
+            &url,
+        );
+        match self.install_git_plugin(&url, rev, path, &task).await {
+            Ok((config, installed_path)) => PluginDownloadResult {
+                success: true,
+                message: format!("插件 {} 安装成功", config.plugin.name),
+                plugin_id: Some(config.plugin.id),
+                installed_path: Some(installed_path),
+            },
+            Err(error) => PluginDownloadResult {
+                success: false,
+                message: format!("从 Git 仓库安装插件失败: {}", error),
+                plugin_id: None,
+                installed_path: None,
+            },
+        }
+    }
+
+    /// 更新已安装的插件
+    ///
+    /// 按安装记录中的来源重新获取：Git 安装的插件拉取最新提交后重新构建，
+    /// 插件仓库中的插件重新下载（或从源码构建）。
+    pub async fn update_plugin(
+        &self,
+        plugin_id: &str,
+        download_id: Option<String>,
+    ) -> PluginDownloadResult {
+        let plugin_dir = get_root_plugin_installed_directory().join(plugin_id);
+        let Some(record) = InstallRecord::load(&plugin_dir) else {
+            return PluginDownloadResult {
+                success: false,
+                message: format!("插件 {} 没有安装记录，无法更新", plugin_id),
+                plugin_id: Some(plugin_id.to_string()),
+                installed_path: None,
+            };
+        };
+
+        log_info!("开始更新插件: {}", plugin_id);
+        match record.source {
+            InstallSource::Git { url, rev, path, .. } => {
+                self.install_plugin_from_git(&url, rev.as_deref(), path.as_deref(), download_id)
+                    .await
+            }
+            InstallSource::Repository { .. } | InstallSource::Source { .. } => {
+                self.download_plugin(plugin_id, download_id).await
+            }
+            InstallSource::Local { path } => PluginDownloadResult {
+                success: false,
+                message: format!(
+                    "插件 {} 是从本地文件 {} 安装的，请重新安装该文件",
+                    plugin_id, path
+                ),
+                plugin_id: Some(plugin_id.to_string()),
+                installed_path: None,
+            },
+        }
+    }
+
+    /// 检出 Git 仓库并安装其中的插件，返回插件配置和安装目录
+    async fn install_git_plugin(
+        &self,
+        url: &str,
+        rev: Option<&str>,
+        path: Option<&str>,
+        task: &DownloadTask,
+    ) -> Result<(PluginConfig, String), String> {
+        let checkout_dir = git::source_checkout_dir(url);
+        self.emit_build_log(&BuildLogLine {
+            build_id: task.download_id.clone(),
+            plugin_id: String::new(),
+            stream: BuildLogStream::Status,
+            line: format!("正在获取源码: {}", url),
+        });
+        let commit = git::checkout(url, rev, &checkout_dir).await?;
+        log_info!("已检出 {} 的提交 {}", url, commit);
+
+        let plugin_dir = match path {
+            Some(path) => checkout_dir.join(path),
+            None => checkout_dir.clone(),
+        };
+        let config_path = plugin_dir.join("config.toml");
+        if !config_path.is_file() {
+            return Err(format!("仓库中找不到 config.toml: {:?}", config_path));
+        }
+        // 防止 path 通过 .. 或符号链接指向仓库之外
+        let canonical_root = checkout_dir
+            .canonicalize()
+            .map_err(|e| format!("无法解析仓库目录: {}", e))?;
+        let inside_checkout = |file: &std::path::Path| {
+            file.canonicalize()
+                .is_ok_and(|file| file.starts_with(&canonical_root))
+        };
+        if !inside_checkout(&config_path) {
+            return Err(format!("源码路径超出仓库范围: {:?}", path));
+        }
+        let config = PluginConfig::from_file(&config_path)
+            .map_err(|e| format!("读取插件配置失败: {}", e))?;
+        validate_plugin_config(&config)?;
+
+        // 优先使用仓库中已提交的预编译动态库
+        let prebuilt_library = plugin_dir.join(plugin_file_name(&config));
+        let library_path = if prebuilt_library.is_file() && inside_checkout(&prebuilt_library) {
+            log_info!("使用仓库中的预编译动态库: {:?}", prebuilt_library);
+            prebuilt_library
+        } else {
+            let declared = config.source.as_ref();
+            let source = SourceConfig {
+                git: None,
+                rev: None,
+                path: declared
+                    .and_then(|source| source.path.clone())
+                    .or_else(|| path.map(str::to_string)),
+                package: declared.and_then(|source| source.package.clone()),
+            };
+            build_plugin_from_source(&config.plugin.id, &source, &checkout_dir, task, |line| {
+                self.emit_build_log(line)
+            })
+            .await?
+            .library_path
+        };
+
+        let installed_path = install_plugin(
+            &config_path,
+            &library_path,
+            &[],
+            InstallSource::Git {
+                url: url.to_string(),
+                rev: rev.map(str::to_string),
+                commit,
+                path: path.map(str::to_string),
+            },
+        )?;
+        Ok((config, installed_path.to_string_lossy().to_string()))
+    }
+
     /// 卸载已安装的插件
     pub fn uninstall_plugin(&self, plugin_id: &str) -> PluginDownloadResult {
         log_info!("开始卸载插件: {}", plugin_id);
@@ -225,6 +476,10 @@ impl PluginRepository {
                     plugin_name,
                     plugin_dir
                 );
+                // 重新安装时需要重新批准权限
+                if let Err(e) = permissions::revoke_permissions(plugin_id) {
+                    log_warn!("{}", e);
+                }
                 PluginDownloadResult {
                     success: true,
                     message: format!("插件 \"{}\" 卸载成功", plugin_name),
@@ -272,6 +527,7 @@ impl PluginRepository {
                 platform: config.plugin.platform,
                 dependencies: config.plugin.dependencies,
                 download: config.download,
+                source: config.source,
             }),
             Err(e) => {
                 log_warn!("Failed to load plugin config from {:?}: {}", config_path, e);
@@ -303,87 +559,60 @@ impl PluginRepository {
         &self,
         plugin_info: &AvailablePluginInfo,
         platform_download: &PlatformDownload,
+        task: &DownloadTask,
     ) -> Result<String, String> {
         // 创建HTTP客户端
-        let client = reqwest::Client::new();
+        let client = self.http_client()?;
 
-        // 下载文件
+        // 流式下载到临时目录，文件名固定为 id-version，以便中断后再次下载时续传
         log_info!("正在下载: {}", platform_download.download_url);
-        let response = client
-            .get(&platform_download.download_url)
-            .send()
-            .await
-            .map_err(|e| format!("下载失败: {}", e))?;
-
-        if !response.status().is_success() {
-            return Err(format!("下载失败，HTTP状态码: {}", response.status()));
-        }
-
-        let file_data = response
-            .bytes()
-            .await
-            .map_err(|e| format!("读取下载数据失败: {}", e))?;
-
-        // 获取目标安装目录
-        let install_dir = get_root_plugin_installed_directory();
-        std::fs::create_dir_all(&install_dir).map_err(|e| format!("创建插件目录失败: {}", e))?;
-
-        // 创建插件专用目录
-        let plugin_dir = install_dir.join(&plugin_info.id);
-        if plugin_dir.exists() {
-            std::fs::remove_dir_all(&plugin_dir)
-                .map_err(|e| format!("删除现有插件目录失败: {}", e))?;
+        let downloaded_path = get_download_temp_directory().join(format!(
+            "{}-{}.download",
+            plugin_info.id, plugin_info.version
+        ));
+        task.download_to_file(
+            &client,
+            &platform_download.download_url,
+            &downloaded_path,
+            &self.download_options,
+            |progress| self.emit_download_progress(progress),
+        )
+        .await?;
+
+        // 校验下载文件
+        if let Err(error) = verify_checksum(&downloaded_path, &platform_download.checksum) {
+            let _ = std::fs::remove_file(&downloaded_path);
+            return Err(error);
         }
-        std::fs::create_dir_all(&plugin_dir).map_err(|e| format!("创建插件目录失败: {}", e))?;
-
-        // 生成文件名：平台前缀
-        let platform_prefix = if cfg!(target_os = "windows") {
-            ""
-        } else if cfg!(target_os = "macos") || cfg!(target_os = "linux") {
-            "lib"
-        } else {
-            ""
-        };
 
-        // 生成文件名：id-version.平台后缀
-        let file_extension = if cfg!(target_os = "windows") {
-            "dll"
-        } else if cfg!(target_os = "macos") {
-            "dylib"
-        } else {
-            "so"
+        let source = InstallSource::Repository {
+            download_url: platform_download.download_url.clone(),
         };
 
-        let library_filename = format!(
-            "{}{}-{}.{}",
-            platform_prefix, plugin_info.id, plugin_info.version, file_extension
-        );
-        let library_path = plugin_dir.join(&library_filename);
-
-        log_info!("保存动态链接库到: {:?}", library_path);
-
-        // 保存动态链接库文件
-        std::fs::write(&library_path, &file_data)
-            .map_err(|e| format!("保存动态链接库失败: {}", e))?;
+        // 下载的是插件包时，配置和动态库都从包内读取
+        if platform_download
+            .download_url
+            .to_lowercase()
+            .ends_with(&format!(".{}", PACKAGE_EXTENSION))
+        {
+            let result = install_package(
+                &downloaded_path,
+                &get_download_temp_directory(),
+                source,
+                &load_settings().packages,
+            );
+            let _ = std::fs::remove_file(&downloaded_path);
+            return result.map(|plugin_dir| plugin_dir.to_string_lossy().to_string());
+        }
 
-        // 复制config.toml文件
-        let source_config_path = get_plugin_repository_directory()
+        // 仓库中的 config.toml，不存在时生成一个基本的配置文件
+        let mut config_path = get_plugin_repository_directory()
             .join(&plugin_info.id)
             .join("config.toml");
-        let target_config_path = plugin_dir.join("config.toml");
-
-        if source_config_path.exists() {
-            std::fs::copy(&source_config_path, &target_config_path)
-                .map_err(|e| format!("复制配置文件失败: {}", e))?;
-            log_info!(
-                "配置文件已复制: {:?} -> {:?}",
-                source_config_path,
-                target_config_path
-            );
-        } else {
-            log_warn!("源配置文件不存在: {:?}", source_config_path);
+        let mut generated_config = None;
+        if !config_path.exists() {
+            log_warn!("源配置文件不存在: {:?}", config_path);
 
-            // 如果源配置文件不存在，创建一个基本的配置文件
             let basic_config = format!(
                 r#"[plugin]
 id = "{}"
@@ -403,20 +632,105 @@ library = "{}-{}"
                 plugin_info.version
             );
 
-            std::fs::write(&target_config_path, basic_config)
+            let basic_config_path =
+                get_download_temp_directory().join(format!("{}-config.toml", plugin_info.id));
+            std::fs::write(&basic_config_path, basic_config)
                 .map_err(|e| format!("创建配置文件失败: {}", e))?;
-            log_info!("已创建基本配置文件: {:?}", target_config_path);
+            log_info!("已创建基本配置文件: {:?}", basic_config_path);
+            config_path = basic_config_path.clone();
+            generated_config = Some(basic_config_path);
         }
 
-        log_info!("插件 {} 安装完成: {:?}", plugin_info.name, plugin_dir);
+        let result = install_plugin(&config_path, &downloaded_path, &[], source);
+
+        let _ = std::fs::remove_file(&downloaded_path);
+        if let Some(generated_config) = generated_config {
+            let _ = std::fs::remove_file(generated_config);
+        }
 
         // 触发插件扫描以更新插件列表
         log_info!("触发插件重新扫描");
 
-        Ok(plugin_dir.to_string_lossy().to_string())
+        result.map(|plugin_dir| plugin_dir.to_string_lossy().to_string())
+    }
+
+    /// 从源码构建并安装插件
+    ///
+    /// 使用插件仓库中的 config.toml，构建出的 cdylib 按下载的动态库同样处理。
+    async fn build_and_install_plugin(
+        &self,
+        plugin_info: &AvailablePluginInfo,
+        source: &SourceConfig,
+        task: &DownloadTask,
+    ) -> Result<String, String> {
+        let config_path = get_plugin_repository_directory()
+            .join(&plugin_info.id)
+            .join("config.toml");
+
+        let output = build_plugin_from_source(
+            &plugin_info.id,
+            source,
+            &get_repository_directory(),
+            task,
+            |line| self.emit_build_log(line),
+        )
+        .await?;
+
+        install_plugin(
+            &config_path,
+            &output.library_path,
+            &[],
+            InstallSource::Source {
+                git: source.git.clone(),
+                commit: output.commit,
+                path: source.path.clone(),
+            },
+        )
+        .map(|plugin_dir| plugin_dir.to_string_lossy().to_string())
+    }
+
+    /// 从本地文件旁加载插件
+    ///
+    /// `path` 为插件包、插件压缩包或动态库文件，动态库需要同目录下的 config.toml 或显式指定 `config_path`。
+    pub fn install_plugin_from_file(
+        &self,
+        path: &str,
+        config_path: Option<&str>,
+    ) -> PluginDownloadResult {
+        log_info!("开始从本地文件安装插件: {}", path);
+
+        match install_plugin_from_file(
+            std::path::Path::new(path),
+            config_path.map(std::path::Path::new),
+            &get_download_temp_directory(),
+        ) {
+            Ok(plugin_dir) => {
+                let plugin_id = plugin_dir
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string());
+                PluginDownloadResult {
+                    success: true,
+                    message: format!("插件 {} 安装成功", plugin_id.clone().unwrap_or_default()),
+                    plugin_id,
+                    installed_path: Some(plugin_dir.to_string_lossy().to_string()),
+                }
+            }
+            Err(error) => PluginDownloadResult {
+                success: false,
+                message: format!("安装插件失败: {}", error),
+                plugin_id: None,
+                installed_path: None,
+            },
+        }
     }
 
-    pub async fn download_github_repo(&self) -> Result<DownloadResponse, String> {
+    /// 下载插件仓库并解压到本地
+    ///
+    /// `download_id` 用于标识本次下载，可通过 `cancel_download` 取消；为 None 时自动生成。
+    pub async fn download_github_repo(
+        &self,
+        download_id: Option<String>,
+    ) -> Result<DownloadResponse, String> {
         let repo_url: String = GITHUB_PLUGIN_REPO.to_string();
         // 验证URL格式
         if !repo_url.starts_with("https://github.com/") {
@@ -434,7 +748,7 @@ library = "{}-{}"
             repo_info.owner, repo_info.name
         );
 
-        // 获取 home_dir/.chat_client
+        // 获取数据根目录
         let plugins_dir = get_plugin_repository_root();
 
         // 确保plugins目录不存在
@@ -442,37 +756,49 @@ library = "{}-{}"
             fs::create_dir_all(&plugins_dir).map_err(|e| format!("无法创建plugins目录: {}", e))?;
         }
 
-        // 下载ZIP文件
-        let client = reqwest::Client::new();
-        let response = client
-            .get(&zip_url)
-            .send()
+        // 流式下载ZIP文件到临时目录
+        let client = self.http_client()?;
+        let task = DownloadTask::new(
+            download_id.unwrap_or_else(|| Uuid::new_v4().to_string()),
+            &repo_info.name,
+        );
+        let zip_path = get_download_temp_directory().join(format!("{}.zip", task.download_id));
+        if let Err(error) = task
+            .download_to_file(
+                &client,
+                &zip_url,
+                &zip_path,
+                &self.download_options,
+                |progress| self.emit_download_progress(progress),
+            )
             .await
-            .map_err(|e| format!("下载失败: {}", e))?;
-
-        if !response.status().is_success() {
+        {
             return Ok(DownloadResponse {
                 success: false,
-                message: format!("下载失败，HTTP状态码: {}", response.status()),
+                message: error,
                 download_path: None,
             });
         }
 
-        let zip_data = response
-            .bytes()
-            .await
-            .map_err(|e| format!("读取下载数据失败: {}", e))?;
+        let result = self.extract_repository_zip(&zip_path, &plugins_dir.join(&repo_info.name));
+        let _ = fs::remove_file(&zip_path);
+        result
+    }
 
+    /// 将仓库ZIP文件解压到目标目录
+    fn extract_repository_zip(
+        &self,
+        zip_path: &std::path::Path,
+        target_dir: &std::path::Path,
+    ) -> Result<DownloadResponse, String> {
         // 解压ZIP文件
-        let cursor = Cursor::new(zip_data);
-        let mut archive = ZipArchive::new(cursor).map_err(|e| format!("无法打开ZIP文件: {}", e))?;
-
-        // 目标目录路径
-        let target_dir = plugins_dir.join(&repo_info.name);
+        let zip_file = fs::File::open(zip_path).map_err(|e| format!("无法打开ZIP文件: {}", e))?;
+        let mut archive =
+            ZipArchive::new(zip_file).map_err(|e| format!("无法打开ZIP文件: {}", e))?;
 
         // 如果目标目录已存在，先删除
         if target_dir.exists() {
-            fs::remove_dir_all(&target_dir).map_err(|e| format!("无法删除现有目录: {}", e))?;
+            fs::remove_dir_all(target_dir).map_err(|e| format!("无法删除现有目录: {}", e))?;
         }
 
         // 解压文件
@@ -0,0 +1,502 @@
+//! 插件资源统计和限制
+//!
+//! 进程外插件（子进程插件和沙箱辅助进程）按进程统计 CPU 时间和常驻内存。进程内插件按线程统计：
+//! 宿主调用插件期间调用线程消耗的 CPU 时间，加上插件自己的线程的 CPU 时间。插件线程在调用实例专属的
+//! 宿主回调（发送消息、流式输出等）时归属到该实例，只做计算、从不调用回调的线程无法统计；
+//! 进程内插件与宿主共享地址空间，无法统计内存。统计依赖 `/proc`，其他平台上只记录实例。
+//!
+//! 插件配置的 `[limits]` 可以设置软限制和硬限制：超过软限制时发送 `plugin-resource-warning` 事件，
+//! 超过硬限制时结束插件进程，实例被标记为故障并卸载，同时发送 `plugin-resource-fault` 事件。
+//! 进程内插件与宿主运行在同一进程中，没有可以结束的进程，超过硬限制时同样标记为故障并卸载，
+//! 卸载会等待插件当前的调用返回。
+
+use crate::plugins::config::ResourceLimits;
+use crate::plugins::manager::PluginManager;
+use serde::Serialize;
+use std::cell::Cell;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// 插件超过软限制时发送的事件
+pub const RESOURCE_WARNING_EVENT: &str = "plugin-resource-warning";
+/// 插件超过硬限制被停止时发送的事件
+pub const RESOURCE_FAULT_EVENT: &str = "plugin-resource-fault";
+
+/// 采样间隔
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+
+thread_local! {
+    /// 当前线程是否正在执行宿主对插件的调用，调用期间的 CPU 时间由 `measure` 统计
+    static IN_HOST_CALL: Cell<bool> = const { Cell::new(false) };
+}
+
+/// 资源状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResourceState {
+    Normal,
+    /// 超过软限制
+    Warning,
+    /// 超过硬限制，实例已被停止
+    Faulted,
+}
+
+/// 一个插件实例的资源占用
+#[derive(Debug, Clone, Serialize)]
+pub struct ResourceUsage {
+    pub instance_id: String,
+    pub plugin_id: String,
+    /// 进程外插件的进程号
+    pub process_id: Option<u32>,
+    /// 进程外插件为进程的线程数，进程内插件为插件创建的线程数
+    pub threads: usize,
+    /// 累计 CPU 时间（毫秒）
+    pub cpu_time_ms: u64,
+    /// 最近一个统计窗口内的平均 CPU 占用，100 表示占满一个核
+    pub cpu_percent: f64,
+    /// 常驻内存（字节），进程内插件为 None
+    pub memory_bytes: Option<u64>,
+    pub state: ResourceState,
+    /// 超限说明
+    pub message: Option<String>,
+}
+
+/// 一次采样中发现的超限，作为资源事件的载荷发送给前端
+#[derive(Debug, Clone, Serialize)]
+pub struct ResourceAlert {
+    pub instance_id: String,
+    pub plugin_id: String,
+    /// 是否超过硬限制
+    pub fault: bool,
+    pub message: String,
+}
+
+/// 一个实例的统计状态
+struct Tracker {
+    limits: ResourceLimits,
+    /// 宿主调用插件时调用线程消耗的 CPU 时间
+    call_cpu: Duration,
+    /// 调用过实例回调的插件线程及其最近一次采样的 CPU 时间
+    threads: HashMap<u32, Duration>,
+    /// 已退出线程的 CPU 时间
+    exited_cpu: Duration,
+    /// (采样时间, 累计 CPU 时间)
+    samples: VecDeque<(Instant, Duration)>,
+    usage: ResourceUsage,
+}
+
+impl Tracker {
+    /// 采样一次并检查限制
+    fn sample(&mut self, now: Instant) -> Option<ResourceAlert> {
+        if self.usage.state == ResourceState::Faulted {
+            return None;
+        }
+
+        let cpu = match self.usage.process_id {
+            Some(pid) => {
+                // 进程已经退出时保留最后一次的数据
+                let (cpu, threads) = proc::process_cpu(pid)?;
+                self.usage.threads = threads;
+                self.usage.memory_bytes = proc::process_memory(pid);
+                cpu
+            }
+            None => {
+                let mut exited = Duration::ZERO;
+                self.threads
+                    .retain(|&tid, last| match proc::thread_cpu(tid) {
+                        Some(cpu) => {
+                            *last = cpu;
+                            true
+                        }
+                        None => {
+                            exited += *last;
+                            false
+                        }
+                    });
+                self.exited_cpu += exited;
+                self.usage.threads = self.threads.len();
+                self.call_cpu + self.exited_cpu + self.threads.values().sum::<Duration>()
+            }
+        };
+        self.usage.cpu_time_ms = cpu.as_millis() as u64;
+
+        let window = Duration::from_secs(self.limits.window_secs.max(1));
+        self.samples.push_back((now, cpu));
+        while self
+            .samples
+            .front()
+            .is_some_and(|(time, _)| now.duration_since(*time) > window)
+        {
+            self.samples.pop_front();
+        }
+        let (first_time, first_cpu) = *self.samples.front()?;
+        let elapsed = now.duration_since(first_time);
+        if !elapsed.is_zero() {
+            self.usage.cpu_percent =
+                cpu.saturating_sub(first_cpu).as_secs_f64() / elapsed.as_secs_f64() * 100.0;
+        }
+        // 窗口填满之前不按 CPU 限制处理，避免启动时的短暂高占用
+        let cpu_ready = elapsed + SAMPLE_INTERVAL >= window;
+
+        self.check_limits(cpu_ready)
+    }
+
+    fn check_limits(&mut self, cpu_ready: bool) -> Option<ResourceAlert> {
+        let cpu = self.usage.cpu_percent;
+        let memory_mb = self.usage.memory_bytes.map(|bytes| bytes / 1024 / 1024);
+        let over_cpu = |limit: Option<f64>| {
+            limit
+                .filter(|limit| cpu_ready && cpu > *limit)
+                .map(|limit| format!("CPU 占用 {:.0}% 超过限制 {:.0}%", cpu, limit))
+        };
+        let over_memory = |limit: Option<u64>| {
+            limit
+                .zip(memory_mb)
+                .filter(|(limit, used)| used > limit)
+                .map(|(limit, used)| format!("内存占用 {} MB 超过限制 {} MB", used, limit))
+        };
+
+        // 已经在卸载的实例不重复处理
+        if self.usage.state == ResourceState::Faulted {
+            return None;
+        }
+
+        let hard = over_cpu(self.limits.cpu_hard_percent)
+            .or_else(|| over_memory(self.limits.memory_hard_mb));
+        if let Some(message) = hard {
+            // 进程内插件没有单独的进程可以结束，只能等待卸载
+            if let Some(pid) = self.usage.process_id {
+                proc::kill(pid);
+            }
+            self.usage.state = ResourceState::Faulted;
+            self.usage.message = Some(message.clone());
+            return Some(self.alert(true, message));
+        }
+
+        let soft = over_cpu(self.limits.cpu_soft_percent)
+            .or_else(|| over_memory(self.limits.memory_soft_mb));
+        match soft {
+            Some(message) => {
+                let first = self.usage.state == ResourceState::Normal;
+                self.usage.state = ResourceState::Warning;
+                self.usage.message = Some(message.clone());
+                // 只在刚超过软限制时提醒一次
+                first.then(|| self.alert(false, message))
+            }
+            None => {
+                self.usage.state = ResourceState::Normal;
+                self.usage.message = None;
+                None
+            }
+        }
+    }
+
+    fn alert(&self, fault: bool, message: String) -> ResourceAlert {
+        ResourceAlert {
+            instance_id: self.usage.instance_id.clone(),
+            plugin_id: self.usage.plugin_id.clone(),
+            fault,
+            message,
+        }
+    }
+}
+
+/// 所有插件实例的资源统计
+#[derive(Default)]
+pub struct ResourceMonitor {
+    trackers: Mutex<HashMap<String, Tracker>>,
+}
+
+impl std::fmt::Debug for ResourceMonitor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let count = self.trackers.lock().map(|t| t.len()).unwrap_or(0);
+        f.debug_struct("ResourceMonitor")
+            .field("instances", &count)
+            .finish()
+    }
+}
+
+impl ResourceMonitor {
+    /// 开始统计实例，替换同一实例之前的记录
+    pub fn register(
+        &self,
+        instance_id: &str,
+        plugin_id: &str,
+        process_id: Option<u32>,
+        limits: ResourceLimits,
+    ) {
+        let tracker = Tracker {
+            limits,
+            call_cpu: Duration::ZERO,
+            threads: HashMap::new(),
+            exited_cpu: Duration::ZERO,
+            samples: VecDeque::new(),
+            usage: ResourceUsage {
+                instance_id: instance_id.to_string(),
+                plugin_id: plugin_id.to_string(),
+                process_id,
+                threads: 0,
+                cpu_time_ms: 0,
+                cpu_percent: 0.0,
+                memory_bytes: None,
+                state: ResourceState::Normal,
+                message: None,
+            },
+        };
+        self.trackers
+            .lock()
+            .unwrap()
+            .insert(instance_id.to_string(), tracker);
+    }
+
+    /// 停止统计实例；因超限被停止的实例保留记录，供前端查看原因
+    pub fn unregister(&self, instance_id: &str) {
+        let mut trackers = self.trackers.lock().unwrap();
+        if trackers
+            .get(instance_id)
+            .is_some_and(|tracker| tracker.usage.state != ResourceState::Faulted)
+        {
+            trackers.remove(instance_id);
+        }
+    }
+
+    /// 在调用线程上执行一次对插件的调用，把消耗的 CPU 时间计入实例
+    pub fn measure<T>(&self, instance_id: &str, f: impl FnOnce() -> T) -> T {
+        let cpu_before = proc::current_thread_cpu();
+        let in_call = IN_HOST_CALL.with(|in_call| in_call.replace(true));
+        let result = f();
+        IN_HOST_CALL.with(|current| current.set(in_call));
+        let cpu = proc::current_thread_cpu().saturating_sub(cpu_before);
+
+        let mut trackers = self.trackers.lock().unwrap();
+        if let Some(tracker) = trackers.get_mut(instance_id) {
+            // 进程外插件按进程统计，宿主中的调用线程只在等待
+            if tracker.usage.process_id.is_none() {
+                tracker.call_cpu += cpu;
+            }
+        }
+        result
+    }
+
+    /// 把调用实例回调的当前线程计入实例
+    ///
+    /// 宿主调用期间的回调发生在宿主线程上，其 CPU 时间已经由 [`Self::measure`] 统计，不重复计入。
+    pub fn attribute_current_thread(&self, instance_id: &str) {
+        if IN_HOST_CALL.with(Cell::get) {
+            return;
+        }
+        let Some(tid) = proc::current_thread_id() else {
+            return;
+        };
+        let mut trackers = self.trackers.lock().unwrap();
+        if let Some(tracker) = trackers.get_mut(instance_id) {
+            if tracker.usage.process_id.is_none() {
+                tracker.threads.entry(tid).or_insert(Duration::ZERO);
+            }
+        }
+    }
+
+    /// 采样所有实例，返回新出现的超限
+    pub fn sample(&self) -> Vec<ResourceAlert> {
+        let now = Instant::now();
+        self.trackers
+            .lock()
+            .unwrap()
+            .values_mut()
+            .filter_map(|tracker| tracker.sample(now))
+            .collect()
+    }
+
+    /// 所有实例最近一次采样的资源占用
+    pub fn usage(&self) -> Vec<ResourceUsage> {
+        let mut usage: Vec<ResourceUsage> = self
+            .trackers
+            .lock()
+            .unwrap()
+            .values()
+            .map(|tracker| tracker.usage.clone())
+            .collect();
+        usage.sort_by(|a, b| (&a.plugin_id, &a.instance_id).cmp(&(&b.plugin_id, &b.instance_id)));
+        usage
+    }
+}
+
+/// 启动后台采样线程，管理器释放后退出
+pub fn start_resource_monitor(manager: &Arc<PluginManager>) {
+    let manager = Arc::downgrade(manager);
+    std::thread::spawn(move || loop {
+        std::thread::sleep(SAMPLE_INTERVAL);
+        let Some(manager) = manager.upgrade() else {
+            break;
+        };
+        for alert in manager.check_resource_limits() {
+            // 卸载可能要等待插件当前的调用结束，不能阻塞采样
+            let manager = manager.clone();
+            std::thread::spawn(move || manager.stop_faulted_instance(&alert));
+        }
+    });
+}
+
+#[cfg(target_os = "linux")]
+mod proc {
+    use std::time::Duration;
+
+    /// 读取 stat 文件中的 CPU 时间（utime + stime）和线程数
+    fn read_stat(path: &str) -> Option<(Duration, usize)> {
+        let content = std::fs::read_to_string(path).ok()?;
+        // 进程名可能包含空格和括号，从最后一个右括号之后开始按字段解析，第一个字段为 state
+        let fields: Vec<&str> = content
+            .get(content.rfind(')')? + 1..)?
+            .split_whitespace()
+            .collect();
+        let utime: u64 = fields.get(11)?.parse().ok()?;
+        let stime: u64 = fields.get(12)?.parse().ok()?;
+        let threads: usize = fields.get(17)?.parse().ok()?;
+        let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) }.max(1) as u64;
+        Some((
+            Duration::from_millis((utime + stime) * 1000 / ticks),
+            threads,
+        ))
+    }
+
+    pub fn process_cpu(pid: u32) -> Option<(Duration, usize)> {
+        read_stat(&format!("/proc/{}/stat", pid))
+    }
+
+    pub fn thread_cpu(tid: u32) -> Option<Duration> {
+        read_stat(&format!("/proc/self/task/{}/stat", tid)).map(|(cpu, _)| cpu)
+    }
+
+    pub fn process_memory(pid: u32) -> Option<u64> {
+        let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+        let kilobytes: u64 = status
+            .lines()
+            .find_map(|line| line.strip_prefix("VmRSS:"))?
+            .trim()
+            .trim_end_matches("kB")
+            .trim()
+            .parse()
+            .ok()?;
+        Some(kilobytes * 1024)
+    }
+
+    /// 当前线程消耗的 CPU 时间
+    pub fn current_thread_cpu() -> Duration {
+        let mut time = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        if unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut time) } != 0 {
+            return Duration::ZERO;
+        }
+        Duration::new(time.tv_sec as u64, time.tv_nsec as u32)
+    }
+
+    /// 当前线程的线程号
+    pub fn current_thread_id() -> Option<u32> {
+        u32::try_from(unsafe { libc::syscall(libc::SYS_gettid) }).ok()
+    }
+
+    pub fn kill(pid: u32) {
+        unsafe {
+            libc::kill(pid as libc::pid_t, libc::SIGKILL);
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod proc {
+    use std::time::Duration;
+
+    pub fn process_cpu(_pid: u32) -> Option<(Duration, usize)> {
+        None
+    }
+
+    pub fn thread_cpu(_tid: u32) -> Option<Duration> {
+        None
+    }
+
+    pub fn process_memory(_pid: u32) -> Option<u64> {
+        None
+    }
+
+    pub fn current_thread_cpu() -> Duration {
+        Duration::ZERO
+    }
+
+    pub fn current_thread_id() -> Option<u32> {
+        None
+    }
+
+    pub fn kill(_pid: u32) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn in_process_monitor(limits: ResourceLimits) -> ResourceMonitor {
+        let monitor = ResourceMonitor::default();
+        monitor.register("instance", "plugin", None, limits);
+        monitor
+    }
+
+    #[test]
+    fn in_process_hard_limit_faults() {
+        let monitor = in_process_monitor(ResourceLimits {
+            cpu_hard_percent: Some(50.0),
+            ..ResourceLimits::default()
+        });
+        let mut trackers = monitor.trackers.lock().unwrap();
+        let tracker = trackers.get_mut("instance").unwrap();
+        tracker.usage.cpu_percent = 90.0;
+
+        let alert = tracker.check_limits(true).expect("expected a fault");
+        assert!(alert.fault);
+        assert_eq!(tracker.usage.state, ResourceState::Faulted);
+        // 卸载完成之前不重复报告
+        assert!(tracker.check_limits(true).is_none());
+    }
+
+    #[test]
+    fn soft_limit_warns_once_and_recovers() {
+        let monitor = in_process_monitor(ResourceLimits {
+            cpu_soft_percent: Some(50.0),
+            ..ResourceLimits::default()
+        });
+        let mut trackers = monitor.trackers.lock().unwrap();
+        let tracker = trackers.get_mut("instance").unwrap();
+        tracker.usage.cpu_percent = 90.0;
+        // 窗口填满之前不按 CPU 限制处理
+        assert!(tracker.check_limits(false).is_none());
+        assert!(tracker.check_limits(true).is_some_and(|alert| !alert.fault));
+
+        tracker.usage.cpu_percent = 10.0;
+        assert!(tracker.check_limits(true).is_none());
+        assert_eq!(tracker.usage.state, ResourceState::Normal);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn callback_threads_are_attributed_outside_host_calls() {
+        let monitor = Arc::new(in_process_monitor(ResourceLimits::default()));
+
+        // 宿主调用期间的回调在宿主线程上，不计为插件线程
+        monitor.measure("instance", || monitor.attribute_current_thread("instance"));
+        assert!(monitor.trackers.lock().unwrap()["instance"]
+            .threads
+            .is_empty());
+
+        let plugin_thread = Arc::clone(&monitor);
+        let tid = std::thread::spawn(move || {
+            plugin_thread.attribute_current_thread("instance");
+            proc::current_thread_id().unwrap()
+        })
+        .join()
+        .unwrap();
+        assert!(monitor.trackers.lock().unwrap()["instance"]
+            .threads
+            .contains_key(&tid));
+    }
+}
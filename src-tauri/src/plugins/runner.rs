@@ -0,0 +1,545 @@
+//! 远程插件运行器
+//!
+//! 在本机通过 `PluginManager` 加载一个插件（任意类型），按远程插件协议通过 TCP 提供给其他机器上的客户端。
+//! 每条连接对应一个插件实例：连接先以 `authenticate` 请求认证，之后客户端的请求映射到管理器调用，
+//! 插件发往前端的事件转换为发给客户端的请求和通知，连接关闭时卸载实例。
+//! 网络连接必须在 `AUTH_TIMEOUT` 内认证，认证失败 `MAX_AUTH_ATTEMPTS` 次后断开，
+//! 同时等待认证的连接不超过 `MAX_UNAUTHENTICATED`。
+//!
+//! 运行器也用作插件沙箱的辅助进程（`--stdio`），此时只通过标准输入输出服务一条连接。
+
+use crate::plugins::events::EventSink;
+use crate::plugins::manager::PluginManager;
+use crate::plugins::runtime::rpc::{
+    CallResult, RpcCall, RpcConnection, RpcHandler, CALL_FAILED, METHOD_NOT_FOUND, UNAUTHORIZED,
+};
+use crate::plugins::runtime::ui::UiNode;
+use plugin_interfaces::{log_info, log_warn, metadata::HistoryMessage};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// 保存认证令牌的环境变量
+pub const RUNNER_TOKEN_ENV: &str = "CHAT_CLIENT_RUNNER_TOKEN";
+
+/// 运行器等待客户端响应的最长时间
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
+/// 网络连接完成认证的期限
+const AUTH_TIMEOUT: Duration = Duration::from_secs(5);
+/// 每条连接允许的认证失败次数
+const MAX_AUTH_ATTEMPTS: usize = 3;
+/// 同时等待认证的网络连接数
+const MAX_UNAUTHENTICATED: usize = 8;
+
+/// 按实例把插件事件转发给对应的连接
+#[derive(Default)]
+struct EventRoutes {
+    sessions: RwLock<HashMap<String, Arc<RunnerSession>>>,
+}
+
+impl EventSink for EventRoutes {
+    fn emit(&self, event: &str, payload: &str) -> Result<(), String> {
+        let data: Value = serde_json::from_str(payload).unwrap_or(Value::Null);
+        let Some(instance_id) = data["instance_id"].as_str() else {
+            return Ok(());
+        };
+        let session = self.sessions.read().unwrap().get(instance_id).cloned();
+        match session {
+            Some(session) => session.forward_event(event, &data),
+            None => Ok(()),
+        }
+    }
+}
+
+/// 通过网络提供一个本机插件
+pub struct PluginRunner {
+    plugin_id: String,
+    token: String,
+    manager: Arc<PluginManager>,
+    routes: Arc<EventRoutes>,
+    /// 还没有认证的网络连接数
+    unauthenticated: Arc<AtomicUsize>,
+}
+
+impl PluginRunner {
+    /// 创建运行器，`plugin_id` 必须能在插件目录中找到
+    pub fn new(plugin_id: &str, token: &str) -> Result<Self, String> {
+        if token.is_empty() {
+            return Err("认证令牌不能为空".to_string());
+        }
+        let routes = Arc::new(EventRoutes::default());
+        // 运行器由使用者指定要服务的插件，宿主的权限批准记录不在这里
+        let manager = Arc::new(PluginManager::new(routes.clone()).trust_manifests());
+        if !manager.scan_plugins().iter().any(|p| p.id == plugin_id) {
+            return Err(format!("插件 {} 未找到", plugin_id));
+        }
+        Ok(Self {
+            plugin_id: plugin_id.to_string(),
+            token: token.to_string(),
+            manager,
+            routes,
+            unauthenticated: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    /// 接受连接直到监听出错
+    pub fn serve(&self, listener: TcpListener) -> Result<(), String> {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log_warn!("接受连接失败: {}", e);
+                    continue;
+                }
+            };
+            let peer = stream
+                .peer_addr()
+                .map(|addr| addr.to_string())
+                .unwrap_or_default();
+            if self.unauthenticated.load(Ordering::SeqCst) >= MAX_UNAUTHENTICATED {
+                log_warn!("等待认证的连接过多，已拒绝 {}", peer);
+                continue;
+            }
+            let (reader, control) = match (stream.try_clone(), stream.try_clone()) {
+                (Ok(reader), Ok(control)) => (reader, control),
+                (Err(e), _) | (_, Err(e)) => {
+                    log_warn!("无法复制网络连接 {}: {}", peer, e);
+                    continue;
+                }
+            };
+            let _ = stream.set_nodelay(true);
+            self.unauthenticated.fetch_add(1, Ordering::SeqCst);
+            let session = self.open_session(
+                peer,
+                reader,
+                stream,
+                SessionEnd::Network {
+                    stream: control,
+                    unauthenticated: self.unauthenticated.clone(),
+                },
+            );
+            std::thread::spawn(move || {
+                std::thread::sleep(AUTH_TIMEOUT);
+                if !session.authenticated.load(Ordering::SeqCst) {
+                    log_warn!("实例 {} 未在期限内完成认证，断开连接", session.instance_id);
+                    session.disconnect();
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// 通过标准输入输出服务一条连接，连接关闭后返回
+    ///
+    /// 插件写到标准输出的内容会破坏协议，因此协议使用复制出的标准输出，标准输出本身重定向到标准错误。
+    #[cfg(target_os = "linux")]
+    pub fn serve_stdio(&self) -> Result<(), String> {
+        use std::os::fd::FromRawFd;
+
+        let writer = unsafe {
+            let fd = libc::dup(libc::STDOUT_FILENO);
+            if fd < 0 || libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) < 0 {
+                return Err(format!(
+                    "重定向标准输出失败: {}",
+                    std::io::Error::last_os_error()
+                ));
+            }
+            std::fs::File::from_raw_fd(fd)
+        };
+        let (closed, wait) = mpsc::channel();
+        self.open_session(
+            "stdio".to_string(),
+            std::io::stdin(),
+            writer,
+            SessionEnd::Stdio(closed),
+        );
+        let _ = wait.recv();
+        Ok(())
+    }
+
+    fn open_session(
+        &self,
+        label: String,
+        reader: impl Read + Send + 'static,
+        writer: impl Write + Send + 'static,
+        end: SessionEnd,
+    ) -> Arc<RunnerSession> {
+        let session = Arc::new(RunnerSession {
+            plugin_id: self.plugin_id.clone(),
+            instance_id: Uuid::new_v4().to_string(),
+            token: self.token.clone(),
+            manager: self.manager.clone(),
+            routes: self.routes.clone(),
+            authenticated: AtomicBool::new(false),
+            failed_attempts: AtomicUsize::new(0),
+            connection: OnceLock::new(),
+            streams: Mutex::new(HashMap::new()),
+            components: Mutex::new(Value::Null),
+            end: Mutex::new(Some(end)),
+        });
+        log_info!("{} 已连接，实例 {}", label, session.instance_id);
+        self.routes
+            .sessions
+            .write()
+            .unwrap()
+            .insert(session.instance_id.clone(), session.clone());
+        let connection = RpcConnection::start(label, reader, writer, session.clone());
+        let _ = session.connection.set(connection);
+        session
+    }
+}
+
+/// 连接的另一端，连接关闭时需要处理
+enum SessionEnd {
+    /// 网络连接；`stream` 用于断开连接，认证或关闭时减少等待认证的连接数
+    Network {
+        stream: TcpStream,
+        unauthenticated: Arc<AtomicUsize>,
+    },
+    /// 标准输入输出，关闭时通知等待中的 `serve_stdio`
+    Stdio(Sender<()>),
+}
+
+/// 一条客户端连接及其插件实例
+struct RunnerSession {
+    plugin_id: String,
+    instance_id: String,
+    token: String,
+    manager: Arc<PluginManager>,
+    routes: Arc<EventRoutes>,
+    authenticated: AtomicBool,
+    failed_attempts: AtomicUsize,
+    connection: OnceLock<Arc<RpcConnection>>,
+    /// 本机流 ID 到客户端流 ID 的映射
+    streams: Mutex<HashMap<String, String>>,
+    /// 最近一次返回给客户端的 UI 组件，用于把下拉框的选项还原为序号
+    components: Mutex<Value>,
+    /// 连接的另一端，连接关闭后取出
+    end: Mutex<Option<SessionEnd>>,
+}
+
+/// 比较令牌，耗时与内容无关
+fn token_matches(expected: &str, actual: &str) -> bool {
+    let (expected, actual) = (expected.as_bytes(), actual.as_bytes());
+    expected.len() == actual.len()
+        && expected
+            .iter()
+            .zip(actual)
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn failed(e: String) -> (i64, String) {
+    (CALL_FAILED, e)
+}
+
+impl RunnerSession {
+    /// 把插件发往前端的事件转发给客户端
+    fn forward_event(&self, event: &str, data: &Value) -> Result<(), String> {
+        let connection = self
+            .connection
+            .get()
+            .ok_or_else(|| "连接尚未建立".to_string())?;
+        match event {
+            "plugin-message" => {
+                connection.notify("send_message", json!({ "content": data["content"] }))
+            }
+            "plugin-stream" => {
+                let stream_id = data["data"]["stream_id"].as_str().unwrap_or_default();
+                match data["type"].as_str().unwrap_or_default() {
+                    "stream_start" => {
+                        let result =
+                            connection.request("stream_start", Value::Null, CLIENT_TIMEOUT)?;
+                        let client_stream_id = result["stream_id"]
+                            .as_str()
+                            .ok_or_else(|| "客户端没有返回流 ID".to_string())?;
+                        self.streams
+                            .lock()
+                            .unwrap()
+                            .insert(stream_id.to_string(), client_stream_id.to_string());
+                        Ok(())
+                    }
+                    "stream_data" => {
+                        let client_stream_id = self.client_stream_id(stream_id)?;
+                        connection.notify(
+                            "stream_data",
+                            json!({
+                                "stream_id": client_stream_id,
+                                "chunk": data["data"]["chunk"],
+                                "is_final": data["data"]["is_final"],
+                            }),
+                        )
+                    }
+                    "stream_end" => {
+                        let client_stream_id = self.client_stream_id(stream_id)?;
+                        self.streams.lock().unwrap().remove(stream_id);
+                        connection.notify(
+                            "stream_end",
+                            json!({
+                                "stream_id": client_stream_id,
+                                "success": data["data"]["success"],
+                                "error": data["data"]["error"],
+                            }),
+                        )
+                    }
+                    // 取消由客户端发起，不需要回传
+                    _ => Ok(()),
+                }
+            }
+            "plugin-ui-refreshed" => connection.notify("refresh_ui", Value::Null),
+            _ => Ok(()),
+        }
+    }
+
+    fn client_stream_id(&self, stream_id: &str) -> Result<String, String> {
+        self.streams
+            .lock()
+            .unwrap()
+            .get(stream_id)
+            .cloned()
+            .ok_or_else(|| format!("流 {} 未找到", stream_id))
+    }
+
+    fn authenticate(&self, call: &RpcCall) -> CallResult {
+        let token = call.string_param("token")?;
+        if !token_matches(&self.token, &token) {
+            let attempts = self.failed_attempts.fetch_add(1, Ordering::SeqCst) + 1;
+            log_warn!("实例 {} 认证失败（第 {} 次）", self.instance_id, attempts);
+            if attempts >= MAX_AUTH_ATTEMPTS {
+                self.disconnect();
+            }
+            return Err((UNAUTHORIZED, "认证失败".to_string()));
+        }
+        if !self.authenticated.swap(true, Ordering::SeqCst) {
+            if let Some(SessionEnd::Network {
+                unauthenticated, ..
+            }) = self.end.lock().unwrap().as_ref()
+            {
+                unauthenticated.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+        Ok(json!({ "plugin_id": self.plugin_id }))
+    }
+
+    /// 断开网络连接，读取线程随即结束并卸载实例
+    fn disconnect(&self) {
+        if let Some(SessionEnd::Network { stream, .. }) = self.end.lock().unwrap().as_ref() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    fn render_ui(&self) -> CallResult {
+        let ui = self
+            .manager
+            .get_plugin_ui(&self.instance_id)
+            .map_err(failed)?;
+        let components: Value =
+            serde_json::from_str(&ui).map_err(|e| failed(format!("插件UI格式错误: {}", e)))?;
+        let nodes = components_to_nodes(&components);
+        *self.components.lock().unwrap() = components;
+        serde_json::to_value(nodes).map_err(|e| failed(e.to_string()))
+    }
+
+    fn ui_event(&self, call: &RpcCall) -> CallResult {
+        let id = call.string_param("id")?;
+        let mut value = call.string_param("value")?;
+        // 下拉框在声明式 UI 中以选项文本回传，原生 UI 需要选项序号
+        if let Some(component) = find_component(&self.components.lock().unwrap(), &id) {
+            if component["type"] == "ComboBox" {
+                if let Some(index) = component["options"]
+                    .as_array()
+                    .and_then(|options| options.iter().position(|option| option == &value))
+                {
+                    value = index.to_string();
+                }
+            }
+        }
+        self.manager
+            .handle_plugin_ui_event(&self.instance_id, &id, &value)
+            .map(Value::Bool)
+            .map_err(failed)
+    }
+}
+
+impl RpcHandler for RunnerSession {
+    fn handle_call(&self, _connection: &RpcConnection, call: &RpcCall) -> CallResult {
+        if call.method == "authenticate" {
+            return self.authenticate(call);
+        }
+        if !self.authenticated.load(Ordering::SeqCst) {
+            return Err((UNAUTHORIZED, "连接尚未认证".to_string()));
+        }
+
+        let instance_id = self.instance_id.as_str();
+        match call.method.as_str() {
+            "mount" => self
+                .manager
+                .mount_plugin(&self.plugin_id, Some(instance_id.to_string()))
+                .map(Value::String)
+                .map_err(failed),
+            "connect" => self
+                .manager
+                .connect_plugin(instance_id)
+                .map(Value::String)
+                .map_err(failed),
+            "disconnect" => self
+                .manager
+                .disconnect_plugin(instance_id)
+                .map(Value::String)
+                .map_err(failed),
+            "dispose" => self
+                .manager
+                .dispose_plugin(instance_id)
+                .map(Value::String)
+                .map_err(failed),
+            "handle_message" => {
+                let message = call.string_param("message")?;
+                let history: Option<Vec<HistoryMessage>> =
+                    serde_json::from_value(call.params["history"].clone()).unwrap_or(None);
+                self.manager
+                    .send_message_to_plugin_instance(
+                        &self.plugin_id,
+                        instance_id,
+                        &message,
+                        history,
+                    )
+                    .map(Value::String)
+                    .map_err(failed)
+            }
+            "render_ui" => self.render_ui(),
+            "ui_event" => self.ui_event(call),
+            "stream_cancelled" => {
+                let client_stream_id = call.string_param("stream_id")?;
+                let stream_id = self
+                    .streams
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .find(|(_, client)| **client == client_stream_id)
+                    .map(|(local, _)| local.clone());
+                match stream_id {
+                    Some(stream_id) => self
+                        .manager
+                        .cancel_stream_message(instance_id, &stream_id)
+                        .map(Value::String)
+                        .map_err(failed),
+                    None => Ok(Value::Null),
+                }
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("未知的方法: {}", call.method))),
+        }
+    }
+
+    fn on_close(&self, connection: &RpcConnection) {
+        log_info!(
+            "{} 已断开，卸载实例 {}",
+            connection.label(),
+            self.instance_id
+        );
+        connection.close_writer();
+        if self.manager.get_plugin_status(&self.instance_id).is_some() {
+            if let Err(e) = self.manager.dispose_plugin(&self.instance_id) {
+                log_warn!("卸载实例 {} 失败: {}", self.instance_id, e);
+            }
+        }
+        self.routes
+            .sessions
+            .write()
+            .unwrap()
+            .remove(&self.instance_id);
+        match self.end.lock().unwrap().take() {
+            Some(SessionEnd::Network {
+                unauthenticated, ..
+            }) if !self.authenticated.load(Ordering::SeqCst) => {
+                unauthenticated.fetch_sub(1, Ordering::SeqCst);
+            }
+            Some(SessionEnd::Stdio(closed)) => {
+                let _ = closed.send(());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 在原生 UI 组件中按 ID 查找组件
+fn find_component<'a>(components: &'a Value, id: &str) -> Option<&'a Value> {
+    for component in components.as_array()? {
+        if component["id"] == id {
+            return Some(&component["component"]);
+        }
+        if let Some(found) = find_component(&component["component"]["children"], id) {
+            return Some(found);
+        }
+    }
+    None
+}
+
+/// 把原生 UI 组件（`Ui::get_components` 的 JSON）转换为声明式 UI 节点
+fn components_to_nodes(components: &Value) -> Vec<UiNode> {
+    let mut nodes = Vec::new();
+    for component in components.as_array().into_iter().flatten() {
+        let id = component["id"].as_str().unwrap_or_default().to_string();
+        let body = &component["component"];
+        let text = |key: &str| body[key].as_str().unwrap_or_default().to_string();
+        let options = || -> Vec<String> {
+            body["options"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|option| option.as_str().map(str::to_string))
+                .collect()
+        };
+        match body["type"].as_str().unwrap_or_default() {
+            "Label" => nodes.push(UiNode::Label { text: text("text") }),
+            "Text" => nodes.push(UiNode::Label {
+                text: text("value"),
+            }),
+            "Button" => nodes.push(UiNode::Button {
+                id,
+                text: text("text"),
+            }),
+            "TextEdit" | "TextField" => nodes.push(UiNode::TextEdit {
+                id,
+                value: text("value"),
+            }),
+            "ComboBox" => {
+                let options = options();
+                let selected = body["selected"]
+                    .as_u64()
+                    .and_then(|index| options.get(index as usize).cloned());
+                nodes.push(UiNode::ComboBox {
+                    id,
+                    options,
+                    selected,
+                    placeholder: text("placeholder"),
+                });
+            }
+            "Select" => nodes.push(UiNode::ComboBox {
+                id,
+                options: options(),
+                selected: body["selected"].as_str().map(str::to_string),
+                placeholder: String::new(),
+            }),
+            "Toggle" => nodes.push(UiNode::Toggle {
+                id,
+                value: body["value"].as_bool().unwrap_or(false),
+            }),
+            "Horizontal" => nodes.push(UiNode::Horizontal {
+                children: components_to_nodes(&body["children"]),
+            }),
+            "Container" if body["layout"] == "Horizontal" => nodes.push(UiNode::Horizontal {
+                children: components_to_nodes(&body["children"]),
+            }),
+            // 纵向和网格布局按顺序展开
+            "Container" => nodes.extend(components_to_nodes(&body["children"])),
+            other => log_warn!("远程插件运行器不支持的UI组件类型: {}", other),
+        }
+    }
+    nodes
+}
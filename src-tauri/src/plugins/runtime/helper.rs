@@ -0,0 +1,85 @@
+//! 沙箱辅助进程
+//!
+//! 启用了 `[sandbox]` 的原生、WebAssembly 和脚本插件不在宿主进程中加载，而是由
+//! `chat-client-plugin-runner --stdio` 在沙箱中加载。辅助进程通过标准输入输出使用远程插件协议，
+//! 认证令牌每次随机生成，通过环境变量传入。
+
+use super::rpc::{RpcPlugin, PROTOCOL_VERSION};
+use super::subprocess;
+use crate::plugins::config::PluginConfig;
+use crate::plugins::runner::RUNNER_TOKEN_ENV;
+use crate::plugins::sandbox::SandboxPolicy;
+use plugin_interfaces::PluginMetadata;
+use serde_json::json;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// 指定运行器程序位置的环境变量，默认使用与宿主程序同目录的 `chat-client-plugin-runner`
+pub const RUNNER_PATH_ENV: &str = "CHAT_CLIENT_PLUGIN_RUNNER";
+
+fn runner_path() -> Result<PathBuf, String> {
+    if let Some(path) = std::env::var_os(RUNNER_PATH_ENV).filter(|v| !v.is_empty()) {
+        return Ok(PathBuf::from(path));
+    }
+    let exe = std::env::current_exe().map_err(|e| format!("无法确定程序位置: {}", e))?;
+    let runner = exe.with_file_name(format!(
+        "chat-client-plugin-runner{}",
+        std::env::consts::EXE_SUFFIX
+    ));
+    if runner.is_file() {
+        Ok(runner)
+    } else {
+        Err(format!(
+            "找不到沙箱辅助程序 {:?}，可通过环境变量 {} 指定",
+            runner, RUNNER_PATH_ENV
+        ))
+    }
+}
+
+/// 在沙箱中启动辅助进程加载插件，并完成认证
+pub fn spawn(metadata: &PluginMetadata, mut policy: SandboxPolicy) -> Result<RpcPlugin, String> {
+    let timeout_secs = PluginConfig::from_file(&metadata.config_path)
+        .map_err(|e| format!("读取插件配置失败: {}", e))?
+        .subprocess
+        .unwrap_or_default()
+        .timeout_secs;
+    let runner = runner_path()?;
+    let token = Uuid::new_v4().to_string();
+
+    // 辅助进程以插件数据目录为数据根目录，不读取宿主的设置和其他插件
+    let mut command = Command::new(&runner);
+    command
+        .arg(&policy.plugin_dir)
+        .arg("--stdio")
+        .arg("--data-dir")
+        .arg(&policy.data_dir)
+        .env(RUNNER_TOKEN_ENV, &token)
+        .env_remove("CHAT_CLIENT_PLUGIN_DIRS")
+        .current_dir(&policy.plugin_dir);
+    policy.executables.push(runner);
+    // 运行器通过扫描插件目录的上级目录找到插件
+    if let Some(parent) = policy.plugin_dir.parent() {
+        policy.list_paths.push(parent.to_path_buf());
+    }
+    policy.apply(&mut command)?;
+
+    let label = format!(
+        "{}:{}",
+        metadata.id,
+        metadata.instance_id.as_deref().unwrap_or_default()
+    );
+    let plugin = subprocess::launch(command, label, Duration::from_secs(timeout_secs))?;
+    plugin
+        .request(
+            "authenticate",
+            json!({
+                "token": token,
+                "plugin_id": metadata.id,
+                "protocol_version": PROTOCOL_VERSION,
+            }),
+        )
+        .map_err(|e| format!("沙箱辅助进程启动失败: {}", e))?;
+    Ok(plugin)
+}
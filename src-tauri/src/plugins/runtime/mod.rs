@@ -0,0 +1,291 @@
+//! 插件运行时
+//!
+//! 原生插件从动态库中创建 `PluginInterface`；其他类型的插件在宿主中实现 `PluginHandler`，
+//! 再通过 `create_plugin_interface_from_handler` 包装成同样的 `PluginInterface`，
+//! 因此 `PluginManager` 可以用相同的方式挂载、连接、卸载和热重载所有插件实例。
+
+pub mod helper;
+pub mod remote;
+pub mod rpc;
+#[cfg(feature = "script-plugins")]
+pub mod script;
+pub mod subprocess;
+pub mod ui;
+#[cfg(feature = "wasm-plugins")]
+pub mod wasm;
+
+use crate::plugins::{
+    builtin,
+    config::PluginConfig,
+    loader::library_file_name,
+    sandbox::{self, SandboxPolicy},
+};
+use libloading::{Library, Symbol};
+use plugin_interfaces::{
+    create_plugin_interface_from_handler, CreatePluginFn, DestroyPluginFn, PluginHandler,
+    PluginInterface, PluginMetadata, CREATE_PLUGIN_SYMBOL, DESTROY_PLUGIN_SYMBOL,
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// 插件类型，在 config.toml 的 `[plugin] kind` 中声明，默认为 native
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PluginKind {
+    /// 动态库（cdylib）插件
+    #[default]
+    Native,
+    /// WebAssembly 组件插件，接口见 `wit/plugin.wit`
+    Wasm,
+    /// 子进程插件，通过 stdin / stdout 上的 JSON-RPC 与宿主通信，协议见 `docs/subprocess-plugins.md`
+    Subprocess,
+    /// Rhai 脚本插件，修改脚本后自动重新加载
+    Script,
+    /// 运行在其他机器上的插件，通过 TCP 使用与子进程插件相同的协议
+    Remote,
+    /// 编译进宿主的内置插件，不能在 config.toml 中声明
+    #[serde(skip)]
+    Builtin,
+}
+
+impl PluginKind {
+    /// 读取插件配置中声明的类型，读取失败时视为原生插件
+    pub fn of(metadata: &PluginMetadata) -> Self {
+        if builtin::is_builtin(metadata) {
+            return PluginKind::Builtin;
+        }
+        PluginConfig::from_file(&metadata.config_path)
+            .map(|config| config.plugin.kind)
+            .unwrap_or_default()
+    }
+
+    /// 插件文件名，`name` 为配置中的 `library`，未设置时为 `id-version`
+    pub fn file_name(&self, name: &str) -> String {
+        match self {
+            PluginKind::Native => library_file_name(name),
+            PluginKind::Wasm => format!("{}.wasm", name),
+            PluginKind::Subprocess | PluginKind::Remote | PluginKind::Builtin => name.to_string(),
+            PluginKind::Script => format!("{}.rhai", name),
+        }
+    }
+}
+
+/// 已加载的插件实现
+pub enum PluginRuntime {
+    /// 动态库插件，通过库中导出的 destroy_plugin 销毁
+    Native(Library),
+    /// 在宿主中实现 `PluginHandler` 的插件，由宿主销毁
+    Hosted,
+    /// 运行在独立进程中的插件（子进程插件、沙箱辅助进程），由宿主销毁
+    Process(u32),
+}
+
+impl std::fmt::Debug for PluginRuntime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PluginRuntime::Native(_) => f.write_str("Native"),
+            PluginRuntime::Hosted => f.write_str("Hosted"),
+            PluginRuntime::Process(pid) => write!(f, "Process({})", pid),
+        }
+    }
+}
+
+impl PluginRuntime {
+    /// 按插件类型加载插件文件，返回运行时和新建的插件函数集合
+    ///
+    /// 插件配置启用了 `[sandbox]` 时，子进程插件在沙箱中启动，原生、WebAssembly 和脚本插件
+    /// 在沙箱中的辅助进程里加载。
+    pub fn load(
+        kind: PluginKind,
+        path: &Path,
+        metadata: &PluginMetadata,
+    ) -> Result<(Self, *mut PluginInterface), String> {
+        // 启用了沙箱的进程内插件改为在沙箱辅助进程中运行
+        if matches!(
+            kind,
+            PluginKind::Native | PluginKind::Wasm | PluginKind::Script
+        ) && !sandbox::is_helper_process()
+        {
+            if let Some(policy) = SandboxPolicy::for_plugin(metadata)? {
+                return Self::process(helper::spawn(metadata, policy)?);
+            }
+        }
+        match kind {
+            PluginKind::Native => Self::load_native(path),
+            PluginKind::Wasm => Self::load_wasm(path),
+            PluginKind::Subprocess => Self::process(subprocess::spawn(path, metadata)?),
+            PluginKind::Remote => Self::hosted(Box::new(remote::connect(metadata)?)),
+            PluginKind::Script => Self::load_script(path),
+            PluginKind::Builtin => Self::hosted(builtin::create_builtin_handler(&metadata.id)?),
+        }
+    }
+
+    fn load_native(path: &Path) -> Result<(Self, *mut PluginInterface), String> {
+        // 动态加载库
+        let library =
+            unsafe { Library::new(path).map_err(|e| format!("加载动态库失败: {}", e))? };
+
+        // 获取创建函数并创建插件实例
+        let handler = unsafe {
+            let create_plugin: Symbol<CreatePluginFn> = library
+                .get(CREATE_PLUGIN_SYMBOL)
+                .map_err(|e| format!("找不到插件创建函数: {}", e))?;
+            create_plugin()
+        };
+        if handler.is_null() {
+            return Err("插件创建失败".to_string());
+        }
+        Ok((PluginRuntime::Native(library), handler))
+    }
+
+    #[cfg(feature = "wasm-plugins")]
+    fn load_wasm(path: &Path) -> Result<(Self, *mut PluginInterface), String> {
+        Self::hosted(Box::new(wasm::WasmPlugin::load(path)?))
+    }
+
+    #[cfg(not(feature = "wasm-plugins"))]
+    fn load_wasm(_path: &Path) -> Result<(Self, *mut PluginInterface), String> {
+        Err("当前版本未启用 WebAssembly 插件支持（wasm-plugins 特性）".to_string())
+    }
+
+    #[cfg(feature = "script-plugins")]
+    fn load_script(path: &Path) -> Result<(Self, *mut PluginInterface), String> {
+        Self::hosted(Box::new(script::ScriptPlugin::load(path)?))
+    }
+
+    #[cfg(not(feature = "script-plugins"))]
+    fn load_script(_path: &Path) -> Result<(Self, *mut PluginInterface), String> {
+        Err("当前版本未启用脚本插件支持（script-plugins 特性）".to_string())
+    }
+
+    /// 把宿主中实现的插件包装成插件函数集合
+    pub fn hosted(handler: Box<dyn PluginHandler>) -> Result<(Self, *mut PluginInterface), String> {
+        let interface = create_plugin_interface_from_handler(handler);
+        if interface.is_null() {
+            return Err("插件创建失败".to_string());
+        }
+        Ok((PluginRuntime::Hosted, interface))
+    }
+
+    /// 包装运行在本机进程中的插件，记录其进程号
+    fn process(plugin: rpc::RpcPlugin) -> Result<(Self, *mut PluginInterface), String> {
+        let process_id = plugin.process_id();
+        let (runtime, interface) = Self::hosted(Box::new(plugin))?;
+        Ok((
+            process_id.map_or(runtime, PluginRuntime::Process),
+            interface,
+        ))
+    }
+
+    /// 插件所在进程的进程号，进程内插件为 None
+    pub fn process_id(&self) -> Option<u32> {
+        match self {
+            PluginRuntime::Process(pid) => Some(*pid),
+            _ => None,
+        }
+    }
+
+    /// 销毁插件实例
+    ///
+    /// # Safety
+    ///
+    /// `handler` 必须是由本运行时创建且尚未销毁的插件函数集合
+    pub unsafe fn destroy(&self, handler: *mut PluginInterface) {
+        match self {
+            PluginRuntime::Native(library) => {
+                let destroy_plugin: Result<Symbol<DestroyPluginFn>, _> =
+                    library.get(DESTROY_PLUGIN_SYMBOL);
+                if let Ok(destroy_fn) = destroy_plugin {
+                    destroy_fn(handler);
+                }
+            }
+            PluginRuntime::Hosted | PluginRuntime::Process(_) => {
+                if !handler.is_null() {
+                    ((*handler).destroy)((*handler).plugin_ptr);
+                    drop(Box::from_raw(handler));
+                }
+            }
+        }
+    }
+}
+
+/// 不加载动态库，检查其格式、架构与当前平台一致并导出了插件创建函数
+fn validate_native_library(path: &Path) -> Result<(), String> {
+    use object::{Object, ObjectKind};
+
+    let data = std::fs::read(path).map_err(|e| format!("读取动态库失败: {}", e))?;
+    let file = object::File::parse(&*data).map_err(|e| format!("无法识别的动态库格式: {}", e))?;
+    if file.format() != object::BinaryFormat::native_object() {
+        return Err(format!("动态库格式 {:?} 与当前平台不符", file.format()));
+    }
+    if file.kind() != ObjectKind::Dynamic {
+        return Err(format!("文件不是动态链接库: {:?}", path));
+    }
+    if let Some(architecture) = host_architecture() {
+        if file.architecture() != architecture {
+            return Err(format!(
+                "动态库架构 {:?} 与当前平台 {:?} 不符",
+                file.architecture(),
+                architecture
+            ));
+        }
+    }
+
+    let symbol = CREATE_PLUGIN_SYMBOL
+        .strip_suffix(b"\0")
+        .unwrap_or(CREATE_PLUGIN_SYMBOL);
+    let exports = file
+        .exports()
+        .map_err(|e| format!("读取动态库导出表失败: {}", e))?;
+    // Mach-O 的 C 符号带下划线前缀
+    let exported = exports.iter().any(|export| {
+        let name = export.name();
+        name == symbol || name.strip_prefix(b"_") == Some(symbol)
+    });
+    if exported {
+        Ok(())
+    } else {
+        Err("找不到插件创建函数: 动态库未导出 create_plugin".to_string())
+    }
+}
+
+fn host_architecture() -> Option<object::Architecture> {
+    if cfg!(target_arch = "x86_64") {
+        Some(object::Architecture::X86_64)
+    } else if cfg!(target_arch = "aarch64") {
+        Some(object::Architecture::Aarch64)
+    } else if cfg!(target_arch = "x86") {
+        Some(object::Architecture::I386)
+    } else {
+        None
+    }
+}
+
+/// 校验插件文件能否按声明的类型加载
+///
+/// 动态库只解析文件头和导出表，不会加载到宿主进程（加载会执行库的初始化代码）；
+/// 子进程插件只检查文件存在，不会启动进程。
+pub fn validate_plugin_file(kind: PluginKind, path: &Path) -> Result<(), String> {
+    match kind {
+        PluginKind::Native => validate_native_library(path),
+        #[cfg(feature = "wasm-plugins")]
+        PluginKind::Wasm => wasm::validate_component(path),
+        #[cfg(not(feature = "wasm-plugins"))]
+        PluginKind::Wasm => {
+            Err("当前版本未启用 WebAssembly 插件支持（wasm-plugins 特性）".to_string())
+        }
+        PluginKind::Subprocess => {
+            if path.is_file() {
+                Ok(())
+            } else {
+                Err(format!("插件程序文件不存在: {:?}", path))
+            }
+        }
+        #[cfg(feature = "script-plugins")]
+        PluginKind::Script => script::validate_script(path),
+        #[cfg(not(feature = "script-plugins"))]
+        PluginKind::Script => Err("当前版本未启用脚本插件支持（script-plugins 特性）".to_string()),
+        PluginKind::Remote => Err("远程插件没有插件文件".to_string()),
+        PluginKind::Builtin => Err("内置插件不能从文件安装".to_string()),
+    }
+}
@@ -0,0 +1,100 @@
+//! 远程插件
+//!
+//! 插件运行在另一台机器上的 `chat-client-plugin-runner` 中，宿主为每个插件实例建立一条 TCP 连接，
+//! 先以 `authenticate` 请求发送令牌，之后使用与子进程插件相同的 JSON-RPC 协议。
+//! 连接本身不加密，因此默认只允许连接本机回环地址，跨网络使用时应通过 SSH 隧道转发到本机端口；
+//! 只有在 `[remote]` 中显式设置 `insecure = true` 时才会直接连接其他主机。
+
+use super::rpc::{RpcPlugin, PROTOCOL_VERSION};
+use crate::plugins::config::{PluginConfig, RemoteConfig};
+use plugin_interfaces::PluginMetadata;
+use serde_json::json;
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// 连接运行器并完成认证
+pub fn connect(metadata: &PluginMetadata) -> Result<RpcPlugin, String> {
+    let config = PluginConfig::from_file(&metadata.config_path)
+        .map_err(|e| format!("读取插件配置失败: {}", e))?;
+    let remote = config
+        .remote
+        .ok_or_else(|| "插件配置缺少 [remote] 段".to_string())?;
+    let token = resolve_token(&remote)?;
+
+    let stream = connect_stream(&remote)?;
+    let _ = stream.set_nodelay(true);
+    let reader = stream
+        .try_clone()
+        .map_err(|e| format!("无法复制网络连接: {}", e))?;
+    let writer = stream
+        .try_clone()
+        .map_err(|e| format!("无法复制网络连接: {}", e))?;
+
+    let label = format!(
+        "{}@{}:{}",
+        metadata.id,
+        remote.address,
+        metadata.instance_id.as_deref().unwrap_or_default()
+    );
+    let plugin = RpcPlugin::start(
+        label,
+        reader,
+        writer,
+        Duration::from_secs(remote.timeout_secs),
+        Box::new(Connection(stream)),
+    );
+    plugin
+        .request(
+            "authenticate",
+            json!({
+                "token": token,
+                "plugin_id": metadata.id,
+                "protocol_version": PROTOCOL_VERSION,
+            }),
+        )
+        .map_err(|e| format!("远程插件 {} 认证失败: {}", remote.address, e))?;
+    Ok(plugin)
+}
+
+fn resolve_token(remote: &RemoteConfig) -> Result<String, String> {
+    if let Some(name) = &remote.token_env {
+        return std::env::var(name).map_err(|_| format!("环境变量 {} 未设置", name));
+    }
+    remote
+        .token
+        .clone()
+        .ok_or_else(|| "远程插件没有配置 token 或 token_env".to_string())
+}
+
+fn connect_stream(remote: &RemoteConfig) -> Result<TcpStream, String> {
+    let addresses: Vec<_> = remote
+        .address
+        .to_socket_addrs()
+        .map_err(|e| format!("无法解析地址 {}: {}", remote.address, e))?
+        .collect();
+    if !remote.insecure && addresses.iter().any(|address| !address.ip().is_loopback()) {
+        return Err(format!(
+            "远程插件地址 {} 不是本机回环地址。连接不加密，请通过 SSH 隧道转发到本机端口，\
+             或在 [remote] 中设置 insecure = true",
+            remote.address
+        ));
+    }
+    let timeout = Duration::from_secs(remote.connect_timeout_secs);
+    let mut last_error = format!("地址 {} 没有可用的 IP", remote.address);
+    for address in addresses {
+        match TcpStream::connect_timeout(&address, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = format!("连接 {} 失败: {}", address, e),
+        }
+    }
+    Err(last_error)
+}
+
+/// 网络连接，释放时关闭，运行器随之卸载对应的插件实例
+struct Connection(TcpStream);
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        let _ = self.0.shutdown(Shutdown::Both);
+    }
+}
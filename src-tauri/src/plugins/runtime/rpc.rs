@@ -0,0 +1,519 @@
+//! 按行分隔的 JSON-RPC 2.0 插件连接
+//!
+//! 子进程插件（stdin / stdout）和远程插件（TCP）使用同一套协议，协议说明见
+//! `docs/subprocess-plugins.md`。每个连接有一个读取线程和一个处理线程：读取线程只负责把响应
+//! 交给等待中的请求，对方发来的请求和通知按顺序交给处理线程，因此处理请求时可以再向对方发起请求。
+
+use super::ui::{update_declarative_ui, DeclarativeUi, UiEvent, UiNode};
+use plugin_interfaces::{
+    log_error, log_info, log_warn,
+    pluginui::{Context, Ui},
+    PluginHandler, PluginInstanceContext, StreamError,
+};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// 协议版本，在 `mount` 请求中告知插件
+pub const PROTOCOL_VERSION: u32 = 1;
+/// 一行消息（包括换行符）的最大字节数，超过时关闭连接
+pub const MAX_LINE_BYTES: usize = 8 * 1024 * 1024;
+
+/// JSON-RPC 错误码：方法不存在
+pub const METHOD_NOT_FOUND: i64 = -32601;
+/// JSON-RPC 错误码：参数错误
+pub const INVALID_PARAMS: i64 = -32602;
+/// 调用失败
+pub const CALL_FAILED: i64 = -32000;
+/// 流已被用户取消
+pub const STREAM_CANCELLED: i64 = -32001;
+/// 远程连接未通过认证
+pub const UNAUTHORIZED: i64 = -32002;
+
+/// 处理结果，错误为 (错误码, 消息)
+pub type CallResult = Result<Value, (i64, String)>;
+
+/// 对方发来的一次请求（有 `id`）或通知（没有 `id`）
+pub struct RpcCall {
+    pub id: Option<Value>,
+    pub method: String,
+    pub params: Value,
+}
+
+impl RpcCall {
+    /// 读取字符串参数
+    pub fn string_param(&self, name: &str) -> Result<String, (i64, String)> {
+        self.params
+            .get(name)
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| (INVALID_PARAMS, format!("缺少字符串参数 {}", name)))
+    }
+
+    /// 读取布尔参数，缺省时使用 `default`
+    pub fn bool_param(&self, name: &str, default: bool) -> bool {
+        self.params
+            .get(name)
+            .and_then(Value::as_bool)
+            .unwrap_or(default)
+    }
+}
+
+/// 处理对方发来的请求和通知
+pub trait RpcHandler: Send + Sync + 'static {
+    fn handle_call(&self, connection: &RpcConnection, call: &RpcCall) -> CallResult;
+
+    /// 对方关闭连接，且之前收到的请求都已处理
+    fn on_close(&self, _connection: &RpcConnection) {}
+}
+
+/// 读取一行（不含换行符），连接结束时返回 None；行超过 `MAX_LINE_BYTES` 或不是 UTF-8 时返回错误
+fn read_line<'a>(
+    reader: &mut impl BufRead,
+    buffer: &'a mut Vec<u8>,
+) -> Result<Option<&'a str>, String> {
+    buffer.clear();
+    let read = reader
+        .take(MAX_LINE_BYTES as u64 + 1)
+        .read_until(b'\n', buffer)
+        .map_err(|e| e.to_string())?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if buffer.len() > MAX_LINE_BYTES {
+        return Err(format!("消息超过 {} 字节", MAX_LINE_BYTES));
+    }
+    let line = std::str::from_utf8(buffer).map_err(|_| "消息不是有效的 UTF-8".to_string())?;
+    Ok(Some(line.trim_end_matches('\n').trim_end_matches('\r')))
+}
+
+/// 一条 JSON-RPC 连接
+pub struct RpcConnection {
+    /// 日志中使用的连接标识
+    label: String,
+    writer: Mutex<Option<Box<dyn Write + Send>>>,
+    next_id: AtomicU64,
+    /// 等待响应的请求
+    pending: Mutex<HashMap<u64, Sender<Result<Value, String>>>>,
+    closed: Mutex<bool>,
+}
+
+impl RpcConnection {
+    /// 在读写两端上建立连接，启动读取线程和处理线程
+    pub fn start(
+        label: String,
+        reader: impl Read + Send + 'static,
+        writer: impl Write + Send + 'static,
+        handler: Arc<dyn RpcHandler>,
+    ) -> Arc<Self> {
+        let connection = Arc::new(Self {
+            label,
+            writer: Mutex::new(Some(Box::new(writer))),
+            next_id: AtomicU64::new(1),
+            pending: Mutex::new(HashMap::new()),
+            closed: Mutex::new(false),
+        });
+
+        let (calls, queue) = mpsc::channel::<RpcCall>();
+        let worker = connection.clone();
+        std::thread::spawn(move || {
+            for call in queue {
+                let result = handler.handle_call(&worker, &call);
+                match call.id {
+                    Some(id) => worker.respond(id, result),
+                    None => {
+                        if let Err((_, e)) = result {
+                            log_warn!("[{}] 处理通知 {} 失败: {}", worker.label, call.method, e);
+                        }
+                    }
+                }
+            }
+            handler.on_close(&worker);
+        });
+
+        let reader_connection = connection.clone();
+        std::thread::spawn(move || {
+            let mut reader = BufReader::new(reader);
+            let mut buffer = Vec::new();
+            loop {
+                match read_line(&mut reader, &mut buffer) {
+                    Ok(None) => break,
+                    Ok(Some(line)) if line.trim().is_empty() => {}
+                    Ok(Some(line)) => reader_connection.dispatch(line, &calls),
+                    Err(e) => {
+                        log_warn!("[{}] 读取连接失败: {}", reader_connection.label, e);
+                        break;
+                    }
+                }
+            }
+            reader_connection.close();
+        });
+
+        connection
+    }
+
+    /// 连接标识
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    fn write(&self, message: &Value) -> Result<(), String> {
+        let mut writer = self.writer.lock().unwrap();
+        let writer = writer.as_mut().ok_or_else(|| "连接已关闭".to_string())?;
+        writeln!(writer, "{}", message)
+            .and_then(|_| writer.flush())
+            .map_err(|e| format!("写入连接失败: {}", e))
+    }
+
+    /// 关闭写入端，对方读到 EOF
+    pub fn close_writer(&self) {
+        self.writer.lock().unwrap().take();
+    }
+
+    /// 发送请求并等待响应
+    pub fn request(&self, method: &str, params: Value, timeout: Duration) -> Result<Value, String> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (sender, receiver) = mpsc::channel();
+        {
+            // 与 close 互斥，连接关闭后不再登记新的请求
+            let closed = self.closed.lock().unwrap();
+            if *closed {
+                return Err("连接已关闭".to_string());
+            }
+            self.pending.lock().unwrap().insert(id, sender);
+        }
+
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        if let Err(e) = self.write(&message) {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(e);
+        }
+
+        match receiver.recv_timeout(timeout) {
+            Ok(response) => response,
+            Err(RecvTimeoutError::Timeout) => {
+                self.pending.lock().unwrap().remove(&id);
+                Err(format!("等待 {} 的响应超时", method))
+            }
+            Err(RecvTimeoutError::Disconnected) => Err("连接已关闭".to_string()),
+        }
+    }
+
+    /// 发送通知，不等待响应
+    pub fn notify(&self, method: &str, params: Value) -> Result<(), String> {
+        self.write(&json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+    }
+
+    /// 对方的输出结束，让所有等待中的请求失败
+    fn close(&self) {
+        *self.closed.lock().unwrap() = true;
+        for (_, sender) in self.pending.lock().unwrap().drain() {
+            let _ = sender.send(Err("连接已关闭".to_string()));
+        }
+    }
+
+    fn respond(&self, id: Value, result: CallResult) {
+        let message = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message },
+            }),
+        };
+        if let Err(e) = self.write(&message) {
+            log_warn!("[{}] {}", self.label, e);
+        }
+    }
+
+    /// 处理读到的一行消息：响应交给等待中的请求，请求和通知交给处理线程
+    fn dispatch(&self, line: &str, calls: &Sender<RpcCall>) {
+        let message: Value = match serde_json::from_str(line) {
+            Ok(message) => message,
+            Err(e) => {
+                log_warn!("[{}] 无法解析消息: {} ({})", self.label, line, e);
+                return;
+            }
+        };
+
+        let id = message.get("id").cloned().filter(|id| !id.is_null());
+        match (message.get("method").and_then(Value::as_str), id) {
+            (Some(method), id) => {
+                let _ = calls.send(RpcCall {
+                    id,
+                    method: method.to_string(),
+                    params: message.get("params").cloned().unwrap_or(Value::Null),
+                });
+            }
+            (None, Some(id)) => {
+                let Some(id) = id.as_u64() else {
+                    log_warn!("[{}] 响应的 id 无效: {}", self.label, id);
+                    return;
+                };
+                let response = match message.get("error") {
+                    Some(error) => Err(error
+                        .get("message")
+                        .and_then(Value::as_str)
+                        .map(str::to_string)
+                        .unwrap_or_else(|| error.to_string())),
+                    None => Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+                };
+                match self.pending.lock().unwrap().remove(&id) {
+                    Some(sender) => {
+                        let _ = sender.send(response);
+                    }
+                    None => log_warn!("[{}] 收到未知请求 {} 的响应", self.label, id),
+                }
+            }
+            (None, None) => log_warn!("[{}] 无法识别的消息: {}", self.label, line),
+        }
+    }
+}
+
+/// 宿主一侧：把插件的请求映射到插件上下文
+#[derive(Default)]
+struct ContextHandler {
+    /// 挂载后一直保留，插件可以在宿主调用之外发送消息
+    context: Mutex<Option<PluginInstanceContext>>,
+}
+
+fn stream_error(error: StreamError) -> (i64, String) {
+    match error {
+        StreamError::StreamCancelled => (STREAM_CANCELLED, "cancelled".to_string()),
+        e => (CALL_FAILED, e.to_string()),
+    }
+}
+
+impl ContextHandler {
+    fn call_context(&self, call: &RpcCall) -> CallResult {
+        let context = self.context.lock().unwrap().clone();
+        let plugin_ctx = context.ok_or_else(|| {
+            (
+                CALL_FAILED,
+                "插件尚未挂载，没有可用的插件上下文".to_string(),
+            )
+        })?;
+        match call.method.as_str() {
+            "send_message" => {
+                let content = call.string_param("content")?;
+                Ok(Value::Bool(plugin_ctx.send_message_to_frontend(&content)))
+            }
+            "stream_start" => plugin_ctx
+                .send_message_stream_start()
+                .map(|stream_id| json!({ "stream_id": stream_id }))
+                .map_err(stream_error),
+            "stream_data" => {
+                let stream_id = call.string_param("stream_id")?;
+                let chunk = call.string_param("chunk")?;
+                plugin_ctx
+                    .send_message_stream(&stream_id, &chunk, call.bool_param("is_final", false))
+                    .map(|_| Value::Null)
+                    .map_err(stream_error)
+            }
+            "stream_end" => {
+                let stream_id = call.string_param("stream_id")?;
+                let error = call.params.get("error").and_then(Value::as_str);
+                plugin_ctx
+                    .send_message_stream_end(&stream_id, call.bool_param("success", true), error)
+                    .map(|_| Value::Null)
+                    .map_err(stream_error)
+            }
+            "get_history" => serde_json::to_value(plugin_ctx.get_history())
+                .map_err(|e| (CALL_FAILED, e.to_string())),
+            "refresh_ui" => Ok(Value::Bool(plugin_ctx.refresh_ui())),
+            _ => Err((METHOD_NOT_FOUND, format!("未知的方法: {}", call.method))),
+        }
+    }
+}
+
+impl RpcHandler for ContextHandler {
+    fn handle_call(&self, connection: &RpcConnection, call: &RpcCall) -> CallResult {
+        if call.method == "log" {
+            let message = call.string_param("message")?;
+            match call.params.get("level").and_then(Value::as_str) {
+                Some("warn") => log_warn!("[{}] {}", connection.label(), message),
+                Some("error") => log_error!("[{}] {}", connection.label(), message),
+                _ => log_info!("[{}] {}", connection.label(), message),
+            }
+            return Ok(Value::Null);
+        }
+
+        let result = self.call_context(call);
+        // 以通知发送的流数据没有响应，取消时另行通知插件
+        if call.id.is_none() {
+            if let Err((STREAM_CANCELLED, _)) = &result {
+                if let Some(stream_id) = call.params.get("stream_id") {
+                    let _ =
+                        connection.notify("stream_cancelled", json!({ "stream_id": stream_id }));
+                }
+            }
+        }
+        result
+    }
+}
+
+/// 在宿主中代表一个通过 JSON-RPC 连接的插件
+pub struct RpcPlugin {
+    connection: Arc<RpcConnection>,
+    handler: Arc<ContextHandler>,
+    timeout: Duration,
+    /// 连接的另一端为本机进程时的进程号，用于资源统计
+    process_id: Option<u32>,
+    /// 连接的另一端（子进程、网络连接），在写入端关闭后释放
+    _transport: Box<dyn Send + Sync>,
+}
+
+impl RpcPlugin {
+    /// 在读写两端上建立插件连接，`transport` 在插件销毁时释放
+    pub fn start(
+        label: String,
+        reader: impl Read + Send + 'static,
+        writer: impl Write + Send + 'static,
+        timeout: Duration,
+        transport: Box<dyn Send + Sync>,
+    ) -> Self {
+        let handler = Arc::new(ContextHandler::default());
+        let connection = RpcConnection::start(label, reader, writer, handler.clone());
+        Self {
+            connection,
+            handler,
+            timeout,
+            process_id: None,
+            _transport: transport,
+        }
+    }
+
+    /// 记录连接另一端的进程号
+    pub fn with_process_id(mut self, process_id: u32) -> Self {
+        self.process_id = Some(process_id);
+        self
+    }
+
+    /// 连接另一端的进程号，网络连接为 None
+    pub fn process_id(&self) -> Option<u32> {
+        self.process_id
+    }
+
+    /// 插件挂载前向对方发送请求（如远程连接的认证）
+    pub fn request(&self, method: &str, params: Value) -> Result<Value, String> {
+        self.connection.request(method, params, self.timeout)
+    }
+
+    fn call(
+        &self,
+        method: &str,
+        params: Value,
+        plugin_ctx: &PluginInstanceContext,
+    ) -> Result<Value, String> {
+        let mut context = self.handler.context.lock().unwrap();
+        if context.is_none() {
+            *context = Some(plugin_ctx.clone());
+        }
+        drop(context);
+        self.request(method, params)
+    }
+
+    /// 调用生命周期方法，插件返回的结果被忽略
+    fn call_lifecycle(
+        &self,
+        method: &str,
+        plugin_ctx: &PluginInstanceContext,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.call(method, Value::Null, plugin_ctx)?;
+        Ok(())
+    }
+}
+
+impl Drop for RpcPlugin {
+    fn drop(&mut self) {
+        // 先关闭写入端，对方读到 EOF 后退出，再释放连接的另一端
+        self.connection.close_writer();
+    }
+}
+
+impl DeclarativeUi for RpcPlugin {
+    fn render_ui(&mut self, plugin_ctx: &PluginInstanceContext) -> Result<Vec<UiNode>, String> {
+        let nodes = self.call("render_ui", Value::Null, plugin_ctx)?;
+        if nodes.is_null() {
+            return Ok(Vec::new());
+        }
+        serde_json::from_value(nodes).map_err(|e| format!("插件UI格式错误: {}", e))
+    }
+
+    fn on_ui_event(
+        &mut self,
+        event: &UiEvent,
+        plugin_ctx: &PluginInstanceContext,
+    ) -> Result<(), String> {
+        let params = serde_json::to_value(event).map_err(|e| e.to_string())?;
+        self.call("ui_event", params, plugin_ctx).map(|_| ())
+    }
+}
+
+impl PluginHandler for RpcPlugin {
+    fn update_ui(&mut self, _ctx: &Context, ui: &mut Ui, plugin_ctx: &PluginInstanceContext) {
+        update_declarative_ui(self, ui, plugin_ctx);
+    }
+
+    fn on_mount(
+        &mut self,
+        plugin_ctx: &PluginInstanceContext,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let metadata = plugin_ctx.get_metadata();
+        let params = json!({
+            "protocol_version": PROTOCOL_VERSION,
+            "metadata": {
+                "id": metadata.id,
+                "name": metadata.name,
+                "version": metadata.version,
+                "instance_id": metadata.instance_id,
+                "config_path": metadata.config_path,
+                "require_history": metadata.require_history,
+            },
+        });
+        self.call("mount", params, plugin_ctx)?;
+        Ok(())
+    }
+
+    fn on_dispose(
+        &mut self,
+        plugin_ctx: &PluginInstanceContext,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.call_lifecycle("dispose", plugin_ctx)
+    }
+
+    fn on_connect(
+        &mut self,
+        plugin_ctx: &PluginInstanceContext,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.call_lifecycle("connect", plugin_ctx)
+    }
+
+    fn on_disconnect(
+        &mut self,
+        plugin_ctx: &PluginInstanceContext,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.call_lifecycle("disconnect", plugin_ctx)
+    }
+
+    fn handle_message(
+        &mut self,
+        message: &str,
+        plugin_ctx: &PluginInstanceContext,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let params = json!({
+            "message": message,
+            "history": plugin_ctx.get_history(),
+        });
+        let reply = self.call("handle_message", params, plugin_ctx)?;
+        Ok(match reply {
+            Value::Null => String::new(),
+            Value::String(reply) => reply,
+            reply => reply.to_string(),
+        })
+    }
+}
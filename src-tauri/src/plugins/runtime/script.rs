@@ -0,0 +1,362 @@
+//! Rhai 脚本插件
+//!
+//! 插件是一个 `.rhai` 脚本，实现 `handle_message(message)`，以及可选的 `on_mount()`、`on_dispose()`、
+//! `on_connect()`、`on_disconnect()`、`render_ui()`、`on_ui_event(id, value)`。
+//! 脚本函数通过 `this` 访问实例状态（一个对象映射），状态在调用之间和重新加载之后保留。
+//! 宿主接口与 `PluginInstanceContext` 对应，见 `register_host_api`。
+//! 脚本文件被修改后，下一次调用前会自动重新编译。
+
+use super::ui::{update_declarative_ui, DeclarativeUi, UiEvent, UiNode};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use plugin_interfaces::{
+    log_error, log_info, log_warn,
+    pluginui::{Context, Ui},
+    PluginHandler, PluginInstanceContext, StreamError,
+};
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, Map, AST};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// 单次调用最多执行的操作数，防止脚本死循环阻塞宿主
+const MAX_OPERATIONS: u64 = 10_000_000;
+
+type SharedContext = Arc<Mutex<Option<PluginInstanceContext>>>;
+
+/// 编译脚本，检查是否实现了 `handle_message`
+pub fn validate_script(path: &Path) -> Result<(), String> {
+    let ast = compile(&Engine::new(), path)?;
+    if has_function(&ast, "handle_message", 1) {
+        Ok(())
+    } else {
+        Err("脚本没有实现 handle_message(message)".to_string())
+    }
+}
+
+fn compile(engine: &Engine, path: &Path) -> Result<AST, String> {
+    engine
+        .compile_file(path.to_path_buf())
+        .map_err(|e| format!("编译脚本 {:?} 失败: {}", path, e))
+}
+
+fn has_function(ast: &AST, name: &str, arity: usize) -> bool {
+    ast.iter_functions()
+        .any(|f| f.name == name && f.params.len() == arity)
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn with_context<R>(
+    context: &SharedContext,
+    f: impl FnOnce(&PluginInstanceContext) -> R,
+) -> Result<R, Box<EvalAltResult>> {
+    match context.lock().unwrap().as_ref() {
+        Some(plugin_ctx) => Ok(f(plugin_ctx)),
+        None => Err("没有可用的插件上下文".into()),
+    }
+}
+
+fn stream_result<T>(result: Result<T, StreamError>) -> Result<T, Box<EvalAltResult>> {
+    result.map_err(|e| match e {
+        StreamError::StreamCancelled => "cancelled".into(),
+        e => e.to_string().into(),
+    })
+}
+
+/// 注册脚本可用的宿主函数
+///
+/// - `send_message(content) -> bool`
+/// - `stream_start() -> string`、`stream_data(id, chunk, is_final)`、`stream_end(id, success[, error])`，
+///   流被取消时抛出 `"cancelled"`
+/// - `get_history() -> array | ()`、`refresh_ui() -> bool`、`metadata() -> map`
+/// - `log_info(text)`、`log_warn(text)`、`log_error(text)`，`print` 等同于 `log_info`
+fn register_host_api(engine: &mut Engine, context: &SharedContext, label: &str) {
+    let ctx = context.clone();
+    engine.register_fn("send_message", move |content: &str| {
+        with_context(&ctx, |plugin_ctx| {
+            plugin_ctx.send_message_to_frontend(content)
+        })
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("stream_start", move || {
+        with_context(&ctx, |plugin_ctx| {
+            stream_result(plugin_ctx.send_message_stream_start())
+        })?
+    });
+
+    let ctx = context.clone();
+    engine.register_fn(
+        "stream_data",
+        move |stream_id: &str, chunk: &str, is_final: bool| {
+            with_context(&ctx, |plugin_ctx| {
+                stream_result(plugin_ctx.send_message_stream(stream_id, chunk, is_final))
+            })?
+        },
+    );
+
+    let ctx = context.clone();
+    engine.register_fn("stream_end", move |stream_id: &str, success: bool| {
+        with_context(&ctx, |plugin_ctx| {
+            stream_result(plugin_ctx.send_message_stream_end(stream_id, success, None))
+        })?
+    });
+
+    let ctx = context.clone();
+    engine.register_fn(
+        "stream_end",
+        move |stream_id: &str, success: bool, error: &str| {
+            with_context(&ctx, |plugin_ctx| {
+                stream_result(plugin_ctx.send_message_stream_end(stream_id, success, Some(error)))
+            })?
+        },
+    );
+
+    let ctx = context.clone();
+    engine.register_fn("get_history", move || {
+        let history = with_context(&ctx, |plugin_ctx| plugin_ctx.get_history())?;
+        rhai::serde::to_dynamic(history)
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("refresh_ui", move || {
+        with_context(&ctx, |plugin_ctx| plugin_ctx.refresh_ui())
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("metadata", move || {
+        with_context(&ctx, |plugin_ctx| {
+            let metadata = plugin_ctx.get_metadata();
+            let mut map = Map::new();
+            map.insert("id".into(), metadata.id.clone().into());
+            map.insert("name".into(), metadata.name.clone().into());
+            map.insert("version".into(), metadata.version.clone().into());
+            map.insert(
+                "instance_id".into(),
+                metadata.instance_id.clone().unwrap_or_default().into(),
+            );
+            map.insert("config_path".into(), metadata.config_path.clone().into());
+            map
+        })
+    });
+
+    let label = label.to_string();
+    let info_label = label.clone();
+    engine.register_fn("log_info", move |text: &str| {
+        log_info!("[{}] {}", info_label, text)
+    });
+    let warn_label = label.clone();
+    engine.register_fn("log_warn", move |text: &str| {
+        log_warn!("[{}] {}", warn_label, text)
+    });
+    let error_label = label.clone();
+    engine.register_fn("log_error", move |text: &str| {
+        log_error!("[{}] {}", error_label, text)
+    });
+    engine.on_print(move |text| log_info!("[{}] {}", label, text));
+}
+
+/// 在宿主中代表一个脚本实例的插件
+pub struct ScriptPlugin {
+    engine: Engine,
+    path: PathBuf,
+    ast: AST,
+    /// 已编译版本的修改时间，用于发现脚本的修改
+    modified: Option<SystemTime>,
+    /// 脚本函数中的 `this`
+    state: Dynamic,
+    context: SharedContext,
+    /// 脚本被修改时通知前端刷新 UI，刷新时会重新编译脚本
+    _watcher: Option<Mutex<RecommendedWatcher>>,
+}
+
+impl ScriptPlugin {
+    /// 编译脚本，每个插件实例拥有独立的引擎和状态
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let label = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let context: SharedContext = Arc::new(Mutex::new(None));
+
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        register_host_api(&mut engine, &context, &label);
+
+        let modified = modified_time(path);
+        let ast = compile(&engine, path)?;
+        if !has_function(&ast, "handle_message", 1) {
+            return Err("脚本没有实现 handle_message(message)".to_string());
+        }
+
+        Ok(Self {
+            engine,
+            path: path.to_path_buf(),
+            ast,
+            modified,
+            state: Dynamic::from_map(Map::new()),
+            _watcher: watch_script(path, &context),
+            context,
+        })
+    }
+
+    /// 脚本文件有修改时重新编译，编译失败则继续使用旧版本
+    fn reload_if_changed(&mut self) {
+        let modified = modified_time(&self.path);
+        if modified == self.modified {
+            return;
+        }
+        self.modified = modified;
+        match compile(&self.engine, &self.path) {
+            Ok(ast) => {
+                self.ast = ast;
+                log_info!("脚本插件 {:?} 已重新加载", self.path);
+            }
+            Err(e) => log_error!("{}，继续使用之前的版本", e),
+        }
+    }
+
+    /// 调用脚本函数，脚本没有定义该函数时返回 `None`
+    fn call(
+        &mut self,
+        plugin_ctx: &PluginInstanceContext,
+        name: &str,
+        args: Vec<Dynamic>,
+    ) -> Result<Option<Dynamic>, String> {
+        self.reload_if_changed();
+        if !has_function(&self.ast, name, args.len()) {
+            return Ok(None);
+        }
+        *self.context.lock().unwrap() = Some(plugin_ctx.clone());
+
+        let options = CallFnOptions::new()
+            .eval_ast(false)
+            .bind_this_ptr(&mut self.state);
+        self.engine
+            .call_fn_with_options::<Dynamic>(
+                options,
+                &mut rhai::Scope::new(),
+                &self.ast,
+                name,
+                args,
+            )
+            .map(Some)
+            .map_err(|e| format!("脚本函数 {} 执行失败: {}", name, e))
+    }
+
+    fn call_hook(
+        &mut self,
+        plugin_ctx: &PluginInstanceContext,
+        name: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.call(plugin_ctx, name, Vec::new())?;
+        Ok(())
+    }
+}
+
+/// 监视脚本文件，修改后请求前端刷新插件 UI
+fn watch_script(path: &Path, context: &SharedContext) -> Option<Mutex<RecommendedWatcher>> {
+    let dir = path.parent()?.to_path_buf();
+    let file_name = path.file_name()?.to_os_string();
+    let context = context.clone();
+    let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else { return };
+        // 编辑器常以新建再重命名的方式保存，同时关注创建和修改
+        if !matches!(event.kind, EventKind::Modify(_) | EventKind::Create(_))
+            || !event
+                .paths
+                .iter()
+                .any(|p| p.file_name() == Some(file_name.as_os_str()))
+        {
+            return;
+        }
+        if let Some(plugin_ctx) = context.lock().unwrap().as_ref() {
+            plugin_ctx.refresh_ui();
+        }
+    });
+
+    match watcher {
+        Ok(mut watcher) => match watcher.watch(&dir, RecursiveMode::NonRecursive) {
+            Ok(()) => Some(Mutex::new(watcher)),
+            Err(e) => {
+                log_warn!("无法监视脚本目录 {:?}: {}", dir, e);
+                None
+            }
+        },
+        Err(e) => {
+            log_warn!("无法监视脚本 {:?}: {}", path, e);
+            None
+        }
+    }
+}
+
+impl DeclarativeUi for ScriptPlugin {
+    fn render_ui(&mut self, plugin_ctx: &PluginInstanceContext) -> Result<Vec<UiNode>, String> {
+        match self.call(plugin_ctx, "render_ui", Vec::new())? {
+            Some(nodes) if !nodes.is_unit() => {
+                rhai::serde::from_dynamic(&nodes).map_err(|e| format!("插件UI格式错误: {}", e))
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    fn on_ui_event(
+        &mut self,
+        event: &UiEvent,
+        plugin_ctx: &PluginInstanceContext,
+    ) -> Result<(), String> {
+        let args = vec![event.id.clone().into(), event.value.clone().into()];
+        self.call(plugin_ctx, "on_ui_event", args).map(|_| ())
+    }
+}
+
+impl PluginHandler for ScriptPlugin {
+    fn update_ui(&mut self, _ctx: &Context, ui: &mut Ui, plugin_ctx: &PluginInstanceContext) {
+        update_declarative_ui(self, ui, plugin_ctx);
+    }
+
+    fn on_mount(
+        &mut self,
+        plugin_ctx: &PluginInstanceContext,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.call_hook(plugin_ctx, "on_mount")
+    }
+
+    fn on_dispose(
+        &mut self,
+        plugin_ctx: &PluginInstanceContext,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.call_hook(plugin_ctx, "on_dispose")
+    }
+
+    fn on_connect(
+        &mut self,
+        plugin_ctx: &PluginInstanceContext,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.call_hook(plugin_ctx, "on_connect")
+    }
+
+    fn on_disconnect(
+        &mut self,
+        plugin_ctx: &PluginInstanceContext,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.call_hook(plugin_ctx, "on_disconnect")
+    }
+
+    fn handle_message(
+        &mut self,
+        message: &str,
+        plugin_ctx: &PluginInstanceContext,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let reply = self
+            .call(plugin_ctx, "handle_message", vec![message.into()])?
+            .ok_or("脚本没有实现 handle_message(message)")?;
+        Ok(if reply.is_unit() {
+            String::new()
+        } else {
+            reply.to_string()
+        })
+    }
+}
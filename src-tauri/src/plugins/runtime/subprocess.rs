@@ -0,0 +1,119 @@
+//! 子进程插件
+//!
+//! 插件是任意语言编写的可执行程序（或由解释器运行的脚本），宿主为每个插件实例启动一个子进程，
+//! 通过 stdin / stdout 上按行分隔的 JSON-RPC 2.0 消息通信，stderr 的输出写入宿主日志。
+//! 协议说明见 `docs/subprocess-plugins.md`。
+
+use super::rpc::RpcPlugin;
+use crate::plugins::config::PluginConfig;
+use crate::plugins::sandbox::{find_program, SandboxPolicy};
+use plugin_interfaces::{log_warn, PluginMetadata};
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 卸载时等待子进程自行退出的时间
+const EXIT_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+/// 按插件配置启动子进程
+///
+/// 设置了 `[subprocess] command` 时以插件文件为第一个参数运行该命令（如 `python3 plugin.py`），
+/// 否则直接执行插件文件。工作目录为插件目录。启用了 `[sandbox]` 时只允许执行插件文件或解释器。
+pub fn spawn(path: &Path, metadata: &PluginMetadata) -> Result<RpcPlugin, String> {
+    let config = PluginConfig::from_file(&metadata.config_path)
+        .map_err(|e| format!("读取插件配置失败: {}", e))?;
+    let settings = config.subprocess.unwrap_or_default();
+
+    let mut command = match &settings.command {
+        Some(program) => {
+            let mut command = Command::new(program);
+            command.arg(path);
+            command
+        }
+        None => Command::new(path),
+    };
+    command.args(&settings.args).envs(&settings.env);
+    if let Some(plugin_dir) = Path::new(&metadata.config_path).parent() {
+        command.current_dir(plugin_dir);
+    }
+
+    if let Some(mut policy) = SandboxPolicy::for_plugin(metadata)? {
+        let program = match &settings.command {
+            Some(program) => find_program(&policy.plugin_dir, program)
+                .ok_or_else(|| format!("找不到解释器 {}", program))?,
+            None => path.to_path_buf(),
+        };
+        policy.executables.push(program);
+        policy.apply(&mut command)?;
+    }
+
+    let label = format!(
+        "{}:{}",
+        metadata.id,
+        metadata.instance_id.as_deref().unwrap_or_default()
+    );
+    launch(command, label, Duration::from_secs(settings.timeout_secs))
+}
+
+/// 启动进程，通过其标准输入输出建立 JSON-RPC 连接，标准错误写入宿主日志
+pub(super) fn launch(
+    mut command: Command,
+    label: String,
+    timeout: Duration,
+) -> Result<RpcPlugin, String> {
+    command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("启动插件进程 {:?} 失败: {}", command.get_program(), e))?;
+
+    let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+        let _ = child.kill();
+        return Err("无法连接插件进程的标准输入输出".to_string());
+    };
+    if let Some(stderr) = child.stderr.take() {
+        let label = label.clone();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                log_warn!("[{}] {}", label, line);
+            }
+        });
+    }
+
+    let process_id = child.id();
+    let process = ChildProcess {
+        child: Mutex::new(child),
+        label: label.clone(),
+    };
+    Ok(
+        RpcPlugin::start(label, stdout, stdin, timeout, Box::new(process))
+            .with_process_id(process_id),
+    )
+}
+
+/// 插件进程，释放时等待其退出，超时则强制结束
+struct ChildProcess {
+    child: Mutex<Child>,
+    label: String,
+}
+
+impl Drop for ChildProcess {
+    fn drop(&mut self) {
+        // stdin 已经关闭，插件读到 EOF 后应当退出
+        let child = self.child.get_mut().unwrap();
+        let deadline = Instant::now() + EXIT_GRACE_PERIOD;
+        while Instant::now() < deadline {
+            match child.try_wait() {
+                Ok(Some(_)) | Err(_) => return,
+                Ok(None) => std::thread::sleep(Duration::from_millis(20)),
+            }
+        }
+        log_warn!("[{}] 插件进程没有按时退出，强制结束", self.label);
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
@@ -0,0 +1,155 @@
+use plugin_interfaces::{log_error, pluginui::Ui, PluginInstanceContext};
+use serde::{Deserialize, Serialize};
+
+/// 声明式 UI 节点
+///
+/// 原生插件直接调用 `Ui` 的即时模式接口；其他类型的插件每次返回完整的节点列表，
+/// 由宿主渲染到 `Ui` 并把组件事件按节点 `id` 回传给插件。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum UiNode {
+    Label {
+        text: String,
+    },
+    Button {
+        id: String,
+        text: String,
+    },
+    TextEdit {
+        id: String,
+        #[serde(default)]
+        value: String,
+    },
+    ComboBox {
+        id: String,
+        options: Vec<String>,
+        #[serde(default)]
+        selected: Option<String>,
+        #[serde(default)]
+        placeholder: String,
+    },
+    Toggle {
+        id: String,
+        #[serde(default)]
+        value: bool,
+    },
+    Horizontal {
+        children: Vec<UiNode>,
+    },
+}
+
+/// 组件事件：按钮的值为空字符串，文本框为新文本，下拉框为选中项，开关为 "true" / "false"
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UiEvent {
+    pub id: String,
+    pub value: String,
+}
+
+/// 使用声明式 UI 的插件
+pub trait DeclarativeUi {
+    /// 返回当前的 UI 节点列表
+    fn render_ui(&mut self, plugin_ctx: &PluginInstanceContext) -> Result<Vec<UiNode>, String>;
+
+    /// 处理组件事件
+    fn on_ui_event(
+        &mut self,
+        event: &UiEvent,
+        plugin_ctx: &PluginInstanceContext,
+    ) -> Result<(), String>;
+}
+
+/// 实现 `PluginHandler::update_ui`：渲染插件的节点并分发本次的组件事件，
+/// 有事件时插件状态可能已经改变，重新获取节点再渲染一次
+pub fn update_declarative_ui(
+    plugin: &mut impl DeclarativeUi,
+    ui: &mut Ui,
+    plugin_ctx: &PluginInstanceContext,
+) {
+    let nodes = match plugin.render_ui(plugin_ctx) {
+        Ok(nodes) => nodes,
+        Err(e) => {
+            log_error!("获取插件UI失败: {}", e);
+            ui.label(&format!("插件UI加载失败: {}", e));
+            return;
+        }
+    };
+
+    let mut events = Vec::new();
+    render_nodes(ui, &nodes, &mut events);
+    if events.is_empty() {
+        return;
+    }
+
+    for event in &events {
+        if let Err(e) = plugin.on_ui_event(event, plugin_ctx) {
+            log_error!("插件处理UI事件 {} 失败: {}", event.id, e);
+        }
+    }
+
+    ui.clear_components_only();
+    match plugin.render_ui(plugin_ctx) {
+        // 事件仍保留在 Ui 中，第二次渲染产生的事件已经处理过，忽略
+        Ok(nodes) => render_nodes(ui, &nodes, &mut Vec::new()),
+        Err(e) => {
+            log_error!("获取插件UI失败: {}", e);
+            ui.label(&format!("插件UI加载失败: {}", e));
+        }
+    }
+}
+
+/// 把节点渲染到 `ui`，收集本次触发的组件事件
+fn render_nodes(ui: &mut Ui, nodes: &[UiNode], events: &mut Vec<UiEvent>) {
+    for node in nodes {
+        match node {
+            UiNode::Label { text } => {
+                ui.label(text);
+            }
+            UiNode::Button { id, text } => {
+                if ui.button(text).clicked() {
+                    events.push(UiEvent {
+                        id: id.clone(),
+                        value: String::new(),
+                    });
+                }
+            }
+            UiNode::TextEdit { id, value } => {
+                let mut value = value.clone();
+                if ui.text_edit_singleline(&mut value).changed() {
+                    events.push(UiEvent {
+                        id: id.clone(),
+                        value,
+                    });
+                }
+            }
+            UiNode::ComboBox {
+                id,
+                options,
+                selected,
+                placeholder,
+            } => {
+                let mut selected = selected.clone();
+                if ui
+                    .combo_box(options.clone(), &mut selected, placeholder)
+                    .clicked()
+                {
+                    events.push(UiEvent {
+                        id: id.clone(),
+                        value: selected.unwrap_or_default(),
+                    });
+                }
+            }
+            UiNode::Toggle { id, value } => {
+                let mut value = *value;
+                if ui.toggle(&mut value).changed() {
+                    events.push(UiEvent {
+                        id: id.clone(),
+                        value: value.to_string(),
+                    });
+                }
+            }
+            UiNode::Horizontal { children } => {
+                ui.horizontal(|ui| render_nodes(ui, children, events));
+            }
+        }
+    }
+}
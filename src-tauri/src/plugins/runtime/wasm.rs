@@ -0,0 +1,282 @@
+//! WebAssembly 组件插件
+//!
+//! 插件编译为实现 `wit/plugin.wit` 中 `plugin` world 的 WebAssembly 组件，同一个 `.wasm`
+//! 文件可以在所有平台上运行。组件只能使用宿主提供的 `host` 接口，没有文件系统、网络等 WASI 能力，
+//! 内存用量受 `MAX_MEMORY_BYTES` 限制，每次调用最多执行 `MAX_FUEL` 条指令。
+
+use super::ui::{update_declarative_ui, DeclarativeUi, UiEvent, UiNode};
+use plugin_interfaces::{
+    log_error, log_info, log_warn,
+    pluginui::{Context, Ui},
+    PluginHandler, PluginInstanceContext, StreamError,
+};
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use wasmtime::component::{Component, Linker};
+use wasmtime::{Config, Engine, Store, StoreLimits, StoreLimitsBuilder, Trap};
+
+wasmtime::component::bindgen!({
+    path: "wit/plugin.wit",
+    world: "plugin",
+});
+
+use chat_client::plugin::host::{self, LogLevel};
+
+/// 单个插件实例可使用的最大线性内存
+const MAX_MEMORY_BYTES: usize = 256 * 1024 * 1024;
+/// 实例化和每次调用导出函数可消耗的燃料（约等于执行的指令数），防止死循环阻塞宿主
+const MAX_FUEL: u64 = 1_000_000_000;
+
+/// 所有 WebAssembly 插件共用的引擎
+fn engine() -> &'static Engine {
+    static ENGINE: OnceLock<Engine> = OnceLock::new();
+    ENGINE.get_or_init(|| {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        Engine::new(&config).expect("创建 WebAssembly 引擎失败")
+    })
+}
+
+/// 编译组件，检查它是否实现了插件接口
+pub fn validate_component(path: &Path) -> Result<(), String> {
+    let component = compile(path)?;
+    let mut store = new_store();
+    Plugin::instantiate(&mut store, &component, &linker()?)
+        .map(|_| ())
+        .map_err(|e| format!("WebAssembly 组件没有实现插件接口: {}", e))
+}
+
+fn compile(path: &Path) -> Result<Component, String> {
+    Component::from_file(engine(), path).map_err(|e| format!("加载 WebAssembly 组件失败: {}", e))
+}
+
+fn linker() -> Result<Linker<WasmState>, String> {
+    let mut linker = Linker::new(engine());
+    Plugin::add_to_linker(&mut linker, |state: &mut WasmState| state)
+        .map_err(|e| format!("注册宿主接口失败: {}", e))?;
+    Ok(linker)
+}
+
+fn new_store() -> Store<WasmState> {
+    let mut store = Store::new(
+        engine(),
+        WasmState {
+            context: Mutex::new(None),
+            limits: StoreLimitsBuilder::new()
+                .memory_size(MAX_MEMORY_BYTES)
+                .build(),
+        },
+    );
+    store.limiter(|state| &mut state.limits);
+    refuel(&mut store);
+    store
+}
+
+fn refuel(store: &mut Store<WasmState>) {
+    // 引擎开启了燃料计量，设置不会失败
+    let _ = store.set_fuel(MAX_FUEL);
+}
+
+/// 组件执行失败的原因，燃料耗尽时给出明确的提示
+fn execution_error(error: wasmtime::Error) -> String {
+    if error.downcast_ref::<Trap>() == Some(&Trap::OutOfFuel) {
+        return format!("WebAssembly 插件单次调用超过执行上限 {} 条指令", MAX_FUEL);
+    }
+    format!("WebAssembly 插件执行失败: {}", error)
+}
+
+/// 组件实例的宿主状态
+struct WasmState {
+    /// 当前调用的插件上下文，只在宿主调用组件导出函数期间存在
+    context: Mutex<Option<PluginInstanceContext>>,
+    limits: StoreLimits,
+}
+
+impl WasmState {
+    fn with_context<R>(&self, f: impl FnOnce(&PluginInstanceContext) -> R) -> Option<R> {
+        let context = self.context.lock().unwrap();
+        match context.as_ref() {
+            Some(plugin_ctx) => Some(f(plugin_ctx)),
+            None => {
+                log_warn!("WebAssembly 插件在宿主调用之外使用了宿主接口");
+                None
+            }
+        }
+    }
+}
+
+fn stream_error(error: StreamError) -> String {
+    match error {
+        StreamError::StreamCancelled => "cancelled".to_string(),
+        e => e.to_string(),
+    }
+}
+
+impl host::Host for WasmState {
+    fn log(&mut self, level: LogLevel, message: String) {
+        match level {
+            LogLevel::Info => log_info!("[wasm] {}", message),
+            LogLevel::Warn => log_warn!("[wasm] {}", message),
+            LogLevel::Error => log_error!("[wasm] {}", message),
+        }
+    }
+
+    fn send_message(&mut self, content: String) -> bool {
+        self.with_context(|plugin_ctx| plugin_ctx.send_message_to_frontend(&content))
+            .unwrap_or(false)
+    }
+
+    fn stream_start(&mut self) -> Result<String, String> {
+        self.with_context(|plugin_ctx| plugin_ctx.send_message_stream_start().map_err(stream_error))
+            .unwrap_or_else(|| Err("没有可用的插件上下文".to_string()))
+    }
+
+    fn stream_data(
+        &mut self,
+        stream_id: String,
+        chunk: String,
+        is_final: bool,
+    ) -> Result<(), String> {
+        self.with_context(|plugin_ctx| {
+            plugin_ctx
+                .send_message_stream(&stream_id, &chunk, is_final)
+                .map_err(stream_error)
+        })
+        .unwrap_or_else(|| Err("没有可用的插件上下文".to_string()))
+    }
+
+    fn stream_end(
+        &mut self,
+        stream_id: String,
+        success: bool,
+        error: Option<String>,
+    ) -> Result<(), String> {
+        self.with_context(|plugin_ctx| {
+            plugin_ctx
+                .send_message_stream_end(&stream_id, success, error.as_deref())
+                .map_err(stream_error)
+        })
+        .unwrap_or_else(|| Err("没有可用的插件上下文".to_string()))
+    }
+
+    fn get_history(&mut self) -> Option<String> {
+        self.with_context(|plugin_ctx| plugin_ctx.get_history())
+            .flatten()
+            .and_then(|history| serde_json::to_string(&history).ok())
+    }
+
+    fn refresh_ui(&mut self) -> bool {
+        self.with_context(|plugin_ctx| plugin_ctx.refresh_ui())
+            .unwrap_or(false)
+    }
+}
+
+/// 在宿主中代表一个 WebAssembly 组件实例的插件
+pub struct WasmPlugin {
+    store: Store<WasmState>,
+    bindings: Plugin,
+}
+
+impl WasmPlugin {
+    /// 编译并实例化组件，每个插件实例拥有独立的组件实例和内存
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let component = compile(path)?;
+        let mut store = new_store();
+        let bindings = Plugin::instantiate(&mut store, &component, &linker()?)
+            .map_err(|e| format!("实例化 WebAssembly 组件失败: {}", e))?;
+        Ok(Self { store, bindings })
+    }
+
+    /// 在插件上下文中调用组件的导出函数
+    fn call<R>(
+        &mut self,
+        plugin_ctx: &PluginInstanceContext,
+        f: impl FnOnce(&Plugin, &mut Store<WasmState>) -> wasmtime::Result<R>,
+    ) -> Result<R, String> {
+        *self.store.data().context.lock().unwrap() = Some(plugin_ctx.clone());
+        refuel(&mut self.store);
+        let result = f(&self.bindings, &mut self.store);
+        *self.store.data().context.lock().unwrap() = None;
+        result.map_err(execution_error)
+    }
+
+    /// 调用返回 `result<_, string>` 的生命周期函数
+    fn call_lifecycle(
+        &mut self,
+        plugin_ctx: &PluginInstanceContext,
+        f: impl FnOnce(&Plugin, &mut Store<WasmState>) -> wasmtime::Result<Result<(), String>>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(self.call(plugin_ctx, f)??)
+    }
+}
+
+impl DeclarativeUi for WasmPlugin {
+    fn render_ui(&mut self, plugin_ctx: &PluginInstanceContext) -> Result<Vec<UiNode>, String> {
+        let json = self.call(plugin_ctx, |plugin, store| plugin.call_render_ui(store))?;
+        serde_json::from_str(&json).map_err(|e| format!("插件UI格式错误: {}", e))
+    }
+
+    fn on_ui_event(
+        &mut self,
+        event: &UiEvent,
+        plugin_ctx: &PluginInstanceContext,
+    ) -> Result<(), String> {
+        self.call(plugin_ctx, |plugin, store| {
+            plugin.call_on_ui_event(store, &event.id, &event.value)
+        })
+    }
+}
+
+impl PluginHandler for WasmPlugin {
+    fn update_ui(&mut self, _ctx: &Context, ui: &mut Ui, plugin_ctx: &PluginInstanceContext) {
+        update_declarative_ui(self, ui, plugin_ctx);
+    }
+
+    fn on_mount(
+        &mut self,
+        plugin_ctx: &PluginInstanceContext,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let metadata = plugin_ctx.get_metadata();
+        let metadata = host::PluginMetadata {
+            id: metadata.id.clone(),
+            name: metadata.name.clone(),
+            version: metadata.version.clone(),
+            instance_id: metadata.instance_id.clone().unwrap_or_default(),
+            config_path: metadata.config_path.clone(),
+        };
+        self.call_lifecycle(plugin_ctx, |plugin, store| {
+            plugin.call_on_mount(store, &metadata)
+        })
+    }
+
+    fn on_dispose(
+        &mut self,
+        plugin_ctx: &PluginInstanceContext,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.call_lifecycle(plugin_ctx, |plugin, store| plugin.call_on_dispose(store))
+    }
+
+    fn on_connect(
+        &mut self,
+        plugin_ctx: &PluginInstanceContext,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.call_lifecycle(plugin_ctx, |plugin, store| plugin.call_on_connect(store))
+    }
+
+    fn on_disconnect(
+        &mut self,
+        plugin_ctx: &PluginInstanceContext,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.call_lifecycle(plugin_ctx, |plugin, store| plugin.call_on_disconnect(store))
+    }
+
+    fn handle_message(
+        &mut self,
+        message: &str,
+        plugin_ctx: &PluginInstanceContext,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        Ok(self.call(plugin_ctx, |plugin, store| {
+            plugin.call_handle_message(store, message)
+        })??)
+    }
+}
@@ -0,0 +1,414 @@
+//! 进程外插件的沙箱
+//!
+//! 插件配置中 `[sandbox] enabled = true` 时，子进程插件直接在沙箱中启动；原生、WebAssembly
+//! 和脚本插件改为在 `chat-client-plugin-runner --stdio` 辅助进程中运行，辅助进程在沙箱中启动。
+//!
+//! 沙箱仅支持 Linux，在子进程执行插件程序之前生效：
+//! - no_new_privs：不能通过 setuid 程序获得更高权限
+//! - Landlock：只能读取插件目录、系统库和声明的路径，只能写入插件数据目录和声明的路径，
+//!   只能执行插件程序本身（或其解释器）和 `subprocess` 权限中的程序
+//! - seccomp：拒绝调试其他进程、挂载、加载内核模块、创建命名空间等系统调用，
+//!   没有 `network` 权限时拒绝创建 Unix 域以外的套接字
+//!
+//! 沙箱按用户批准的 `[permissions]` 授权：`filesystem` 和 `serial` 中的路径可以读写，
+//! `subprocess` 中的程序可以执行，申请了 `network` 才能访问网络。`[sandbox]` 中的
+//! `read`、`write` 和 `network` 只能在权限范围内使用，超出的条目被忽略。
+//!
+//! 内核不支持 Landlock 时拒绝启动插件，而不是在没有限制的情况下运行。
+
+use crate::plugins::config::PluginConfig;
+use crate::plugins::directories::get_plugin_data_directory;
+use crate::plugins::permissions::wildcard_match;
+use plugin_interfaces::{log_warn, PluginMetadata};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// 传给沙箱中插件的数据目录环境变量
+pub const PLUGIN_DATA_DIR_ENV: &str = "CHAT_CLIENT_PLUGIN_DATA_DIR";
+
+/// 运行程序所需的系统目录和文件，只读
+const SYSTEM_READ_PATHS: &[&str] = &[
+    "/usr",
+    "/lib",
+    "/lib32",
+    "/lib64",
+    "/bin",
+    "/sbin",
+    "/nix/store",
+    "/etc/ld.so.cache",
+    "/etc/ld.so.conf",
+    "/etc/ld.so.conf.d",
+    "/etc/localtime",
+    "/etc/ssl",
+    "/etc/pki",
+    "/etc/ca-certificates",
+    "/etc/resolv.conf",
+    "/etc/hosts",
+    "/etc/host.conf",
+    "/etc/gai.conf",
+    "/etc/nsswitch.conf",
+    "/proc",
+    "/sys/devices/system/cpu",
+    "/sys/fs/cgroup",
+];
+
+/// 动态链接器所在目录，需要执行权限
+const SYSTEM_EXEC_PATHS: &[&str] = &["/lib", "/lib32", "/lib64", "/usr/lib", "/usr/lib64"];
+
+/// 可以读写的设备文件
+const DEVICE_PATHS: &[&str] = &[
+    "/dev/null",
+    "/dev/zero",
+    "/dev/full",
+    "/dev/random",
+    "/dev/urandom",
+];
+
+static HELPER_PROCESS: AtomicBool = AtomicBool::new(false);
+
+/// 标记当前进程是沙箱辅助进程，辅助进程中加载插件时不再启动新的辅助进程
+pub fn mark_helper_process() {
+    HELPER_PROCESS.store(true, Ordering::SeqCst);
+}
+
+/// 当前进程是否是沙箱辅助进程
+pub fn is_helper_process() -> bool {
+    HELPER_PROCESS.load(Ordering::SeqCst)
+}
+
+/// 由插件配置得到的沙箱策略
+#[derive(Debug, Clone)]
+pub struct SandboxPolicy {
+    /// 插件目录
+    pub plugin_dir: PathBuf,
+    /// 插件数据目录，可读写，通过环境变量 `CHAT_CLIENT_PLUGIN_DATA_DIR` 告知插件
+    pub data_dir: PathBuf,
+    /// 允许执行的文件，如插件程序和解释器
+    pub executables: Vec<PathBuf>,
+    /// 只读路径（含子目录）
+    pub read_paths: Vec<PathBuf>,
+    /// 读写路径（含子目录），其中的文件不能执行
+    pub write_paths: Vec<PathBuf>,
+    /// 只能列出内容的目录
+    pub list_paths: Vec<PathBuf>,
+    /// 是否允许访问网络
+    pub network: bool,
+}
+
+impl SandboxPolicy {
+    /// 读取插件配置中的 `[sandbox]` 和 `[permissions]`，未启用沙箱时返回 None
+    ///
+    /// 插件挂载前宿主已经检查过 `[permissions]` 已获批准。
+    pub fn for_plugin(metadata: &PluginMetadata) -> Result<Option<Self>, String> {
+        // 内置插件没有配置文件
+        let Ok(config) = PluginConfig::from_file(&metadata.config_path) else {
+            return Ok(None);
+        };
+        let Some(sandbox) = config.sandbox.filter(|sandbox| sandbox.enabled) else {
+            return Ok(None);
+        };
+
+        let plugin_dir = Path::new(&metadata.config_path)
+            .parent()
+            .and_then(|dir| dir.canonicalize().ok())
+            .ok_or_else(|| format!("无法访问插件目录: {}", metadata.config_path))?;
+        let data_dir = get_plugin_data_directory(&metadata.id);
+        std::fs::create_dir_all(&data_dir)
+            .map_err(|e| format!("创建插件数据目录 {:?} 失败: {}", data_dir, e))?;
+
+        let permissions = &config.permissions;
+        let declared = |paths: &[String]| -> Vec<PathBuf> {
+            paths
+                .iter()
+                .filter(|path| {
+                    let allowed = permissions.allows_path(path);
+                    if !allowed {
+                        log_warn!(
+                            "插件 {} 沙箱中声明的路径 {} 不在申请的权限范围内，已忽略",
+                            metadata.id,
+                            path
+                        );
+                    }
+                    allowed
+                })
+                .flat_map(|path| expand_path(&metadata.id, &plugin_dir, path))
+                .collect()
+        };
+        let granted = |paths: &[String]| -> Vec<PathBuf> {
+            paths
+                .iter()
+                .flat_map(|path| expand_path(&metadata.id, &plugin_dir, path))
+                .collect()
+        };
+
+        let mut read_paths = vec![plugin_dir.clone()];
+        read_paths.extend(declared(&sandbox.read));
+        let mut write_paths = vec![data_dir.clone()];
+        write_paths.extend(declared(&sandbox.write));
+        write_paths.extend(granted(&permissions.filesystem));
+        write_paths.extend(granted(&permissions.serial));
+
+        let executables = permissions
+            .subprocess
+            .iter()
+            .filter_map(|program| {
+                let found = find_program(&plugin_dir, program);
+                if found.is_none() {
+                    log_warn!("插件 {} 申请启动的程序不存在: {}", metadata.id, program);
+                }
+                found
+            })
+            .collect();
+
+        let network = !permissions.network.is_empty();
+        if sandbox.network && !network {
+            log_warn!(
+                "插件 {} 没有申请 network 权限，沙箱中不允许访问网络",
+                metadata.id
+            );
+        }
+
+        Ok(Some(Self {
+            plugin_dir,
+            data_dir,
+            executables,
+            read_paths,
+            write_paths,
+            list_paths: Vec::new(),
+            network,
+        }))
+    }
+
+    /// 让 `command` 启动的进程在沙箱中运行
+    pub fn apply(&self, command: &mut Command) -> Result<(), String> {
+        command.env(PLUGIN_DATA_DIR_ENV, &self.data_dir);
+        platform::confine(command, self)
+    }
+}
+
+/// 解析配置中的路径：`~/` 开头的相对于主目录，其他相对路径相对于插件目录
+fn resolve_path(plugin_dir: &Path, path: &str) -> PathBuf {
+    if let Some(rest) = path.strip_prefix("~/") {
+        if let Some(home) = dirs::home_dir() {
+            return home.join(rest);
+        }
+    }
+    plugin_dir.join(path)
+}
+
+/// 展开配置中的路径，文件名中的 `*` 匹配目录中已有的条目；不存在的路径被忽略
+///
+/// 只支持最后一级中的通配符，如 `/dev/ttyUSB*`。
+fn expand_path(plugin_id: &str, plugin_dir: &Path, path: &str) -> Vec<PathBuf> {
+    let resolved = resolve_path(plugin_dir, path);
+    if !path.contains('*') {
+        if !resolved.exists() {
+            log_warn!("插件 {} 沙箱中的路径不存在: {:?}", plugin_id, resolved);
+            return Vec::new();
+        }
+        return vec![resolved];
+    }
+
+    let (Some(parent), Some(pattern)) = (
+        resolved.parent(),
+        resolved.file_name().and_then(|name| name.to_str()),
+    ) else {
+        return Vec::new();
+    };
+    if parent.to_string_lossy().contains('*') {
+        log_warn!(
+            "插件 {} 沙箱中的路径只支持最后一级使用通配符: {}",
+            plugin_id,
+            path
+        );
+        return Vec::new();
+    }
+    let Ok(entries) = std::fs::read_dir(parent) else {
+        log_warn!("插件 {} 沙箱中的目录不存在: {:?}", plugin_id, parent);
+        return Vec::new();
+    };
+    entries
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| wildcard_match(pattern, name))
+        })
+        .map(|entry| entry.path())
+        .collect()
+}
+
+/// 按 `PATH` 查找程序，包含路径分隔符时相对于插件目录
+pub fn find_program(plugin_dir: &Path, program: &str) -> Option<PathBuf> {
+    if program.contains('/') {
+        return Some(plugin_dir.join(program));
+    }
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(program))
+        .find(|candidate| candidate.is_file())
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{SandboxPolicy, DEVICE_PATHS, SYSTEM_EXEC_PATHS, SYSTEM_READ_PATHS};
+    use landlock::{
+        path_beneath_rules, Access, AccessFs, RulesetAttr, RulesetCreated, RulesetCreatedAttr,
+        RulesetError, RulesetStatus, ABI,
+    };
+    use seccompiler::{
+        BpfProgram, SeccompAction, SeccompCmpArgLen, SeccompCmpOp, SeccompCondition, SeccompFilter,
+        SeccompRule, TargetArch,
+    };
+    use std::collections::BTreeMap;
+    use std::io;
+    use std::os::unix::process::CommandExt;
+    use std::process::Command;
+
+    /// 拒绝的系统调用，返回 EPERM
+    const DENIED_SYSCALLS: &[i64] = &[
+        libc::SYS_ptrace,
+        libc::SYS_process_vm_readv,
+        libc::SYS_process_vm_writev,
+        libc::SYS_mount,
+        libc::SYS_umount2,
+        libc::SYS_pivot_root,
+        libc::SYS_chroot,
+        libc::SYS_unshare,
+        libc::SYS_setns,
+        libc::SYS_kexec_load,
+        libc::SYS_init_module,
+        libc::SYS_finit_module,
+        libc::SYS_delete_module,
+        libc::SYS_bpf,
+        libc::SYS_perf_event_open,
+        libc::SYS_keyctl,
+        libc::SYS_add_key,
+        libc::SYS_request_key,
+        libc::SYS_reboot,
+        libc::SYS_swapon,
+        libc::SYS_swapoff,
+        libc::SYS_acct,
+        libc::SYS_userfaultfd,
+        libc::SYS_open_by_handle_at,
+        // io_uring 可以绕过对 socket 的过滤
+        libc::SYS_io_uring_setup,
+    ];
+
+    /// `clone` 中创建新命名空间的标志
+    const NAMESPACE_FLAGS: &[libc::c_int] = &[
+        libc::CLONE_NEWUSER,
+        libc::CLONE_NEWNS,
+        libc::CLONE_NEWNET,
+        libc::CLONE_NEWPID,
+        libc::CLONE_NEWIPC,
+        libc::CLONE_NEWUTS,
+        libc::CLONE_NEWCGROUP,
+    ];
+
+    pub(super) fn confine(command: &mut Command, policy: &SandboxPolicy) -> Result<(), String> {
+        let ruleset =
+            landlock_ruleset(policy).map_err(|e| format!("创建 Landlock 规则失败: {}", e))?;
+        let filters = seccomp_filters(policy.network)?;
+
+        let mut ruleset = Some(ruleset);
+        // fork 之后、exec 之前在子进程中执行，规则和过滤器都已在父进程中准备好
+        unsafe {
+            command.pre_exec(move || {
+                let ruleset = ruleset
+                    .take()
+                    .ok_or_else(|| io::Error::other("沙箱规则已被使用"))?;
+                let status = ruleset.restrict_self().map_err(io::Error::other)?;
+                if status.ruleset == RulesetStatus::NotEnforced {
+                    return Err(io::Error::other("内核不支持 Landlock，无法启用插件沙箱"));
+                }
+                for filter in &filters {
+                    seccompiler::apply_filter(filter).map_err(io::Error::other)?;
+                }
+                Ok(())
+            });
+        }
+        Ok(())
+    }
+
+    fn landlock_ruleset(policy: &SandboxPolicy) -> Result<RulesetCreated, RulesetError> {
+        let abi = ABI::V5;
+        let read = AccessFs::ReadFile | AccessFs::ReadDir;
+        let mut write = AccessFs::from_all(abi);
+        write.remove(AccessFs::Execute);
+        let execute = AccessFs::from_read(abi);
+
+        landlock::Ruleset::default()
+            .handle_access(AccessFs::from_all(abi))?
+            .create()?
+            .add_rules(path_beneath_rules(SYSTEM_READ_PATHS, read))?
+            .add_rules(path_beneath_rules(&policy.read_paths, read))?
+            .add_rules(path_beneath_rules(&policy.list_paths, AccessFs::ReadDir))?
+            .add_rules(path_beneath_rules(DEVICE_PATHS, write))?
+            .add_rules(path_beneath_rules(&policy.write_paths, write))?
+            .add_rules(path_beneath_rules(SYSTEM_EXEC_PATHS, execute))?
+            .add_rules(path_beneath_rules(&policy.executables, execute))
+    }
+
+    fn seccomp_filters(network: bool) -> Result<Vec<BpfProgram>, String> {
+        let arch = TargetArch::try_from(std::env::consts::ARCH)
+            .map_err(|e| format!("插件沙箱不支持当前架构: {}", e))?;
+        let condition = |arg, op, value| {
+            SeccompCondition::new(arg, SeccompCmpArgLen::Dword, op, value)
+                .and_then(|condition| SeccompRule::new(vec![condition]))
+                .map_err(|e| format!("创建 seccomp 规则失败: {}", e))
+        };
+
+        let mut rules: BTreeMap<i64, Vec<SeccompRule>> = DENIED_SYSCALLS
+            .iter()
+            .map(|&syscall| (syscall, Vec::new()))
+            .collect();
+        rules.insert(
+            libc::SYS_clone,
+            NAMESPACE_FLAGS
+                .iter()
+                .map(|&flag| condition(0, SeccompCmpOp::MaskedEq(flag as u64), flag as u64))
+                .collect::<Result<_, _>>()?,
+        );
+        if !network {
+            rules.insert(
+                libc::SYS_socket,
+                vec![condition(0, SeccompCmpOp::Ne, libc::AF_UNIX as u64)?],
+            );
+        }
+        let deny = SeccompFilter::new(
+            rules,
+            SeccompAction::Allow,
+            SeccompAction::Errno(libc::EPERM as u32),
+            arch,
+        );
+        // clone3 的参数在内存中，无法按标志过滤；返回 ENOSYS 让 C 库退回到 clone
+        let clone3 = SeccompFilter::new(
+            BTreeMap::from([(libc::SYS_clone3, Vec::new())]),
+            SeccompAction::Allow,
+            SeccompAction::Errno(libc::ENOSYS as u32),
+            arch,
+        );
+
+        [deny, clone3]
+            .into_iter()
+            .map(|filter| {
+                filter
+                    .and_then(BpfProgram::try_from)
+                    .map_err(|e| format!("编译 seccomp 过滤器失败: {}", e))
+            })
+            .collect()
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    use super::SandboxPolicy;
+    use std::process::Command;
+
+    pub(super) fn confine(_command: &mut Command, _policy: &SandboxPolicy) -> Result<(), String> {
+        Err("插件沙箱仅支持 Linux".to_string())
+    }
+}
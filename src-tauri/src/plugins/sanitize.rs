@@ -0,0 +1,1296 @@
+//! 插件 Markdown 的清理
+//!
+//! 前端用 markdown-it（开启原始 HTML）和 KaTeX 渲染插件发送的消息，插件可以借此注入脚本或
+//! `javascript:` 链接。宿主在事件交给前端之前清理 `plugin-message` 的 `content` 和
+//! `plugin-stream` 的 `chunk`：
+//!
+//! - 不在允许列表中的 HTML 标签、注释和自动链接的 `<` 转义为 `&lt;`，按文本显示；
+//!   保留的标签只留下安全的属性，`href`/`src` 按允许的协议检查。
+//! - Markdown 链接和引用定义的地址不是相对地址、也不在允许的协议内时被删除。
+//! - KaTeX 中会生成链接或 HTML 属性的命令（`\href`、`\html*` 等）的反斜杠被转义，
+//!   Markdown 中显示为原文，公式中成为换行加普通文本。
+//!
+//! 结构明确时围栏代码块和行内代码原样保留。数学公式中出现的围栏无法判断范围；出现无法准确判断的
+//! 围栏（缩进的围栏、HTML 块或公式中的围栏等）后，后续围栏全部按普通文本清理，此后代码块中被转义的
+//! `<` 会显示为 `&lt;`。行内代码可能被 markdown-it 以不同方式配对时（链接地址、公式、表格等附近），
+//! 所在段落的其余部分同样按普通文本清理。
+//!
+//! 流式消息的分块可能在标签或链接中间断开，清理器会暂存无法判断的结尾，等待后续分块。
+
+use crate::plugins::settings::{load_settings, SanitizeSettings};
+use plugin_interfaces::log_info;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+
+/// 等待后续分块时最多暂存的字节数，超过后按已有内容判断
+const MAX_PENDING: usize = 4096;
+/// 同时跟踪的链接地址数，超过后段落的其余部分不再原样保留行内代码
+const MAX_LINK_TAILS: usize = 16;
+/// 无论设置如何都不保留的标签
+const FORBIDDEN_TAGS: &[&str] = &[
+    "script",
+    "style",
+    "iframe",
+    "frame",
+    "frameset",
+    "object",
+    "embed",
+    "applet",
+    "form",
+    "input",
+    "button",
+    "textarea",
+    "select",
+    "option",
+    "link",
+    "meta",
+    "base",
+    "svg",
+    "math",
+    "template",
+    "noscript",
+    "xmp",
+    "plaintext",
+];
+/// 保留的标签上允许的属性
+const ALLOWED_ATTRIBUTES: &[&str] = &[
+    "href", "src", "alt", "title", "width", "height", "align", "colspan", "rowspan", "start",
+    "open",
+];
+/// 需要检查协议的属性
+const URL_ATTRIBUTES: &[&str] = &["href", "src"];
+/// 被禁用的 KaTeX 命令
+const KATEX_COMMANDS: &[&str] = &[
+    "href",
+    "url",
+    "includegraphics",
+    "htmlClass",
+    "htmlId",
+    "htmlStyle",
+    "htmlData",
+];
+
+static SETTINGS: OnceLock<RwLock<Arc<SanitizeSettings>>> = OnceLock::new();
+
+fn get_settings_slot() -> &'static RwLock<Arc<SanitizeSettings>> {
+    SETTINGS.get_or_init(|| RwLock::new(Arc::new(load_settings().sanitize)))
+}
+
+/// 当前生效的清理设置
+pub fn current_settings() -> Arc<SanitizeSettings> {
+    Arc::clone(&get_settings_slot().read().unwrap())
+}
+
+/// 从设置文件重新读取清理设置
+pub fn reload_settings() {
+    *get_settings_slot().write().unwrap() = Arc::new(load_settings().sanitize);
+    log_info!("插件消息清理设置已更新");
+}
+
+impl SanitizeSettings {
+    /// 是否需要清理该插件发送的内容
+    pub fn applies_to(&self, plugin_id: &str) -> bool {
+        self.enabled && !self.trusted_plugins.iter().any(|id| id == plugin_id)
+    }
+
+    fn tag_allowed(&self, name: &str) -> bool {
+        self.allow_html
+            && !FORBIDDEN_TAGS.contains(&name)
+            && self
+                .allowed_tags
+                .iter()
+                .any(|tag| tag.eq_ignore_ascii_case(name))
+    }
+
+    fn scheme_allowed(&self, scheme: &str) -> bool {
+        is_scheme(scheme)
+            && self
+                .allowed_url_schemes
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(scheme))
+    }
+
+    /// 地址是否是相对地址或使用允许的协议
+    fn url_allowed(&self, url: &str) -> bool {
+        let url = url.trim();
+        match url.find([':', '/', '?', '#']) {
+            Some(index) if url[index..].starts_with(':') => self.scheme_allowed(&url[..index]),
+            Some(index) => is_plain_path(&url[..index]),
+            None => is_plain_path(url),
+        }
+    }
+}
+
+/// URL 协议名：字母开头，由字母、数字、`+`、`.` 和 `-` 组成
+fn is_scheme(scheme: &str) -> bool {
+    let mut chars = scheme.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '.' | '-'))
+}
+
+/// 相对地址在第一个 `/`、`?` 或 `#` 之前不能包含实体、转义和空白，以免解码后变成协议
+fn is_plain_path(prefix: &str) -> bool {
+    prefix
+        .chars()
+        .all(|c| !c.is_whitespace() && !c.is_control() && c != '&' && c != '\\')
+}
+
+/// 清理一段完整的 Markdown
+pub fn sanitize_markdown(settings: &Arc<SanitizeSettings>, text: &str) -> String {
+    let mut sanitizer = MarkdownSanitizer::new(Arc::clone(settings));
+    let mut output = sanitizer.push(text);
+    output.push_str(&sanitizer.finish());
+    output
+}
+
+/// 清理 `plugin-message` 载荷中的 `content`，内容不变时原样返回
+pub fn sanitize_message_payload(settings: &Arc<SanitizeSettings>, payload: &str) -> String {
+    let Ok(mut value) = serde_json::from_str::<Value>(payload) else {
+        return payload.to_string();
+    };
+    let Some(content) = value.get_mut("content") else {
+        return payload.to_string();
+    };
+    let Some(text) = content.as_str() else {
+        return payload.to_string();
+    };
+    let sanitized = sanitize_markdown(settings, text);
+    if sanitized == text {
+        return payload.to_string();
+    }
+    *content = Value::String(sanitized);
+    value.to_string()
+}
+
+/// 按流ID保存的流式消息清理状态
+#[derive(Default)]
+pub struct StreamSanitizers {
+    streams: Mutex<HashMap<String, MarkdownSanitizer>>,
+}
+
+impl StreamSanitizers {
+    /// 清理一条 `plugin-stream` 载荷，返回依次发送的载荷
+    ///
+    /// 流结束时如果还有暂存的内容，先补发一个 `stream_data`。
+    pub fn sanitize(&self, settings: &Arc<SanitizeSettings>, payload: &str) -> Vec<String> {
+        let Ok(mut value) = serde_json::from_str::<Value>(payload) else {
+            return vec![payload.to_string()];
+        };
+        let kind = value["type"].as_str().unwrap_or_default().to_string();
+        let Some(stream_id) = value["data"]["stream_id"].as_str().map(str::to_string) else {
+            return vec![payload.to_string()];
+        };
+
+        match kind.as_str() {
+            "stream_data" => {
+                let Some(data) = value.get_mut("data").and_then(Value::as_object_mut) else {
+                    return vec![payload.to_string()];
+                };
+                let chunk = data
+                    .get("chunk")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default();
+                let is_final = data.get("is_final").and_then(Value::as_bool) == Some(true);
+
+                let mut streams = self.streams.lock().unwrap();
+                let sanitizer = streams
+                    .entry(stream_id.clone())
+                    .or_insert_with(|| MarkdownSanitizer::new(Arc::clone(settings)));
+                let mut output = sanitizer.push(chunk);
+                if is_final {
+                    output.push_str(&sanitizer.finish());
+                    streams.remove(&stream_id);
+                }
+                drop(streams);
+
+                data.insert("chunk".to_string(), Value::String(output));
+                vec![value.to_string()]
+            }
+            "stream_end" => {
+                let sanitizer = self.streams.lock().unwrap().remove(&stream_id);
+                let rest = sanitizer
+                    .map(|mut sanitizer| sanitizer.finish())
+                    .unwrap_or_default();
+                if rest.is_empty() {
+                    return vec![payload.to_string()];
+                }
+                let mut flush = value.clone();
+                flush["type"] = Value::from("stream_data");
+                flush["data"] = serde_json::json!({
+                    "stream_id": stream_id,
+                    "chunk": rest,
+                    "is_final": false,
+                });
+                vec![flush.to_string(), payload.to_string()]
+            }
+            "stream_cancel" => {
+                self.streams.lock().unwrap().remove(&stream_id);
+                vec![payload.to_string()]
+            }
+            _ => vec![payload.to_string()],
+        }
+    }
+}
+
+/// 围栏代码块的标记
+#[derive(Debug, Clone, Copy)]
+struct Fence {
+    marker: char,
+    len: usize,
+}
+
+/// 增量清理 Markdown 的状态机
+///
+/// 结构判断基于已经输出的内容，和前端看到的文本一致。
+pub struct MarkdownSanitizer {
+    settings: Arc<SanitizeSettings>,
+    /// 等待后续分块的输入
+    pending: String,
+    /// 当前所在的围栏代码块
+    fence: Option<Fence>,
+    /// 出现过无法准确判断的结构，之后不再跳过围栏代码块
+    tainted: bool,
+    /// 处在 `$$` 之后、下一个 `$` 之前，可能是跨越多行的公式
+    display_math: bool,
+    /// 处在 `\[` 之后、下一个 `\]` 之前，前端会把它们转换为 `$$` 并删除相邻的空白
+    bracket_math: bool,
+    /// 当前段落中的反引号可能被 markdown-it 以不同方式配对，不再原样保留行内代码
+    code_spans_blocked: bool,
+    /// 当前段落中可能还没有结束的链接地址和标题
+    link_tails: Vec<LinkTail>,
+    /// 当前输出行中出现过 `$`，之后的反引号可能处在行内公式中
+    line_dollar: bool,
+    /// 可能处在 HTML 块中（到空行结束）
+    in_html_block: bool,
+    /// 处在 `<pre>` 块中（到 `</pre>` 结束）
+    in_pre: bool,
+    /// 上一行以 `]:` 结尾，本行可能是引用定义的地址
+    prev_line_ref: bool,
+    /// 刚输出 `](` 或 `]:`，下一个非空白字符开始链接地址
+    expect_destination: bool,
+    /// 当前输出行还没有任何字符
+    at_line_start: bool,
+    /// 当前输出行的第一个非空白字符
+    line_first: Option<char>,
+    /// 当前输出行以原样保留的标签或自动链接开头
+    line_html: bool,
+    /// 当前输出行最后两个非空白字符
+    line_tail: [char; 2],
+    /// 最后输出的字符
+    last_char: char,
+}
+
+impl MarkdownSanitizer {
+    pub fn new(settings: Arc<SanitizeSettings>) -> Self {
+        Self {
+            settings,
+            pending: String::new(),
+            fence: None,
+            tainted: false,
+            display_math: false,
+            bracket_math: false,
+            code_spans_blocked: false,
+            link_tails: Vec::new(),
+            line_dollar: false,
+            in_html_block: false,
+            in_pre: false,
+            prev_line_ref: false,
+            expect_destination: false,
+            at_line_start: true,
+            line_first: None,
+            line_html: false,
+            line_tail: ['\0'; 2],
+            last_char: '\0',
+        }
+    }
+
+    /// 追加一段输入，返回可以确定的输出，无法判断的结尾暂存到下一次调用
+    pub fn push(&mut self, text: &str) -> String {
+        self.pending.push_str(text);
+        self.process(false)
+    }
+
+    /// 输入结束，输出暂存的内容
+    pub fn finish(&mut self) -> String {
+        self.process(true)
+    }
+
+    fn process(&mut self, last: bool) -> String {
+        // 与 markdown-it 一样把 \r\n 和 \r 视为换行；结尾的 \r 等下一块确定是否跟着 \n
+        let mut input = std::mem::take(&mut self.pending).replace("\r\n", "\n");
+        let held_cr = !last && input.ends_with('\r');
+        if held_cr {
+            input.pop();
+        }
+        let input = input.replace('\r', "\n");
+
+        let mut output = String::new();
+        let mut index = 0;
+        while index < input.len() {
+            let rest = &input[index..];
+            let step = Step {
+                rest,
+                last,
+                wait: !last && rest.len() < MAX_PENDING,
+            };
+            match self.step(&step, &mut output) {
+                Some(consumed) => index += consumed,
+                None => break,
+            }
+        }
+
+        self.pending = input[index..].to_string();
+        if held_cr {
+            self.pending.push('\r');
+        }
+        output
+    }
+
+    /// 处理输入开头的一个结构，返回消耗的字节数；需要等待后续输入时返回 None
+    fn step(&mut self, step: &Step, output: &mut String) -> Option<usize> {
+        let first = step.rest.chars().next()?;
+        if let Some(fence) = self.fence {
+            return self.step_fence(step, fence, output);
+        }
+        if self.at_line_start && (first == '`' || first == '~') {
+            if let Some(consumed) = self.step_fence_open(step, first, output) {
+                return consumed;
+            }
+        }
+        self.step_text(step, first, output)
+    }
+
+    /// 行首的围栏开始行；不是可以原样保留的围栏时返回 None，按普通文本处理
+    fn step_fence_open(
+        &mut self,
+        step: &Step,
+        marker: char,
+        output: &mut String,
+    ) -> Option<Option<usize>> {
+        let line_end = step.rest.find('\n');
+        let line = &step.rest[..line_end.unwrap_or(step.rest.len())];
+        let len = line.len() - line.trim_start_matches(marker).len();
+        if len < 3 && len < line.len() {
+            return None;
+        }
+        if line_end.is_none() && step.wait {
+            return Some(None);
+        }
+        if len < 3 {
+            return None;
+        }
+        let info = &line[len..];
+        let simple_info = info.chars().all(|c| {
+            c.is_ascii_alphanumeric() || matches!(c, ' ' | '\t' | '_' | '+' | '.' | '#' | '-')
+        });
+        let complete = line_end.is_some() || step.last;
+        if !simple_info
+            || !complete
+            || self.tainted
+            || self.display_math
+            || self.bracket_math
+            || self.in_html_block
+            || self.in_pre
+            || self.prev_line_ref
+        {
+            // 无法确定 markdown-it 是否把这一行当作围栏，之后的围栏都不再原样保留
+            self.tainted = true;
+            return None;
+        }
+
+        self.expect_destination = false;
+        self.emit(output, line);
+        self.fence = Some(Fence { marker, len });
+        if line_end.is_some() {
+            self.emit(output, "\n");
+            return Some(Some(line.len() + 1));
+        }
+        Some(Some(line.len()))
+    }
+
+    /// 围栏代码块内的内容原样输出，直到结束行
+    fn step_fence(&mut self, step: &Step, fence: Fence, output: &mut String) -> Option<usize> {
+        let line_end = step.rest.find('\n');
+        let line = &step.rest[..line_end.unwrap_or(step.rest.len())];
+        let consumed = line_end.map_or(line.len(), |end| end + 1);
+
+        if self.at_line_start && could_close(line, fence) {
+            if line_end.is_none() && step.wait {
+                return None;
+            }
+            if line_end.is_none() && !step.last {
+                // 超长的行被截断判断，提前结束围栏，之后不再原样保留围栏
+                self.tainted = true;
+                self.fence = None;
+            } else if closes(line, fence) {
+                if self.bracket_math {
+                    // 转换 `\[ \]` 时删除的空白可能让结束行并入其他行
+                    self.tainted = true;
+                }
+                self.fence = None;
+            }
+        }
+        self.emit(output, &step.rest[..consumed]);
+        Some(consumed)
+    }
+
+    fn step_text(&mut self, step: &Step, first: char, output: &mut String) -> Option<usize> {
+        let rest = step.rest;
+        if self.expect_destination && !first.is_whitespace() {
+            self.expect_destination = false;
+            match self.step_destination(step, output) {
+                Err(()) => {
+                    self.expect_destination = true;
+                    return None;
+                }
+                Ok(Some(consumed)) => return Some(consumed),
+                Ok(None) => {}
+            }
+        }
+
+        match first {
+            '<' => self.step_angle(step, output),
+            '\\' => {
+                let name_len = rest[1..]
+                    .chars()
+                    .take_while(|c| c.is_ascii_alphabetic())
+                    .count();
+                if 1 + name_len == rest.len() && step.wait {
+                    return None;
+                }
+                if rest[1..].starts_with('\\') {
+                    // `\\` 在 TeX 中是一个整体，不能让它的第二个反斜杠和后面的命令名组合
+                    self.emit(output, "\\\\");
+                    return Some(2);
+                }
+                if rest[1..].starts_with('`') {
+                    // 转义的反引号不能开始行内代码
+                    self.emit(output, &rest[..2]);
+                    return Some(2);
+                }
+                let name = &rest[1..1 + name_len];
+                if KATEX_COMMANDS.contains(&name) {
+                    self.emit(output, "\\\\");
+                    self.emit(output, name);
+                    return Some(1 + name_len);
+                }
+                self.emit_char(output, '\\');
+                Some(1)
+            }
+            ']' => {
+                if rest.len() == 1 && step.wait {
+                    return None;
+                }
+                if rest.starts_with("](") || rest.starts_with("]:") {
+                    self.emit(output, &rest[..2]);
+                    self.expect_destination = true;
+                    if rest.starts_with("]:") || self.link_tails.len() >= MAX_LINK_TAILS {
+                        // 引用定义的标题可以在下一行，不判断它的范围
+                        self.code_spans_blocked = true;
+                    } else {
+                        self.link_tails.push(LinkTail::Open);
+                    }
+                    return Some(2);
+                }
+                self.emit_char(output, ']');
+                Some(1)
+            }
+            '`' => self.step_backticks(step, output),
+            '~' => {
+                if rest.len() < 3 && step.wait {
+                    return None;
+                }
+                if rest.starts_with("~~~") {
+                    // 行内或缩进的围栏标记，无法确定代码块的范围
+                    self.tainted = true;
+                }
+                self.emit_char(output, first);
+                Some(1)
+            }
+            '$' => {
+                let run = rest.len() - rest.trim_start_matches('$').len();
+                if run == rest.len() && step.wait {
+                    return None;
+                }
+                // texmath 的公式块从 `$$` 开始，内容中不能有 `$`
+                if self.display_math {
+                    self.display_math = false;
+                } else if run >= 2 {
+                    self.display_math = true;
+                }
+                self.line_dollar = true;
+                self.emit(output, &rest[..run]);
+                Some(run)
+            }
+            _ => {
+                self.emit_char(output, first);
+                Some(first.len_utf8())
+            }
+        }
+    }
+
+    /// 反引号串；能确定 markdown-it 也把它当作行内代码时原样输出整个代码
+    fn step_backticks(&mut self, step: &Step, output: &mut String) -> Option<usize> {
+        let rest = step.rest;
+        let run = rest.len() - rest.trim_start_matches('`').len();
+        if run == rest.len() && step.wait {
+            return None;
+        }
+        if run >= 3 {
+            // 行内或缩进的围栏标记，无法确定代码块的范围
+            self.tainted = true;
+        }
+
+        if self.code_span_allowed() {
+            let line_end = rest.find('\n');
+            if line_end.is_none() && step.wait {
+                return None;
+            }
+            let line = &rest[..line_end.unwrap_or(rest.len())];
+            // 只保留在同一行结束、不跨越表格单元格的代码
+            if let Some(close) = closing_backticks(&line[run..], run) {
+                let end = run + close + run;
+                if !line[run..end - run].contains('|') {
+                    self.emit(output, &line[..end]);
+                    return Some(end);
+                }
+            }
+        }
+
+        // 之后的反引号可能和 markdown-it 的配对不同，段落的其余部分按普通文本处理
+        self.code_spans_blocked = true;
+        self.emit(output, &rest[..run]);
+        Some(run)
+    }
+
+    /// 当前位置的反引号是否一定会被 markdown-it 当作行内代码的开始
+    fn code_span_allowed(&self) -> bool {
+        !(self.code_spans_blocked
+            || !self.link_tails.is_empty()
+            || self.line_dollar
+            || self.display_math
+            || self.bracket_math
+            || self.line_html
+            || self.in_html_block
+            || self.in_pre)
+    }
+
+    /// 处理 `](` 或 `]:` 之后的链接地址，返回消耗的字节数；按普通文本继续处理时返回 None，
+    /// 需要等待后续输入时返回 Err
+    ///
+    /// 不允许的地址被删除。允许的地址通常仍按普通文本处理，以免 markdown-it 不把它当作链接时
+    /// 其中的标签被原样保留；只有不可能是标签、注释或声明的 `<...>` 地址直接输出。
+    fn step_destination(&mut self, step: &Step, output: &mut String) -> Result<Option<usize>, ()> {
+        let rest = step.rest;
+        let (destination, len) = if let Some(inner) = rest.strip_prefix('<') {
+            match inner.find(['>', '<', '\n']) {
+                Some(end) if inner.as_bytes()[end] == b'>' => (&inner[..end], end + 2),
+                Some(_) => return Ok(None),
+                None if step.wait => return Err(()),
+                None => return Ok(None),
+            }
+        } else {
+            match destination_end(rest) {
+                Some(end) => (&rest[..end], end),
+                None if step.wait => return Err(()),
+                None => (rest, rest.len()),
+            }
+        };
+        if !self.settings.url_allowed(destination) {
+            // 删除地址，链接仍然显示文字
+            return Ok(Some(len));
+        }
+        let bracketed = &rest[..len];
+        if rest.starts_with('<')
+            && !destination.starts_with(['!', '?'])
+            && parse_tag(bracketed).is_none()
+        {
+            if bracketed.contains('`') {
+                self.code_spans_blocked = true;
+            }
+            self.emit(output, bracketed);
+            return Ok(Some(len));
+        }
+        Ok(None)
+    }
+
+    /// `<` 开头的标签、自动链接或普通文本
+    fn step_angle(&mut self, step: &Step, output: &mut String) -> Option<usize> {
+        let rest = step.rest;
+        let Some(next) = rest[1..].chars().next() else {
+            if step.wait {
+                return None;
+            }
+            self.emit_char(output, '<');
+            return Some(1);
+        };
+        // 只有字母、数字和邮件地址中可能出现的符号之后的 < 才可能开始标签或自动链接
+        if !(next.is_ascii_alphanumeric() || "/!?.#$%&'*+=^_`{|}~-".contains(next)) {
+            self.emit_char(output, '<');
+            return Some(1);
+        }
+
+        match rest[1..].find(['>', '<', '\n']).map(|index| index + 1) {
+            Some(end) if rest.as_bytes()[end] == b'>' => {
+                let candidate = &rest[..=end];
+                if let Some(tag) = parse_tag(candidate) {
+                    if self.settings.tag_allowed(&tag.name) {
+                        self.emit_tag(output, &tag);
+                        return Some(end + 1);
+                    }
+                } else if self.autolink_allowed(&candidate[1..end]) {
+                    if self.line_first.is_none() {
+                        self.line_html = true;
+                    }
+                    if candidate.contains('`') {
+                        // markdown-it 不认为是自动链接时其中的反引号可能开始行内代码
+                        self.code_spans_blocked = true;
+                    }
+                    self.emit(output, candidate);
+                    return Some(end + 1);
+                }
+            }
+            None if step.wait => return None,
+            _ => {}
+        }
+
+        // 转义后 markdown-it 和浏览器都不会把它当作标签，显示时解码为 `<`
+        self.emit(output, "&lt;");
+        Some(1)
+    }
+
+    /// `<...>` 中的内容是否是允许的自动链接
+    fn autolink_allowed(&self, inner: &str) -> bool {
+        if inner
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || c == '<' || c == '>')
+        {
+            return false;
+        }
+        if let Some((scheme, _)) = inner.split_once(':') {
+            return (2..=32).contains(&scheme.len()) && self.settings.scheme_allowed(scheme);
+        }
+        is_email(inner) && self.settings.scheme_allowed("mailto")
+    }
+
+    /// 输出只保留安全属性的标签
+    fn emit_tag(&mut self, output: &mut String, tag: &Tag) {
+        if tag.name == "pre" {
+            if tag.closing {
+                self.in_pre = false;
+            } else if self.line_first.is_none() {
+                self.in_pre = true;
+            }
+        }
+        if self.line_first.is_none() {
+            self.line_html = true;
+        }
+
+        let mut rebuilt = String::from("<");
+        if tag.closing {
+            rebuilt.push('/');
+        }
+        rebuilt.push_str(&tag.name);
+        for (name, value) in &tag.attributes {
+            if !ALLOWED_ATTRIBUTES.contains(&name.as_str()) {
+                continue;
+            }
+            match value {
+                Some(value) => {
+                    if URL_ATTRIBUTES.contains(&name.as_str()) && !self.settings.url_allowed(value)
+                    {
+                        continue;
+                    }
+                    rebuilt.push_str(&format!(" {}=\"{}\"", name, value.replace('"', "&quot;")));
+                }
+                None => rebuilt.push_str(&format!(" {}", name)),
+            }
+        }
+        if tag.self_closing {
+            rebuilt.push_str(" /");
+        }
+        rebuilt.push('>');
+        self.emit(output, &rebuilt);
+    }
+
+    fn emit(&mut self, output: &mut String, text: &str) {
+        for c in text.chars() {
+            self.emit_char(output, c);
+        }
+    }
+
+    /// 输出一个字符并更新行状态
+    fn emit_char(&mut self, output: &mut String, c: char) {
+        if c == '\n' {
+            self.end_line();
+        } else {
+            if !c.is_whitespace() {
+                self.line_first.get_or_insert(c);
+                self.line_tail = [self.line_tail[1], c];
+            }
+            self.at_line_start = false;
+        }
+        // 前端在渲染前把所有 `\[ \]` 转换为 `$$`，包括代码中的
+        if self.last_char == '\\' && c == '[' {
+            self.bracket_math = true;
+        } else if self.last_char == '\\' && c == ']' && self.bracket_math {
+            self.bracket_math = false;
+            self.code_spans_blocked = true;
+        }
+        self.link_tails.retain_mut(|tail| match tail.advance(c) {
+            Some(next) => {
+                *tail = next;
+                true
+            }
+            None => false,
+        });
+        self.last_char = c;
+        output.push(c);
+    }
+
+    fn end_line(&mut self) {
+        if self.fence.is_none() {
+            if self.line_first.is_none() {
+                self.in_html_block = false;
+            } else if self.line_html {
+                self.in_html_block = true;
+            }
+            self.prev_line_ref = self.line_tail == [']', ':'];
+        }
+        if self.line_first.is_none() {
+            // 空行结束段落
+            self.code_spans_blocked = false;
+            self.link_tails.clear();
+        }
+        self.line_dollar = false;
+        self.at_line_start = true;
+        self.line_first = None;
+        self.line_html = false;
+        self.line_tail = ['\0'; 2];
+    }
+}
+
+/// markdown-it 解析 `](` 之后的链接地址和标题的进度
+#[derive(Debug, Clone, Copy)]
+enum LinkTail {
+    /// 地址之前的空白
+    Open,
+    /// `<...>` 形式的地址
+    Bracketed { escaped: bool },
+    /// 不带尖括号的地址
+    Plain { depth: usize, escaped: bool },
+    /// 地址之后的空白，`spaced` 表示已经出现空白
+    Spaced { spaced: bool },
+    /// 标题，`close` 是结束标记
+    Title { close: char, escaped: bool },
+    /// 标题之后的空白
+    Closing,
+}
+
+impl LinkTail {
+    /// 输出一个字符后的进度；链接已经结束或不可能是链接时返回 None
+    fn advance(self, c: char) -> Option<LinkTail> {
+        let space = matches!(c, ' ' | '\t' | '\n');
+        match self {
+            LinkTail::Open if space => Some(self),
+            LinkTail::Open => match c {
+                '<' => Some(LinkTail::Bracketed { escaped: false }),
+                ')' => None,
+                _ => LinkTail::Plain {
+                    depth: 0,
+                    escaped: false,
+                }
+                .advance(c),
+            },
+            LinkTail::Bracketed { escaped: true } => Some(LinkTail::Bracketed { escaped: false }),
+            LinkTail::Bracketed { .. } => match c {
+                '\n' | '<' => None,
+                '>' => Some(LinkTail::Spaced { spaced: false }),
+                '\\' => Some(LinkTail::Bracketed { escaped: true }),
+                _ => Some(self),
+            },
+            // markdown-it 在反斜杠加空格处结束地址，之后不可能是链接
+            LinkTail::Plain {
+                depth,
+                escaped: true,
+            } => (c != ' ').then_some(LinkTail::Plain {
+                depth,
+                escaped: false,
+            }),
+            LinkTail::Plain { depth, .. } => match c {
+                _ if space => (depth == 0).then_some(LinkTail::Spaced { spaced: true }),
+                _ if c.is_control() => None,
+                '\\' => Some(LinkTail::Plain {
+                    depth,
+                    escaped: true,
+                }),
+                '(' => Some(LinkTail::Plain {
+                    depth: depth + 1,
+                    escaped: false,
+                }),
+                ')' => depth.checked_sub(1).map(|depth| LinkTail::Plain {
+                    depth,
+                    escaped: false,
+                }),
+                _ => Some(self),
+            },
+            LinkTail::Spaced { .. } if space => Some(LinkTail::Spaced { spaced: true }),
+            LinkTail::Spaced { spaced } => match c {
+                '"' | '\'' if spaced => Some(LinkTail::Title {
+                    close: c,
+                    escaped: false,
+                }),
+                '(' if spaced => Some(LinkTail::Title {
+                    close: ')',
+                    escaped: false,
+                }),
+                _ => None,
+            },
+            LinkTail::Title {
+                close,
+                escaped: true,
+            } => Some(LinkTail::Title {
+                close,
+                escaped: false,
+            }),
+            LinkTail::Title { close, .. } => match c {
+                _ if c == close => Some(LinkTail::Closing),
+                '\\' => Some(LinkTail::Title {
+                    close,
+                    escaped: true,
+                }),
+                '(' if close == ')' => None,
+                _ => Some(self),
+            },
+            LinkTail::Closing if space => Some(self),
+            LinkTail::Closing => None,
+        }
+    }
+}
+
+/// 一次处理的输入
+struct Step<'a> {
+    rest: &'a str,
+    /// 输入已经结束
+    last: bool,
+    /// 可以等待后续输入
+    wait: bool,
+}
+
+/// 行的开头是否可能是围栏结束行（0 到 3 个空格、足够长的标记、之后只有空白）
+fn could_close(line: &str, fence: Fence) -> bool {
+    let after = line.trim_start_matches(' ');
+    if line.len() - after.len() > 3 {
+        return false;
+    }
+    let tail = after.trim_start_matches(fence.marker);
+    let len = after.len() - tail.len();
+    tail.is_empty() || (len >= fence.len && tail.chars().all(|c| c == ' ' || c == '\t'))
+}
+
+/// 完整的一行是否结束围栏
+fn closes(line: &str, fence: Fence) -> bool {
+    let after = line.trim_start_matches(' ');
+    let tail = after.trim_start_matches(fence.marker);
+    line.len() - after.len() <= 3
+        && after.len() - tail.len() >= fence.len
+        && tail.chars().all(|c| c == ' ' || c == '\t')
+}
+
+/// 第一个长度恰好为 `len` 的反引号串的位置
+fn closing_backticks(text: &str, len: usize) -> Option<usize> {
+    let mut index = 0;
+    while let Some(start) = text[index..].find('`').map(|offset| index + offset) {
+        let run = text[start..].len() - text[start..].trim_start_matches('`').len();
+        if run == len {
+            return Some(start);
+        }
+        index = start + run;
+    }
+    None
+}
+
+/// 不带尖括号的链接地址的结束位置：空白、控制字符或不配对的 `)`；输入在地址中结束时返回 None
+fn destination_end(text: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut escaped = false;
+    for (index, c) in text.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' => escaped = true,
+            '(' => depth += 1,
+            ')' if depth == 0 => return Some(index),
+            ')' => depth -= 1,
+            c if c.is_whitespace() || c.is_control() => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// 自动链接中的邮件地址
+fn is_email(text: &str) -> bool {
+    let Some((local, domain)) = text.split_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && local
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || ".!#$%&'*+/=?^_`{|}~-".contains(c))
+        && !domain.is_empty()
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+/// 解析出的 HTML 标签
+#[derive(Debug)]
+struct Tag {
+    name: String,
+    closing: bool,
+    self_closing: bool,
+    attributes: Vec<(String, Option<String>)>,
+}
+
+/// 解析 `<name attr="value">` 或 `</name>`，不是单个完整标签时返回 None
+fn parse_tag(text: &str) -> Option<Tag> {
+    let inner = text.strip_prefix('<')?.strip_suffix('>')?;
+    let (closing, inner) = match inner.strip_prefix('/') {
+        Some(inner) => (true, inner),
+        None => (false, inner),
+    };
+    let name_len = inner
+        .char_indices()
+        .find(|&(index, c)| !(c.is_ascii_alphanumeric() || (index > 0 && c == '-')))
+        .map_or(inner.len(), |(index, _)| index);
+    if name_len == 0 || !inner.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let name = inner[..name_len].to_ascii_lowercase();
+    let mut rest = &inner[name_len..];
+
+    if closing {
+        return rest.trim().is_empty().then_some(Tag {
+            name,
+            closing,
+            self_closing: false,
+            attributes: Vec::new(),
+        });
+    }
+
+    let mut attributes = Vec::new();
+    loop {
+        let trimmed = rest.trim_start();
+        let had_space = trimmed.len() < rest.len();
+        rest = trimmed;
+        if rest.is_empty() {
+            break;
+        }
+        if rest == "/" {
+            return Some(Tag {
+                name,
+                closing,
+                self_closing: true,
+                attributes,
+            });
+        }
+        if !had_space {
+            return None;
+        }
+
+        let attr_len = rest
+            .char_indices()
+            .find(|&(index, c)| {
+                !(c.is_ascii_alphabetic()
+                    || matches!(c, '_' | ':')
+                    || (index > 0 && (c.is_ascii_digit() || matches!(c, '.' | '-'))))
+            })
+            .map_or(rest.len(), |(index, _)| index);
+        if attr_len == 0 {
+            return None;
+        }
+        let attr = rest[..attr_len].to_ascii_lowercase();
+        rest = &rest[attr_len..];
+
+        let after_name = rest.trim_start();
+        let Some(value_start) = after_name.strip_prefix('=') else {
+            attributes.push((attr, None));
+            continue;
+        };
+        let value_start = value_start.trim_start();
+        let (value, remaining) = match value_start.chars().next()? {
+            quote @ ('"' | '\'') => {
+                let end = value_start[1..].find(quote)? + 1;
+                (&value_start[1..end], &value_start[end + 1..])
+            }
+            _ => {
+                let end = value_start
+                    .find(|c: char| c.is_whitespace() || "\"'=<>`".contains(c))
+                    .unwrap_or(value_start.len());
+                if end == 0 {
+                    return None;
+                }
+                (&value_start[..end], &value_start[end..])
+            }
+        };
+        attributes.push((attr, Some(value.to_string())));
+        rest = remaining;
+    }
+
+    Some(Tag {
+        name,
+        closing,
+        self_closing: false,
+        attributes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(allow_html: bool) -> Arc<SanitizeSettings> {
+        Arc::new(SanitizeSettings {
+            allow_html,
+            ..SanitizeSettings::default()
+        })
+    }
+
+    fn clean(text: &str) -> String {
+        sanitize_markdown(&settings(false), text)
+    }
+
+    fn clean_html(text: &str) -> String {
+        sanitize_markdown(&settings(true), text)
+    }
+
+    /// 按给定的分块流式清理
+    fn clean_chunks(chunks: &[&str]) -> String {
+        let mut sanitizer = MarkdownSanitizer::new(settings(true));
+        let mut output: String = chunks.iter().map(|chunk| sanitizer.push(chunk)).collect();
+        output.push_str(&sanitizer.finish());
+        output
+    }
+
+    #[test]
+    fn raw_html_is_escaped_unless_allowed() {
+        assert_eq!(clean("<b>bold</b>"), "&lt;b>bold&lt;/b>");
+        assert_eq!(clean_html("<b>bold</b>"), "<b>bold</b>");
+        assert_eq!(
+            clean_html("<script>alert(1)</script>"),
+            "&lt;script>alert(1)&lt;/script>"
+        );
+        assert_eq!(clean_html("<!-- <b> -->"), "&lt;!-- <b> -->");
+        assert_eq!(clean_html("<custom-tag>"), "&lt;custom-tag>");
+        // 不能开始标签的 < 原样保留
+        assert_eq!(clean("1 < 2"), "1 < 2");
+    }
+
+    #[test]
+    fn unsafe_attributes_are_removed() {
+        assert_eq!(
+            clean_html("<img src=\"a.png\" onerror=\"alert(1)\" alt='x'>"),
+            "<img src=\"a.png\" alt=\"x\">"
+        );
+        assert_eq!(
+            clean_html("<span style=\"color:red\" title=\"a\">"),
+            "<span title=\"a\">"
+        );
+        assert_eq!(clean_html("<br/>"), "<br />");
+    }
+
+    #[test]
+    fn attribute_urls_are_checked() {
+        assert_eq!(
+            clean_html("<a href=\"javascript:alert(1)\">x</a>"),
+            "<a>x</a>"
+        );
+        assert_eq!(
+            clean_html("<img src=\"data:image/svg+xml,<svg>\">"),
+            "&lt;img src=\"data:image/svg+xml,&lt;svg>\">"
+        );
+        assert_eq!(
+            clean_html("<a href=\"javascript&#58;alert(1)\">x</a>"),
+            "<a>x</a>"
+        );
+        assert_eq!(
+            clean_html("<a href=\"https://example.com\">x</a>"),
+            "<a href=\"https://example.com\">x</a>"
+        );
+    }
+
+    #[test]
+    fn disallowed_link_destinations_are_dropped() {
+        assert_eq!(clean("[x](javascript:alert(1))"), "[x]()");
+        assert_eq!(clean("![x](data:text/html,hi)"), "![x]()");
+        assert_eq!(clean("[x](<javascript:alert(1)>)"), "[x]()");
+        assert_eq!(
+            clean("[x](javascript:alert(1) \"title\")"),
+            "[x]( \"title\")"
+        );
+        assert_eq!(clean("[x](JaVaScRiPt:alert(1))"), "[x]()");
+    }
+
+    #[test]
+    fn obfuscated_schemes_are_dropped() {
+        assert_eq!(clean("[x](javascript&#58;alert(1))"), "[x]()");
+        assert_eq!(clean("[x](&#106;avascript:alert(1))"), "[x]()");
+        assert_eq!(clean("[x](java&#x09;script:alert(1))"), "[x]()");
+        assert_eq!(clean("[x](javascript\\:alert(1))"), "[x]()");
+    }
+
+    #[test]
+    fn allowed_link_destinations_are_kept() {
+        for text in [
+            "[x](https://example.com/a_(b))",
+            "[x](/docs/page?a=1&b=2)",
+            "[x](#section)",
+            "[x](<https://example.com/a b>)",
+            "[x]()",
+            "[mail](mailto:someone@example.com)",
+        ] {
+            assert_eq!(clean(text), text);
+        }
+    }
+
+    #[test]
+    fn bracketed_destinations_cannot_smuggle_tags() {
+        assert_eq!(clean("[x](<b>"), "[x](&lt;b>");
+        assert_eq!(clean("[x](<style/>"), "[x](&lt;style/>");
+        assert_eq!(clean("[x](<!-->"), "[x](&lt;!-->");
+        assert_eq!(clean_html("[x](<b>)"), "[x](<b>)");
+    }
+
+    #[test]
+    fn autolinks_follow_allowed_schemes() {
+        assert_eq!(clean("<https://example.com>"), "<https://example.com>");
+        assert_eq!(clean("<someone@example.com>"), "<someone@example.com>");
+        assert_eq!(clean("<javascript:alert(1)>"), "&lt;javascript:alert(1)>");
+        assert_eq!(clean("<data:text/html,hi>"), "&lt;data:text/html,hi>");
+    }
+
+    #[test]
+    fn reference_definitions_are_checked() {
+        assert_eq!(clean("[a]: javascript:alert(1)\n[a]"), "[a]: \n[a]");
+        assert_eq!(clean("[a]:\n  data:text/html,hi \"t\""), "[a]:\n   \"t\"");
+        assert_eq!(
+            clean("[a]: https://example.com \"t\""),
+            "[a]: https://example.com \"t\""
+        );
+    }
+
+    #[test]
+    fn katex_link_commands_are_disabled() {
+        assert_eq!(
+            clean("$\\href{javascript:alert(1)}{x}$"),
+            "$\\\\href{javascript:alert(1)}{x}$"
+        );
+        assert_eq!(clean("$\\htmlClass{a}{b}$"), "$\\\\htmlClass{a}{b}$");
+        // `\\` 之后的命令同样被转义
+        assert_eq!(clean("$a\\\\\\url{x}$"), "$a\\\\\\\\url{x}$");
+        // 其他命令原样交给 KaTeX
+        for text in ["$\\frac{a}{b}$", "$$\\sum_{i=1}^n i$$", "$a \\\\ b$"] {
+            assert_eq!(clean(text), text);
+        }
+    }
+
+    #[test]
+    fn fenced_code_is_kept_verbatim() {
+        let text = "```html\n<script>alert(1)</script>\n```\n<b>";
+        assert_eq!(
+            clean(text),
+            "```html\n<script>alert(1)</script>\n```\n&lt;b>"
+        );
+    }
+
+    #[test]
+    fn inline_code_is_kept_verbatim() {
+        for text in [
+            "Use `Vec<String>` here",
+            "``a ` <b> ``",
+            "[x](/a) and `<T>`",
+        ] {
+            assert_eq!(clean(text), text);
+        }
+        // markdown-it 可能以不同方式配对的反引号按普通文本处理
+        assert_eq!(clean("\\`a <b> `"), "\\`a &lt;b> `");
+        assert_eq!(clean("$`$ <b> `"), "$`$ &lt;b> `");
+        assert_eq!(clean("[x](/u \"a `b\") <b> `"), "[x](/u \"a `b\") &lt;b> `");
+        assert_eq!(clean("`a\nb` <b> `"), "`a\nb` &lt;b> `");
+        assert_eq!(clean("| `a | <b> ` |"), "| `a | &lt;b> ` |");
+    }
+
+    #[test]
+    fn fences_after_math_are_kept_verbatim() {
+        let fence = "```html\n<b>x</b>\n```\n";
+        for math in ["$$a+b$$\n\n", "\\[a+b\\]\n\n", "$x$ and $$y$$\n"] {
+            let text = format!("{}{}", math, fence);
+            assert_eq!(clean(&text), text);
+        }
+        // 公式中的围栏无法确定范围
+        assert_eq!(clean("$$\n```\n<b>\n```\n$$"), "$$\n```\n&lt;b>\n```\n$$");
+    }
+
+    #[test]
+    fn constructs_split_across_chunks() {
+        assert_eq!(
+            clean_chunks(&["<scr", "ipt>alert(1)</script>"]),
+            "&lt;script>alert(1)&lt;/script>"
+        );
+        assert_eq!(clean_chunks(&["[x](java", "script:alert(1))"]), "[x]()");
+        assert_eq!(clean_chunks(&["[x]", "(javascript:alert(1))"]), "[x]()");
+        assert_eq!(clean_chunks(&["<a href=\"java", "script:x\">"]), "<a>");
+        assert_eq!(clean_chunks(&["$\\hr", "ef{x}{y}$"]), "$\\\\href{x}{y}$");
+        assert_eq!(
+            clean_chunks(&["```\n<b>", "</b>\n``", "`\n<b>"]),
+            "```\n<b></b>\n```\n<b>"
+        );
+        assert_eq!(clean_chunks(&["`a<", "b>` <script>"]), "`a<b>` &lt;script>");
+    }
+
+    #[test]
+    fn stream_payloads_flush_held_back_input() {
+        let streams = StreamSanitizers::default();
+        let settings = settings(false);
+        let data = |chunk: &str| {
+            serde_json::json!({
+                "type": "stream_data",
+                "data": { "stream_id": "s", "chunk": chunk, "is_final": false },
+            })
+            .to_string()
+        };
+        let chunk = |payload: &str| {
+            let value: Value = serde_json::from_str(payload).unwrap();
+            value["data"]["chunk"].as_str().unwrap().to_string()
+        };
+
+        let first = streams.sanitize(&settings, &data("text <scr"));
+        assert_eq!(chunk(&first[0]), "text ");
+        let end = serde_json::json!({
+            "type": "stream_end",
+            "data": { "stream_id": "s", "success": true, "error": null },
+        })
+        .to_string();
+        let flushed = streams.sanitize(&settings, &end);
+        assert_eq!(flushed.len(), 2);
+        assert_eq!(chunk(&flushed[0]), "&lt;scr");
+        assert_eq!(flushed[1], end);
+    }
+
+    #[test]
+    fn trusted_plugins_are_not_sanitized() {
+        let settings = SanitizeSettings {
+            trusted_plugins: vec!["trusted".to_string()],
+            ..SanitizeSettings::default()
+        };
+        assert!(!settings.applies_to("trusted"));
+        assert!(settings.applies_to("other"));
+    }
+}
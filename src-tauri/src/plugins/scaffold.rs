@@ -0,0 +1,333 @@
+use plugin_interfaces::log_info;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+const CARGO_TOML_TEMPLATE: &str = include_str!("../../templates/plugin/Cargo.toml.tmpl");
+const CONFIG_TOML_TEMPLATE: &str = include_str!("../../templates/plugin/config.toml.tmpl");
+const ECHO_TEMPLATE: &str = include_str!("../../templates/plugin/echo.rs.tmpl");
+const STREAMING_TEMPLATE: &str = include_str!("../../templates/plugin/streaming.rs.tmpl");
+const UI_TEMPLATE: &str = include_str!("../../templates/plugin/ui.rs.tmpl");
+
+/// 新插件使用的模板
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PluginTemplate {
+    /// 最小的回显插件
+    #[default]
+    Echo,
+    /// 基于 tokio 的流式输出插件
+    Streaming,
+    /// 展示各类 UI 组件的插件
+    Ui,
+}
+
+impl PluginTemplate {
+    /// 从模板名称解析（echo / streaming / ui）
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "echo" => Ok(Self::Echo),
+            "streaming" | "stream" => Ok(Self::Streaming),
+            "ui" => Ok(Self::Ui),
+            other => Err(format!(
+                "未知的插件模板: {} (可选: echo, streaming, ui)",
+                other
+            )),
+        }
+    }
+
+    fn source(self) -> &'static str {
+        match self {
+            Self::Echo => ECHO_TEMPLATE,
+            Self::Streaming => STREAMING_TEMPLATE,
+            Self::Ui => UI_TEMPLATE,
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Self::Echo => "回显收到的消息",
+            Self::Streaming => "以流式消息逐块返回内容",
+            Self::Ui => "展示插件 UI 组件的用法",
+        }
+    }
+
+    /// 模板额外需要的依赖（Cargo.toml 中 `[dependencies]` 的内容）
+    fn dependencies(self) -> &'static str {
+        match self {
+            Self::Streaming => "tokio = { version = \"1.45.1\", features = [\"full\"] }\n",
+            Self::Echo | Self::Ui => "",
+        }
+    }
+}
+
+/// 生成新插件的参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScaffoldOptions {
+    /// 插件ID，只能包含小写字母、数字、`_` 和 `-`
+    pub id: String,
+    /// 插件显示名称
+    pub name: String,
+    pub author: String,
+    /// 动态库名称，默认由插件ID转换而来
+    #[serde(default)]
+    pub library_name: Option<String>,
+    #[serde(default)]
+    pub template: PluginTemplate,
+    /// 新插件 crate 的父目录，插件生成在 `<output_dir>/<id>` 中
+    pub output_dir: PathBuf,
+    /// 需要加入新 crate 的 workspace 目录（包含 `[workspace]` 的 Cargo.toml 所在目录）
+    #[serde(default)]
+    pub workspace: Option<PathBuf>,
+}
+
+/// 根据模板生成新的插件 crate，返回插件目录
+pub fn scaffold_plugin(options: &ScaffoldOptions) -> Result<PathBuf, String> {
+    validate_plugin_id(&options.id)?;
+    let name = options.name.trim();
+    if name.is_empty() {
+        return Err("插件名称不能为空".to_string());
+    }
+    let library_name = match options.library_name.as_deref().map(str::trim) {
+        Some(library_name) if !library_name.is_empty() => library_name.to_string(),
+        _ => options.id.replace('-', "_"),
+    };
+    validate_library_name(&library_name)?;
+
+    let plugin_dir = options.output_dir.join(&options.id);
+    if plugin_dir.exists() {
+        return Err(format!("目标目录已存在: {:?}", plugin_dir));
+    }
+
+    let author = options.author.trim();
+    let replacements = [
+        ("{{crate_name}}", options.id.replace('_', "-")),
+        ("{{plugin_id}}", options.id.clone()),
+        ("{{plugin_name}}", toml_string(name)),
+        ("{{author}}", toml_string(author)),
+        (
+            "{{description}}",
+            toml_string(options.template.description()),
+        ),
+        ("{{library_name}}", library_name),
+        ("{{struct_name}}", struct_name(&options.id)),
+        (
+            "{{dependencies}}",
+            options.template.dependencies().to_string(),
+        ),
+    ];
+
+    let files = [
+        ("Cargo.toml", CARGO_TOML_TEMPLATE),
+        ("config.toml", CONFIG_TOML_TEMPLATE),
+        ("src/lib.rs", options.template.source()),
+    ];
+
+    // 先确认 workspace 可写入，避免生成一半后失败
+    let workspace_manifest = match &options.workspace {
+        Some(workspace) => Some(workspace_member(workspace, &plugin_dir)?),
+        None => None,
+    };
+
+    fs::create_dir_all(plugin_dir.join("src")).map_err(|e| format!("创建插件目录失败: {}", e))?;
+    for (relative_path, template) in files {
+        let content = render(template, &replacements);
+        let path = plugin_dir.join(relative_path);
+        if let Err(e) = fs::write(&path, content) {
+            let _ = fs::remove_dir_all(&plugin_dir);
+            return Err(format!("写入文件失败 {:?}: {}", path, e));
+        }
+    }
+
+    if let Some((manifest_path, member)) = workspace_manifest {
+        add_workspace_member(&manifest_path, &member)?;
+        log_info!("已将 {} 加入 workspace {:?}", member, manifest_path);
+    }
+
+    log_info!("插件 {} 已生成: {:?}", options.id, plugin_dir);
+    Ok(plugin_dir)
+}
+
+fn render(template: &str, replacements: &[(&str, String)]) -> String {
+    replacements
+        .iter()
+        .fold(template.to_string(), |content, (placeholder, value)| {
+            content.replace(placeholder, value)
+        })
+}
+
+/// 转换为带引号并转义的 TOML 字符串
+fn toml_string(value: &str) -> String {
+    toml::Value::String(value.to_string()).to_string()
+}
+
+fn validate_plugin_id(id: &str) -> Result<(), String> {
+    let valid = id.chars().next().is_some_and(|c| c.is_ascii_lowercase())
+        && id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "无效的插件ID: {} (需以小写字母开头，只能包含小写字母、数字、_ 和 -)",
+            id
+        ))
+    }
+}
+
+fn validate_library_name(name: &str) -> Result<(), String> {
+    let valid = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if valid && name != "_" {
+        Ok(())
+    } else {
+        Err(format!(
+            "无效的动态库名称: {} (只能包含字母、数字和 _，且不能以数字开头)",
+            name
+        ))
+    }
+}
+
+/// 由插件ID生成结构体名称，例如 `my_echo-plugin` -> `MyEchoPlugin`
+fn struct_name(id: &str) -> String {
+    id.split(['_', '-'])
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// 返回 workspace 的 Cargo.toml 路径以及新 crate 相对于 workspace 的成员路径
+fn workspace_member(workspace: &Path, plugin_dir: &Path) -> Result<(PathBuf, String), String> {
+    let manifest_path = workspace.join("Cargo.toml");
+    let content = fs::read_to_string(&manifest_path)
+        .map_err(|e| format!("读取 workspace 配置失败 {:?}: {}", manifest_path, e))?;
+    let manifest: toml::Table =
+        toml::from_str(&content).map_err(|e| format!("解析 workspace 配置失败: {}", e))?;
+    if !manifest.contains_key("workspace") {
+        return Err(format!("{:?} 中没有 [workspace] 配置", manifest_path));
+    }
+
+    let workspace = absolute_path(workspace)?;
+    let plugin_dir = absolute_path(plugin_dir)?;
+    let relative = plugin_dir.strip_prefix(&workspace).map_err(|_| {
+        format!(
+            "插件目录 {:?} 不在 workspace {:?} 中",
+            plugin_dir, workspace
+        )
+    })?;
+    let member = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    Ok((manifest_path, member))
+}
+
+/// 转换为绝对路径并去掉 `.`、`..`（目标目录尚不存在，不能使用 canonicalize）
+fn absolute_path(path: &Path) -> Result<PathBuf, String> {
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .map_err(|e| format!("获取当前目录失败: {}", e))?
+            .join(path)
+    };
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    Ok(normalized)
+}
+
+/// 在 `[workspace] members` 数组中追加成员，保留文件其余内容和格式
+fn add_workspace_member(manifest_path: &Path, member: &str) -> Result<(), String> {
+    let content = fs::read_to_string(manifest_path)
+        .map_err(|e| format!("读取 workspace 配置失败 {:?}: {}", manifest_path, e))?;
+    let manifest: toml::Table =
+        toml::from_str(&content).map_err(|e| format!("解析 workspace 配置失败: {}", e))?;
+    let members = manifest
+        .get("workspace")
+        .and_then(|workspace| workspace.get("members"))
+        .and_then(|members| members.as_array());
+    if members.is_some_and(|members| members.iter().any(|m| m.as_str() == Some(member))) {
+        return Ok(());
+    }
+
+    let quoted = toml_string(member);
+    let updated = match members {
+        Some(_) => insert_into_members_array(&content, &quoted)
+            .ok_or_else(|| "无法定位 workspace members 数组".to_string())?,
+        None => {
+            // 没有 members 时，在 [workspace] 表头后插入
+            let header = content
+                .find("[workspace]")
+                .ok_or_else(|| "无法定位 [workspace] 表头".to_string())?;
+            let insert_at = header + "[workspace]".len();
+            format!(
+                "{}\nmembers = [{}]{}",
+                &content[..insert_at],
+                quoted,
+                &content[insert_at..]
+            )
+        }
+    };
+
+    fs::write(manifest_path, updated)
+        .map_err(|e| format!("写入 workspace 配置失败 {:?}: {}", manifest_path, e))
+}
+
+/// 在 `members = [...]` 的右括号前插入新成员
+fn insert_into_members_array(content: &str, quoted: &str) -> Option<String> {
+    let workspace_start = content.find("[workspace]")?;
+    let members_start = workspace_start + content[workspace_start..].find("members")?;
+    let open = members_start + content[members_start..].find('[')?;
+
+    // 跳过字符串中的字符，找到匹配的右括号
+    let mut in_string = false;
+    let mut close = None;
+    for (offset, c) in content[open + 1..].char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            ']' if !in_string => {
+                close = Some(open + 1 + offset);
+                break;
+            }
+            _ => {}
+        }
+    }
+    let close = close?;
+
+    let inner = &content[open + 1..close];
+    let trimmed = inner.trim_end();
+    let multiline = inner.contains('\n');
+    let insertion = match (trimmed.trim().is_empty(), trimmed.ends_with(','), multiline) {
+        (true, _, _) => quoted.to_string(),
+        (false, true, true) => format!("\n    {},", quoted),
+        (false, false, true) => format!(",\n    {},", quoted),
+        (false, true, false) => format!(" {}", quoted),
+        (false, false, false) => format!(", {}", quoted),
+    };
+    Some(format!(
+        "{}{}{}{}",
+        &content[..open + 1],
+        trimmed,
+        insertion,
+        &content[open + 1 + trimmed.len()..]
+    ))
+}
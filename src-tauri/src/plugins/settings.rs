@@ -0,0 +1,269 @@
+use plugin_interfaces::{log_info, log_warn};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::plugins::directories::{get_path_settings_file, get_settings_file};
+
+/// 后端持久化设置（保存在 settings.toml 中）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppSettings {
+    pub network: NetworkSettings,
+    pub packages: PackageSettings,
+    pub sanitize: SanitizeSettings,
+}
+
+/// 插件包安装设置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PackageSettings {
+    /// 受信任的 Ed25519 公钥（十六进制编码）
+    pub trusted_keys: Vec<String>,
+    /// 是否只允许安装带有受信任签名的插件包
+    pub require_signature: bool,
+}
+
+/// 插件消息的清理设置
+///
+/// 插件发送的消息和流式消息在交给前端渲染之前按这些设置清理，见 [`crate::plugins::sanitize`]。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SanitizeSettings {
+    /// 是否清理插件发送的 Markdown
+    pub enabled: bool,
+    /// 是否保留 `allowed_tags` 中的 HTML 标签；为 false 时所有原始 HTML 都按文本显示
+    pub allow_html: bool,
+    /// `allow_html` 开启时保留的 HTML 标签
+    pub allowed_tags: Vec<String>,
+    /// 链接和图片允许的 URL 协议，相对地址总是允许
+    pub allowed_url_schemes: Vec<String>,
+    /// 受信任的插件ID，这些插件发送的内容不经清理
+    pub trusted_plugins: Vec<String>,
+}
+
+impl Default for SanitizeSettings {
+    fn default() -> Self {
+        let list = |items: &[&str]| items.iter().map(|item| item.to_string()).collect();
+        Self {
+            enabled: true,
+            allow_html: false,
+            allowed_tags: list(&[
+                "a",
+                "b",
+                "i",
+                "em",
+                "strong",
+                "u",
+                "s",
+                "del",
+                "ins",
+                "sub",
+                "sup",
+                "mark",
+                "small",
+                "kbd",
+                "code",
+                "pre",
+                "br",
+                "hr",
+                "p",
+                "div",
+                "span",
+                "blockquote",
+                "ul",
+                "ol",
+                "li",
+                "table",
+                "thead",
+                "tbody",
+                "tr",
+                "th",
+                "td",
+                "h1",
+                "h2",
+                "h3",
+                "h4",
+                "h5",
+                "h6",
+                "img",
+                "details",
+                "summary",
+            ]),
+            allowed_url_schemes: list(&["http", "https", "mailto"]),
+            trusted_plugins: Vec::new(),
+        }
+    }
+}
+
+/// 网络访问设置，用于插件仓库同步和插件下载
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkSettings {
+    /// 代理地址，例如 `http://proxy.example.com:8080`；为空时使用系统代理环境变量
+    pub proxy: Option<String>,
+    /// 不经过代理的主机列表，仅在设置了 `proxy` 时生效
+    pub no_proxy: Vec<String>,
+    /// 额外信任的 CA 证书文件（PEM 格式，可包含多个证书）
+    pub ca_certificates: Vec<PathBuf>,
+    /// 自定义 User-Agent
+    pub user_agent: Option<String>,
+    /// 建立连接的超时时间（秒）
+    pub connect_timeout_secs: u64,
+    /// 两次读取数据之间的超时时间（秒）
+    pub read_timeout_secs: u64,
+    /// 下载遇到可恢复错误时的最大重试次数
+    pub download_retries: u32,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            no_proxy: Vec::new(),
+            ca_certificates: Vec::new(),
+            user_agent: None,
+            connect_timeout_secs: 15,
+            read_timeout_secs: 30,
+            download_retries: 5,
+        }
+    }
+}
+
+impl NetworkSettings {
+    /// 用环境变量覆盖设置项
+    ///
+    /// 支持 `CHAT_CLIENT_PROXY`、`CHAT_CLIENT_NO_PROXY`（逗号分隔）、
+    /// `CHAT_CLIENT_CA_CERTS`（按系统路径分隔符分隔）、`CHAT_CLIENT_USER_AGENT`、
+    /// `CHAT_CLIENT_CONNECT_TIMEOUT_SECS`、`CHAT_CLIENT_READ_TIMEOUT_SECS`
+    /// 和 `CHAT_CLIENT_DOWNLOAD_RETRIES`。重试次数超出范围时返回错误。
+    pub fn with_env_overrides(mut self) -> Result<Self, String> {
+        if let Some(proxy) = env_string("CHAT_CLIENT_PROXY") {
+            self.proxy = Some(proxy);
+        }
+        if let Some(no_proxy) = env_string("CHAT_CLIENT_NO_PROXY") {
+            self.no_proxy = no_proxy
+                .split(',')
+                .map(|host| host.trim().to_string())
+                .filter(|host| !host.is_empty())
+                .collect();
+        }
+        if let Some(paths) = std::env::var_os("CHAT_CLIENT_CA_CERTS") {
+            self.ca_certificates = std::env::split_paths(&paths).collect();
+        }
+        if let Some(user_agent) = env_string("CHAT_CLIENT_USER_AGENT") {
+            self.user_agent = Some(user_agent);
+        }
+        if let Some(secs) = env_number("CHAT_CLIENT_CONNECT_TIMEOUT_SECS") {
+            self.connect_timeout_secs = secs;
+        }
+        if let Some(secs) = env_number("CHAT_CLIENT_READ_TIMEOUT_SECS") {
+            self.read_timeout_secs = secs;
+        }
+        if let Some(retries) = env_number("CHAT_CLIENT_DOWNLOAD_RETRIES") {
+            self.download_retries = u32::try_from(retries).map_err(|_| {
+                format!(
+                    "环境变量 CHAT_CLIENT_DOWNLOAD_RETRIES 的值超出范围: {}",
+                    retries
+                )
+            })?;
+        }
+        Ok(self)
+    }
+}
+
+fn env_string(name: &str) -> Option<String> {
+    std::env::var(name)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+fn env_number(name: &str) -> Option<u64> {
+    let value = env_string(name)?;
+    match value.parse() {
+        Ok(number) => Some(number),
+        Err(_) => {
+            log_warn!("环境变量 {} 的值无效: {}", name, value);
+            None
+        }
+    }
+}
+
+/// 读取设置文件，文件不存在或解析失败时返回默认设置
+pub fn load_settings() -> AppSettings {
+    let settings_file = get_settings_file();
+    if !settings_file.exists() {
+        return AppSettings::default();
+    }
+
+    match std::fs::read_to_string(&settings_file) {
+        Ok(content) => match toml::from_str(&content) {
+            Ok(settings) => settings,
+            Err(e) => {
+                log_warn!("解析设置文件失败 {:?}: {}", settings_file, e);
+                AppSettings::default()
+            }
+        },
+        Err(e) => {
+            log_warn!("读取设置文件失败 {:?}: {}", settings_file, e);
+            AppSettings::default()
+        }
+    }
+}
+
+/// 保存设置文件
+pub fn save_settings(settings: &AppSettings) -> Result<(), String> {
+    let settings_file = get_settings_file();
+    if let Some(parent) = settings_file.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("创建设置目录失败: {}", e))?;
+    }
+
+    let content = toml::to_string_pretty(settings).map_err(|e| format!("序列化设置失败: {}", e))?;
+    std::fs::write(&settings_file, content).map_err(|e| format!("保存设置文件失败: {}", e))?;
+    log_info!("设置已保存: {:?}", settings_file);
+    Ok(())
+}
+
+/// 当前生效的网络设置（设置文件 + 环境变量覆盖）
+pub fn effective_network_settings() -> Result<NetworkSettings, String> {
+    load_settings().network.with_env_overrides()
+}
+
+/// 目录设置（保存在平台配置目录的 paths.toml 中，修改数据目录后需要重启应用）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PathSettings {
+    /// 数据根目录，为空时使用默认位置
+    pub data_dir: Option<PathBuf>,
+    /// 额外扫描的插件目录
+    pub plugin_dirs: Vec<PathBuf>,
+}
+
+/// 读取目录设置，文件不存在或解析失败时返回默认设置
+pub fn load_path_settings() -> PathSettings {
+    let path_settings_file = get_path_settings_file();
+    let Ok(content) = std::fs::read_to_string(&path_settings_file) else {
+        return PathSettings::default();
+    };
+    match toml::from_str(&content) {
+        Ok(settings) => settings,
+        Err(e) => {
+            log_warn!("解析目录设置失败 {:?}: {}", path_settings_file, e);
+            PathSettings::default()
+        }
+    }
+}
+
+/// 保存目录设置
+pub fn save_path_settings(settings: &PathSettings) -> Result<(), String> {
+    let path_settings_file = get_path_settings_file();
+    if let Some(parent) = path_settings_file.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("创建配置目录失败: {}", e))?;
+    }
+
+    let content =
+        toml::to_string_pretty(settings).map_err(|e| format!("序列化目录设置失败: {}", e))?;
+    std::fs::write(&path_settings_file, content).map_err(|e| format!("保存目录设置失败: {}", e))?;
+    log_info!("目录设置已保存: {:?}", path_settings_file);
+    Ok(())
+}
@@ -0,0 +1,207 @@
+use plugin_interfaces::log_error;
+use plugin_interfaces::metadata::HistoryMessage;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// 跟踪文件格式版本
+pub const TRACE_FORMAT_VERSION: u32 = 1;
+
+/// 宿主对插件实例的一次调用
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum TraceCall {
+    Mount {
+        plugin_id: String,
+        instance_id: String,
+    },
+    Dispose {
+        instance_id: String,
+    },
+    Connect {
+        instance_id: String,
+    },
+    Disconnect {
+        instance_id: String,
+    },
+    SendMessage {
+        plugin_id: String,
+        instance_id: String,
+        message: String,
+        history: Option<Vec<HistoryMessage>>,
+    },
+    GetUi {
+        instance_id: String,
+    },
+    UiUpdate {
+        instance_id: String,
+        component_id: String,
+        value: String,
+    },
+    UiEvent {
+        instance_id: String,
+        component_id: String,
+        value: String,
+    },
+    CancelStream {
+        instance_id: String,
+        stream_id: String,
+    },
+}
+
+/// 跟踪文件中的一条记录
+///
+/// 宿主调用记为一对 `call` / `return`（以 `seq` 关联），
+/// 插件在调用期间或之后发往前端的事件记为 `event`。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TraceRecord {
+    Start {
+        version: u32,
+    },
+    Call {
+        seq: u64,
+        call: TraceCall,
+    },
+    Return {
+        seq: u64,
+        result: Result<serde_json::Value, String>,
+    },
+    Event {
+        event: String,
+        payload: String,
+    },
+}
+
+/// 带时间戳的跟踪记录
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceEntry {
+    /// 记录时间（Unix 毫秒）
+    pub timestamp: u64,
+    /// 距跟踪开始的毫秒数，回放时用于还原调用间隔
+    pub elapsed_ms: u64,
+    #[serde(flatten)]
+    pub record: TraceRecord,
+}
+
+/// 把宿主与插件之间的往来写入 JSON Lines 跟踪文件
+#[derive(Debug)]
+pub struct TraceRecorder {
+    path: PathBuf,
+    started: Instant,
+    next_seq: AtomicU64,
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl TraceRecorder {
+    /// 创建跟踪文件（已存在时覆盖）
+    pub fn create(path: &Path) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("创建跟踪目录失败: {}", e))?;
+        }
+        let file = File::create(path).map_err(|e| format!("创建跟踪文件失败: {}", e))?;
+        let recorder = Self {
+            path: path.to_path_buf(),
+            started: Instant::now(),
+            next_seq: AtomicU64::new(1),
+            writer: Mutex::new(BufWriter::new(file)),
+        };
+        recorder.write(TraceRecord::Start {
+            version: TRACE_FORMAT_VERSION,
+        });
+        Ok(recorder)
+    }
+
+    /// 跟踪文件路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 记录一次宿主调用，返回用于关联返回值的序号
+    pub fn record_call(&self, call: TraceCall) -> u64 {
+        let seq = self.next_seq.fetch_add(1, Ordering::SeqCst);
+        self.write(TraceRecord::Call { seq, call });
+        seq
+    }
+
+    /// 记录宿主调用的返回值
+    pub fn record_return<T: Serialize>(&self, seq: u64, result: &Result<T, String>) {
+        let result = match result {
+            Ok(value) => serde_json::to_value(value).map_err(|e| e.to_string()),
+            Err(e) => Err(e.clone()),
+        };
+        self.write(TraceRecord::Return { seq, result });
+    }
+
+    /// 记录插件发往前端的事件
+    pub fn record_event(&self, event: &str, payload: &str) {
+        self.write(TraceRecord::Event {
+            event: event.to_string(),
+            payload: payload.to_string(),
+        });
+    }
+
+    /// 将缓冲区写入文件
+    pub fn flush(&self) -> Result<(), String> {
+        self.writer
+            .lock()
+            .unwrap()
+            .flush()
+            .map_err(|e| format!("写入跟踪文件失败: {}", e))
+    }
+
+    fn write(&self, record: TraceRecord) {
+        let entry = TraceEntry {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as u64,
+            elapsed_ms: self.started.elapsed().as_millis() as u64,
+            record,
+        };
+        let line = match serde_json::to_string(&entry) {
+            Ok(line) => line,
+            Err(e) => {
+                log_error!("序列化跟踪记录失败: {}", e);
+                return;
+            }
+        };
+
+        let mut writer = self.writer.lock().unwrap();
+        // 逐条刷新，应用异常退出时跟踪文件仍然完整
+        if let Err(e) = writeln!(writer, "{}", line).and_then(|_| writer.flush()) {
+            log_error!("写入跟踪文件 {:?} 失败: {}", self.path, e);
+        }
+    }
+}
+
+impl Drop for TraceRecorder {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// 读取跟踪文件
+pub fn load_trace(path: &Path) -> Result<Vec<TraceEntry>, String> {
+    let file = File::open(path).map_err(|e| format!("打开跟踪文件 {:?} 失败: {}", path, e))?;
+    let mut entries = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| format!("读取跟踪文件失败: {}", e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: TraceEntry = serde_json::from_str(&line)
+            .map_err(|e| format!("跟踪文件第 {} 行格式错误: {}", index + 1, e))?;
+        if let TraceRecord::Start { version } = entry.record {
+            if version > TRACE_FORMAT_VERSION {
+                return Err(format!("不支持的跟踪文件版本: {}", version));
+            }
+        }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
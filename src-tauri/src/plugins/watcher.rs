@@ -0,0 +1,187 @@
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use plugin_interfaces::{log_error, log_info, log_warn};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use crate::plugins::{
+    builder::{cargo_build, BuildLogLine, BuildLogStream, CargoBuild},
+    install::plugin_library_name,
+    linked::{load_linked_plugins, LinkedPlugin},
+    loader::library_file_name,
+    manager::PluginManager,
+};
+
+/// 文件变化后等待多久没有新的变化才开始构建
+const REBUILD_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// 构建日志回调
+pub type BuildLogCallback = Arc<dyn Fn(&BuildLogLine) + Send + Sync>;
+
+/// 正在监听的关联目录，键为目录路径
+///
+/// 移除监听器会关闭事件通道，对应的构建线程随之退出。
+static LINKED_WATCHERS: OnceLock<Mutex<HashMap<PathBuf, RecommendedWatcher>>> = OnceLock::new();
+
+fn get_linked_watchers() -> &'static Mutex<HashMap<PathBuf, RecommendedWatcher>> {
+    LINKED_WATCHERS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// 为所有开启了自动构建的关联目录启动监听（应用启动时调用）
+pub fn start_linked_plugin_watchers(manager: Arc<PluginManager>, on_log: BuildLogCallback) {
+    for linked in load_linked_plugins() {
+        if !linked.auto_rebuild {
+            continue;
+        }
+        if let Err(e) = watch_linked_plugin(&linked, Arc::clone(&manager), Arc::clone(&on_log)) {
+            log_warn!("监听关联目录失败 {:?}: {}", linked.path, e);
+        }
+    }
+}
+
+/// 监听关联目录，文件变化后重新构建并热重载插件
+pub fn watch_linked_plugin(
+    linked: &LinkedPlugin,
+    manager: Arc<PluginManager>,
+    on_log: BuildLogCallback,
+) -> Result<(), String> {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(sender).map_err(|e| format!("创建文件监听器失败: {}", e))?;
+    watcher
+        .watch(&linked.path, RecursiveMode::Recursive)
+        .map_err(|e| format!("监听目录失败: {}", e))?;
+
+    let path = linked.path.clone();
+    std::thread::spawn(move || rebuild_loop(path, receiver, manager, on_log));
+
+    get_linked_watchers()
+        .lock()
+        .unwrap()
+        .insert(linked.path.clone(), watcher);
+    log_info!("开始监听关联目录: {:?}", linked.path);
+    Ok(())
+}
+
+/// 停止监听关联目录
+pub fn unwatch_linked_plugin(path: &Path) {
+    if get_linked_watchers().lock().unwrap().remove(path).is_some() {
+        log_info!("停止监听关联目录: {:?}", path);
+    }
+}
+
+/// 构建关联目录中的插件并热重载已挂载的实例
+///
+/// 使用 debug 配置构建到 `<目录>/target`，构建产物复制为 `PluginLoader` 查找的文件名。
+pub async fn rebuild_linked_plugin(
+    path: &Path,
+    manager: &PluginManager,
+    on_log: &(dyn Fn(&BuildLogLine) + Send + Sync),
+) -> Result<String, String> {
+    let linked = LinkedPlugin {
+        path: path.to_path_buf(),
+        auto_rebuild: true,
+        plugin_id: None,
+    };
+    let config = linked.config()?;
+    let plugin_id = config.plugin.id.clone();
+    let log = |stream: BuildLogStream, line: String| {
+        on_log(&BuildLogLine {
+            build_id: format!("link-{}", plugin_id),
+            plugin_id: plugin_id.clone(),
+            stream,
+            line,
+        })
+    };
+
+    let target_dir = path.join("target");
+    let library_path = cargo_build(
+        &CargoBuild {
+            manifest_path: &path.join("Cargo.toml"),
+            target_dir: &target_dir,
+            release: false,
+            package: None,
+        },
+        || false,
+        log,
+    )
+    .await?;
+
+    let expected_path = target_dir
+        .join("debug")
+        .join(library_file_name(&plugin_library_name(&config)));
+    if library_path != expected_path {
+        // 已挂载的实例可能正映射着 expected_path，先复制到同目录的临时文件再替换，
+        // 不能就地覆盖正在使用的动态库
+        let temp_path = expected_path.with_extension("tmp");
+        std::fs::copy(&library_path, &temp_path)
+            .map_err(|e| format!("复制构建产物失败: {}", e))?;
+        if let Err(e) = std::fs::rename(&temp_path, &expected_path) {
+            let _ = std::fs::remove_file(&temp_path);
+            return Err(format!("替换构建产物失败: {}", e));
+        }
+    }
+
+    let message = manager.reload_plugin(&plugin_id)?;
+    log(BuildLogStream::Status, message.clone());
+    Ok(message)
+}
+
+/// 构建线程：合并短时间内的多次变化，然后构建一次
+fn rebuild_loop(
+    path: PathBuf,
+    receiver: Receiver<notify::Result<notify::Event>>,
+    manager: Arc<PluginManager>,
+    on_log: BuildLogCallback,
+) {
+    let runtime = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            log_error!("创建构建运行时失败: {}", e);
+            return;
+        }
+    };
+
+    while let Ok(event) = receiver.recv() {
+        match event {
+            Ok(event) if is_source_change(&path, &event) => {}
+            Ok(_) => continue,
+            Err(e) => {
+                log_warn!("文件监听出错 {:?}: {}", path, e);
+                continue;
+            }
+        }
+
+        loop {
+            match receiver.recv_timeout(REBUILD_DEBOUNCE) {
+                Ok(_) => continue,
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+
+        log_info!("关联目录发生变化，开始重新构建: {:?}", path);
+        if let Err(e) = runtime.block_on(rebuild_linked_plugin(&path, &manager, &*on_log)) {
+            log_error!("重新构建关联插件失败 {:?}: {}", path, e);
+        }
+    }
+}
+
+/// 是否为需要重新构建的源码变化（忽略构建输出和版本控制目录）
+fn is_source_change(root: &Path, event: &notify::Event) -> bool {
+    if matches!(event.kind, EventKind::Access(_)) {
+        return false;
+    }
+    event.paths.iter().any(|path| {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        !matches!(
+            relative.components().next(),
+            Some(Component::Normal(name)) if name == "target" || name == ".git"
+        )
+    })
+}
@@ -0,0 +1,197 @@
+//! 集成测试公共部分：构建示例插件并准备隔离的数据目录
+//!
+//! 示例插件只构建一次；数据根目录和插件目录指向临时目录，不会读写用户的真实数据。
+//! 插件通过 FFI 加载，测试之间共享进程级的目录设置，因此用 `lock` 让测试依次执行。
+
+#![allow(dead_code)]
+
+use chat_client_lib::plugins::{
+    builder::{cargo_build, CargoBuild},
+    directories, EventSink, PluginEvent, PluginHandler, PluginManager, RecordingEventSink,
+};
+use plugin_interfaces::{
+    pluginui::{Context, Ui},
+    PluginInstanceContext,
+};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+pub const EXAMPLE_PLUGIN_ID: &str = "example_plugin";
+
+/// 构建好的示例插件和临时目录
+pub struct Harness {
+    _temp_dir: TempDir,
+    /// 示例插件所在的插件目录（包含 config.toml 和动态库）
+    pub plugin_dir: PathBuf,
+    /// 构建出的动态库
+    pub library_path: PathBuf,
+    pub data_root: PathBuf,
+}
+
+static HARNESS: OnceLock<Harness> = OnceLock::new();
+static TEST_LOCK: Mutex<()> = Mutex::new(());
+
+/// 获取测试环境，第一次调用时构建示例插件
+pub fn harness() -> &'static Harness {
+    HARNESS.get_or_init(|| Harness::setup().expect("Failed to set up plugin test harness"))
+}
+
+/// 让测试依次执行，前一个测试失败时锁被污染也继续使用
+pub fn lock() -> MutexGuard<'static, ()> {
+    TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl Harness {
+    fn setup() -> Result<Self, String> {
+        let workspace = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let example_dir = workspace.join("src").join("plugins").join("example");
+
+        let runtime = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
+        let library_path = runtime.block_on(cargo_build(
+            &CargoBuild {
+                manifest_path: &example_dir.join("Cargo.toml"),
+                target_dir: &workspace.join("target"),
+                release: false,
+                package: None,
+            },
+            || false,
+            |_, line| eprintln!("{}", line),
+        ))?;
+
+        let temp_dir = TempDir::new().map_err(|e| e.to_string())?;
+        let data_root = temp_dir.path().join("data");
+        let plugins_root = temp_dir.path().join("plugins");
+        let plugin_dir = plugins_root.join("example");
+        std::fs::create_dir_all(&plugin_dir).map_err(|e| e.to_string())?;
+        std::fs::copy(
+            example_dir.join("config.toml"),
+            plugin_dir.join("config.toml"),
+        )
+        .map_err(|e| e.to_string())?;
+        let library_file = library_path.file_name().ok_or("动态库路径无效")?;
+        std::fs::copy(&library_path, plugin_dir.join(library_file)).map_err(|e| e.to_string())?;
+
+        directories::set_data_root_override(Some(data_root.clone()));
+        directories::set_plugin_directory_overrides(vec![plugins_root]);
+
+        Ok(Self {
+            _temp_dir: temp_dir,
+            plugin_dir,
+            library_path,
+            data_root,
+        })
+    }
+
+    /// 创建使用记录事件接收方的插件管理器
+    pub fn manager(&self) -> (PluginManager, Arc<RecordingEventSink>) {
+        let sink = Arc::new(RecordingEventSink::new());
+        let manager = PluginManager::new(Arc::clone(&sink) as Arc<dyn EventSink>);
+        (manager, sink)
+    }
+}
+
+/// 等待满足条件的事件出现，超时返回 None
+pub fn wait_for_event<F>(
+    sink: &RecordingEventSink,
+    timeout: Duration,
+    predicate: F,
+) -> Option<PluginEvent>
+where
+    F: Fn(&PluginEvent, &Value) -> bool,
+{
+    let deadline = Instant::now() + timeout;
+    loop {
+        let found = sink
+            .events()
+            .into_iter()
+            .find(|event| event.json().is_ok_and(|payload| predicate(event, &payload)));
+        if found.is_some() || Instant::now() >= deadline {
+            return found;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+/// 按显示文本查找 UI 组件ID（包括水平布局中的子组件）
+pub fn find_component_id(ui_json: &str, text: &str) -> Option<String> {
+    let components: Vec<Value> = serde_json::from_str(ui_json).ok()?;
+    find_in_components(&components, text)
+}
+
+fn find_in_components(components: &[Value], text: &str) -> Option<String> {
+    components.iter().find_map(|component| {
+        let body = &component["component"];
+        if body["text"].as_str() == Some(text) {
+            return component["id"].as_str().map(str::to_string);
+        }
+        body["children"]
+            .as_array()
+            .and_then(|children| find_in_components(children, text))
+    })
+}
+
+/// 测试用的内置插件：用 `reply` 生成回复，同时把回复发给前端
+///
+/// ```ignore
+/// register_builtin_plugin(BuiltinPlugin {
+///     create: || Box::new(TestPlugin::new("Echo", str::to_string)),
+///     ..
+/// });
+/// ```
+pub struct TestPlugin {
+    label: &'static str,
+    reply: fn(&str) -> String,
+}
+
+impl TestPlugin {
+    pub fn new(label: &'static str, reply: fn(&str) -> String) -> Self {
+        Self { label, reply }
+    }
+}
+
+impl PluginHandler for TestPlugin {
+    fn update_ui(&mut self, _ctx: &Context, ui: &mut Ui, _plugin_ctx: &PluginInstanceContext) {
+        ui.label(self.label);
+    }
+
+    fn on_mount(
+        &mut self,
+        _plugin_ctx: &PluginInstanceContext,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    fn on_dispose(
+        &mut self,
+        _plugin_ctx: &PluginInstanceContext,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    fn on_connect(
+        &mut self,
+        _plugin_ctx: &PluginInstanceContext,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    fn on_disconnect(
+        &mut self,
+        _plugin_ctx: &PluginInstanceContext,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    fn handle_message(
+        &mut self,
+        message: &str,
+        plugin_ctx: &PluginInstanceContext,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let reply = (self.reply)(message);
+        plugin_ctx.send_message_to_frontend(&reply);
+        Ok(reply)
+    }
+}
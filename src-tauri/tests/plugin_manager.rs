@@ -0,0 +1,357 @@
+//! 通过 `PluginManager` 驱动真实的示例插件动态库
+
+mod common;
+
+use chat_client_lib::plugins::{
+    load_trace, package::host_target, register_builtin_plugin, replay_trace, BuiltinPlugin,
+    PackageBuilder, PluginLoader, PluginRepository, ReplayOptions, TraceRecord,
+};
+use common::{find_component_id, harness, lock, wait_for_event, TestPlugin, EXAMPLE_PLUGIN_ID};
+use std::time::Duration;
+
+#[test]
+fn loader_finds_example_plugin() {
+    let _guard = lock();
+    let harness = harness();
+
+    let plugins = PluginLoader::new().scan_plugins();
+    let example = plugins
+        .iter()
+        .find(|plugin| plugin.id == EXAMPLE_PLUGIN_ID)
+        .expect("example plugin not found");
+
+    let library_path = example.library_path.as_deref().expect("library not found");
+    assert!(library_path.starts_with(harness.plugin_dir.to_str().unwrap()));
+}
+
+#[test]
+fn mount_connect_message_and_dispose() {
+    let _guard = lock();
+    let (manager, sink) = harness().manager();
+
+    let instance_id = "lifecycle".to_string();
+    manager
+        .mount_plugin(EXAMPLE_PLUGIN_ID, Some(instance_id.clone()))
+        .unwrap();
+    assert_eq!(manager.get_plugin_status(&instance_id), Some((true, false)));
+
+    // 未连接时不能发送消息
+    assert!(manager
+        .send_message_to_plugin_instance(EXAMPLE_PLUGIN_ID, &instance_id, "hello", None)
+        .is_err());
+
+    manager.connect_plugin(&instance_id).unwrap();
+    assert_eq!(manager.get_plugin_status(&instance_id), Some((true, true)));
+
+    let response = manager
+        .send_message_to_plugin_instance(EXAMPLE_PLUGIN_ID, &instance_id, "hello", None)
+        .unwrap();
+    assert!(
+        response.contains("hello"),
+        "unexpected response: {}",
+        response
+    );
+
+    let message = wait_for_event(&sink, Duration::from_secs(5), |event, payload| {
+        event.event == "plugin-message" && payload["instance_id"] == instance_id.as_str()
+    })
+    .expect("plugin-message event not received");
+    let payload = message.json().unwrap();
+    assert!(payload["content"].as_str().unwrap().contains("hello"));
+
+    manager.disconnect_plugin(&instance_id).unwrap();
+    assert_eq!(manager.get_plugin_status(&instance_id), Some((true, false)));
+
+    manager.dispose_plugin(&instance_id).unwrap();
+    assert_eq!(
+        manager.get_plugin_status(&instance_id),
+        Some((false, false))
+    );
+    assert!(manager.connect_plugin(&instance_id).is_err());
+}
+
+#[test]
+fn ui_events_update_ui_and_send_messages() {
+    let _guard = lock();
+    let (manager, sink) = harness().manager();
+
+    let instance_id = "ui".to_string();
+    manager
+        .mount_plugin(EXAMPLE_PLUGIN_ID, Some(instance_id.clone()))
+        .unwrap();
+    manager.connect_plugin(&instance_id).unwrap();
+
+    let ui = manager.get_plugin_ui(&instance_id).unwrap();
+    assert!(ui.contains("Test Plugin"));
+    let button = find_component_id(&ui, "Short").expect("Short button not found");
+
+    assert!(manager
+        .handle_plugin_ui_event(&instance_id, &button, "")
+        .unwrap());
+    wait_for_event(&sink, Duration::from_secs(5), |event, payload| {
+        event.event == "plugin-message" && payload["content"] == "Test Message"
+    })
+    .expect("button click did not send a message");
+    wait_for_event(&sink, Duration::from_secs(5), |event, payload| {
+        event.event == "plugin-ui-updated" && payload["instance"] == instance_id.as_str()
+    })
+    .expect("plugin-ui-updated event not received");
+
+    manager.dispose_plugin(&instance_id).unwrap();
+}
+
+#[test]
+fn stream_can_be_cancelled() {
+    let _guard = lock();
+    let (manager, sink) = harness().manager();
+
+    let instance_id = "stream".to_string();
+    manager
+        .mount_plugin(EXAMPLE_PLUGIN_ID, Some(instance_id.clone()))
+        .unwrap();
+    manager.connect_plugin(&instance_id).unwrap();
+
+    let ui = manager.get_plugin_ui(&instance_id).unwrap();
+    let button = find_component_id(&ui, "Background Stream Demo").unwrap();
+    manager
+        .handle_plugin_ui_event(&instance_id, &button, "")
+        .unwrap();
+
+    let start = wait_for_event(&sink, Duration::from_secs(5), |event, payload| {
+        event.event == "plugin-stream" && payload["type"] == "stream_start"
+    })
+    .expect("stream_start not received");
+    let stream_id = start.json().unwrap()["data"]["stream_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    wait_for_event(&sink, Duration::from_secs(5), |_, payload| {
+        payload["type"] == "stream_data" && payload["data"]["stream_id"] == stream_id.as_str()
+    })
+    .expect("stream_data not received");
+
+    manager
+        .cancel_stream_message(&instance_id, &stream_id)
+        .unwrap();
+    wait_for_event(&sink, Duration::from_secs(5), |_, payload| {
+        payload["type"] == "stream_cancel" && payload["data"]["stream_id"] == stream_id.as_str()
+    })
+    .expect("stream_cancel not received");
+
+    // 示例插件每 2 秒发送一块数据，取消后的数据块会被拒绝
+    let chunks_before = sink.events_named("plugin-stream").len();
+    std::thread::sleep(Duration::from_millis(2500));
+    assert_eq!(sink.events_named("plugin-stream").len(), chunks_before);
+    assert!(manager
+        .cancel_stream_message(&instance_id, &stream_id)
+        .is_err());
+
+    manager.dispose_plugin(&instance_id).unwrap();
+}
+
+#[test]
+fn managers_receive_only_their_own_events() {
+    let _guard = lock();
+    let harness = harness();
+    let (first, first_sink) = harness.manager();
+    let (second, second_sink) = harness.manager();
+
+    for (manager, instance_id) in [(&first, "first"), (&second, "second")] {
+        manager
+            .mount_plugin(EXAMPLE_PLUGIN_ID, Some(instance_id.to_string()))
+            .unwrap();
+        manager.connect_plugin(instance_id).unwrap();
+    }
+
+    first
+        .send_message_to_plugin_instance(EXAMPLE_PLUGIN_ID, "first", "ping", None)
+        .unwrap();
+    wait_for_event(&first_sink, Duration::from_secs(5), |event, _| {
+        event.event == "plugin-message"
+    })
+    .expect("first manager did not receive its event");
+    assert!(second_sink.events_named("plugin-message").is_empty());
+
+    first.dispose_plugin("first").unwrap();
+    second.dispose_plugin("second").unwrap();
+}
+
+#[test]
+fn repository_installs_and_uninstalls_library() {
+    let _guard = lock();
+    let harness = harness();
+    let repository = PluginRepository::new();
+
+    let config_path = harness.plugin_dir.join("config.toml");
+    let result = repository
+        .install_plugin_from_file(harness.library_path.to_str().unwrap(), config_path.to_str());
+    assert!(result.success, "install failed: {}", result.message);
+    let installed_path = result.installed_path.expect("installed path missing");
+    assert!(installed_path.starts_with(harness.data_root.to_str().unwrap()));
+
+    let result = repository.uninstall_plugin(EXAMPLE_PLUGIN_ID);
+    assert!(result.success, "uninstall failed: {}", result.message);
+}
+
+#[test]
+fn repository_installs_verified_package() {
+    let _guard = lock();
+    let harness = harness();
+    let repository = PluginRepository::new();
+
+    let package_path = harness.data_root.join("example_plugin.ccpkg");
+    std::fs::create_dir_all(&harness.data_root).unwrap();
+    PackageBuilder::new(harness.plugin_dir.join("config.toml"))
+        .unwrap()
+        .library(&host_target(), &harness.library_path)
+        .write(&package_path)
+        .unwrap();
+
+    let result = repository.install_plugin_from_file(package_path.to_str().unwrap(), None);
+    assert!(result.success, "install failed: {}", result.message);
+    let installed_path = result.installed_path.expect("installed path missing");
+    assert!(std::path::Path::new(&installed_path)
+        .join("config.toml")
+        .is_file());
+
+    let result = repository.uninstall_plugin(EXAMPLE_PLUGIN_ID);
+    assert!(result.success, "uninstall failed: {}", result.message);
+    let _ = std::fs::remove_file(&package_path);
+}
+
+#[test]
+fn recorded_trace_replays_without_differences() {
+    let _guard = lock();
+    let harness = harness();
+    let (manager, sink) = harness.manager();
+    let trace_path = harness.data_root.join("traces").join("replay.jsonl");
+    manager.start_trace(&trace_path).unwrap();
+
+    let instance_id = "trace".to_string();
+    manager
+        .mount_plugin(EXAMPLE_PLUGIN_ID, Some(instance_id.clone()))
+        .unwrap();
+    manager.connect_plugin(&instance_id).unwrap();
+    manager
+        .send_message_to_plugin_instance(EXAMPLE_PLUGIN_ID, &instance_id, "hello", None)
+        .unwrap();
+    let ui = manager.get_plugin_ui(&instance_id).unwrap();
+    let button = find_component_id(&ui, "Short").unwrap();
+    manager
+        .handle_plugin_ui_event(&instance_id, &button, "")
+        .unwrap();
+    wait_for_event(&sink, Duration::from_secs(5), |event, payload| {
+        event.event == "plugin-message" && payload["content"] == "Test Message"
+    })
+    .expect("button click did not send a message");
+    manager.dispose_plugin(&instance_id).unwrap();
+    assert_eq!(manager.stop_trace(), Some(trace_path.clone()));
+
+    let entries = load_trace(&trace_path).unwrap();
+    let calls = entries
+        .iter()
+        .filter(|entry| matches!(entry.record, TraceRecord::Call { .. }))
+        .count();
+    assert_eq!(calls, 6);
+    assert!(entries
+        .iter()
+        .any(|entry| matches!(&entry.record, TraceRecord::Event { event, .. } if event == "plugin-message")));
+
+    let options = ReplayOptions {
+        realtime: false,
+        ..ReplayOptions::default()
+    };
+    let report = replay_trace(&trace_path, &options).unwrap();
+    assert_eq!(report.calls, 6);
+    assert!(
+        report.is_identical(),
+        "differences: {:#?}",
+        report.differences
+    );
+}
+
+#[test]
+fn builtin_plugin_mounts_without_library() {
+    let _guard = lock();
+    let (manager, sink) = harness().manager();
+    register_builtin_plugin(BuiltinPlugin {
+        id: "test-length",
+        name: "Length",
+        version: "0.1.0",
+        description: "Replies with the message length",
+        author: "tests",
+        require_history: false,
+        create: || {
+            Box::new(TestPlugin::new("Length", |message| {
+                format!("length: {}", message.chars().count())
+            }))
+        },
+    });
+
+    let plugins = manager.scan_plugins();
+    let builtin = plugins
+        .iter()
+        .find(|plugin| plugin.id == "test-length")
+        .expect("built-in plugin not listed");
+    assert!(builtin.config_path.starts_with("builtin:"));
+
+    let instance_id = "builtin".to_string();
+    manager
+        .mount_plugin("test-length", Some(instance_id.clone()))
+        .unwrap();
+    manager.connect_plugin(&instance_id).unwrap();
+    let response = manager
+        .send_message_to_plugin_instance("test-length", &instance_id, "hello", None)
+        .unwrap();
+    assert!(
+        response.contains("length: 5"),
+        "unexpected response: {}",
+        response
+    );
+    wait_for_event(&sink, Duration::from_secs(5), |event, payload| {
+        event.event == "plugin-message" && payload["content"] == "length: 5"
+    })
+    .expect("plugin-message event not received");
+
+    // 内置插件同样可以热重载
+    manager.reload_plugin("test-length").unwrap();
+    assert_eq!(manager.get_plugin_status(&instance_id), Some((true, true)));
+    manager.dispose_plugin(&instance_id).unwrap();
+}
+
+#[test]
+fn plugin_messages_are_sanitized() {
+    let _guard = lock();
+    let (manager, sink) = harness().manager();
+    register_builtin_plugin(BuiltinPlugin {
+        id: "test-echo",
+        name: "Echo",
+        version: "0.1.0",
+        description: "Echoes messages to the frontend",
+        author: "tests",
+        require_history: false,
+        create: || Box::new(TestPlugin::new("Echo", str::to_string)),
+    });
+
+    let instance_id = "echo".to_string();
+    manager
+        .mount_plugin("test-echo", Some(instance_id.clone()))
+        .unwrap();
+    manager.connect_plugin(&instance_id).unwrap();
+    let message =
+        "<img src=x onerror=alert(1)> [x](javascript:alert(1))\n```html\n<b>code</b>\n```";
+    manager
+        .send_message_to_plugin_instance("test-echo", &instance_id, message, None)
+        .unwrap();
+
+    let event = wait_for_event(&sink, Duration::from_secs(5), |event, _| {
+        event.event == "plugin-message"
+    })
+    .expect("plugin-message event not received");
+    let payload: serde_json::Value = serde_json::from_str(&event.payload).unwrap();
+    let content = payload["content"].as_str().unwrap();
+    assert!(content.starts_with("&lt;img src=x onerror=alert(1)> [x]()"));
+    // 围栏代码块原样保留
+    assert!(content.ends_with("```html\n<b>code</b>\n```"));
+    manager.dispose_plugin(&instance_id).unwrap();
+}
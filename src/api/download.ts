@@ -3,18 +3,56 @@
  */
 
 import { invoke } from '@tauri-apps/api/core'
-import type { DownloadResponse } from './types'
+import { listen, UnlistenFn } from '@tauri-apps/api/event'
+import type { DownloadResponse, DownloadProgress, BuildLogLine } from './types'
 
 /**
  * 下载GitHub仓库到plugins目录
+ * @param downloadId 可选的下载ID，用于接收进度事件和取消下载
  * @returns Promise<DownloadResponse> 下载结果
  */
-export async function downloadGithubRepo(): Promise<DownloadResponse> {
+export async function downloadGithubRepo(downloadId?: string): Promise<DownloadResponse> {
   try {
-    const response = await invoke<DownloadResponse>('download_github_repo')
+    const response = await invoke<DownloadResponse>('download_github_repo', {
+      downloadId: downloadId || null
+    })
     return response
   } catch (error) {
     console.error('Failed to download GitHub repo:', error)
     throw error
   }
 }
+
+/**
+ * 监听下载进度事件
+ * @param callback 进度回调
+ * @returns Promise<UnlistenFn> 取消监听的函数
+ */
+export async function listenDownloadProgress(
+  callback: (progress: DownloadProgress) => void
+): Promise<UnlistenFn> {
+  return await listen<string>('plugin-download-progress', (event) => {
+    try {
+      callback(JSON.parse(event.payload) as DownloadProgress)
+    } catch (e) {
+      console.error('Failed to parse plugin-download-progress event:', e)
+    }
+  })
+}
+
+/**
+ * 监听源码构建日志事件（当前平台没有预编译版本时插件会从源码构建）
+ * @param callback 日志回调，每次一行
+ * @returns Promise<UnlistenFn> 取消监听的函数
+ */
+export async function listenBuildLog(
+  callback: (line: BuildLogLine) => void
+): Promise<UnlistenFn> {
+  return await listen<string>('plugin-build-log', (event) => {
+    try {
+      callback(JSON.parse(event.payload) as BuildLogLine)
+    } catch (e) {
+      console.error('Failed to parse plugin-build-log event:', e)
+    }
+  })
+}
@@ -18,8 +18,26 @@ export {
   sendMessageToCurrentPlugin,
   scanAvailablePlugins,
   downloadPlugin,
+  installPluginFromFile,
+  installPluginFromGit,
+  updatePlugin,
   uninstallPlugin,
-  cancelStreamMessage
+  getPluginPermissions,
+  approvePluginPermissions,
+  cancelStreamMessage,
+  cancelDownload,
+  reloadPlugin,
+  listLinkedPlugins,
+  linkPluginDirectory,
+  unlinkPluginDirectory,
+  scaffoldPlugin,
+  rebuildLinkedPlugin,
+  startPluginTrace,
+  stopPluginTrace,
+  getPluginTracePath,
+  replayPluginTrace,
+  getPluginResourceUsage,
+  listenPluginEvent
 } from './plugins'
 
 // 导出插件UI相关 API
@@ -33,7 +51,18 @@ export {
 export { setupEventListeners, cleanupEventListeners } from './listener'
 
 // 导出下载相关 API
-export { downloadGithubRepo } from './download'
+export { downloadGithubRepo, listenDownloadProgress, listenBuildLog } from './download'
+
+// 导出后端设置相关 API
+export {
+  getNetworkSettings,
+  setNetworkSettings,
+  getSanitizeSettings,
+  setSanitizeSettings,
+  getPathSettings,
+  setPathSettings,
+  getEffectiveDirectories
+} from './settings'
 
 // 导出常用的 Tauri API（重新导出以便统一管理）
 export { invoke } from '@tauri-apps/api/core'
@@ -4,6 +4,7 @@ import { useTabManagerStore } from "@/stores/tabManager"
 import { useStreamStore } from "@/stores/stream"
 import { usePluginStore } from "@/stores/plugins"
 import { listen, UnlistenFn } from "@tauri-apps/api/event"
+import { ElMessage } from "element-plus"
 import { ref } from "vue"
 // 事件监听器
 const eventListeners = ref<UnlistenFn[]>([])
@@ -129,6 +130,29 @@ const setupEventListeners = async () => {
             }
         })
         eventListeners.value.push(unlistenPluginDisconnectRequest)
+
+        // 监听插件资源占用超过软限制的事件
+        const unlistenResourceWarning = await listen('plugin-resource-warning', (event) => {
+            try {
+                const data = JSON.parse(event.payload as string)
+                ElMessage.warning(`插件 ${data.plugin_id} 资源占用过高: ${data.message}`)
+            } catch (e) {
+                console.error('Failed to parse plugin-resource-warning event:', e)
+            }
+        })
+        eventListeners.value.push(unlistenResourceWarning)
+
+        // 监听插件超过硬限制被停止的事件
+        const unlistenResourceFault = await listen('plugin-resource-fault', async (event) => {
+            try {
+                const data = JSON.parse(event.payload as string)
+                await pluginStore.syncInstanceState(data.instance_id, data.plugin_id)
+                ElMessage.error(`插件 ${data.plugin_id} 超过资源限制，已停止: ${data.message}`)
+            } catch (e) {
+                console.error('Failed to handle plugin-resource-fault event:', e)
+            }
+        })
+        eventListeners.value.push(unlistenResourceFault)
     } catch (error) {
         console.error('Failed to setup event listeners:', error)
     }
@@ -3,7 +3,17 @@
  */
 
 import { invoke } from '@tauri-apps/api/core'
-import type { PluginMetadata, AvailablePluginInfo, PluginDownloadResult } from './types'
+import { listen, UnlistenFn } from '@tauri-apps/api/event'
+import type {
+  PluginMetadata,
+  AvailablePluginInfo,
+  PluginDownloadResult,
+  LinkedPlugin,
+  ScaffoldOptions,
+  ReplayReport,
+  PluginResourceUsage,
+  PermissionRequest
+} from './types'
 import type { BaseMessage } from '../stores/history'
 
 /**
@@ -116,12 +126,19 @@ export async function scanAvailablePlugins(): Promise<AvailablePluginInfo[]> {
 /**
  * 下载并安装插件
  * @param pluginId 插件ID
+ * @param downloadId 可选的下载ID，用于接收进度事件和取消下载
  * @returns Promise<PluginDownloadResult> 下载结果
  */
-export async function downloadPlugin(pluginId: string): Promise<PluginDownloadResult> {
+export async function downloadPlugin(
+  pluginId: string,
+  downloadId?: string
+): Promise<PluginDownloadResult> {
   console.log('下载插件:', pluginId)
   try {
-    const result = await invoke<PluginDownloadResult>('download_plugin', { pluginId })
+    const result = await invoke<PluginDownloadResult>('download_plugin', {
+      pluginId,
+      downloadId: downloadId || null
+    })
     return result
   } catch (error) {
     console.error('Failed to download plugin:', error)
@@ -129,6 +146,109 @@ export async function downloadPlugin(pluginId: string): Promise<PluginDownloadRe
   }
 }
 
+/**
+ * 从本地插件压缩包或动态库文件安装插件
+ * @param path 插件包（.ccpkg）、插件压缩包（.zip）或动态库文件路径
+ * @param configPath 可选的配置文件路径，安装单独的动态库时默认使用同目录下的 config.toml
+ * @returns Promise<PluginDownloadResult> 安装结果
+ */
+export async function installPluginFromFile(
+  path: string,
+  configPath?: string
+): Promise<PluginDownloadResult> {
+  console.log('从本地文件安装插件:', path)
+  try {
+    const result = await invoke<PluginDownloadResult>('install_plugin_from_file', {
+      path,
+      configPath: configPath || null
+    })
+    return result
+  } catch (error) {
+    console.error('Failed to install plugin from file:', error)
+    throw error
+  }
+}
+
+/**
+ * 从 Git 仓库安装插件（支持私有仓库地址和本地 Git 仓库路径）
+ * @param url 仓库地址或本地路径
+ * @param rev 可选的分支、标签或提交
+ * @param path 可选的插件目录（config.toml 所在目录）在仓库中的相对路径
+ * @param downloadId 可选的下载ID，用于接收构建日志和取消安装
+ * @returns Promise<PluginDownloadResult> 安装结果
+ */
+export async function installPluginFromGit(
+  url: string,
+  rev?: string,
+  path?: string,
+  downloadId?: string
+): Promise<PluginDownloadResult> {
+  console.log('从 Git 仓库安装插件:', url)
+  try {
+    const result = await invoke<PluginDownloadResult>('install_plugin_from_git', {
+      url,
+      rev: rev || null,
+      path: path || null,
+      downloadId: downloadId || null
+    })
+    return result
+  } catch (error) {
+    console.error('Failed to install plugin from git:', error)
+    throw error
+  }
+}
+
+/**
+ * 按安装来源更新已安装的插件（Git 安装的插件会拉取最新提交并重新构建）
+ * @param pluginId 插件ID
+ * @param downloadId 可选的下载ID，用于接收进度事件和取消更新
+ * @returns Promise<PluginDownloadResult> 更新结果
+ */
+export async function updatePlugin(
+  pluginId: string,
+  downloadId?: string
+): Promise<PluginDownloadResult> {
+  console.log('更新插件:', pluginId)
+  try {
+    const result = await invoke<PluginDownloadResult>('update_plugin', {
+      pluginId,
+      downloadId: downloadId || null
+    })
+    return result
+  } catch (error) {
+    console.error('Failed to update plugin:', error)
+    throw error
+  }
+}
+
+/**
+ * 获取插件申请的权限和批准状态
+ * @param pluginId 插件ID
+ * @returns Promise<PermissionRequest> 权限申请，pending 为空表示无需批准
+ */
+export async function getPluginPermissions(pluginId: string): Promise<PermissionRequest> {
+  try {
+    return await invoke<PermissionRequest>('get_plugin_permissions', { pluginId })
+  } catch (error) {
+    console.error('Failed to get plugin permissions:', error)
+    throw error
+  }
+}
+
+/**
+ * 批准插件当前版本申请的全部权限
+ * @param pluginId 插件ID
+ * @returns Promise<PermissionRequest> 批准后的权限状态
+ */
+export async function approvePluginPermissions(pluginId: string): Promise<PermissionRequest> {
+  try {
+    return await invoke<PermissionRequest>('approve_plugin_permissions', { pluginId })
+  } catch (error) {
+    console.error('Failed to approve plugin permissions:', error)
+    throw error
+  }
+}
+
 /**
  * 卸载已安装的插件
  * @param pluginId 插件ID
@@ -161,3 +281,194 @@ export async function cancelStreamMessage(instanceId: string, streamId: string):
     throw error
   }
 }
+
+/**
+ * 取消正在进行的下载
+ * @param downloadId 下载ID
+ * @returns Promise<string> 成功消息
+ */
+export async function cancelDownload(downloadId: string): Promise<string> {
+  console.log('取消下载:', downloadId)
+  try {
+    const result = await invoke<string>('cancel_download', { downloadId })
+    return result
+  } catch (error) {
+    console.error('Failed to cancel download:', error)
+    throw error
+  }
+}
+
+/**
+ * 热重载插件的所有已挂载实例
+ * @param pluginId 插件ID
+ * @returns Promise<string> 操作结果消息
+ */
+export async function reloadPlugin(pluginId: string): Promise<string> {
+  try {
+    return await invoke<string>('reload_plugin', { pluginId })
+  } catch (error) {
+    console.error('Failed to reload plugin:', error)
+    throw error
+  }
+}
+
+/**
+ * 获取关联的插件开发目录
+ * @returns Promise<LinkedPlugin[]> 关联目录列表
+ */
+export async function listLinkedPlugins(): Promise<LinkedPlugin[]> {
+  try {
+    return await invoke<LinkedPlugin[]>('list_linked_plugins')
+  } catch (error) {
+    console.error('Failed to list linked plugins:', error)
+    throw error
+  }
+}
+
+/**
+ * 关联插件开发目录
+ * @param path 插件目录（包含 config.toml）
+ * @param autoRebuild 是否在文件变化时自动构建并热重载
+ * @returns Promise<LinkedPlugin> 关联结果
+ */
+export async function linkPluginDirectory(
+  path: string,
+  autoRebuild: boolean = false
+): Promise<LinkedPlugin> {
+  try {
+    return await invoke<LinkedPlugin>('link_plugin_directory', { path, autoRebuild })
+  } catch (error) {
+    console.error('Failed to link plugin directory:', error)
+    throw error
+  }
+}
+
+/**
+ * 取消关联插件开发目录
+ * @param path 插件目录
+ */
+export async function unlinkPluginDirectory(path: string): Promise<void> {
+  try {
+    await invoke('unlink_plugin_directory', { path })
+  } catch (error) {
+    console.error('Failed to unlink plugin directory:', error)
+    throw error
+  }
+}
+
+/**
+ * 立即构建关联目录中的插件并热重载（构建日志通过 plugin-build-log 事件发送）
+ * @param path 插件目录
+ * @returns Promise<string> 操作结果消息
+ */
+export async function rebuildLinkedPlugin(path: string): Promise<string> {
+  try {
+    return await invoke<string>('rebuild_linked_plugin', { path })
+  } catch (error) {
+    console.error('Failed to rebuild linked plugin:', error)
+    throw error
+  }
+}
+
+/**
+ * 根据模板生成新的插件 crate
+ * @param options 插件ID、名称、作者、模板和输出目录，可选加入 workspace
+ * @returns Promise<string> 新插件目录
+ */
+export async function scaffoldPlugin(options: ScaffoldOptions): Promise<string> {
+  try {
+    return await invoke<string>('scaffold_plugin', { options })
+  } catch (error) {
+    console.error('Failed to scaffold plugin:', error)
+    throw error
+  }
+}
+
+/**
+ * 开始记录插件跟踪（宿主调用、返回值和插件事件）
+ * @param path 跟踪文件路径，省略时写入数据目录下的 traces 目录
+ * @returns Promise<string> 跟踪文件路径
+ */
+export async function startPluginTrace(path?: string): Promise<string> {
+  try {
+    return await invoke<string>('start_plugin_trace', { path: path ?? null })
+  } catch (error) {
+    console.error('Failed to start plugin trace:', error)
+    throw error
+  }
+}
+
+/**
+ * 停止记录插件跟踪
+ * @returns Promise<string | null> 跟踪文件路径，未在跟踪时为 null
+ */
+export async function stopPluginTrace(): Promise<string | null> {
+  try {
+    return await invoke<string | null>('stop_plugin_trace')
+  } catch (error) {
+    console.error('Failed to stop plugin trace:', error)
+    throw error
+  }
+}
+
+/**
+ * 获取当前跟踪文件路径
+ * @returns Promise<string | null> 跟踪文件路径，未在跟踪时为 null
+ */
+export async function getPluginTracePath(): Promise<string | null> {
+  try {
+    return await invoke<string | null>('get_plugin_trace_path')
+  } catch (error) {
+    console.error('Failed to get plugin trace path:', error)
+    throw error
+  }
+}
+
+/**
+ * 回放跟踪文件，并将插件的返回值和事件与记录比较
+ * @param path 跟踪文件路径
+ * @param realtime 是否按记录的时间间隔发起调用，默认 true
+ * @returns Promise<ReplayReport> 回放报告
+ */
+export async function replayPluginTrace(path: string, realtime = true): Promise<ReplayReport> {
+  try {
+    return await invoke<ReplayReport>('replay_plugin_trace', { path, realtime })
+  } catch (error) {
+    console.error('Failed to replay plugin trace:', error)
+    throw error
+  }
+}
+
+/**
+ * 获取所有插件实例的资源占用
+ * @returns Promise<PluginResourceUsage[]> 各实例最近一次采样的资源占用
+ */
+export async function getPluginResourceUsage(): Promise<PluginResourceUsage[]> {
+  try {
+    return await invoke<PluginResourceUsage[]>('get_plugin_resource_usage')
+  } catch (error) {
+    console.error('Failed to get plugin resource usage:', error)
+    throw error
+  }
+}
+
+/**
+ * 监听插件实例发送的自定义事件（插件需要在 config.toml 的 [permissions] events 中申请）
+ * @param instanceId 插件实例ID
+ * @param event 插件发送的事件名
+ * @param callback 事件回调，参数为插件发送的 JSON 载荷
+ * @returns Promise<UnlistenFn> 取消监听的函数
+ */
+export async function listenPluginEvent(
+  instanceId: string,
+  event: string,
+  callback: (payload: unknown) => void
+): Promise<UnlistenFn> {
+  return await listen<string>(`plugin:${instanceId}:${event}`, (e) => {
+    try {
+      callback(JSON.parse(e.payload))
+    } catch (error) {
+      console.error(`Failed to parse plugin event ${event}:`, error)
+    }
+  })
+}
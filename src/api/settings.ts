@@ -0,0 +1,97 @@
+/**
+ * 后端设置相关的 Tauri API 调用
+ */
+
+import { invoke } from '@tauri-apps/api/core'
+import type { NetworkSettings, PathSettings, EffectiveDirectories, SanitizeSettings } from './types'
+
+/**
+ * 获取网络设置（不含环境变量覆盖）
+ * @returns Promise<NetworkSettings> 网络设置
+ */
+export async function getNetworkSettings(): Promise<NetworkSettings> {
+  try {
+    return await invoke<NetworkSettings>('get_network_settings')
+  } catch (error) {
+    console.error('Failed to get network settings:', error)
+    throw error
+  }
+}
+
+/**
+ * 保存网络设置，保存后插件仓库和下载立即使用新设置
+ * @param network 网络设置
+ */
+export async function setNetworkSettings(network: NetworkSettings): Promise<void> {
+  try {
+    await invoke('set_network_settings', { network })
+  } catch (error) {
+    console.error('Failed to set network settings:', error)
+    throw error
+  }
+}
+
+/**
+ * 获取插件消息的清理设置
+ * @returns Promise<SanitizeSettings> 清理设置
+ */
+export async function getSanitizeSettings(): Promise<SanitizeSettings> {
+  try {
+    return await invoke<SanitizeSettings>('get_sanitize_settings')
+  } catch (error) {
+    console.error('Failed to get sanitize settings:', error)
+    throw error
+  }
+}
+
+/**
+ * 保存插件消息的清理设置，之后插件发送的消息立即使用新设置
+ * @param sanitize 清理设置
+ */
+export async function setSanitizeSettings(sanitize: SanitizeSettings): Promise<void> {
+  try {
+    await invoke('set_sanitize_settings', { sanitize })
+  } catch (error) {
+    console.error('Failed to set sanitize settings:', error)
+    throw error
+  }
+}
+
+/**
+ * 获取目录设置（数据目录和额外的插件目录）
+ * @returns Promise<PathSettings> 目录设置
+ */
+export async function getPathSettings(): Promise<PathSettings> {
+  try {
+    return await invoke<PathSettings>('get_path_settings')
+  } catch (error) {
+    console.error('Failed to get path settings:', error)
+    throw error
+  }
+}
+
+/**
+ * 保存目录设置，插件目录立即生效，数据目录需要重启应用后生效
+ * @param paths 目录设置
+ */
+export async function setPathSettings(paths: PathSettings): Promise<void> {
+  try {
+    await invoke('set_path_settings', { paths })
+  } catch (error) {
+    console.error('Failed to set path settings:', error)
+    throw error
+  }
+}
+
+/**
+ * 获取当前生效的数据目录和插件扫描目录（含命令行参数和环境变量）
+ * @returns Promise<EffectiveDirectories> 生效的目录
+ */
+export async function getEffectiveDirectories(): Promise<EffectiveDirectories> {
+  try {
+    return await invoke<EffectiveDirectories>('get_effective_directories')
+  } catch (error) {
+    console.error('Failed to get effective directories:', error)
+    throw error
+  }
+}
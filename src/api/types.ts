@@ -56,6 +56,12 @@ export interface AvailablePluginInfo {
       download_url: string
     }
   }
+  source?: {
+    git?: string
+    rev?: string
+    path?: string
+    package?: string
+  }
 }
 
 /**
@@ -68,5 +74,158 @@ export interface PluginDownloadResult {
   installed_path?: string
 }
 
+/**
+ * 下载进度（plugin-download-progress 事件载荷）
+ */
+export interface DownloadProgress {
+  download_id: string
+  target: string
+  downloaded: number
+  total?: number
+}
+
+/**
+ * 关联的插件开发目录
+ */
+export interface LinkedPlugin {
+  path: string
+  auto_rebuild: boolean
+  /** 关联时读取到的插件ID */
+  plugin_id?: string
+}
+
+/**
+ * 新插件模板：回显、流式输出、UI 组件示例
+ */
+export type PluginTemplate = 'echo' | 'streaming' | 'ui'
+
+/**
+ * 生成新插件的参数，插件生成在 `output_dir/<id>` 中
+ */
+export interface ScaffoldOptions {
+  id: string
+  name: string
+  author: string
+  library_name?: string | null
+  template: PluginTemplate
+  output_dir: string
+  workspace?: string | null
+}
+
+/**
+ * 源码构建日志（build_id 与触发构建的下载ID相同）
+ */
+export interface BuildLogLine {
+  build_id: string
+  plugin_id: string
+  stream: 'status' | 'stdout' | 'stderr'
+  line: string
+}
+
+/**
+ * 网络设置（用于插件仓库同步和插件下载）
+ */
+export interface NetworkSettings {
+  proxy?: string | null
+  no_proxy: string[]
+  ca_certificates: string[]
+  user_agent?: string | null
+  connect_timeout_secs: number
+  read_timeout_secs: number
+  download_retries: number
+}
+
+/**
+ * 插件消息的清理设置（插件发送的 Markdown 在渲染前由后端清理）
+ */
+export interface SanitizeSettings {
+  enabled: boolean
+  allow_html: boolean
+  allowed_tags: string[]
+  allowed_url_schemes: string[]
+  trusted_plugins: string[]
+}
+
 // 重新导出插件UI相关类型
 export * from './plugin-ui-types'
+
+/**
+ * 目录设置（修改数据目录后需要重启应用）
+ */
+export interface PathSettings {
+  data_dir?: string | null
+  plugin_dirs: string[]
+}
+
+/**
+ * 当前生效的目录
+ */
+export interface EffectiveDirectories {
+  data_root: string
+  plugin_directories: string[]
+}
+
+/**
+ * 插件跟踪回放结果与记录之间的一处差异
+ */
+export interface TraceDifference {
+  location: string
+  expected?: string | null
+  actual?: string | null
+}
+
+/**
+ * 插件跟踪回放报告
+ */
+export interface ReplayReport {
+  calls: number
+  expected_events: number
+  actual_events: number
+  differences: TraceDifference[]
+}
+
+/**
+ * 插件实例的资源占用
+ */
+export interface PluginResourceUsage {
+  instance_id: string
+  plugin_id: string
+  /** 进程外插件的进程号 */
+  process_id?: number | null
+  threads: number
+  cpu_time_ms: number
+  /** 最近统计窗口内的平均 CPU 占用，100 表示占满一个核 */
+  cpu_percent: number
+  /** 常驻内存（字节），进程内插件为 null */
+  memory_bytes?: number | null
+  state: 'normal' | 'warning' | 'faulted'
+  message?: string | null
+}
+
+/**
+ * 插件在 config.toml 的 [permissions] 中申请的权限
+ */
+export interface PluginPermissions {
+  network: string[]
+  filesystem: string[]
+  serial: string[]
+  subprocess: string[]
+  plugins: string[]
+  events: string[]
+  config: string[]
+}
+
+/**
+ * 插件的权限申请和批准状态
+ */
+export interface PermissionRequest {
+  plugin_id: string
+  plugin_name: string
+  version: string
+  /** 当前版本申请的全部权限 */
+  requested: PluginPermissions
+  /** 需要用户批准的权限，全部为空表示无需批准 */
+  pending: PluginPermissions
+  /** 之前批准过的版本 */
+  approved_version?: string | null
+}
@@ -1,6 +1,6 @@
 import { defineStore } from 'pinia'
-import { ref } from 'vue'
-import { ElMessage } from 'element-plus'
+import { h, ref } from 'vue'
+import { ElMessage, ElMessageBox } from 'element-plus'
 import {
   scanPlugins,
   mountPlugin,
@@ -9,7 +9,15 @@ import {
   disconnectPlugin,
   sendMessageToPlugin,
   getPluginStatus,
-  type PluginMetadata
+  getPluginPermissions,
+  approvePluginPermissions,
+  installPluginFromFile,
+  installPluginFromGit,
+  updatePlugin,
+  linkPluginDirectory,
+  type PluginMetadata,
+  type PluginPermissions,
+  type PluginDownloadResult
 } from '@/api'
 import type { BaseMessage } from './history'
 
@@ -93,9 +101,101 @@ export const usePluginStore = defineStore('plugins', () => {
     }
   }
 
+  // 权限类别的显示名称
+  const permissionLabels: Record<keyof PluginPermissions, string> = {
+    network: '网络主机',
+    filesystem: '文件路径',
+    serial: '串口设备',
+    subprocess: '启动程序',
+    plugins: '调用插件',
+    events: '前端事件',
+    config: '读取设置'
+  }
+
+  // 插件申请了尚未批准的权限时请用户确认，返回插件是否可以挂载
+  const confirmPluginPermissions = async (pluginId: string) => {
+    try {
+      const request = await getPluginPermissions(pluginId)
+      const lines = (Object.keys(permissionLabels) as (keyof PluginPermissions)[])
+        .filter(key => request.pending[key].length > 0)
+        .map(key => `${permissionLabels[key]}: ${request.pending[key].join(', ')}`)
+      if (lines.length === 0) {
+        return true
+      }
+
+      const title = request.approved_version
+        ? `插件 "${request.plugin_name}" v${request.version} 申请了新的权限`
+        : `插件 "${request.plugin_name}" 申请以下权限`
+      await ElMessageBox.confirm(
+        h('div', lines.map(line => h('div', line))),
+        title,
+        {
+          confirmButtonText: '允许',
+          cancelButtonText: '拒绝',
+          type: 'warning'
+        }
+      )
+      await approvePluginPermissions(pluginId)
+      return true
+    } catch (error) {
+      if (error !== 'cancel') {
+        console.error('确认插件权限失败:', error)
+        ElMessage.error(`确认插件权限失败: ${error}`)
+      }
+      return false
+    }
+  }
+
+  // 安装完成后刷新插件列表并立即确认插件申请的权限
+  const finishInstall = async (result: PluginDownloadResult) => {
+    if (!result.success) {
+      ElMessage.error(`安装失败: ${result.message || '未知错误'}`)
+      return result
+    }
+    ElMessage.success(result.message)
+    await refreshPlugins()
+    if (result.plugin_id) {
+      await confirmPluginPermissions(result.plugin_id)
+    }
+    return result
+  }
+
+  // 从本地插件包（.ccpkg）、压缩包或动态库安装插件
+  const installPluginFile = async (path: string, configPath?: string) => {
+    return finishInstall(await installPluginFromFile(path, configPath))
+  }
+
+  // 从 Git 仓库安装插件
+  const installPluginGit = async (url: string, rev?: string, path?: string, downloadId?: string) => {
+    return finishInstall(await installPluginFromGit(url, rev, path, downloadId))
+  }
+
+  // 更新已安装的插件
+  const updatePluginById = async (pluginId: string, downloadId?: string) => {
+    return finishInstall(await updatePlugin(pluginId, downloadId))
+  }
+
+  // 关联插件开发目录
+  const linkPluginDir = async (path: string, autoRebuild: boolean = false) => {
+    const linked = await linkPluginDirectory(path, autoRebuild)
+    await refreshPlugins()
+    if (linked.plugin_id) {
+      await confirmPluginPermissions(linked.plugin_id)
+    }
+    return linked
+  }
+
   // 挂载插件实例
   const mountPluginById = async (pluginId: string, instanceId: string) => {
     try {
+      if (!(await confirmPluginPermissions(pluginId))) {
+        setInstanceState(instanceId, pluginId, {
+          isLoading: false,
+          error: '插件申请的权限未获批准'
+        })
+        return null
+      }
+
       const tempInstanceId = instanceId;
       setInstanceState(tempInstanceId, pluginId, { isLoading: true, error: undefined })
 
@@ -331,6 +431,11 @@ export const usePluginStore = defineStore('plugins', () => {
     syncInstanceState,
     loadPlugins,
     refreshPlugins,
+    confirmPluginPermissions,
+    installPluginFile,
+    installPluginGit,
+    updatePluginById,
+    linkPluginDir,
     mountPluginById,
     disposePluginInstance,
     connectPluginInstance,
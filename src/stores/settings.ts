@@ -1,5 +1,6 @@
 import { ref, reactive } from 'vue'
 import { defineStore } from 'pinia'
+import { getPathSettings, setPathSettings } from '@/api/settings'
 
 // 设置接口定义
 export interface AppSettings {
@@ -8,7 +9,7 @@ export interface AppSettings {
   language: 'zh-CN' | 'en-US'
   autoConnect: boolean
   
-  // 插件设置
+  // 插件设置（pluginDirectory 保存在后端的目录设置中，作为额外的插件扫描目录）
   pluginDirectory: string
   pluginHotReload: boolean
   pluginLogLevel: 'error' | 'warn' | 'info' | 'debug'
@@ -34,7 +35,7 @@ const defaultSettings: AppSettings = {
   autoConnect: true,
   
   // 插件设置
-  pluginDirectory: './plugins',
+  pluginDirectory: '',
   pluginHotReload: false,
   pluginLogLevel: 'info',
   
@@ -73,6 +74,14 @@ export const useSettingsStore = defineStore('settings', () => {
         const parsed = JSON.parse(savedSettings)
         Object.assign(settings, { ...defaultSettings, ...parsed })
       }
+
+      // 插件目录以后端的目录设置为准
+      try {
+        const paths = await getPathSettings()
+        settings.pluginDirectory = paths.plugin_dirs[0] ?? ''
+      } catch (error) {
+        console.error('加载插件目录设置失败:', error)
+      }
       
       console.log('设置加载完成:', settings)
     } catch (error) {
@@ -95,6 +104,15 @@ export const useSettingsStore = defineStore('settings', () => {
       
       // 保存到本地存储
       localStorage.setItem(STORAGE_KEY, JSON.stringify(settings))
+
+      // 同步插件目录到后端，保留其余额外目录
+      const paths = await getPathSettings()
+      const pluginDirectory = settings.pluginDirectory.trim()
+      const otherDirs = paths.plugin_dirs.slice(1)
+      await setPathSettings({
+        ...paths,
+        plugin_dirs: pluginDirectory ? [pluginDirectory, ...otherDirs] : otherDirs
+      })
       
       console.log('设置保存完成:', settings)
       return true